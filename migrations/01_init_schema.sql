@@ -71,68 +71,68 @@ CREATE INDEX IF NOT EXISTS idx_ch_msd ON chapters(manga_source_data_id);
 CREATE INDEX IF NOT EXISTS idx_manga_title ON manga(title);
 
 -- Seed data: insert default sources
--- This matches the Source enum values from the Rust code
+-- Generated from src/sources/catalogue.rs (checked by a unit test there)
 INSERT INTO sources (id, name, url) VALUES
     (1, 'MangaDex', 'https://mangadex.org'),
-    (2, 'Mangakakalot', 'https://mangakakalot.com'),
-    (3, 'Anilist', 'https://anilist.co'),
+    (2, 'FireScans', 'https://firescans.xyz'),
+    (3, 'RizzComic', 'https://rizzcomic.com'),
     (4, 'MyAnimeList', 'https://myanimelist.net'),
-    (5, 'Fanfox', 'https://fanfox.net'),
-    (6, 'MangaPark', 'https://mangapark.net'),
-    (7, 'FireScans', 'https://fr-scan.com'),
-    (8, 'RizzComic', 'https://rizzfables.com'),
-    (9, 'ResetScans', 'https://reset-scans.us'),
-    (10, 'AsuraScans', 'https://asuratoon.com'),
-    (11, 'Mangabaka', 'https://mangabaka.com'),
-    (12, 'VoidScans', 'https://void-scans.com'),
-    (13, 'NitroScans', 'https://nitroscans.com'),
-    (14, 'LuminousScans', 'https://luminousscans.com'),
-    (15, 'AkumaScans', 'https://akumascans.com'),
+    (5, 'AniList', 'https://anilist.co'),
+    (6, 'DrakeComic', 'https://drakecomic.org'),
+    (7, 'KDTNovels', 'https://kdtnovels.com'),
+    (8, 'Asmotoon', 'https://asmotoon.com'),
+    (9, 'ResetScans', 'https://reset-scans.org'),
+    (10, 'Kagane', 'https://kagane.org'),
+    (11, 'AsuraScans', 'https://asuracomic.net'),
+    (12, 'BookLive', 'https://booklive.jp'),
+    (13, 'Comikey', 'https://comikey.com'),
+    (14, 'DENPA', 'https://denpa.pub'),
+    (15, 'Dark Horse', 'https://www.darkhorse.com'),
     (16, 'DayComics', 'https://daycomics.com'),
-    (17, 'ReaperScans', 'https://reaperscans.com'),
-    (18, 'FlameComics', 'https://flamecomics.com'),
-    (19, 'HiveToons', 'https://hivetoons.org'),
-    (20, 'KogaScans', 'https://kogascans.com'),
-    (21, 'KenScans', 'https://kenscans.com'),
-    (22, 'YokaiJump', 'https://yokaijump.fr'),
-    (23, 'MirageScans', 'https://miragescans.com'),
-    (24, 'ManhuaUS', 'https://manhuaus.com'),
-    (25, 'ZeroScans', 'https://zscans.com'),
-    (26, 'Utoon', 'https://utoon.net'),
+    (17, 'FAKKU', 'https://www.fakku.net'),
+    (18, 'FlameComics', 'https://flamecomics.xyz'),
+    (19, 'GrimScans', 'https://grimscans.com'),
+    (20, 'HiveToons', 'https://hivetoons.org'),
+    (21, 'INKR', 'https://comics.inkr.com'),
+    (22, 'Irodori', 'https://irodoricomics.com'),
+    (23, 'J-Novel', 'https://j-novel.club'),
+    (24, 'Kana', 'https://www.mangakana.com'),
+    (25, 'KenScans', 'https://kencomics.com'),
+    (26, 'Kodansha', 'https://kodansha.us'),
     (27, 'KodokuStudio', 'https://kodokustudio.com'),
-    (28, 'PrismScans', 'https://prismascans.com'),
+    (28, 'Lezhin', 'https://www.lezhinus.com'),
     (29, 'LunaToons', 'https://lunatoons.com'),
-    (30, 'CosmicScans', 'https://cosmic-scans.com'),
-    (31, 'NyxScans', 'https://nyxscans.com'),
-    (32, 'VoidScansV2', 'https://void-scans.com'),
-    (33, 'QiScans', 'https://qiscans.com'),
-    (34, 'RizzFables', 'https://rizzfables.com'),
-    (35, 'RokariComics', 'https://rokaricomics.com'),
-    (36, 'MiracleScans', 'https://miraclescans.com'),
-    (37, 'SirenScans', 'https://sirenscans.com'),
-    (38, 'StoneScape', 'https://stonescape.xyz'),
-    (39, 'AsmoToon', 'https://asmotoon.com'),
-    (40, 'FreakScans', 'https://freakscans.com'),
-    (41, 'TempleScan', 'https://templetoons.com'),
-    (42, 'ThunderScans', 'https://thunderscans.com'),
-    (43, 'DrakeComic', 'https://drakecomic.com'),
-    (44, 'WitchScans', 'https://witchscans.com'),
-    (45, 'VortexScans', 'https://vortexscans.org'),
-    (46, 'MadaraScans', 'https://madaradex.org'),
+    (30, 'MadaraScans', 'https://madarascans.com'),
+    (31, 'Manhuaus', 'https://manhuaus.com'),
+    (32, 'Manta', 'https://manta.net'),
+    (33, 'MediBang', 'https://medibang.com'),
+    (34, 'NyxScans', 'https://nyxscans.com'),
+    (35, 'One Peace', 'https://onepeacebooks.com'),
+    (36, 'Others', 'https://example.com/others'),
+    (37, 'Pocket', 'https://www.pocketcomics.com'),
+    (38, 'QiScans', 'https://qiscans.org'),
+    (39, 'RizzFables', 'https://rizzfables.com'),
+    (40, 'RokariComics', 'https://rokaricomics.com'),
+    (41, 'Seven Seas', 'https://sevenseasentertainment.com'),
+    (42, 'Shueisha', 'https://www.shonenjump.com'),
+    (43, 'SirenScans', 'https://sirenscans.com'),
+    (44, 'Square Enix', 'https://squareenixmangaandbooks.square-enix-games.com'),
+    (45, 'StoneScape', 'https://stonescape.xyz'),
+    (46, 'TOKYOPOP', 'https://www.tokyopop.com'),
     (47, 'Tapas', 'https://tapas.io'),
-    (48, 'KaganeScans', 'https://kaganescans.com'),
-    (49, 'KDTNovels', 'https://kdt-novels.com'),
-    (50, 'RizzleFlix', 'https://rizzleflix.com'),
-    (51, 'EclipseScans', 'https://eclipsescans.com'),
-    (52, 'VerseScans', 'https://versescans.com'),
-    (53, 'FreeScanlation', 'https://free-scanlation.com'),
+    (48, 'Tappytoon', 'https://www.tappytoon.com'),
+    (49, 'TempleScan', 'https://templetoons.com'),
+    (50, 'ThunderScans', 'https://en-thunderscans.com'),
+    (51, 'Titan', 'https://titan-comics.com'),
+    (52, 'Toomics', 'https://www.toomics.com'),
+    (53, 'UDON', 'https://www.udonentertainment.com'),
     (54, 'VastVisual', 'https://vastvisual.com'),
-    (55, 'MangaQueen', 'https://mangaqueen.com'),
-    (56, 'OmegaScans', 'https://omegascans.org'),
-    (57, 'ApolloComics', 'https://apollocomics.com'),
+    (55, 'VIZ', 'https://www.viz.com'),
+    (56, 'VortexScans', 'https://vortexscans.org'),
+    (57, 'Webcomics', 'https://www.webcomicsapp.com'),
     (58, 'Webtoon', 'https://www.webtoons.com'),
-    (59, 'ArtemisScans', 'https://artemisscans.com'),
-    (60, 'TitanManga', 'https://titanmanga.com'),
+    (59, 'WitchScans', 'https://witchscans.com'),
+    (60, 'Yen Press', 'https://yenpress.com'),
     (61, 'MavinTranslations', 'https://mavintranslations.com')
 ON CONFLICT (id) DO NOTHING;
 
@@ -0,0 +1,75 @@
+-- Bring an existing sources table in line with src/sources/catalogue.rs.
+-- Databases created from an older 01_init_schema.sql were seeded with a
+-- different ID layout (2=Mangakakalot, 7=FireScans, ...), which made
+-- downloads and listings report the wrong source names.
+
+-- Runs in one transaction (src/migrations.rs wraps every migration).
+
+-- Names are UNIQUE, so move every row out of the way before renaming.
+UPDATE sources SET name = '__legacy_' || id;
+
+INSERT INTO sources (id, name, url) VALUES
+    (1, 'MangaDex', 'https://mangadex.org'),
+    (2, 'FireScans', 'https://firescans.xyz'),
+    (3, 'RizzComic', 'https://rizzcomic.com'),
+    (4, 'MyAnimeList', 'https://myanimelist.net'),
+    (5, 'AniList', 'https://anilist.co'),
+    (6, 'DrakeComic', 'https://drakecomic.org'),
+    (7, 'KDTNovels', 'https://kdtnovels.com'),
+    (8, 'Asmotoon', 'https://asmotoon.com'),
+    (9, 'ResetScans', 'https://reset-scans.org'),
+    (10, 'Kagane', 'https://kagane.org'),
+    (11, 'AsuraScans', 'https://asuracomic.net'),
+    (12, 'BookLive', 'https://booklive.jp'),
+    (13, 'Comikey', 'https://comikey.com'),
+    (14, 'DENPA', 'https://denpa.pub'),
+    (15, 'Dark Horse', 'https://www.darkhorse.com'),
+    (16, 'DayComics', 'https://daycomics.com'),
+    (17, 'FAKKU', 'https://www.fakku.net'),
+    (18, 'FlameComics', 'https://flamecomics.xyz'),
+    (19, 'GrimScans', 'https://grimscans.com'),
+    (20, 'HiveToons', 'https://hivetoons.org'),
+    (21, 'INKR', 'https://comics.inkr.com'),
+    (22, 'Irodori', 'https://irodoricomics.com'),
+    (23, 'J-Novel', 'https://j-novel.club'),
+    (24, 'Kana', 'https://www.mangakana.com'),
+    (25, 'KenScans', 'https://kencomics.com'),
+    (26, 'Kodansha', 'https://kodansha.us'),
+    (27, 'KodokuStudio', 'https://kodokustudio.com'),
+    (28, 'Lezhin', 'https://www.lezhinus.com'),
+    (29, 'LunaToons', 'https://lunatoons.com'),
+    (30, 'MadaraScans', 'https://madarascans.com'),
+    (31, 'Manhuaus', 'https://manhuaus.com'),
+    (32, 'Manta', 'https://manta.net'),
+    (33, 'MediBang', 'https://medibang.com'),
+    (34, 'NyxScans', 'https://nyxscans.com'),
+    (35, 'One Peace', 'https://onepeacebooks.com'),
+    (36, 'Others', 'https://example.com/others'),
+    (37, 'Pocket', 'https://www.pocketcomics.com'),
+    (38, 'QiScans', 'https://qiscans.org'),
+    (39, 'RizzFables', 'https://rizzfables.com'),
+    (40, 'RokariComics', 'https://rokaricomics.com'),
+    (41, 'Seven Seas', 'https://sevenseasentertainment.com'),
+    (42, 'Shueisha', 'https://www.shonenjump.com'),
+    (43, 'SirenScans', 'https://sirenscans.com'),
+    (44, 'Square Enix', 'https://squareenixmangaandbooks.square-enix-games.com'),
+    (45, 'StoneScape', 'https://stonescape.xyz'),
+    (46, 'TOKYOPOP', 'https://www.tokyopop.com'),
+    (47, 'Tapas', 'https://tapas.io'),
+    (48, 'Tappytoon', 'https://www.tappytoon.com'),
+    (49, 'TempleScan', 'https://templetoons.com'),
+    (50, 'ThunderScans', 'https://en-thunderscans.com'),
+    (51, 'Titan', 'https://titan-comics.com'),
+    (52, 'Toomics', 'https://www.toomics.com'),
+    (53, 'UDON', 'https://www.udonentertainment.com'),
+    (54, 'VastVisual', 'https://vastvisual.com'),
+    (55, 'VIZ', 'https://www.viz.com'),
+    (56, 'VortexScans', 'https://vortexscans.org'),
+    (57, 'Webcomics', 'https://www.webcomicsapp.com'),
+    (58, 'Webtoon', 'https://www.webtoons.com'),
+    (59, 'WitchScans', 'https://witchscans.com'),
+    (60, 'Yen Press', 'https://yenpress.com'),
+    (61, 'MavinTranslations', 'https://mavintranslations.com')
+ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, url = EXCLUDED.url;
+
+SELECT setval('sources_id_seq', (SELECT MAX(id) FROM sources));
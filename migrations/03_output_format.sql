@@ -0,0 +1,4 @@
+-- Per-manga download output format (cbz, epub, pdf or folder).
+-- NULL means "use output_format from config.toml".
+
+ALTER TABLE manga ADD COLUMN IF NOT EXISTS output_format VARCHAR(16);
@@ -0,0 +1,26 @@
+-- Persistent download queue drained by the workers in src/download_queue.rs.
+-- A chapter has at most one queued or running job at a time; finished and
+-- failed jobs are kept for the history shown by GET /queue.
+
+CREATE TABLE IF NOT EXISTS download_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    chapter_id INTEGER NOT NULL,
+    state VARCHAR(16) NOT NULL DEFAULT 'queued'
+        CHECK (state IN ('queued', 'running', 'done', 'failed')),
+    -- Output format override (cbz, epub, pdf, folder); NULL uses the manga's or config default
+    format VARCHAR(16),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    -- Unix timestamp before which a queued job is not picked up (retry backoff)
+    next_attempt_at BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    file_path TEXT,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    FOREIGN KEY (chapter_id) REFERENCES chapters (id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_download_jobs_active
+    ON download_jobs (chapter_id) WHERE state IN ('queued', 'running');
+CREATE INDEX IF NOT EXISTS idx_download_jobs_pending
+    ON download_jobs (state, next_attempt_at);
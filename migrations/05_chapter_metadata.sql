@@ -0,0 +1,24 @@
+-- Structured chapter details. `chapter_number` stays the label the source shows;
+-- the columns below are parsed from it or filled by sources that report them.
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS number DOUBLE PRECISION;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS volume TEXT;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS title TEXT;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS language VARCHAR(16);
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS scanlation_group TEXT;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS published_at BIGINT;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS page_count INTEGER;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS first_seen_at BIGINT;
+
+-- Backfill the number and volume the way Chapter::fill_from_label parses labels.
+UPDATE chapters
+SET volume = substring(chapter_number FROM '(?i)\mvol(?:ume)?\.?\s*(\d+)')
+WHERE volume IS NULL;
+
+UPDATE chapters
+SET number = substring(
+        regexp_replace(chapter_number, '\mvol(ume)?\.?\s*\d+', '', 'gi')
+        FROM '(\d+(?:\.\d+)?)'
+    )::DOUBLE PRECISION
+WHERE number IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_ch_msd_number ON chapters(manga_source_data_id, number);
@@ -0,0 +1,8 @@
+-- Reading-order key computed by src/chapter_number.rs (main chapters by season
+-- and number, then extras, then side stories). Existing rows start from their
+-- parsed number; re-importing a manga's chapters stores the full key.
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS sort_key DOUBLE PRECISION;
+
+UPDATE chapters SET sort_key = number WHERE sort_key IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_ch_msd_sort ON chapters(manga_source_data_id, sort_key);
@@ -0,0 +1,3 @@
+-- Per-manga source preference: lower priority serves a chapter first; sources
+-- without one follow in the order they were linked.
+ALTER TABLE manga_source_data ADD COLUMN IF NOT EXISTS priority INTEGER;
@@ -0,0 +1,26 @@
+-- Fuzzy series matching (src/matching.rs). Cover images are reduced to a 64-bit
+-- difference hash once and cached on the manga row.
+ALTER TABLE manga ADD COLUMN IF NOT EXISTS cover_hash BIGINT;
+
+-- Proposed merges of two manga rows. Pairs are stored with manga_a < manga_b so
+-- a pair is only ever proposed once; rejected pairs stay to keep it that way.
+-- Titles are copied in so the history survives the merge itself.
+CREATE TABLE IF NOT EXISTS merge_proposals (
+    id SERIAL PRIMARY KEY,
+    manga_a UUID NOT NULL,
+    manga_b UUID NOT NULL,
+    title_a TEXT NOT NULL,
+    title_b TEXT NOT NULL,
+    score DOUBLE PRECISION NOT NULL,
+    -- Human-readable evidence, e.g. "title 0.92; cover distance 3"
+    reasons TEXT NOT NULL,
+    state VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (state IN ('pending', 'accepted', 'rejected')),
+    -- TRUE when merged by the automatic confidence threshold
+    automatic BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL,
+    decided_at BIGINT,
+    UNIQUE (manga_a, manga_b)
+);
+
+CREATE INDEX IF NOT EXISTS idx_merge_proposals_state ON merge_proposals (state, score DESC);
@@ -0,0 +1,20 @@
+-- Audit log of manual and automatic manga merges and splits, so each one can
+-- be undone. `snapshot` holds what the undo needs:
+--   merge: the merged-away manga row ("other"), the target row before the merge
+--          ("target") and every source link, chapter and provider ID row the
+--          other manga had ("sources", "chapters", "providers")
+--   split: the IDs of the source links moved to the new manga ("source_ids")
+CREATE TABLE IF NOT EXISTS manga_audit (
+    id SERIAL PRIMARY KEY,
+    action VARCHAR(16) NOT NULL CHECK (action IN ('merge', 'split')),
+    -- merge: the manga that was kept; split: the manga that was split
+    manga_id UUID NOT NULL,
+    -- merge: the manga merged away; split: the manga created by the split
+    other_manga_id UUID NOT NULL,
+    snapshot JSONB NOT NULL,
+    created_at BIGINT NOT NULL,
+    undone_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_manga_audit_manga ON manga_audit (manga_id);
+CREATE INDEX IF NOT EXISTS idx_manga_audit_other ON manga_audit (other_manga_id);
@@ -0,0 +1,27 @@
+-- Align column types with the Rust models so both storage backends bind the
+-- same values: manga IDs are generated in Rust as strings (and stored as TEXT
+-- by SQLite), `monitored` is a flag, and intervals and provider IDs are i64.
+ALTER TABLE manga_source_data DROP CONSTRAINT IF EXISTS manga_source_data_manga_id_fkey;
+ALTER TABLE provider_ids DROP CONSTRAINT IF EXISTS provider_ids_manga_id_fkey;
+
+ALTER TABLE manga
+    ALTER COLUMN id TYPE TEXT USING id::text,
+    ALTER COLUMN id SET DEFAULT uuid_generate_v4()::text,
+    ALTER COLUMN monitored TYPE BOOLEAN USING monitored <> 0,
+    ALTER COLUMN check_interval_secs TYPE BIGINT,
+    ALTER COLUMN discover_interval_secs TYPE BIGINT,
+    ALTER COLUMN mal_id TYPE BIGINT,
+    ALTER COLUMN anilist_id TYPE BIGINT;
+ALTER TABLE manga_source_data ALTER COLUMN manga_id TYPE TEXT USING manga_id::text;
+ALTER TABLE provider_ids ALTER COLUMN manga_id TYPE TEXT USING manga_id::text;
+ALTER TABLE merge_proposals
+    ALTER COLUMN manga_a TYPE TEXT USING manga_a::text,
+    ALTER COLUMN manga_b TYPE TEXT USING manga_b::text;
+ALTER TABLE manga_audit
+    ALTER COLUMN manga_id TYPE TEXT USING manga_id::text,
+    ALTER COLUMN other_manga_id TYPE TEXT USING other_manga_id::text;
+
+ALTER TABLE manga_source_data ADD CONSTRAINT manga_source_data_manga_id_fkey
+    FOREIGN KEY (manga_id) REFERENCES manga (id) ON DELETE CASCADE;
+ALTER TABLE provider_ids ADD CONSTRAINT provider_ids_manga_id_fkey
+    FOREIGN KEY (manga_id) REFERENCES manga (id) ON DELETE CASCADE;
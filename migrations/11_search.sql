@@ -0,0 +1,21 @@
+-- Manga search (GET /manga?q=...): a weighted full-text document per manga and
+-- trigram indexes for typo-tolerant title matching.
+-- The 'simple' configuration keeps titles in any language intact (no stemming
+-- or stop words), so the same words match in every field.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Publication status as the source reports it (ongoing, completed, ...)
+ALTER TABLE manga ADD COLUMN IF NOT EXISTS status VARCHAR(32);
+
+-- Title A, alternative titles B, tags C, description D
+ALTER TABLE manga ADD COLUMN IF NOT EXISTS search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple'::regconfig, coalesce(title, '')), 'A') ||
+    setweight(to_tsvector('simple'::regconfig, coalesce(alt_titles, '')), 'B') ||
+    setweight(to_tsvector('simple'::regconfig, coalesce(tags, '')), 'C') ||
+    setweight(to_tsvector('simple'::regconfig, coalesce(description, '')), 'D')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_manga_search_vector ON manga USING gin (search_vector);
+CREATE INDEX IF NOT EXISTS idx_manga_title_trgm ON manga USING gin (title gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_manga_alt_titles_trgm ON manga USING gin (alt_titles gin_trgm_ops);
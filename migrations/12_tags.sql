@@ -0,0 +1,19 @@
+-- Normalized tags: one row per canonical tag (seeded from the dictionary in
+-- src/tags.rs, plus labels the dictionary does not know) and a link table.
+-- `manga.tags` keeps the canonical names joined with ", " for ComicInfo and
+-- the full-text document.
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    -- NULL for tags outside the dictionary
+    category VARCHAR(16) CHECK (category IN ('genre', 'theme', 'format', 'content_warning'))
+);
+
+CREATE TABLE IF NOT EXISTS manga_tags (
+    manga_id TEXT NOT NULL REFERENCES manga (id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (manga_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_manga_tags_tag ON manga_tags (tag_id);
@@ -0,0 +1,17 @@
+-- Creators: one row per person (or publisher), matched across providers by
+-- `name_key` (lowercased words, sorted, so "Oda, Eiichiro" and "Eiichiro Oda"
+-- agree), and their credits on manga.
+CREATE TABLE IF NOT EXISTS people (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    name_key TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS manga_people (
+    manga_id TEXT NOT NULL REFERENCES manga (id) ON DELETE CASCADE,
+    person_id INTEGER NOT NULL REFERENCES people (id) ON DELETE CASCADE,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('author', 'artist', 'publisher')),
+    PRIMARY KEY (manga_id, person_id, role)
+);
+
+CREATE INDEX IF NOT EXISTS idx_manga_people_person ON manga_people (person_id);
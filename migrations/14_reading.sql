@@ -0,0 +1,30 @@
+-- Readers sharing the server, the manga each follows, and how far they got
+-- in every chapter they opened. `page` is the last page viewed, zero-based.
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS library_entries (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    manga_id TEXT NOT NULL REFERENCES manga (id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL
+        CHECK (status IN ('reading', 'completed', 'on_hold', 'dropped', 'plan_to_read')),
+    added_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    UNIQUE (user_id, manga_id)
+);
+
+CREATE TABLE IF NOT EXISTS chapter_reads (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    chapter_id INTEGER NOT NULL REFERENCES chapters (id) ON DELETE CASCADE,
+    page INTEGER NOT NULL DEFAULT 0,
+    completed BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (user_id, chapter_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_library_entries_manga ON library_entries (manga_id);
+CREATE INDEX IF NOT EXISTS idx_chapter_reads_chapter ON chapter_reads (chapter_id);
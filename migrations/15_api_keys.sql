@@ -0,0 +1,15 @@
+-- API keys for the HTTP server. Only the SHA-256 of a key is stored; `prefix`
+-- (its first characters) tells keys apart. `scopes` is a comma-separated list
+-- of read, download and admin. A key may belong to a user, who it then is on
+-- the /me endpoints.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    prefix TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+    created_at BIGINT NOT NULL,
+    last_used_at BIGINT,
+    revoked_at BIGINT
+);
@@ -0,0 +1,9 @@
+-- Where a downloaded chapter lives in `download_dir`. Set after a download
+-- succeeds or when the library scanner links an existing file to the chapter;
+-- `file_hash` is the SHA-256 of the archive, hex encoded.
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS file_path TEXT;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS file_size BIGINT;
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS file_hash VARCHAR(64);
+ALTER TABLE chapters ADD COLUMN IF NOT EXISTS downloaded_at BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_ch_file_path ON chapters(file_path) WHERE file_path IS NOT NULL;
@@ -9,7 +9,6 @@
 //! - `AppState`: Main application state with database connection, clients, and configuration
 //! - `MetadataProgress`: Progress tracking for metadata synchronization operations
 
-use deadpool_postgres::Pool;
 use reqwest::Client;
 use serde::Serialize;
 use std::sync::{Arc, Mutex};
@@ -48,8 +47,8 @@ pub struct MetadataProgress {
 /// This struct is wrapped in `web::Data` and shared across all HTTP request handlers.
 /// All mutable state is protected by `Mutex` for thread-safety.
 pub struct AppState {
-    /// PostgreSQL database connection pool (inherently thread-safe)
-    pub pool: Pool,
+    /// Database backend selected by `[database]` in config.toml
+    pub storage: Arc<dyn crate::storage::Storage>,
     /// Standard reqwest HTTP client
     pub client: Client,
     /// Enhanced HTTP client with bot detection bypass
@@ -58,8 +57,22 @@ pub struct AppState {
     pub metrics: crate::metrics::MetricsTracker,
     /// Application configuration
     pub config: crate::config::Config,
+    /// Page download settings (per-host concurrency cap, transcoding)
+    pub page_fetcher: crate::scraper::PageFetcher,
+    /// Recently resolved chapter pages, for readers streaming page by page
+    pub page_cache: crate::sources::pages::PageCache,
     /// Progress tracking for crawler operations
     pub crawl_progress: Mutex<crate::crawler::CrawlProgress>,
+    /// Progress and findings of the `/verify/library` job
+    pub library_verify: Mutex<crate::library::VerifyProgress>,
+    /// Progress and findings of the `/library/scan` job
+    pub library_scan: Mutex<crate::library::ScanProgress>,
+    /// Progress of the `/library/rename` job
+    pub library_rename: Mutex<crate::library::RenameProgress>,
+    /// Progress of the `/manga/{id}/bundle` job
+    pub bundle_job: Mutex<crate::bundle::BundleProgress>,
+    /// Progress of the `/matching/run` job
+    pub matching_job: Mutex<crate::matching::MatchingProgress>,
     /// Progress tracking for metadata sync operations
     pub metadata_progress: Mutex<MetadataProgress>,
     /// Flag to cancel ongoing metadata sync
@@ -0,0 +1,373 @@
+//! API keys, scopes and the middleware that checks them on every request.
+//!
+//! Callers present a key as `Authorization: Bearer <key>` or `X-Api-Key: <key>`,
+//! or as the password of HTTP Basic authentication, which is all most OPDS
+//! reading apps can send (the user name is ignored).
+//! Keys are random (`tsk_` and 64 hex digits) and only their SHA-256 is stored,
+//! in `api_keys`, with the [`Scope`]s they grant and optionally the user they
+//! belong to. A key tied to a user is that user on the `/me` endpoints.
+//!
+//! Each route needs one scope ([`required_scope`], decided from the route's
+//! pattern rather than the raw, possibly percent-encoded path): browsing needs `read`,
+//! anything that fetches from sources or writes files needs `download`, and
+//! imports, crawls, metadata syncs, merges, user and key management need
+//! `admin`, which also grants the other two. Requests without a key get
+//! `[server] anonymous_scopes` from `config.toml` (never `admin`).
+//!
+//! Create the first admin key from the command line:
+//!
+//! ```text
+//! rust_manga_scraper --create-api-key "my laptop" --scopes admin
+//! ```
+
+use crate::app_state::AppState;
+use crate::models::ApiKey;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpResponse};
+use base64::Engine;
+use log::error;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Header carrying an API key, as an alternative to `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+const KEY_PREFIX: &str = "tsk_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Browse the library and keep reading progress
+    Read,
+    /// Download chapters and queue downloads
+    Download,
+    /// Everything, including imports, crawls, merges, users and keys
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::Read, Scope::Download, Scope::Admin];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Download => "download",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sc| sc.as_str() == s)
+    }
+}
+
+/// Scopes from a comma-separated list such as `"read,download"`; unknown
+/// names are an error
+pub fn parse_scopes(list: &str) -> Result<Vec<Scope>, String> {
+    let mut scopes = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let scope = Scope::parse(name).ok_or_else(|| format!("unknown scope '{}'", name))?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    Ok(scopes)
+}
+
+/// Scopes as stored in `api_keys.scopes`
+pub fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A new random API key
+pub fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", KEY_PREFIX, hex)
+}
+
+/// What `api_keys.key_hash` holds for `key`
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The start of a key, kept in `api_keys.prefix` so keys can be told apart
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(KEY_PREFIX.len() + 8).collect()
+}
+
+/// The key presented with a request, if any
+pub fn presented_key(headers: &HeaderMap) -> Option<String> {
+    let authorization = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+    let key = match authorization {
+        Some(v) if v.starts_with("Basic ") => basic_password(&v["Basic ".len()..]),
+        Some(v) => v.strip_prefix("Bearer ").map(str::to_string),
+        None => None,
+    };
+    key.or_else(|| {
+        headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    })
+    .map(|k| k.trim().to_string())
+    .filter(|k| !k.is_empty())
+}
+
+/// The password of Basic credentials, or the user name when the password is
+/// empty, for apps with a single field
+fn basic_password(credentials: &str) -> Option<String> {
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(credentials.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some(if password.is_empty() { user } else { password }.to_string())
+}
+
+/// The scope a request needs, or `None` for routes open to everyone
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let write = method != Method::GET && method != Method::HEAD;
+    Some(match segments.as_slice() {
+        ["auth", "whoami"] => return None,
+        // OPDS-PSE pages and the chapter feed that resolves them fetch from sources
+        ["opds", "chapters", ..] | ["opds", "manga", _, "chapters", ..] => Scope::Download,
+        ["opds", ..] => Scope::Read,
+        ["import" | "crawl" | "metadata" | "matching" | "audit", ..]
+        | ["verify" | "library" | "users" | "auth", ..] => Scope::Admin,
+        ["download", ..] | ["chapters", _, "pages"] | ["manga", _, "bundle"] => Scope::Download,
+        ["queue", "manga" | "unread", ..] => Scope::Download,
+        ["me", ..] | ["chapters", _, "read"] => Scope::Read,
+        // Anything else that changes state is an administrative action
+        _ if write => Scope::Admin,
+        _ => Scope::Read,
+    })
+}
+
+/// What the router matches `req` against: the pattern of the route it
+/// resolves to (e.g. `/import/source/{source}`), or the percent-decoded path
+/// when no route matches. The raw path must not be used: `/%69mport/...`
+/// routes to `/import/...`.
+pub fn route_path(req: &ServiceRequest) -> String {
+    let path = req.match_info().as_str();
+    req.resource_map()
+        .match_pattern(path)
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Who is making a request: the API key presented, if any, and the scopes
+/// granted. Stored in the request extensions by [`authenticate`].
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub key: Option<ApiKey>,
+    pub scopes: Vec<Scope>,
+}
+
+impl Caller {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+/// Middleware resolving the caller's key and refusing requests outside its
+/// scopes: `401` without a usable key, `403` with a key lacking the scope
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let Some(data) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let path = route_path(&req);
+    let caller = match presented_key(req.headers()) {
+        Some(key) => {
+            let now = chrono::Utc::now().timestamp();
+            match data
+                .storage
+                .authenticate_api_key(&hash_key(&key), now)
+                .await
+            {
+                Ok(Some(key)) => Caller {
+                    scopes: key.scopes.clone(),
+                    key: Some(key),
+                },
+                Ok(None) => {
+                    let resp = unauthorized(&path, "invalid or revoked API key");
+                    return Ok(req.into_response(resp).map_into_right_body());
+                }
+                Err(e) => {
+                    error!("Database error checking API key: {}", e);
+                    return Ok(req
+                        .into_response(
+                            HttpResponse::InternalServerError()
+                                .json(serde_json::json!({"error": "Database error"})),
+                        )
+                        .map_into_right_body());
+                }
+            }
+        }
+        None => Caller {
+            key: None,
+            scopes: data
+                .config
+                .server
+                .anonymous_scopes
+                .iter()
+                .copied()
+                .filter(|s| *s != Scope::Admin)
+                .collect(),
+        },
+    };
+
+    if let Some(scope) = required_scope(req.method(), &path) {
+        if !caller.allows(scope) {
+            let resp = if caller.key.is_none() {
+                unauthorized(
+                    &path,
+                    &format!("an API key with the '{}' scope is required", scope.as_str()),
+                )
+            } else {
+                HttpResponse::Forbidden().json(serde_json::json!({
+                    "error": format!("API key lacks the '{}' scope", scope.as_str())
+                }))
+            };
+            return Ok(req.into_response(resp).map_into_right_body());
+        }
+    }
+    req.extensions_mut().insert(caller);
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+fn unauthorized(path: &str, message: &str) -> HttpResponse {
+    // Reading apps only prompt for credentials on a Basic challenge
+    let challenge = if path.starts_with("/opds") {
+        "Basic realm=\"tsubaki\""
+    } else {
+        "Bearer"
+    };
+    HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, challenge))
+        .json(serde_json::json!({ "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    #[test]
+    fn test_required_scope() {
+        let get = |path| required_scope(&Method::GET, path);
+        let post = |path| required_scope(&Method::POST, path);
+        assert_eq!(get("/manga"), Some(Scope::Read));
+        assert_eq!(get("/manga/abc/chapters"), Some(Scope::Read));
+        assert_eq!(get("/me/continue"), Some(Scope::Read));
+        assert_eq!(post("/chapters/5/read"), Some(Scope::Read));
+        assert_eq!(get("/chapters/5/pages"), Some(Scope::Download));
+        assert_eq!(get("/download/abc/12"), Some(Scope::Download));
+        assert_eq!(post("/queue/manga/abc"), Some(Scope::Download));
+        assert_eq!(post("/queue/7/retry"), Some(Scope::Admin));
+        assert_eq!(get("/import/source/mangadex"), Some(Scope::Admin));
+        assert_eq!(get("/crawl/full"), Some(Scope::Admin));
+        assert_eq!(post("/metadata/cancel"), Some(Scope::Admin));
+        assert_eq!(get("/library/scan/status"), Some(Scope::Admin));
+        assert_eq!(post("/manga/abc/merge"), Some(Scope::Admin));
+        assert_eq!(get("/users"), Some(Scope::Admin));
+        assert_eq!(get("/auth/keys"), Some(Scope::Admin));
+        assert_eq!(get("/auth/whoami"), None);
+        assert_eq!(get("/opds/manga/abc"), Some(Scope::Read));
+        assert_eq!(get("/opds/chapters/5/pages/0"), Some(Scope::Download));
+        assert_eq!(get("/opds/manga/abc/chapters/5"), Some(Scope::Download));
+    }
+
+    #[actix_web::test]
+    async fn test_scope_uses_routed_path() {
+        use actix_web::{middleware::from_fn, test, App, HttpResponse};
+
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(|req: ServiceRequest, next: Next<_>| async move {
+                    let scope = required_scope(req.method(), &route_path(&req));
+                    let resp = next.call(req).await?;
+                    Ok(resp.map_body(move |_, _| format!("{:?}", scope)))
+                }))
+                .route("/import/source/{source}", web::get().to(HttpResponse::Ok))
+                .route("/manga", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        for (path, scope) in [
+            ("/import/source/x", "Some(Admin)"),
+            ("/%69mport/source/x", "Some(Admin)"),
+            ("/%69%6D%70%6F%72%74/source/x", "Some(Admin)"),
+            ("/manga", "Some(Read)"),
+            // Unrouted paths are still decoded
+            ("/%75sers", "Some(Admin)"),
+        ] {
+            let req = test::TestRequest::get().uri(path).to_request();
+            let body = test::call_and_read_body(&app, req).await;
+            assert_eq!(body, scope.as_bytes(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert_eq!(
+            parse_scopes("read, download,read"),
+            Ok(vec![Scope::Read, Scope::Download])
+        );
+        assert!(parse_scopes("read,write").is_err());
+        assert_eq!(join_scopes(&[Scope::Read, Scope::Admin]), "read,admin");
+        let admin = Caller {
+            key: None,
+            scopes: vec![Scope::Admin],
+        };
+        assert!(admin.allows(Scope::Download));
+        let reader = Caller {
+            key: None,
+            scopes: vec![Scope::Read],
+        };
+        assert!(!reader.allows(Scope::Download));
+    }
+
+    #[test]
+    fn test_keys() {
+        let key = generate_key();
+        assert!(key.starts_with(KEY_PREFIX) && key.len() == KEY_PREFIX.len() + 64);
+        assert_ne!(key, generate_key());
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_eq!(hash_key(&key).len(), 64);
+        assert_eq!(key_prefix(&key).len(), 12);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(presented_key(&headers), None);
+        headers.insert(
+            HeaderName::from_static("x-api-key"),
+            HeaderValue::from_static("tsk_a"),
+        );
+        assert_eq!(presented_key(&headers).as_deref(), Some("tsk_a"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer tsk_b"));
+        assert_eq!(presented_key(&headers).as_deref(), Some("tsk_b"));
+        // "tablet:tsk_c", and "tsk_e:" from an app with a single field
+        for (basic, key) in [("dGFibGV0OnRza19j", "tsk_c"), ("dHNrX2U6", "tsk_e")] {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Basic {}", basic)).unwrap(),
+            );
+            assert_eq!(presented_key(&headers).as_deref(), Some(key));
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Volume bundling: merge downloaded chapter archives into one file.
+//!
+//! `POST /manga/{id}/bundle` picks the chapter CBZs in the manga's download
+//! folder that belong to a volume (or fall in a chapter range), using each
+//! archive's ComicInfo.xml and falling back to the `Vol.X Ch.Y` file name. Their
+//! pages are renumbered into a single CBZ, EPUB or PDF under `volumes/`, and the
+//! first page of every chapter carries a ComicInfo bookmark. The per-chapter
+//! files can be removed afterwards. Progress is polled with `GET /bundle/status`.
+
+use crate::app_state::AppState;
+use crate::comicinfo::ComicInfo;
+use crate::helpers::extract_number;
+use crate::images::ImageFormat;
+use crate::library::SKIPPED_DIRS;
+use crate::models::BundleRequest;
+use crate::naming::ChapterName;
+use crate::output::{Chapter, OutputFormat};
+use crate::scraper::{sanitize_filename, PageImage};
+use actix_web::web;
+use chrono::Utc;
+use log::{error, info};
+use regex::Regex;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Which chapters go into a bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    Volume(String),
+    /// Inclusive chapter range, as written in the request.
+    Range(String, String),
+}
+
+impl Selection {
+    /// Volume wins over a range; a range needs both ends.
+    pub fn from_request(req: &BundleRequest) -> Option<Self> {
+        if let Some(v) = req
+            .volume
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+        {
+            return Some(Selection::Volume(v.to_string()));
+        }
+        let from = req.from.as_deref()?;
+        let to = req.to.as_deref()?;
+        let (a, b) = (chapter_key(from)?, chapter_key(to)?);
+        (a <= b).then(|| Selection::Range(from.trim().to_string(), to.trim().to_string()))
+    }
+
+    fn matches(&self, chapter: &ChapterArchive) -> bool {
+        match self {
+            Selection::Volume(v) => {
+                chapter.volume.as_deref().and_then(chapter_key) == chapter_key(v)
+            }
+            Selection::Range(from, to) => match (chapter.key, chapter_key(from), chapter_key(to)) {
+                (Some(n), Some(a), Some(b)) => a <= n && n <= b,
+                _ => false,
+            },
+        }
+    }
+
+    /// `Vol.3` or `Ch.1-10`, used in the bundle's file name.
+    fn label(&self) -> String {
+        match self {
+            Selection::Volume(v) => format!("Vol.{}", v),
+            Selection::Range(from, to) => format!("Ch.{}-{}", from, to),
+        }
+    }
+}
+
+fn chapter_key(s: &str) -> Option<f64> {
+    extract_number(s)?.parse().ok()
+}
+
+/// A downloaded chapter archive and what it claims to contain.
+#[derive(Debug, Clone)]
+pub struct ChapterArchive {
+    pub path: PathBuf,
+    pub number: String,
+    pub volume: Option<String>,
+    pub title: Option<String>,
+    pub comicinfo: Option<ComicInfo>,
+    key: Option<f64>,
+}
+
+impl ChapterArchive {
+    /// Read the archive's ComicInfo.xml, or parse the `Vol.X Ch.Y - Title`
+    /// label the downloader puts in file names.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let comicinfo = read_comicinfo(path)?;
+        let (number, volume, title) = match &comicinfo {
+            Some(info) if !info.number.is_empty() => {
+                (info.number.clone(), info.volume.clone(), info.title.clone())
+            }
+            _ => label_from_file_name(path),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            key: chapter_key(&number),
+            number,
+            volume,
+            title,
+            comicinfo,
+        })
+    }
+
+    fn bookmark(&self) -> String {
+        match &self.title {
+            Some(t) => format!("Chapter {} - {}", self.number, t),
+            None => format!("Chapter {}", self.number),
+        }
+    }
+}
+
+pub(crate) fn read_comicinfo(path: &Path) -> std::io::Result<Option<ComicInfo>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut xml = String::new();
+    match archive.by_name("ComicInfo.xml") {
+        Ok(mut entry) => entry.read_to_string(&mut xml)?,
+        Err(_) => return Ok(None),
+    };
+    Ok(ComicInfo::from_xml(&xml))
+}
+
+fn label_from_file_name(path: &Path) -> (String, Option<String>, Option<String>) {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ch_re = Regex::new(r"Ch\.(\d+(?:\.\d+)?)").unwrap();
+    let vol_re = Regex::new(r"Vol\.(\d+)").unwrap();
+    let number = ch_re
+        .captures(stem)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_default();
+    let volume = vol_re
+        .captures(stem)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string());
+    let title = ch_re
+        .find(stem)
+        .and_then(|m| stem[m.end()..].strip_prefix(" - "))
+        .map(str::to_string);
+    (number, volume, title)
+}
+
+/// Chapter CBZs inside `manga_dir` and its volume subfolders (bundles under
+/// `volumes/` and the cover folders are skipped), in chapter order.
+pub fn scan_chapters(manga_dir: &Path) -> std::io::Result<Vec<ChapterArchive>> {
+    let mut chapters = Vec::new();
+    let mut pending = vec![manga_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if !SKIPPED_DIRS.contains(&name) {
+                    pending.push(path);
+                }
+                continue;
+            }
+            let is_cbz = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("cbz"));
+            if is_cbz {
+                match ChapterArchive::open(&path) {
+                    Ok(chapter) => chapters.push(chapter),
+                    Err(e) => log::warn!("Skipping unreadable archive {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+    chapters.sort_by(|a, b| {
+        a.key
+            .unwrap_or(f64::MAX)
+            .total_cmp(&b.key.unwrap_or(f64::MAX))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(chapters)
+}
+
+/// Chapters matching `selection`, one archive per chapter number (the first
+/// in path order when several sources were downloaded).
+pub fn select(chapters: Vec<ChapterArchive>, selection: &Selection) -> Vec<ChapterArchive> {
+    let mut picked: Vec<ChapterArchive> = Vec::new();
+    for chapter in chapters.into_iter().filter(|c| selection.matches(c)) {
+        if !picked.iter().any(|p| p.key == chapter.key) {
+            picked.push(chapter);
+        }
+    }
+    picked
+}
+
+/// Page images of a chapter archive in entry-name order, skipping ComicInfo.xml.
+pub fn read_pages(path: &Path) -> Result<Vec<PageImage>, zip::result::ZipError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|n| !n.ends_with('/') && !n.to_lowercase().ends_with(".xml"))
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    let mut pages = Vec::with_capacity(names.len());
+    for name in names {
+        let mut data = Vec::new();
+        archive.by_name(&name)?.read_to_end(&mut data)?;
+        let format = ImageFormat::sniff(&data);
+        pages.push(PageImage { data, format });
+    }
+    Ok(pages)
+}
+
+/// Merge the chapters' pages into one [`Chapter`] whose ComicInfo describes the
+/// bundle and bookmarks the first page of every chapter.
+pub fn build_bundle(
+    series: &str,
+    selection: &Selection,
+    chapters: Vec<(ChapterArchive, Vec<PageImage>)>,
+) -> Chapter {
+    let base = chapters
+        .iter()
+        .find_map(|(c, _)| c.comicinfo.clone())
+        .unwrap_or_default();
+    let mut info = ComicInfo {
+        series: series.to_string(),
+        pages: Vec::new(),
+        ..base
+    };
+    match selection {
+        Selection::Volume(v) => {
+            info.number = v.clone();
+            info.volume = Some(v.clone());
+            info.title = Some(format!("Volume {}", v));
+        }
+        Selection::Range(from, to) => {
+            info.number = from.clone();
+            info.volume = None;
+            info.title = Some(format!("Chapters {}-{}", from, to));
+        }
+    }
+
+    let mut bookmarks = Vec::new();
+    let mut pages = Vec::new();
+    for (chapter, images) in chapters {
+        bookmarks.push((pages.len(), chapter.bookmark()));
+        pages.extend(images);
+    }
+    let mut bundle = Chapter::new(Some(&info), pages);
+    if let Some(info) = bundle.comicinfo.as_mut() {
+        for (index, bookmark) in bookmarks {
+            if let Some(page) = info.pages.get_mut(index) {
+                page.bookmark = Some(bookmark);
+            }
+        }
+    }
+    bundle
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct BundleProgress {
+    pub in_progress: bool,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub manga_id: Option<String>,
+    pub chapters_total: usize,
+    pub chapters_done: usize,
+    /// Path of the finished bundle.
+    pub output: Option<String>,
+    /// Per-chapter files deleted after bundling.
+    pub removed: usize,
+    pub error: Option<String>,
+}
+
+/// Start a bundle job for the manga titled `manga_title`. Returns `false` if
+/// one is already running.
+pub fn spawn_bundle(
+    data: web::Data<AppState>,
+    manga_id: String,
+    manga_title: String,
+    selection: Selection,
+    format: OutputFormat,
+    remove_chapters: bool,
+) -> bool {
+    {
+        let mut p = data.bundle_job.lock().unwrap();
+        if p.in_progress {
+            return false;
+        }
+        *p = BundleProgress {
+            in_progress: true,
+            started_at: Some(Utc::now().timestamp()),
+            manga_id: Some(manga_id),
+            ..Default::default()
+        };
+    }
+
+    actix_web::rt::spawn(async move {
+        let series = ChapterName::new(&manga_title, 0, "", "");
+        let series_dir = data.config.chapter_template.series_dir(&series);
+        let manga_dir = Path::new(&data.config.download_dir).join(series_dir);
+        info!("Bundling {} of {}", selection.label(), manga_title);
+
+        let dir = manga_dir.clone();
+        let chapters = match tokio::task::spawn_blocking(move || scan_chapters(&dir)).await {
+            Ok(Ok(chapters)) => select(chapters, &selection),
+            Ok(Err(e)) => {
+                return finish(
+                    &data,
+                    Some(format!("cannot read {}: {}", manga_dir.display(), e)),
+                )
+            }
+            Err(e) => return finish(&data, Some(e.to_string())),
+        };
+        if chapters.is_empty() {
+            return finish(
+                &data,
+                Some(format!(
+                    "no downloaded chapters match {}",
+                    selection.label()
+                )),
+            );
+        }
+        data.bundle_job.lock().unwrap().chapters_total = chapters.len();
+
+        let mut loaded = Vec::with_capacity(chapters.len());
+        for chapter in chapters {
+            let path = chapter.path.clone();
+            match tokio::task::spawn_blocking(move || read_pages(&path)).await {
+                Ok(Ok(pages)) => loaded.push((chapter, pages)),
+                Ok(Err(e)) => {
+                    return finish(&data, Some(format!("{}: {}", chapter.path.display(), e)))
+                }
+                Err(e) => return finish(&data, Some(e.to_string())),
+            }
+            data.bundle_job.lock().unwrap().chapters_done += 1;
+        }
+
+        let sources: Vec<PathBuf> = loaded.iter().map(|(c, _)| c.path.clone()).collect();
+        let ext = format.extension().unwrap_or("cbz");
+        let out_path = manga_dir.join("volumes").join(format!(
+            "{} - {}.{}",
+            sanitize_filename(&manga_title),
+            selection.label(),
+            ext
+        ));
+        let target = out_path.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(target.parent().unwrap())?;
+            let bundle = build_bundle(&manga_title, &selection, loaded);
+            format.writer().write_to(&bundle, &target)
+        })
+        .await;
+        match written {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return finish(&data, Some(e.to_string())),
+            Err(e) => return finish(&data, Some(e.to_string())),
+        }
+
+        let mut removed = 0;
+        if remove_chapters {
+            for path in &sources {
+                match std::fs::remove_file(path) {
+                    Ok(()) => removed += 1,
+                    Err(e) => error!("Failed to remove {}: {}", path.display(), e),
+                }
+            }
+        }
+        {
+            let mut p = data.bundle_job.lock().unwrap();
+            p.output = Some(out_path.to_string_lossy().to_string());
+            p.removed = removed;
+        }
+        finish(&data, None);
+    });
+    true
+}
+
+fn finish(data: &web::Data<AppState>, error: Option<String>) {
+    let mut p = data.bundle_job.lock().unwrap();
+    if let Some(e) = &error {
+        error!("Bundle failed: {}", e);
+    } else {
+        info!(
+            "Bundle finished: {} chapters into {}",
+            p.chapters_done,
+            p.output.as_deref().unwrap_or("")
+        );
+    }
+    p.in_progress = false;
+    p.finished_at = Some(Utc::now().timestamp());
+    p.error = error;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn png() -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn write_chapter(path: &Path, comicinfo: Option<&ComicInfo>, pages: usize) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        if let Some(info) = comicinfo {
+            zip.start_file("ComicInfo.xml", FileOptions::default())
+                .unwrap();
+            zip.write_all(info.to_xml().as_bytes()).unwrap();
+        }
+        for i in 1..=pages {
+            zip.start_file(format!("{:03}.png", i), FileOptions::default())
+                .unwrap();
+            zip.write_all(&png()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_selection_from_request() {
+        let req = |volume: Option<&str>, from: Option<&str>, to: Option<&str>| BundleRequest {
+            volume: volume.map(str::to_string),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+            format: None,
+            remove_chapters: false,
+        };
+        assert_eq!(
+            Selection::from_request(&req(Some("2"), Some("1"), Some("5"))),
+            Some(Selection::Volume("2".to_string()))
+        );
+        assert_eq!(
+            Selection::from_request(&req(None, Some("1"), Some("5.5"))),
+            Some(Selection::Range("1".to_string(), "5.5".to_string()))
+        );
+        assert_eq!(
+            Selection::from_request(&req(None, Some("9"), Some("2"))),
+            None
+        );
+        assert_eq!(Selection::from_request(&req(None, Some("1"), None)), None);
+    }
+
+    #[test]
+    fn test_scan_select_and_bundle() {
+        let dir = std::env::temp_dir().join(format!("bundle-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("volumes")).unwrap();
+
+        let info = |label: &str| ComicInfo::new("Series", label);
+        write_chapter(
+            &dir.join("2-FireScans-Series - Ch.10.cbz"),
+            Some(&info("Vol.1 Ch.10")),
+            1,
+        );
+        write_chapter(
+            &dir.join("2-FireScans-Series - Ch.2.cbz"),
+            Some(&info("Vol.1 Ch.2 - Start")),
+            2,
+        );
+        write_chapter(
+            &dir.join("9-ResetScans-Series - Ch.2.cbz"),
+            Some(&info("Vol.1 Ch.2")),
+            5,
+        );
+        write_chapter(
+            &dir.join("2-FireScans-Series - Vol.2 Ch.11 - Later.cbz"),
+            None,
+            1,
+        );
+        write_chapter(&dir.join("volumes/Series - Vol.1.cbz"), Some(&info("1")), 9);
+
+        let chapters = scan_chapters(&dir).unwrap();
+        let numbers: Vec<&str> = chapters.iter().map(|c| c.number.as_str()).collect();
+        assert_eq!(numbers, ["2", "2", "10", "11"]);
+        assert_eq!(chapters[3].volume.as_deref(), Some("2"));
+        assert_eq!(chapters[3].title.as_deref(), Some("Later"));
+
+        let selection = Selection::Volume("1".to_string());
+        let picked = select(chapters, &selection);
+        assert_eq!(picked.len(), 2);
+        assert!(picked[0].path.ends_with("2-FireScans-Series - Ch.2.cbz"));
+
+        let loaded = picked
+            .into_iter()
+            .map(|c| {
+                let pages = read_pages(&c.path).unwrap();
+                (c, pages)
+            })
+            .collect();
+        let bundle = build_bundle("Series", &selection, loaded);
+        assert_eq!(bundle.pages.len(), 3);
+        let xml = bundle.comicinfo.as_ref().unwrap().to_xml();
+        assert!(xml.contains("<Title>Volume 1</Title>"));
+        assert!(xml.contains("<PageCount>3</PageCount>"));
+        assert!(xml.contains(r#"Bookmark="Chapter 2 - Start""#));
+        assert!(xml.contains(r#"<Page Image="2" ImageSize"#));
+        assert!(xml.contains(r#"Bookmark="Chapter 10""#));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
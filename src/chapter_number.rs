@@ -0,0 +1,427 @@
+//! Canonical chapter numbers.
+//!
+//! Sources label chapters as free text ("Chapter 10.5", "Vol.2 Ch.15 - Title",
+//! "Prologue", "Side Story 3", "S2 Ep 10"). [`ChapterNumber::parse`] turns a
+//! label into a kind, an optional season and a number, and
+//! [`ChapterNumber::sort_key`] orders them: main chapters by season and number,
+//! then extras, then side stories. The key is stored as `chapters.sort_key`.
+//!
+//! [`find_gaps`] compares the main chapters of every source linked to a manga
+//! and lists, per source, the whole numbers it is missing and which other
+//! sources have them (`GET /manga/{id}/gaps`). [`merge_chapters`] builds the
+//! deduplicated chapter list served by `GET /manga/{id}/chapters?merged=true`,
+//! and [`find_same_chapter`] finds a chapter's copy on another source.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_manga_scraper::chapter_number::{ChapterKind, ChapterNumber};
+//!
+//! let n = ChapterNumber::parse("S2 Ep 10 - Return");
+//! assert_eq!((n.kind, n.season, n.number), (ChapterKind::Main, Some(2), Some(10.0)));
+//! assert!(ChapterNumber::parse("Chapter 10.5").sort_key() < n.sort_key());
+//! ```
+
+use crate::models::{Chapter, ChapterWithSource};
+use regex::Regex;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::OnceLock;
+
+/// Sort keys of one season span this much.
+const SEASON_SPAN: f64 = 100_000.0;
+/// Sort keys of one kind span this much (room for 99 seasons).
+const KIND_SPAN: f64 = 10_000_000.0;
+/// Seasons whose highest chapter is above this are treated as mis-parsed and
+/// skipped by gap detection.
+const MAX_GAP_RANGE: u32 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterKind {
+    /// Part of the main numbering; a prologue is chapter 0.
+    Main,
+    /// Extra, special, bonus, omake or epilogue chapters.
+    Extra,
+    /// Side stories and spin-offs, numbered on their own.
+    SideStory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChapterNumber {
+    pub kind: ChapterKind,
+    pub season: Option<u32>,
+    pub number: Option<f64>,
+}
+
+fn regexes() -> &'static [Regex; 3] {
+    static RE: OnceLock<[Regex; 3]> = OnceLock::new();
+    RE.get_or_init(|| {
+        [
+            Regex::new(r"\bvol(?:ume)?\.?\s*\d+").unwrap(),
+            Regex::new(
+                r"\b(?:s|season)\s*\.?\s*(\d+)\s*[,:\-]?\s*(?:e|ep|episode|ch|chapter)\.?\s*(\d+(?:\.\d+)?)",
+            )
+            .unwrap(),
+            Regex::new(r"(\d+(?:\.\d+)?)").unwrap(),
+        ]
+    })
+}
+
+impl ChapterNumber {
+    pub fn parse(label: &str) -> Self {
+        let [vol_re, season_re, number_re] = regexes();
+        let lower = label.to_lowercase();
+        let without_volume = vol_re.replace_all(&lower, "");
+        // Anything after " - " is a chapter title and may contain numbers of its own.
+        let head = without_volume
+            .split_once(" - ")
+            .map_or(without_volume.as_ref(), |(head, _)| head);
+
+        let kind = if ["side story", "side-story", "gaiden", "spin-off", "spinoff"]
+            .iter()
+            .any(|w| head.contains(w))
+        {
+            ChapterKind::SideStory
+        } else if ["extra", "special", "bonus", "omake", "epilogue"]
+            .iter()
+            .any(|w| head.contains(w))
+        {
+            ChapterKind::Extra
+        } else {
+            ChapterKind::Main
+        };
+
+        if let Some(cap) = season_re.captures(head) {
+            return Self {
+                kind,
+                season: cap[1].parse().ok(),
+                number: cap[2].parse().ok(),
+            };
+        }
+        let number = number_re
+            .captures(head)
+            .and_then(|cap| cap[1].parse().ok())
+            .or_else(|| (kind == ChapterKind::Main && head.contains("prologue")).then_some(0.0));
+        Self {
+            kind,
+            season: None,
+            number,
+        }
+    }
+
+    /// A plain chapter: main sequence, no season.
+    pub fn is_plain(&self) -> bool {
+        self.kind == ChapterKind::Main && self.season.is_none()
+    }
+
+    /// Ascending reading order. `None` for main chapters without a number,
+    /// which sort last.
+    pub fn sort_key(&self) -> Option<f64> {
+        let rank = match self.kind {
+            ChapterKind::Main => 0.0,
+            ChapterKind::Extra => 1.0,
+            ChapterKind::SideStory => 2.0,
+        };
+        if self.kind == ChapterKind::Main && self.number.is_none() {
+            return None;
+        }
+        Some(
+            rank * KIND_SPAN
+                + self.season.unwrap_or(0) as f64 * SEASON_SPAN
+                + self.number.unwrap_or(0.0),
+        )
+    }
+
+    /// The sort key when it identifies one chapter, for matching copies across
+    /// sources. `None` for labels without a number: "Extra - Beach Episode"
+    /// and "Epilogue" share a sort key without being the same chapter.
+    pub fn match_key(&self) -> Option<f64> {
+        self.number.and(self.sort_key())
+    }
+}
+
+/// A whole chapter number a source does not have.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissingChapter {
+    pub season: Option<u32>,
+    pub number: u32,
+    /// Other linked sources that have this chapter.
+    pub available_from: Vec<i32>,
+}
+
+/// Missing main chapters of each source, in the order the sources were given.
+/// Every source is compared against the whole range from 1 to the highest
+/// chapter any source has in that season; decimal chapters are never expected.
+pub fn find_gaps(sources: &[(i32, Vec<ChapterNumber>)]) -> Vec<(i32, Vec<MissingChapter>)> {
+    // (season, whole number) -> sources that have it
+    let mut have: BTreeMap<(Option<u32>, u32), BTreeSet<i32>> = BTreeMap::new();
+    let mut highest: BTreeMap<Option<u32>, u32> = BTreeMap::new();
+    for (source_id, numbers) in sources {
+        for n in numbers.iter().filter(|n| n.kind == ChapterKind::Main) {
+            let Some(number) = n.number.filter(|x| x.fract() == 0.0 && *x >= 0.0) else {
+                continue;
+            };
+            let number = number as u32;
+            have.entry((n.season, number))
+                .or_default()
+                .insert(*source_id);
+            let top = highest.entry(n.season).or_default();
+            *top = (*top).max(number);
+        }
+    }
+
+    sources
+        .iter()
+        .map(|(source_id, _)| {
+            let missing = highest
+                .iter()
+                .filter(|(_, top)| **top <= MAX_GAP_RANGE)
+                .flat_map(|(season, top)| (1..=*top).map(move |n| (*season, n)))
+                .filter_map(|key| {
+                    let holders = have.get(&key);
+                    if holders.is_some_and(|h| h.contains(source_id)) {
+                        return None;
+                    }
+                    Some(MissingChapter {
+                        season: key.0,
+                        number: key.1,
+                        available_from: holders
+                            .map(|h| h.iter().copied().collect())
+                            .unwrap_or_default(),
+                    })
+                })
+                .collect();
+            (*source_id, missing)
+        })
+        .collect()
+}
+
+/// The chapter in `chapters` with the same canonical number as `label` (see
+/// [`ChapterNumber::match_key`]). There is no fuzzy fallback: "Chapter 10" is
+/// never a copy of "Chapter 1", nor "Chapter 3" of "Side Story 3".
+pub fn find_same_chapter<'a>(chapters: &'a [Chapter], label: &str) -> Option<&'a Chapter> {
+    let key = ChapterNumber::parse(label).match_key()?;
+    chapters
+        .iter()
+        .find(|c| ChapterNumber::parse(&c.chapter_number).match_key() == Some(key))
+}
+
+/// One chapter of the merged cross-source view: the copy that will be served
+/// plus the other sources that have the same chapter, in fallback order.
+#[derive(Debug, Serialize)]
+pub struct MergedChapter {
+    #[serde(flatten)]
+    pub chapter: ChapterWithSource,
+    pub fallback_sources: Vec<i32>,
+}
+
+/// Deduplicate chapters across sources by canonical number. `chapters` must be
+/// in source priority order; the first source with a chapter serves it. The
+/// result is in reading order, with chapters that have no number at the end.
+pub fn merge_chapters(chapters: Vec<ChapterWithSource>) -> Vec<MergedChapter> {
+    let mut merged: Vec<(Option<f64>, MergedChapter)> = Vec::new();
+    let mut by_key: HashMap<u64, usize> = HashMap::new();
+    for chapter in chapters {
+        let parsed = ChapterNumber::parse(&chapter.chapter_number);
+        let key = parsed.sort_key();
+        // Unnumbered chapters share a sort key with every other chapter of
+        // their kind, so they are never merged.
+        if let Some(&i) = parsed.match_key().and_then(|k| by_key.get(&k.to_bits())) {
+            let entry = &mut merged[i].1;
+            if entry.chapter.source_id != chapter.source_id
+                && !entry.fallback_sources.contains(&chapter.source_id)
+            {
+                entry.fallback_sources.push(chapter.source_id);
+            }
+            continue;
+        }
+        if let Some(k) = parsed.match_key() {
+            by_key.insert(k.to_bits(), merged.len());
+        }
+        merged.push((
+            key,
+            MergedChapter {
+                chapter,
+                fallback_sources: Vec::new(),
+            },
+        ));
+    }
+    merged.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    merged.into_iter().map(|(_, m)| m).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(label: &str) -> (ChapterKind, Option<u32>, Option<f64>) {
+        let n = ChapterNumber::parse(label);
+        (n.kind, n.season, n.number)
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        use ChapterKind::*;
+        assert_eq!(parse("Chapter 10.5"), (Main, None, Some(10.5)));
+        assert_eq!(
+            parse("Vol.2 Ch.15 - 100 Days Later"),
+            (Main, None, Some(15.0))
+        );
+        assert_eq!(parse("Prologue"), (Main, None, Some(0.0)));
+        assert_eq!(parse("Extra"), (Extra, None, None));
+        assert_eq!(parse("Side Story 3"), (SideStory, None, Some(3.0)));
+        assert_eq!(parse("S2 Ep 10"), (Main, Some(2), Some(10.0)));
+        assert_eq!(parse("Season 1 Episode 4"), (Main, Some(1), Some(4.0)));
+        assert_eq!(parse("Oneshot"), (Main, None, None));
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let mut labels = vec![
+            "Side Story 1",
+            "Extra",
+            "S2 Ep 1",
+            "Chapter 10",
+            "Chapter 9.5",
+            "Prologue",
+            "Chapter 2",
+        ];
+        labels.sort_by(|a, b| {
+            let (a, b) = (ChapterNumber::parse(a), ChapterNumber::parse(b));
+            a.sort_key().partial_cmp(&b.sort_key()).unwrap()
+        });
+        assert_eq!(
+            labels,
+            vec![
+                "Prologue",
+                "Chapter 2",
+                "Chapter 9.5",
+                "Chapter 10",
+                "S2 Ep 1",
+                "Extra",
+                "Side Story 1"
+            ]
+        );
+        assert_eq!(ChapterNumber::parse("Oneshot").sort_key(), None);
+    }
+
+    #[test]
+    fn test_match_key() {
+        let key = |label: &str| ChapterNumber::parse(label).match_key();
+        assert_eq!(key("Ch. 5"), key("Chapter 5"));
+        assert_eq!(key("Extra 2"), key("Bonus 2"));
+        assert_ne!(key("Chapter 1"), key("Chapter 10"));
+        assert_ne!(key("Side Story 3"), key("Chapter 3"));
+        assert!(ChapterNumber::parse("Extra - Beach Episode").sort_key().is_some());
+        assert_eq!(key("Extra - Beach Episode"), None);
+        assert_eq!(key("Epilogue"), None);
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let numbers = |labels: &[&str]| labels.iter().map(|l| ChapterNumber::parse(l)).collect();
+        let gaps = find_gaps(&[
+            (
+                1,
+                numbers(&[
+                    "Chapter 1",
+                    "Chapter 2",
+                    "Chapter 4",
+                    "Chapter 4.5",
+                    "Extra",
+                ]),
+            ),
+            (2, numbers(&["Chapter 1", "Chapter 3", "Chapter 5"])),
+        ]);
+        assert_eq!(
+            gaps[0],
+            (
+                1,
+                vec![
+                    MissingChapter {
+                        season: None,
+                        number: 3,
+                        available_from: vec![2]
+                    },
+                    MissingChapter {
+                        season: None,
+                        number: 5,
+                        available_from: vec![2]
+                    },
+                ]
+            )
+        );
+        let missing: Vec<u32> = gaps[1].1.iter().map(|m| m.number).collect();
+        assert_eq!(missing, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_merge_chapters_prefers_first_source() {
+        let chapter = |id: i32, source_id: i32, label: &str| {
+            ChapterWithSource::new(
+                crate::models::Chapter {
+                    id,
+                    chapter_number: label.to_string(),
+                    ..Default::default()
+                },
+                source_id,
+                format!("Source {}", source_id),
+            )
+        };
+        let merged = merge_chapters(vec![
+            chapter(1, 7, "Chapter 2"),
+            chapter(2, 7, "Side Story 1"),
+            chapter(3, 3, "Ch. 1"),
+            chapter(4, 3, "Chapter 2"),
+            chapter(5, 3, "Oneshot"),
+            chapter(6, 3, "Extra - Beach Episode"),
+            chapter(7, 3, "Special - Festival"),
+        ]);
+        let served: Vec<(i32, Vec<i32>)> = merged
+            .iter()
+            .map(|m| (m.chapter.id, m.fallback_sources.clone()))
+            .collect();
+        assert_eq!(
+            served,
+            vec![
+                (3, vec![]),
+                (1, vec![3]),
+                (6, vec![]),
+                (7, vec![]),
+                (2, vec![]),
+                (5, vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_same_chapter_needs_equal_number() {
+        let chapters = |labels: &[&str]| -> Vec<Chapter> {
+            labels
+                .iter()
+                .enumerate()
+                .map(|(i, l)| Chapter {
+                    id: i as i32 + 1,
+                    chapter_number: l.to_string(),
+                    ..Default::default()
+                })
+                .collect()
+        };
+        let found = |labels: &[&str], label: &str| {
+            find_same_chapter(&chapters(labels), label).map(|c| c.id)
+        };
+        assert_eq!(found(&["Chapter 10"], "Chapter 1"), None);
+        assert_eq!(found(&["Chapter 2"], "Vol.2 Ch.15"), None);
+        assert_eq!(found(&["Chapter 3"], "Side Story 3"), None);
+        assert_eq!(found(&["Extra"], "Extra - Beach Episode"), None);
+        assert_eq!(found(&["Chapter 10", "Ch. 1"], "Chapter 1"), Some(2));
+        assert_eq!(found(&["Vol.1 Ch. 15 - Title"], "Chapter 15"), Some(1));
+    }
+}
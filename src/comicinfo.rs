@@ -0,0 +1,462 @@
+//! ComicInfo.xml generation (ComicInfo 2.x schema).
+//!
+//! [`ComicInfo::for_chapter`] fills the series-level fields from the stored
+//! manga record and the source catalogue; the downloader then adds one
+//! [`ComicPage`] per page with [`ComicInfo::with_pages`] once the images are
+//! fetched, so `PageCount`, `<Pages>` and webtoon detection reflect what is
+//! actually in the archive. Elements are written in schema order, which strict
+//! readers such as Komga and Kavita expect.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_manga_scraper::comicinfo::{age_rating, ComicInfo};
+//!
+//! let mut info = ComicInfo::new("One Piece", "Vol.3 Chapter 21 - Town");
+//! info.age_rating = age_rating("suggestive");
+//! let xml = info.to_xml();
+//! assert!(xml.contains("<Number>21</Number>"));
+//! assert!(xml.contains("<Volume>3</Volume>"));
+//! assert!(xml.contains("<AgeRating>Teen</AgeRating>"));
+//! ```
+
+use crate::helpers::{extract_number, xml_escape, xml_unescape};
+use crate::images::{self, ImageFormat};
+use crate::models::Manga;
+use crate::people::{self, PersonRole};
+use crate::sources::catalogue::{self, EngineFamily};
+use regex::Regex;
+
+/// Pages at least this many times taller than wide are long-strip webtoon pages.
+const WEBTOON_ASPECT_RATIO: f64 = 2.0;
+
+/// Reading style, written as the `Manga` element (plus `Format` for webtoons).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingStyle {
+    /// Japanese manga, read right to left.
+    RightToLeft,
+    /// Manhwa / manhua paged left to right.
+    LeftToRight,
+    /// Vertical long strip.
+    Webtoon,
+}
+
+/// One `<Page>` entry. Dimensions are `None` when the header cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComicPage {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size: usize,
+    /// Reader bookmark, e.g. the chapter starting on this page in a volume bundle.
+    pub bookmark: Option<String>,
+}
+
+impl ComicPage {
+    /// Read the page dimensions from the image header.
+    pub fn measure(data: &[u8], format: Option<ImageFormat>) -> Self {
+        let dims = format.and_then(|f| images::dimensions(data, f));
+        Self {
+            width: dims.map(|d| d.0),
+            height: dims.map(|d| d.1),
+            size: data.len(),
+            bookmark: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ComicInfo {
+    pub title: Option<String>,
+    pub series: String,
+    pub number: String,
+    pub volume: Option<String>,
+    pub summary: Option<String>,
+    pub year: Option<i32>,
+    pub writer: Option<String>,
+    pub penciller: Option<String>,
+    /// The scanlation group (or publisher) that released the chapter.
+    pub publisher: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<String>,
+    /// Public URL of the chapter on its source.
+    pub web: Option<String>,
+    pub language_iso: Option<String>,
+    pub reading_style: Option<ReadingStyle>,
+    /// One of the schema's `AgeRating` values, see [`age_rating`].
+    pub age_rating: Option<&'static str>,
+    pub pages: Vec<ComicPage>,
+}
+
+impl ComicInfo {
+    /// Series and chapter fields only. `Number`, `Volume` and `Title` are
+    /// parsed from labels like `"Vol.2 Chapter 10.5 - Title"`.
+    pub fn new(series: &str, chapter_label: &str) -> Self {
+        let (number, volume, title) = parse_chapter_label(chapter_label);
+        Self {
+            series: series.to_string(),
+            number,
+            volume,
+            title,
+            ..Default::default()
+        }
+    }
+
+    /// Everything we know about a chapter before its pages are fetched: manga
+    /// metadata, the chapter URL and, for scanlation sites, the group name.
+    pub fn for_chapter(
+        manga: &Manga,
+        source_id: i32,
+        chapter_label: &str,
+        chapter_url: &str,
+    ) -> Self {
+        let mut info = Self::new(&manga.title, chapter_label);
+        info.summary = non_empty(manga.description.as_deref());
+        info.writer = people::names(&manga.people, PersonRole::Author);
+        info.penciller = people::names(&manga.people, PersonRole::Artist);
+        info.genre = non_empty(manga.tags.as_deref());
+        info.tags = info.genre.clone();
+        info.age_rating = manga.rating.as_deref().and_then(age_rating);
+        info.web = chapter_web_url(source_id, chapter_url);
+        info.publisher = catalogue::get(source_id)
+            .filter(|e| {
+                !matches!(
+                    e.engine,
+                    EngineFamily::MangaDexApi | EngineFamily::MetadataApi
+                ) && e.slug != "others"
+            })
+            .map(|e| e.name.to_string());
+        // Every catalogued source publishes English releases.
+        info.language_iso = Some("en".to_string());
+        info.reading_style = Some(reading_style_from_tags(manga.tags.as_deref().unwrap_or("")));
+        info
+    }
+
+    /// Attach the archive's pages, in reading order. Chapters whose pages are
+    /// mostly long strips are marked as webtoons whatever the tags said.
+    pub fn with_pages(mut self, pages: Vec<ComicPage>) -> Self {
+        let ratios: Vec<f64> = pages
+            .iter()
+            .filter_map(|p| match (p.width, p.height) {
+                (Some(w), Some(h)) if w > 0 => Some(h as f64 / w as f64),
+                _ => None,
+            })
+            .collect();
+        let tall = ratios
+            .iter()
+            .filter(|r| **r >= WEBTOON_ASPECT_RATIO)
+            .count();
+        if !ratios.is_empty() && tall * 2 > ratios.len() {
+            self.reading_style = Some(ReadingStyle::Webtoon);
+        }
+        self.pages = pages;
+        self
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="utf-8"?>"#.to_string(),
+            r#"<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema">"#.to_string(),
+        ];
+        let mut element = |name: &str, value: Option<&str>| {
+            if let Some(v) = value {
+                lines.push(format!("  <{0}>{1}</{0}>", name, xml_escape(v)));
+            }
+        };
+        element("Title", self.title.as_deref());
+        element("Series", Some(&self.series));
+        element("Number", Some(&self.number));
+        element("Volume", self.volume.as_deref());
+        element("Summary", self.summary.as_deref());
+        element("Year", self.year.map(|y| y.to_string()).as_deref());
+        element("Writer", self.writer.as_deref());
+        element("Penciller", self.penciller.as_deref());
+        element("Publisher", self.publisher.as_deref());
+        element("Genre", self.genre.as_deref());
+        element("Tags", self.tags.as_deref());
+        element("Web", self.web.as_deref());
+        if !self.pages.is_empty() {
+            element("PageCount", Some(&self.pages.len().to_string()));
+        }
+        element("LanguageISO", self.language_iso.as_deref());
+        let (format, manga) = match self.reading_style {
+            Some(ReadingStyle::RightToLeft) => (None, Some("YesAndRightToLeft")),
+            Some(ReadingStyle::LeftToRight) => (None, Some("Yes")),
+            Some(ReadingStyle::Webtoon) => (Some("Webtoon"), Some("Yes")),
+            None => (None, None),
+        };
+        element("Format", format);
+        element("Manga", manga);
+        element("AgeRating", self.age_rating);
+
+        if !self.pages.is_empty() {
+            lines.push("  <Pages>".to_string());
+            for (i, page) in self.pages.iter().enumerate() {
+                let mut attrs = format!(r#"Image="{}""#, i);
+                if i == 0 {
+                    attrs.push_str(r#" Type="FrontCover""#);
+                }
+                attrs.push_str(&format!(r#" ImageSize="{}""#, page.size));
+                if let (Some(w), Some(h)) = (page.width, page.height) {
+                    attrs.push_str(&format!(r#" ImageWidth="{}" ImageHeight="{}""#, w, h));
+                }
+                if let Some(b) = &page.bookmark {
+                    attrs.push_str(&format!(r#" Bookmark="{}""#, xml_escape(b)));
+                }
+                lines.push(format!("    <Page {} />", attrs));
+            }
+            lines.push("  </Pages>".to_string());
+        }
+        lines.push("</ComicInfo>".to_string());
+        lines.join("\n")
+    }
+}
+
+/// `AgeRating` values of the ComicInfo 2.x schema.
+const AGE_RATINGS: &[&str] = &[
+    "Adults Only 18+",
+    "Early Childhood",
+    "Everyone",
+    "Everyone 10+",
+    "G",
+    "Kids to Adults",
+    "M",
+    "MA15+",
+    "Mature 17+",
+    "PG",
+    "R18+",
+    "Rating Pending",
+    "Teen",
+    "X18+",
+];
+
+impl ComicInfo {
+    /// Read back the series and chapter fields of a ComicInfo.xml written by
+    /// [`to_xml`](Self::to_xml) or another tool. `<Pages>` is not parsed.
+    /// Returns `None` when there is no `<Series>`.
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let field = |name: &str| {
+            let re = Regex::new(&format!(r"(?s)<{0}>(.*?)</{0}>", name)).ok()?;
+            let value = xml_unescape(re.captures(xml)?.get(1)?.as_str().trim());
+            (!value.is_empty()).then_some(value)
+        };
+        let reading_style = match (field("Format").as_deref(), field("Manga").as_deref()) {
+            (Some(f), _) if f.eq_ignore_ascii_case("webtoon") => Some(ReadingStyle::Webtoon),
+            (_, Some("YesAndRightToLeft")) => Some(ReadingStyle::RightToLeft),
+            (_, Some("Yes")) => Some(ReadingStyle::LeftToRight),
+            _ => None,
+        };
+        Some(Self {
+            title: field("Title"),
+            series: field("Series")?,
+            number: field("Number").unwrap_or_default(),
+            volume: field("Volume"),
+            summary: field("Summary"),
+            year: field("Year").and_then(|y| y.parse().ok()),
+            writer: field("Writer"),
+            penciller: field("Penciller"),
+            publisher: field("Publisher"),
+            genre: field("Genre"),
+            tags: field("Tags"),
+            web: field("Web"),
+            language_iso: field("LanguageISO"),
+            reading_style,
+            age_rating: field("AgeRating").and_then(|r| {
+                AGE_RATINGS
+                    .iter()
+                    .find(|a| a.eq_ignore_ascii_case(&r))
+                    .copied()
+            }),
+            pages: Vec::new(),
+        })
+    }
+}
+
+fn non_empty(s: Option<&str>) -> Option<String> {
+    s.map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Split a chapter label into `(number, volume, title)`. Labels without a
+/// number (e.g. `"byurl"`) are kept whole as the number.
+pub(crate) fn parse_chapter_label(label: &str) -> (String, Option<String>, Option<String>) {
+    let vol_re = Regex::new(r"(?i)\bvol(?:ume)?\.?\s*(\d+)").unwrap();
+    let volume = vol_re
+        .captures(label)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string());
+    let without_volume = vol_re.replace(label, "");
+    let (head, title) = match without_volume.split_once(" - ") {
+        Some((head, title)) => (head.to_string(), non_empty(Some(title))),
+        None => (without_volume.to_string(), None),
+    };
+    let number = extract_number(&head).unwrap_or_else(|| label.trim().to_string());
+    (number, volume, title)
+}
+
+/// Reading style implied by the genre tags; defaults to right-to-left manga.
+fn reading_style_from_tags(tags: &str) -> ReadingStyle {
+    let tags = tags.to_lowercase();
+    if tags.contains("long strip") || tags.contains("webtoon") {
+        ReadingStyle::Webtoon
+    } else if tags.contains("manhwa") || tags.contains("manhua") {
+        ReadingStyle::LeftToRight
+    } else {
+        ReadingStyle::RightToLeft
+    }
+}
+
+/// Map a stored `manga.rating` (MangaDex content rating, Jikan rating string,
+/// or the AniList adult flag stored as `erotica`) to a ComicInfo `AgeRating`.
+pub fn age_rating(rating: &str) -> Option<&'static str> {
+    let r = rating.trim().to_lowercase();
+    let rated = match r.as_str() {
+        "safe" => "Everyone",
+        "suggestive" => "Teen",
+        "erotica" => "Mature 17+",
+        "pornographic" => "Adults Only 18+",
+        _ if r.starts_with("g ") || r.starts_with("g-") => "G",
+        _ if r.starts_with("pg-13") => "Teen",
+        _ if r.starts_with("pg") => "PG",
+        _ if r.starts_with("rx") => "X18+",
+        _ if r.starts_with("r+") || r.starts_with("r ") || r.starts_with("r-") => "Mature 17+",
+        _ => return None,
+    };
+    Some(rated)
+}
+
+/// Browser URL for a chapter. MangaDex chapters are stored by UUID.
+pub(crate) fn chapter_web_url(source_id: i32, chapter_url: &str) -> Option<String> {
+    if chapter_url.starts_with("http://") || chapter_url.starts_with("https://") {
+        Some(chapter_url.to_string())
+    } else if source_id == 1 && !chapter_url.is_empty() {
+        Some(format!("https://mangadex.org/chapter/{}", chapter_url))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manga(tags: Option<&str>, rating: Option<&str>) -> Manga {
+        Manga {
+            id: "m1".to_string(),
+            title: "Solo <Leveling>".to_string(),
+            alt_titles: None,
+            cover_url: None,
+            description: Some("A hunter & his shadows".to_string()),
+            tags: tags.map(str::to_string),
+            rating: rating.map(str::to_string),
+            monitored: None,
+            check_interval_secs: None,
+            discover_interval_secs: None,
+            last_chapter_check: None,
+            last_discover_check: None,
+            status: None,
+            people: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_chapter_label() {
+        assert_eq!(
+            parse_chapter_label("Vol.2 Chapter 10.5 - The Gate"),
+            (
+                "10.5".to_string(),
+                Some("2".to_string()),
+                Some("The Gate".to_string())
+            )
+        );
+        assert_eq!(parse_chapter_label("Ch. 7"), ("7".to_string(), None, None));
+        assert_eq!(
+            parse_chapter_label("byurl"),
+            ("byurl".to_string(), None, None)
+        );
+    }
+
+    #[test]
+    fn test_age_rating_mapping() {
+        assert_eq!(age_rating("Safe"), Some("Everyone"));
+        assert_eq!(age_rating("pornographic"), Some("Adults Only 18+"));
+        assert_eq!(age_rating("PG-13 - Teens 13 or older"), Some("Teen"));
+        assert_eq!(age_rating("R+ - Mild Nudity"), Some("Mature 17+"));
+        assert_eq!(age_rating("Rx - Hentai"), Some("X18+"));
+        assert_eq!(age_rating("G - All Ages"), Some("G"));
+        assert_eq!(age_rating("unknown"), None);
+    }
+
+    #[test]
+    fn test_for_chapter_fills_series_fields() {
+        let mut m = manga(Some("Action, Manhwa"), Some("suggestive"));
+        m.people = vec![
+            people::credit("Chugong", PersonRole::Author).unwrap(),
+            people::credit("DUBU", PersonRole::Artist).unwrap(),
+            people::credit("D&C Media", PersonRole::Publisher).unwrap(),
+        ];
+        let xml = ComicInfo::for_chapter(&m, 2, "Chapter 3", "https://firescans.xyz/ch-3").to_xml();
+        assert!(xml.contains("<Series>Solo &lt;Leveling&gt;</Series>"));
+        assert!(xml.contains("<Summary>A hunter &amp; his shadows</Summary>"));
+        assert!(xml.contains("<Writer>Chugong</Writer>"));
+        assert!(xml.contains("<Penciller>DUBU</Penciller>"));
+        assert!(xml.contains("<Publisher>FireScans</Publisher>"));
+        assert!(xml.contains("<Genre>Action, Manhwa</Genre>"));
+        assert!(xml.contains("<Web>https://firescans.xyz/ch-3</Web>"));
+        assert!(xml.contains("<LanguageISO>en</LanguageISO>"));
+        assert!(xml.contains("<Manga>Yes</Manga>"));
+        assert!(xml.contains("<AgeRating>Teen</AgeRating>"));
+        assert!(!xml.contains("<Pages>"));
+
+        let md = ComicInfo::for_chapter(&manga(None, None), 1, "1", "abc-123");
+        assert_eq!(md.publisher, None);
+        assert_eq!(md.writer, None);
+        assert_eq!(
+            md.web.as_deref(),
+            Some("https://mangadex.org/chapter/abc-123")
+        );
+        assert_eq!(md.reading_style, Some(ReadingStyle::RightToLeft));
+    }
+
+    #[test]
+    fn test_from_xml_round_trip() {
+        let m = manga(Some("Action"), Some("erotica"));
+        let mut info =
+            ComicInfo::for_chapter(&m, 2, "Vol.1 Ch.4 - Rain", "https://firescans.xyz/4");
+        info.year = Some(2021);
+        let parsed = ComicInfo::from_xml(&info.to_xml()).unwrap();
+        assert_eq!(parsed.series, "Solo <Leveling>");
+        assert_eq!(parsed.number, "4");
+        assert_eq!(parsed.volume.as_deref(), Some("1"));
+        assert_eq!(parsed.title.as_deref(), Some("Rain"));
+        assert_eq!(parsed.year, Some(2021));
+        assert_eq!(parsed.age_rating, Some("Mature 17+"));
+        assert_eq!(parsed.reading_style, Some(ReadingStyle::RightToLeft));
+        assert_eq!(parsed.summary, info.summary);
+        assert!(ComicInfo::from_xml("<ComicInfo/>").is_none());
+    }
+
+    #[test]
+    fn test_pages_and_webtoon_detection() {
+        let strip = |h| ComicPage {
+            width: Some(800),
+            height: Some(h),
+            size: 10,
+            bookmark: None,
+        };
+        let info = ComicInfo::new("S", "1").with_pages(vec![strip(4000), strip(4000), strip(900)]);
+        assert_eq!(info.reading_style, Some(ReadingStyle::Webtoon));
+        let xml = info.to_xml();
+        assert!(xml.contains("<PageCount>3</PageCount>"));
+        assert!(xml.contains("<Format>Webtoon</Format>"));
+        assert!(xml.contains(
+            r#"<Page Image="0" Type="FrontCover" ImageSize="10" ImageWidth="800" ImageHeight="4000" />"#
+        ));
+        assert!(
+            xml.contains(r#"<Page Image="2" ImageSize="10" ImageWidth="800" ImageHeight="900" />"#)
+        );
+
+        let paged = ComicInfo::new("S", "1").with_pages(vec![strip(1200)]);
+        assert_eq!(paged.reading_style, None);
+    }
+}
@@ -5,8 +5,93 @@ use std::path::Path;
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub download_dir: String,
+    /// Default chapter output format (`cbz`, `epub`, `pdf` or `folder`),
+    /// overridden per manga and per request
+    #[serde(default)]
+    pub output_format: crate::output::OutputFormat,
+    /// Where chapters are saved inside `download_dir`, see [`crate::naming`]
+    #[serde(default)]
+    pub chapter_template: crate::naming::Template,
+    /// Re-encode downloaded pages into this format (`jpeg`, `png` or `webp`);
+    /// pages are stored as served when unset
+    #[serde(default)]
+    pub transcode_pages: Option<crate::images::ImageFormat>,
+    /// Page bodies smaller than this many bytes are rejected as broken
+    #[serde(default = "default_min_page_bytes")]
+    pub min_page_bytes: usize,
+    /// Number of workers draining the download queue
+    #[serde(default = "default_download_workers")]
+    pub download_workers: usize,
+    /// Attempts a queued download gets before it is marked failed
+    #[serde(default = "default_download_max_attempts")]
+    pub download_max_attempts: i32,
+    /// Delay before the first retry of a failed download; doubles per attempt
+    #[serde(default = "default_download_retry_delay")]
+    pub download_retry_delay_secs: u64,
+    /// Series matching scores at or above this become merge proposals
+    #[serde(default = "default_matching_min_score")]
+    pub matching_min_score: f64,
+    /// Merge automatically at or above this score; proposals only when unset
+    #[serde(default)]
+    pub matching_auto_merge_threshold: Option<f64>,
     #[serde(default)]
     pub bot_detection: BotDetectionConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+}
+
+/// Where the HTTP API listens and what callers without an API key may do
+#[derive(Debug, Deserialize, Clone)]
+pub struct ServerConfig {
+    /// Address to listen on; "0.0.0.0" accepts connections from the network
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    /// Port to listen on; the first free port in 8080-8090 when unset
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// PEM certificate chain; HTTPS is served when this and `tls_key` are set
+    #[serde(default)]
+    pub tls_cert: Option<String>,
+    /// PEM private key for `tls_cert`
+    #[serde(default)]
+    pub tls_key: Option<String>,
+    /// Scopes granted to requests without an API key (`admin` is ignored).
+    /// Must be empty unless `bind` is a loopback address.
+    #[serde(default = "default_anonymous_scopes")]
+    pub anonymous_scopes: Vec<crate::auth::Scope>,
+}
+
+/// Which storage backend holds the library, see [`crate::storage`]
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Postgres,
+    Sqlite,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DatabaseConfig {
+    #[serde(default)]
+    pub backend: Backend,
+    /// PostgreSQL connection URL; overrides the individual settings below
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default = "default_db_host")]
+    pub host: String,
+    #[serde(default = "default_db_port")]
+    pub port: u16,
+    #[serde(default = "default_db_name")]
+    pub dbname: String,
+    #[serde(default = "default_db_user")]
+    pub user: String,
+    #[serde(default = "default_db_password")]
+    pub password: String,
+    /// Database file used by the SQLite backend
+    #[serde(default = "default_sqlite_path")]
+    pub sqlite_path: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -58,6 +143,10 @@ pub struct BotDetectionConfig {
     /// Rate limiting delay between requests in milliseconds
     #[serde(default = "default_rate_limit")]
     pub _rate_limit_delay_ms: u64,
+
+    /// Maximum number of page images fetched at once from a single host
+    #[serde(default = "default_pages_per_host")]
+    pub max_concurrent_pages_per_host: usize,
 }
 
 fn default_true() -> bool {
@@ -84,6 +173,118 @@ fn default_browser_timeout() -> u64 {
 fn default_rate_limit() -> u64 {
     300
 }
+fn default_min_page_bytes() -> usize {
+    256
+}
+fn default_pages_per_host() -> usize {
+    4
+}
+fn default_download_workers() -> usize {
+    2
+}
+fn default_download_max_attempts() -> i32 {
+    5
+}
+fn default_download_retry_delay() -> u64 {
+    60
+}
+fn default_matching_min_score() -> f64 {
+    0.8
+}
+fn default_db_host() -> String {
+    "localhost".to_string()
+}
+fn default_db_port() -> u16 {
+    5432
+}
+fn default_db_name() -> String {
+    "manga_scraper".to_string()
+}
+fn default_db_user() -> String {
+    "manga_admin".to_string()
+}
+fn default_db_password() -> String {
+    "manga_password".to_string()
+}
+fn default_sqlite_path() -> String {
+    "manga.db".to_string()
+}
+fn default_bind() -> String {
+    "127.0.0.1".to_string()
+}
+fn default_anonymous_scopes() -> Vec<crate::auth::Scope> {
+    vec![crate::auth::Scope::Read, crate::auth::Scope::Download]
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: default_bind(),
+            port: None,
+            tls_cert: None,
+            tls_key: None,
+            anonymous_scopes: default_anonymous_scopes(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Ports to try binding, in order
+    pub fn ports(&self) -> Vec<u16> {
+        match self.port {
+            Some(port) => vec![port],
+            None => (8080..=8090).collect(),
+        }
+    }
+
+    /// TLS settings for `HttpServer::bind_openssl`, or `None` to serve plain HTTP
+    pub fn tls_acceptor(&self) -> std::io::Result<Option<openssl::ssl::SslAcceptorBuilder>> {
+        use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+        let (cert, key) = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) => return Ok(None),
+            _ => {
+                return Err(std::io::Error::other(
+                    "[server] tls_cert and tls_key must be set together",
+                ))
+            }
+        };
+        let mut builder =
+            SslAcceptor::mozilla_intermediate(SslMethod::tls()).map_err(std::io::Error::other)?;
+        builder
+            .set_private_key_file(key, SslFiletype::PEM)
+            .map_err(|e| std::io::Error::other(format!("{}: {}", key, e)))?;
+        builder
+            .set_certificate_chain_file(cert)
+            .map_err(|e| std::io::Error::other(format!("{}: {}", cert, e)))?;
+        Ok(Some(builder))
+    }
+
+    /// Whether `bind` only accepts connections from this machine
+    pub fn is_loopback(&self) -> bool {
+        self.bind == "localhost"
+            || self
+                .bind
+                .parse::<std::net::IpAddr>()
+                .map(|ip| ip.is_loopback())
+                .unwrap_or(false)
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            url: None,
+            host: default_db_host(),
+            port: default_db_port(),
+            dbname: default_db_name(),
+            user: default_db_user(),
+            password: default_db_password(),
+            sqlite_path: default_sqlite_path(),
+        }
+    }
+}
 
 impl Default for BotDetectionConfig {
     fn default() -> Self {
@@ -100,6 +301,7 @@ impl Default for BotDetectionConfig {
             _browser_headless: true,
             _browser_disable_images: true,
             _rate_limit_delay_ms: 300,
+            max_concurrent_pages_per_host: 4,
         }
     }
 }
@@ -108,22 +310,83 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             download_dir: "downloads".to_string(),
+            output_format: Default::default(),
+            chapter_template: Default::default(),
+            transcode_pages: None,
+            min_page_bytes: default_min_page_bytes(),
+            download_workers: default_download_workers(),
+            download_max_attempts: default_download_max_attempts(),
+            download_retry_delay_secs: default_download_retry_delay(),
+            matching_min_score: default_matching_min_score(),
+            matching_auto_merge_threshold: None,
             bot_detection: BotDetectionConfig::default(),
+            database: DatabaseConfig::default(),
+            server: ServerConfig::default(),
         }
     }
 }
 
 impl Config {
+    /// `config.toml`, or the defaults when it is missing or invalid.
     pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|e| {
+            log::warn!("{}; using the default configuration", e);
+            Self::default()
+        })
+    }
+
+    /// `config.toml` (the defaults when it is missing), failing when the file
+    /// does not parse or [`Config::validate`] rejects it.
+    pub fn try_load() -> Result<Self, String> {
         let path = Path::new("config.toml");
-        if path.exists() {
-            if let Ok(content) = fs::read_to_string(path) {
-                if let Ok(cfg) = toml::from_str::<Config>(&content) {
-                    return cfg;
-                }
-            }
+        let cfg = if path.exists() {
+            let content = fs::read_to_string(path).map_err(|e| format!("config.toml: {}", e))?;
+            toml::from_str::<Config>(&content).map_err(|e| format!("config.toml: {}", e))?
+        } else {
+            Self::default()
+        };
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    /// Reject settings that parse but cannot work.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(format) = self.transcode_pages.filter(|f| !f.can_encode()) {
+            return Err(format!(
+                "transcode_pages = \"{}\" is not supported, use jpeg, png or webp",
+                format.extension()
+            ));
+        }
+        if !self.server.is_loopback() && !self.server.anonymous_scopes.is_empty() {
+            return Err(format!(
+                "[server] bind = \"{}\" accepts connections from the network, so \
+                 anonymous_scopes must be [] and every caller needs an API key",
+                self.server.bind
+            ));
         }
-        Self::default()
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images::ImageFormat;
+
+    #[test]
+    fn test_validate() {
+        let mut cfg = Config::default();
+        assert!(cfg.validate().is_ok());
+
+        cfg.transcode_pages = Some(ImageFormat::Avif);
+        assert!(cfg.validate().unwrap_err().contains("transcode_pages"));
+        cfg.transcode_pages = Some(ImageFormat::Webp);
+        assert!(cfg.validate().is_ok());
+
+        cfg.server.bind = "0.0.0.0".to_string();
+        assert!(cfg.validate().unwrap_err().contains("anonymous_scopes"));
+        cfg.server.anonymous_scopes.clear();
+        assert!(cfg.validate().is_ok());
     }
 }
 
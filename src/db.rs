@@ -1,112 +1,70 @@
+//! SQLite versions of the repository functions in `pg_db.rs`, used by
+//! [`crate::storage::sqlite::SqliteStorage`]. Each function mirrors its
+//! PostgreSQL counterpart; see there for semantics. The schema is created in
+//! place by [`create_tables`] rather than by the numbered migrations, which
+//! are PostgreSQL-only.
+
 extern crate log;
-use crate::models::{Chapter, Manga, MangaSourceData};
+use crate::models::{
+    Chapter, ChapterFile, ChapterRead, ChapterWithSource, ContinueReading, DownloadJob, LibraryEntry, Manga,
+    MangaAuditEntry, MangaSourceData, Credit, MergeProposal, Person, Tag, User, ApiKey,
+};
+use crate::auth::{self, Scope};
+use crate::people::{self, PersonRole};
+use crate::reading::LibraryStatus;
+use crate::search::{self, MangaSearch, SearchSort, TagFilter};
+use crate::sources::catalogue;
+use crate::tags::{self, TagRef};
+use crate::storage::{
+    MangaTitles, MatchCandidate, MetadataProvider, PerSourceCounts, ProviderLinks, SampleChapter,
+    SourceStats, UndoOutcome,
+};
 use log::error;
 use rusqlite::params;
-use rusqlite::{Connection, Result, Transaction};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
+use serde_json::{json, Map, Value as Json};
 
-pub fn init_db() -> Result<Connection> {
-    let conn = Connection::open("manga.db")?;
+/// Open (creating if needed) the database file at `path`; `:memory:` gives a
+/// private in-memory database.
+pub fn open(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
     Ok(conn)
 }
 
 pub fn seed_sources(conn: &Connection) -> Result<()> {
     log::info!("Seeding sources table...");
-    // Existing
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (1, 'MangaDex', 'https://mangadex.org')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (2, 'FireScans', 'https://firescans.xyz')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (3, 'RizzComic', 'https://rizzcomic.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (4, 'MyAnimeList', 'https://myanimelist.net')", [])?;
-    conn.execute(
-        "INSERT OR IGNORE INTO sources (id, name, url) VALUES (5, 'AniList', 'https://anilist.co')",
-        [],
-    )?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (6, 'DrakeComic', 'https://drakecomic.org')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (7, 'KDTNovels', 'https://kdtnovels.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (8, 'Asmotoon', 'https://asmotoon.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (9, 'ResetScans', 'https://reset-scans.org')", [])?;
-    conn.execute(
-        "INSERT OR IGNORE INTO sources (id, name, url) VALUES (10, 'Kagane', 'https://kagane.org')",
-        [],
-    )?;
-
-    // New seeds requested
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (11, 'Asura Scans', 'https://asurascans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (12, 'BookLive', 'https://booklive.jp')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (13, 'Comikey', 'https://comikey.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (14, 'DENPA BOOKS', 'https://denpa.pub')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (15, 'Dark Horse Comics', 'https://www.darkhorse.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (16, 'Day Comics', 'https://daycomics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (17, 'FAKKU', 'https://www.fakku.net')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (18, 'Flame Comics', 'https://flamecomics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (19, 'Grim Scans', 'https://grimscans.team')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (20, 'Hive Toons', 'https://hivetoons.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (21, 'INKR Comics', 'https://inkr.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (22, 'Irodori Comics', 'https://irodoricomics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (23, 'J-Novel Club', 'https://j-novel.club')", [])?;
-    conn.execute(
-        "INSERT OR IGNORE INTO sources (id, name, url) VALUES (24, 'Kana', 'https://www.kana.fr')",
-        [],
-    )?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (25, 'Kenscans', 'https://kenscans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (26, 'Kodansha Comics', 'https://kodansha.us')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (27, 'Kodoku Studio', 'https://kodokustudio.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (28, 'Lezhin', 'https://www.lezhinus.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (29, 'Luna Toons', 'https://lunatoons.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (30, 'Madarascans', 'https://madarascans.com')", [])?;
-    // 1 is MangaDex already
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (31, 'Manhuaus', 'https://manhuaus.com')", [])?;
-    conn.execute(
-        "INSERT OR IGNORE INTO sources (id, name, url) VALUES (32, 'Manta', 'https://manta.net')",
-        [],
-    )?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (33, 'MediBang', 'https://medibang.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (34, 'Nyx Scans', 'https://nyxscans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (35, 'One Peace Books', 'https://onepeacebooks.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (36, 'Others', 'https://example.com/others')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (37, 'Pocket Comics', 'https://www.pocketcomics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (38, 'Qi Scans', 'https://qiscans.com')", [])?;
-    // 9 is Reset Scans already
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (39, 'Rizz Fables', 'https://rizzfables.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (40, 'Rokari Comics', 'https://rokaricomics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (41, 'Seven Seas Entertainment', 'https://sevenseasentertainment.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (42, 'Shusuisha', 'https://www.shueisha.co.jp')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (43, 'Siren Scans', 'https://sirenscans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (44, 'Square Enix Manga', 'https://square-enix-books.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (45, 'StoneScape', 'https://stonescape.xyz')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (46, 'TOKYOPOP', 'https://tokyopop.com')", [])?;
-    conn.execute(
-        "INSERT OR IGNORE INTO sources (id, name, url) VALUES (47, 'Tapas', 'https://tapas.io')",
-        [],
-    )?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (48, 'Tappytoon', 'https://www.tappytoon.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (49, 'Temple Scan', 'https://templescan.net')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (50, 'Thunderscans', 'https://thunderscans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (51, 'Titan Manga', 'https://titan-comics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (52, 'Toomics', 'https://toomics.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (53, 'UDON Entertainment', 'https://udonentertainment.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (54, 'VAST Visual', 'https://vastvisual.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (55, 'VIZ Media', 'https://www.viz.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (56, 'Vortex Scans', 'https://vortexscans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (57, 'Webcomics', 'https://www.webcomicsapp.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (58, 'Webtoon', 'https://www.webtoons.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (59, 'Witch Scans', 'https://witchscans.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (60, 'Yen Press', 'https://yenpress.com')", [])?;
-    conn.execute("INSERT OR IGNORE INTO sources (id, name, url) VALUES (61, 'Mavin Translations', 'https://mavintranslations.com')", [])?;
-
+    conn.execute_batch(&catalogue::seed_sql())?;
     log::info!("Sources seeded successfully.");
     Ok(())
 }
 
+pub fn check_sources_table(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id, name FROM sources ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i32, String)>>>()?;
+    Ok(catalogue::table_problems(&rows))
+}
+
+pub fn seed_tags(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&tags::seed_sql())
+}
+
+/// Create every table at the current schema (the equivalent of all
+/// PostgreSQL migrations) and add columns missing from older databases.
 pub fn create_tables(conn: &Connection) -> Result<()> {
     log::info!("Creating tables if not exists...");
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sources (\n            id INTEGER PRIMARY KEY AUTOINCREMENT,\n            name TEXT NOT NULL UNIQUE,\n            url TEXT NOT NULL\n        );",
-        [],
-    )?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sources (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            url TEXT NOT NULL
+        );
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS manga (
+        CREATE TABLE IF NOT EXISTS manga (
             id TEXT PRIMARY KEY,
             title TEXT NOT NULL,
             alt_titles TEXT,
@@ -114,6 +72,7 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             description TEXT,
             tags TEXT,
             rating TEXT,
+            status TEXT,
             monitored INTEGER,
             check_interval_secs INTEGER,
             discover_interval_secs INTEGER,
@@ -121,52 +80,221 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             last_discover_check INTEGER,
             mal_id INTEGER,
             anilist_id INTEGER,
-            mangabaka_id TEXT
+            mangabaka_id TEXT,
+            output_format TEXT,
+            cover_hash INTEGER
+        );
+
+        CREATE TABLE IF NOT EXISTS manga_source_data (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            manga_id TEXT NOT NULL,
+            source_id INTEGER NOT NULL,
+            source_manga_id TEXT NOT NULL,
+            source_manga_url TEXT NOT NULL,
+            priority INTEGER,
+            FOREIGN KEY (manga_id) REFERENCES manga (id),
+            FOREIGN KEY (source_id) REFERENCES sources (id),
+            UNIQUE(manga_id, source_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS chapters (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            manga_source_data_id INTEGER NOT NULL,
+            chapter_number TEXT NOT NULL,
+            url TEXT NOT NULL,
+            scraped BOOLEAN NOT NULL DEFAULT 0,
+            number REAL,
+            volume TEXT,
+            title TEXT,
+            language TEXT,
+            scanlation_group TEXT,
+            published_at INTEGER,
+            page_count INTEGER,
+            first_seen_at INTEGER,
+            sort_key REAL,
+            file_path TEXT,
+            file_size INTEGER,
+            file_hash TEXT,
+            downloaded_at INTEGER,
+            FOREIGN KEY (manga_source_data_id) REFERENCES manga_source_data (id),
+            UNIQUE(manga_source_data_id, url)
+        );
+
+        CREATE TABLE IF NOT EXISTS provider_ids (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            manga_id TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            provider_id TEXT NOT NULL,
+            UNIQUE(manga_id, provider),
+            FOREIGN KEY (manga_id) REFERENCES manga(id)
         );",
-        [],
     )?;
 
     // Migrations for existing DBs: add missing columns
     ensure_column(conn, "manga", "rating", "TEXT")?;
+    ensure_column(conn, "manga", "status", "TEXT")?;
     ensure_column(conn, "manga", "monitored", "INTEGER")?;
     ensure_column(conn, "manga", "check_interval_secs", "INTEGER")?;
     ensure_column(conn, "manga", "discover_interval_secs", "INTEGER")?;
     ensure_column(conn, "manga", "last_chapter_check", "INTEGER")?;
     ensure_column(conn, "manga", "last_discover_check", "INTEGER")?;
     ensure_column(conn, "manga", "mangabaka_id", "TEXT")?;
+    ensure_column(conn, "manga", "output_format", "TEXT")?;
+    ensure_column(conn, "manga", "cover_hash", "INTEGER")?;
+    ensure_column(conn, "manga_source_data", "priority", "INTEGER")?;
+    ensure_column(conn, "chapters", "number", "REAL")?;
+    ensure_column(conn, "chapters", "volume", "TEXT")?;
+    ensure_column(conn, "chapters", "title", "TEXT")?;
+    ensure_column(conn, "chapters", "language", "TEXT")?;
+    ensure_column(conn, "chapters", "scanlation_group", "TEXT")?;
+    ensure_column(conn, "chapters", "published_at", "INTEGER")?;
+    ensure_column(conn, "chapters", "page_count", "INTEGER")?;
+    ensure_column(conn, "chapters", "first_seen_at", "INTEGER")?;
+    ensure_column(conn, "chapters", "sort_key", "REAL")?;
+    ensure_column(conn, "chapters", "file_path", "TEXT")?;
+    ensure_column(conn, "chapters", "file_size", "INTEGER")?;
+    ensure_column(conn, "chapters", "file_hash", "TEXT")?;
+    ensure_column(conn, "chapters", "downloaded_at", "INTEGER")?;
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS manga_source_data (\n            id INTEGER PRIMARY KEY AUTOINCREMENT,\n            manga_id TEXT NOT NULL,\n            source_id INTEGER NOT NULL,\n            source_manga_id TEXT NOT NULL,\n            source_manga_url TEXT NOT NULL,\n            FOREIGN KEY (manga_id) REFERENCES manga (id),\n            FOREIGN KEY (source_id) REFERENCES sources (id),\n            UNIQUE(manga_id, source_id)\n        );",
-        [],
-    )?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS download_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            chapter_id INTEGER NOT NULL,
+            state TEXT NOT NULL DEFAULT 'queued'
+                CHECK (state IN ('queued', 'running', 'done', 'failed')),
+            format TEXT,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            max_attempts INTEGER NOT NULL DEFAULT 5,
+            next_attempt_at INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            file_path TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (chapter_id) REFERENCES chapters (id)
+        );
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS chapters (\n            id INTEGER PRIMARY KEY AUTOINCREMENT,\n            manga_source_data_id INTEGER NOT NULL,\n            chapter_number TEXT NOT NULL,\n            url TEXT NOT NULL,\n            scraped BOOLEAN NOT NULL DEFAULT 0,\n            FOREIGN KEY (manga_source_data_id) REFERENCES manga_source_data (id),\n            UNIQUE(manga_source_data_id, url)\n        );",
-        [],
-    )?;
+        CREATE TABLE IF NOT EXISTS merge_proposals (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            manga_a TEXT NOT NULL,
+            manga_b TEXT NOT NULL,
+            title_a TEXT NOT NULL,
+            title_b TEXT NOT NULL,
+            score REAL NOT NULL,
+            reasons TEXT NOT NULL,
+            state TEXT NOT NULL DEFAULT 'pending'
+                CHECK (state IN ('pending', 'accepted', 'rejected')),
+            automatic BOOLEAN NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            decided_at INTEGER,
+            UNIQUE (manga_a, manga_b)
+        );
 
-    // Helpful indexes
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_msd_manga ON manga_source_data(manga_id);",
-        [],
-    )?;
+        CREATE TABLE IF NOT EXISTS manga_audit (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            action TEXT NOT NULL CHECK (action IN ('merge', 'split')),
+            manga_id TEXT NOT NULL,
+            other_manga_id TEXT NOT NULL,
+            snapshot TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            undone_at INTEGER
+        );
 
-    // Provider IDs mapping
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS provider_ids (\n            id INTEGER PRIMARY KEY AUTOINCREMENT,\n            manga_id TEXT NOT NULL,\n            provider TEXT NOT NULL,\n            provider_id TEXT NOT NULL,\n            UNIQUE(manga_id, provider),\n            FOREIGN KEY (manga_id) REFERENCES manga(id)\n        );",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_provider_manga ON provider_ids(manga_id);",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_ch_msd ON chapters(manga_source_data_id);",
-        [],
+        CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            slug TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            category TEXT CHECK (category IN ('genre', 'theme', 'format', 'content_warning'))
+        );
+
+        CREATE TABLE IF NOT EXISTS manga_tags (
+            manga_id TEXT NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (manga_id, tag_id),
+            FOREIGN KEY (manga_id) REFERENCES manga (id),
+            FOREIGN KEY (tag_id) REFERENCES tags (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS people (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            name_key TEXT NOT NULL UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS manga_people (
+            manga_id TEXT NOT NULL,
+            person_id INTEGER NOT NULL,
+            role TEXT NOT NULL CHECK (role IN ('author', 'artist', 'publisher')),
+            PRIMARY KEY (manga_id, person_id, role),
+            FOREIGN KEY (manga_id) REFERENCES manga (id),
+            FOREIGN KEY (person_id) REFERENCES people (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            created_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS library_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            manga_id TEXT NOT NULL,
+            status TEXT NOT NULL
+                CHECK (status IN ('reading', 'completed', 'on_hold', 'dropped', 'plan_to_read')),
+            added_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            UNIQUE (user_id, manga_id),
+            FOREIGN KEY (user_id) REFERENCES users (id),
+            FOREIGN KEY (manga_id) REFERENCES manga (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS chapter_reads (
+            user_id INTEGER NOT NULL,
+            chapter_id INTEGER NOT NULL,
+            page INTEGER NOT NULL DEFAULT 0,
+            completed BOOLEAN NOT NULL DEFAULT 0,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (user_id, chapter_id),
+            FOREIGN KEY (user_id) REFERENCES users (id),
+            FOREIGN KEY (chapter_id) REFERENCES chapters (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS api_keys (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            key_hash TEXT NOT NULL UNIQUE,
+            prefix TEXT NOT NULL,
+            scopes TEXT NOT NULL,
+            user_id INTEGER,
+            created_at INTEGER NOT NULL,
+            last_used_at INTEGER,
+            revoked_at INTEGER,
+            FOREIGN KEY (user_id) REFERENCES users (id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_msd_manga ON manga_source_data(manga_id);
+        CREATE INDEX IF NOT EXISTS idx_msd_source ON manga_source_data(source_id);
+        CREATE INDEX IF NOT EXISTS idx_provider_manga ON provider_ids(manga_id);
+        CREATE INDEX IF NOT EXISTS idx_ch_msd ON chapters(manga_source_data_id);
+        CREATE INDEX IF NOT EXISTS idx_ch_msd_sort ON chapters(manga_source_data_id, sort_key);
+        CREATE INDEX IF NOT EXISTS idx_ch_file_path ON chapters(file_path) WHERE file_path IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_manga_title ON manga(title);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_download_jobs_active
+            ON download_jobs (chapter_id) WHERE state IN ('queued', 'running');
+        CREATE INDEX IF NOT EXISTS idx_download_jobs_pending
+            ON download_jobs (state, next_attempt_at);
+        CREATE INDEX IF NOT EXISTS idx_merge_proposals_state ON merge_proposals (state, score DESC);
+        CREATE INDEX IF NOT EXISTS idx_manga_audit_manga ON manga_audit (manga_id);
+        CREATE INDEX IF NOT EXISTS idx_manga_audit_other ON manga_audit (other_manga_id);
+        CREATE INDEX IF NOT EXISTS idx_manga_tags_tag ON manga_tags (tag_id);
+        CREATE INDEX IF NOT EXISTS idx_manga_people_person ON manga_people (person_id);
+        CREATE INDEX IF NOT EXISTS idx_library_entries_manga ON library_entries (manga_id);
+        CREATE INDEX IF NOT EXISTS idx_chapter_reads_chapter ON chapter_reads (chapter_id);",
     )?;
 
     log::info!("Tables ensured.");
     seed_sources(conn)?;
+    seed_tags(conn)?;
     Ok(())
 }
 
@@ -188,86 +316,49 @@ fn ensure_column(conn: &Connection, table: &str, column: &str, column_type: &str
             "ALTER TABLE {} ADD COLUMN {} {}",
             table, column, column_type
         );
-        let _ = conn.execute(&sql, params![]);
+        conn.execute(&sql, params![])?;
     }
     Ok(())
 }
 
-pub fn get_manga_paginated(
-    conn: &Connection,
-    limit: Option<i32>,
-    offset: Option<i32>,
-    sort_by: &str,
-    rating_filter: Option<&str>,
-) -> Result<Vec<Manga>> {
-    let sort_expr = match sort_by {
-        "rating" => "CASE lower(coalesce(rating,'')) WHEN 'safe' THEN 1 WHEN 'suggestive' THEN 2 WHEN 'erotica' THEN 3 WHEN 'pornographic' THEN 4 ELSE 5 END, title",
-        _ => "title",
-    };
-
-    let mut base = format!(
-        "SELECT id, title, alt_titles, cover_url, description, tags, rating, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check FROM manga"
-    );
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(r) = rating_filter {
-        base.push_str(" WHERE lower(rating) = lower(?)");
-        params.push(Box::new(r.to_string()));
-    }
-    base.push_str(&format!(" ORDER BY {} LIMIT ? OFFSET ?", sort_expr));
-
-    params.push(Box::new(limit.unwrap_or(100)));
-    params.push(Box::new(offset.unwrap_or(0)));
-    let mut stmt = conn.prepare(&base)?;
-    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-    let rows = stmt.query_map(param_refs.as_slice(), |row| {
-        Ok(Manga {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            alt_titles: row.get(2)?,
-            cover_url: row.get(3)?,
-            description: row.get(4)?,
-            tags: row.get(5)?,
-            rating: row.get(6)?,
-            monitored: row.get(7).ok(),
-            check_interval_secs: row.get(8).ok(),
-            discover_interval_secs: row.get(9).ok(),
-            last_chapter_check: row.get(10).ok(),
-            last_discover_check: row.get(11).ok(),
-        })
-    })?;
+/// Bind a list of integers as one parameter, for `IN (SELECT value FROM json_each(?))`
+fn json_ids(ids: &[i32]) -> String {
+    serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string())
+}
 
-    let mut manga_list = Vec::new();
-    for row in rows {
-        manga_list.push(row?);
-    }
+const MANGA_COLUMNS: &str = "id, title, alt_titles, cover_url, description, tags, rating, status, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check";
 
-    Ok(manga_list)
+fn manga_from_row(row: &Row) -> Result<Manga> {
+    Ok(Manga {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        alt_titles: row.get(2)?,
+        cover_url: row.get(3)?,
+        description: row.get(4)?,
+        tags: row.get(5)?,
+        rating: row.get(6)?,
+        status: row.get(7)?,
+        monitored: row.get(8)?,
+        check_interval_secs: row.get(9)?,
+        discover_interval_secs: row.get(10)?,
+        last_chapter_check: row.get(11)?,
+        last_discover_check: row.get(12)?,
+        people: Vec::new(),
+    })
 }
 
-pub fn get_manga_count(conn: &Connection) -> Result<i32> {
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM manga")?;
-    let count: i32 = stmt.query_row([], |row| row.get(0))?;
-    Ok(count)
-}
+const RATING_ORDER: &str = "CASE lower(coalesce(rating,'')) WHEN 'safe' THEN 1 WHEN 'suggestive' THEN 2 WHEN 'erotica' THEN 3 WHEN 'pornographic' THEN 4 ELSE 5 END, title";
 
-pub fn get_chapter_count(conn: &Connection) -> Result<i32> {
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM chapters")?;
-    let count: i32 = stmt.query_row([], |row| row.get(0))?;
-    Ok(count)
+pub fn get_manga_count(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM manga", [], |row| row.get(0))
 }
 
-pub fn get_source_count(conn: &Connection) -> Result<i32> {
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM sources")?;
-    let count: i32 = stmt.query_row([], |row| row.get(0))?;
-    Ok(count)
+pub fn get_chapter_count(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM chapters", [], |row| row.get(0))
 }
 
-#[derive(Debug, serde::Serialize)]
-pub struct PerSourceCounts {
-    pub source_id: i32,
-    pub source_name: String,
-    pub manga: i32,
-    pub chapters: i32,
+pub fn get_source_count(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM sources", [], |row| row.get(0))
 }
 
 pub fn get_per_source_counts(conn: &Connection) -> Result<Vec<PerSourceCounts>> {
@@ -286,11 +377,45 @@ pub fn get_per_source_counts(conn: &Connection) -> Result<Vec<PerSourceCounts>>
             chapters: row.get(3)?,
         })
     })?;
-    let mut out = Vec::new();
-    for r in rows {
-        out.push(r?);
-    }
-    Ok(out)
+    rows.collect()
+}
+
+pub fn get_source_stats(conn: &Connection, source_id: i32) -> Result<SourceStats> {
+    conn.query_row(
+        "SELECT
+            (SELECT COUNT(DISTINCT msd.manga_id) FROM manga_source_data msd WHERE msd.source_id = ?1),
+            (SELECT COUNT(*) FROM chapters c JOIN manga_source_data msd ON c.manga_source_data_id = msd.id
+             WHERE msd.source_id = ?1),
+            (SELECT COUNT(DISTINCT m.id) FROM manga m JOIN manga_source_data msd ON msd.manga_id = m.id
+             WHERE msd.source_id = ?1
+               AND (COALESCE(m.mangabaka_id, '') <> '' OR COALESCE(m.mal_id, 0) > 0 OR COALESCE(m.anilist_id, 0) > 0))",
+        [source_id],
+        |row| {
+            Ok(SourceStats {
+                manga: row.get(0)?,
+                chapters: row.get(1)?,
+                metadata_linked: row.get(2)?,
+            })
+        },
+    )
+}
+
+pub fn get_sample_chapter(conn: &Connection, source_id: i32) -> Result<Option<SampleChapter>> {
+    conn.query_row(
+        "SELECT msd.manga_id, c.chapter_number, c.url, msd.source_manga_url
+         FROM manga_source_data msd JOIN chapters c ON c.manga_source_data_id = msd.id
+         WHERE msd.source_id = ?1 LIMIT 1",
+        [source_id],
+        |row| {
+            Ok(SampleChapter {
+                manga_id: row.get(0)?,
+                chapter_number: row.get(1)?,
+                url: row.get(2)?,
+                series_url: row.get(3)?,
+            })
+        },
+    )
+    .optional()
 }
 
 pub fn get_source_name(conn: &Connection, source_id: i32) -> Result<String> {
@@ -299,181 +424,355 @@ pub fn get_source_name(conn: &Connection, source_id: i32) -> Result<String> {
     Ok(name)
 }
 
-pub fn search_manga_paginated(
-    conn: &Connection,
-    query: &str,
-    tags: Option<&str>,
-    rating: Option<&str>,
-    limit: Option<i32>,
-    offset: Option<i32>,
-    sort_by: &str,
-) -> Result<Vec<Manga>> {
-    let search_pattern = format!("%{}%", query);
-    let sort_expr = match sort_by {
-        "rating" => "CASE lower(coalesce(rating,'')) WHEN 'safe' THEN 1 WHEN 'suggestive' THEN 2 WHEN 'erotica' THEN 3 WHEN 'pornographic' THEN 4 ELSE 5 END, title",
-        _ => "title",
+/// WHERE clause for a manga search over `manga m`, with its parameters
+fn search_clause(search: &MangaSearch) -> (String, Vec<Value>) {
+    let mut params: Vec<Value> = Vec::new();
+    let mut conditions: Vec<String> = Vec::new();
+    let next = |params: &mut Vec<Value>, value: Value| {
+        params.push(value);
+        format!("?{}", params.len())
     };
 
-    let mut where_clauses: Vec<&str> = Vec::new();
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-    // title OR alt_titles
-    where_clauses.push("(title LIKE ? OR alt_titles LIKE ?)");
-    params.push(Box::new(search_pattern.clone()));
-    params.push(Box::new(search_pattern.clone()));
-
-    if let Some(tag_filter) = tags {
-        where_clauses.push("tags LIKE ?");
-        let tag_pattern = format!("%{}%", tag_filter);
-        params.push(Box::new(tag_pattern));
+    if let Some(text) = search.text() {
+        let mut terms = search::query_terms(text);
+        if terms.is_empty() {
+            terms.push(text.to_lowercase());
+        }
+        for term in terms {
+            let p = next(&mut params, Value::Text(term));
+            conditions.push(format!(
+                "(instr(lower(m.title), {p}) OR instr(lower(coalesce(m.alt_titles, '')), {p})
+                  OR instr(lower(coalesce(m.tags, '')), {p}) OR instr(lower(coalesce(m.description, '')), {p}))",
+                p = p
+            ));
+        }
+    }
+    if let Some(status) = &search.status {
+        let p = next(&mut params, Value::Text(status.clone()));
+        conditions.push(format!("lower(m.status) = lower({})", p));
+    }
+    if let Some(source_id) = search.source_id {
+        let p = next(&mut params, Value::Integer(source_id as i64));
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM manga_source_data msd WHERE msd.manga_id = m.id AND msd.source_id = {})",
+            p
+        ));
     }
-    if let Some(r) = rating {
-        where_clauses.push("lower(rating) = lower(?)");
-        params.push(Box::new(r.to_string()));
+    let tags = search.include_tags.iter().map(|t| ("", t));
+    for (negate, tag) in tags.chain(search.exclude_tags.iter().map(|t| ("NOT ", t))) {
+        let (column, value) = match tag {
+            TagFilter::Id(id) => ("t.id", Value::Integer(*id as i64)),
+            TagFilter::Slug(slug) => ("t.slug", Value::Text(slug.clone())),
+        };
+        let p = next(&mut params, value);
+        conditions.push(format!(
+            "{}EXISTS (SELECT 1 FROM manga_tags mt JOIN tags t ON t.id = mt.tag_id
+             WHERE mt.manga_id = m.id AND {} = {})",
+            negate, column, p
+        ));
+    }
+    if let Some(rating) = &search.rating {
+        let p = next(&mut params, Value::Text(rating.clone()));
+        conditions.push(format!("lower(m.rating) = lower({})", p));
+    }
+    if let Some(has_unread) = search.has_unread {
+        conditions.push(format!(
+            "{}EXISTS (SELECT 1 FROM manga_source_data msd JOIN chapters c ON c.manga_source_data_id = msd.id
+             WHERE msd.manga_id = m.id AND NOT c.scraped)",
+            if has_unread { "" } else { "NOT " }
+        ));
     }
 
-    let where_clause = if where_clauses.is_empty() {
-        String::new()
+    let clause = if conditions.is_empty() {
+        "1".to_string()
     } else {
-        format!(" WHERE {}", where_clauses.join(" AND "))
+        conditions.join(" AND ")
     };
-    let query_str = format!(
-        "SELECT id, title, alt_titles, cover_url, description, tags, rating, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check FROM manga{} ORDER BY {} LIMIT ? OFFSET ?",
-        where_clause, sort_expr
-    );
-
-    let limit_val = limit.unwrap_or(100);
-    let offset_val = offset.unwrap_or(0);
-    params.push(Box::new(limit_val));
-    params.push(Box::new(offset_val));
-
-    let mut stmt = conn.prepare(&query_str)?;
-    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-    let rows = stmt.query_map(param_refs.as_slice(), |row| {
-        Ok(Manga {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            alt_titles: row.get(2)?,
-            cover_url: row.get(3)?,
-            description: row.get(4)?,
-            tags: row.get(5)?,
-            rating: row.get(6)?,
-            monitored: row.get(7).ok(),
-            check_interval_secs: row.get(8).ok(),
-            discover_interval_secs: row.get(9).ok(),
-            last_chapter_check: row.get(10).ok(),
-            last_discover_check: row.get(11).ok(),
-        })
-    })?;
+    (clause, params)
+}
 
-    let mut manga_list = Vec::new();
-    for row in rows {
-        manga_list.push(row?);
-    }
+/// When a manga last got a new chapter, for `SearchSort::Updated`
+const UPDATED_ORDER: &str = "(SELECT MAX(c.first_seen_at) FROM manga_source_data msd
+    JOIN chapters c ON c.manga_source_data_id = msd.id WHERE msd.manga_id = m.id)";
 
-    Ok(manga_list)
+pub fn search_manga(conn: &Connection, search: &MangaSearch) -> Result<Vec<Manga>> {
+    let (clause, mut params) = search_clause(search);
+    let order = match (search.sort, search.text()) {
+        (SearchSort::Relevance, Some(text)) => {
+            params.push(Value::Text(text.to_lowercase()));
+            format!(
+                "CASE WHEN lower(m.title) = ?{q} THEN 4 WHEN instr(lower(m.title), ?{q}) = 1 THEN 3
+                      WHEN instr(lower(m.title), ?{q}) > 0 THEN 2
+                      WHEN instr(lower(coalesce(m.alt_titles, '')), ?{q}) > 0 THEN 1 ELSE 0 END DESC, m.title",
+                q = params.len()
+            )
+        }
+        (SearchSort::Rating, _) => RATING_ORDER.to_string(),
+        (SearchSort::Updated, _) => format!("{} DESC NULLS LAST, m.title", UPDATED_ORDER),
+        _ => "m.title".to_string(),
+    };
+    params.push(Value::Integer(search.limit));
+    params.push(Value::Integer(search.offset));
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM manga m WHERE {} ORDER BY {} LIMIT ?{} OFFSET ?{}",
+        MANGA_COLUMNS,
+        clause,
+        order,
+        params.len() - 1,
+        params.len()
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), manga_from_row)?;
+    rows.collect()
 }
 
-pub fn get_manga_by_id(conn: &Connection, manga_id: &str) -> Result<Option<Manga>> {
-    let mut stmt = conn.prepare("SELECT id, title, alt_titles, cover_url, description, tags, rating, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check FROM manga WHERE id = ?1")?;
-    let mut rows = stmt.query_map(&[manga_id], |row| {
-        Ok(Manga {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            alt_titles: row.get(2)?,
-            cover_url: row.get(3)?,
-            description: row.get(4)?,
-            tags: row.get(5)?,
-            rating: row.get(6)?,
-            monitored: row.get(7).ok(),
-            check_interval_secs: row.get(8).ok(),
-            discover_interval_secs: row.get(9).ok(),
-            last_chapter_check: row.get(10).ok(),
-            last_discover_check: row.get(11).ok(),
-        })
-    })?;
+pub fn count_manga_matching(conn: &Connection, search: &MangaSearch) -> Result<i64> {
+    let (clause, params) = search_clause(search);
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM manga m WHERE {}", clause),
+        rusqlite::params_from_iter(params),
+        |row| row.get(0),
+    )
+}
 
-    if let Some(row) = rows.next() {
-        Ok(Some(row?))
-    } else {
-        Ok(None)
+pub fn get_manga_by_id(conn: &Connection, manga_id: &str) -> Result<Option<Manga>> {
+    let manga = conn
+        .query_row(
+            &format!("SELECT {} FROM manga WHERE id = ?1", MANGA_COLUMNS),
+            [manga_id],
+            manga_from_row,
+        )
+        .optional()?;
+    match manga {
+        Some(mut manga) => {
+            manga.people = get_manga_people(conn, manga_id)?;
+            Ok(Some(manga))
+        }
+        None => Ok(None),
     }
 }
 
+const CHAPTER_COLUMNS: &str = "c.id, c.manga_source_data_id, c.chapter_number, c.url, c.scraped,
+    c.number, c.volume, c.title, c.language, c.scanlation_group, c.published_at, c.page_count,
+    c.first_seen_at, c.sort_key, c.file_path, c.file_size, c.file_hash, c.downloaded_at";
+
+fn chapter_from_row(row: &Row) -> Result<Chapter> {
+    Ok(Chapter {
+        id: row.get(0)?,
+        manga_source_data_id: row.get(1)?,
+        chapter_number: row.get(2)?,
+        url: row.get(3)?,
+        scraped: row.get(4)?,
+        number: row.get(5)?,
+        volume: row.get(6)?,
+        title: row.get(7)?,
+        language: row.get(8)?,
+        scanlation_group: row.get(9)?,
+        published_at: row.get(10)?,
+        page_count: row.get(11)?,
+        first_seen_at: row.get(12)?,
+        sort_key: row.get(13)?,
+        file_path: row.get(14)?,
+        file_size: row.get(15)?,
+        file_hash: row.get(16)?,
+        downloaded_at: row.get(17)?,
+    })
+}
+
 pub fn get_chapters_by_manga_source_data_id(
     conn: &Connection,
     manga_id: &str,
     source_id: i32,
 ) -> Result<Vec<Chapter>> {
-    let mut stmt =
-        conn.prepare("SELECT id FROM manga_source_data WHERE manga_id = ?1 AND source_id = ?2")?;
-    let manga_source_data_id: i32 =
-        stmt.query_row(&[manga_id, &source_id.to_string()], |row| row.get(0))?;
-
-    let mut stmt = conn.prepare("SELECT id, manga_source_data_id, chapter_number, url, scraped FROM chapters WHERE manga_source_data_id = ?1")?;
-    let rows = stmt.query_map(&[&manga_source_data_id], |row| {
-        Ok(Chapter {
-            id: row.get(0)?,
-            manga_source_data_id: row.get(1)?,
-            chapter_number: row.get(2)?,
-            url: row.get(3)?,
-            scraped: row.get(4)?,
-        })
-    })?;
+    let manga_source_data_id: i32 = conn.query_row(
+        "SELECT id FROM manga_source_data WHERE manga_id = ?1 AND source_id = ?2",
+        params![manga_id, source_id],
+        |row| row.get(0),
+    )?;
 
-    let mut chapters = Vec::new();
-    for row in rows {
-        chapters.push(row?);
-    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM chapters c WHERE c.manga_source_data_id = ?1
+         ORDER BY c.sort_key NULLS LAST, c.id",
+        CHAPTER_COLUMNS
+    ))?;
+    let rows = stmt.query_map([manga_source_data_id], chapter_from_row)?;
+    rows.collect()
+}
+
+pub fn get_chapter_with_source(
+    conn: &Connection,
+    chapter_id: i32,
+) -> Result<Option<ChapterWithSource>> {
+    conn.query_row(
+        &format!(
+            "SELECT {}, msd.source_id, s.name
+             FROM chapters c
+             JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+             JOIN sources s ON s.id = msd.source_id
+             WHERE c.id = ?1",
+            CHAPTER_COLUMNS
+        ),
+        [chapter_id],
+        |row| {
+            Ok(ChapterWithSource::new(
+                chapter_from_row(row)?,
+                row.get(18)?,
+                row.get(19)?,
+            ))
+        },
+    )
+    .optional()
+}
+
+/// Record how many pages a chapter has, once its pages have been resolved
+pub fn set_chapter_page_count(conn: &Connection, chapter_id: i32, page_count: i32) -> Result<()> {
+    conn.execute(
+        "UPDATE chapters SET page_count = ?2 WHERE id = ?1",
+        params![chapter_id, page_count],
+    )?;
+    Ok(())
+}
+
+pub fn set_chapter_file(
+    conn: &Connection,
+    chapter_id: i32,
+    file: &ChapterFile,
+    now_ts: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE chapters SET scraped = 1, file_path = ?2, file_size = ?3, file_hash = ?4, downloaded_at = ?5
+         WHERE id = ?1",
+        params![chapter_id, file.path, file.size, file.hash, now_ts],
+    )?;
+    Ok(())
+}
+
+pub fn clear_chapter_file(conn: &Connection, chapter_id: i32) -> Result<()> {
+    conn.execute(
+        "UPDATE chapters SET scraped = 0, file_path = NULL, file_size = NULL, file_hash = NULL, downloaded_at = NULL
+         WHERE id = ?1",
+        [chapter_id],
+    )?;
+    Ok(())
+}
 
-    Ok(chapters)
+pub fn get_downloaded_chapters(conn: &Connection) -> Result<Vec<(String, ChapterWithSource)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, msd.source_id, s.name, m.title
+         FROM chapters c
+         JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         JOIN sources s ON s.id = msd.source_id
+         JOIN manga m ON m.id = msd.manga_id
+         WHERE c.file_path IS NOT NULL
+         ORDER BY c.id",
+        CHAPTER_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        let chapter = ChapterWithSource::new(chapter_from_row(row)?, row.get(18)?, row.get(19)?);
+        Ok((row.get(20)?, chapter))
+    })?;
+    rows.collect()
 }
 
-pub fn insert_manga(tx: &Transaction, manga: &Manga) -> Result<()> {
-    match tx.execute(
-        "INSERT INTO manga (id, title, alt_titles, cover_url, description, tags, rating) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+/// Insert or update a manga. Tags are replaced by their canonical forms and
+/// linked in `manga_tags`; a manga scraped without tags keeps the ones it had.
+/// Credits in `manga.people` replace the stored ones in the same roles.
+pub fn insert_manga(conn: &mut Connection, manga: &Manga) -> Result<()> {
+    let tags = tags::parse_list(None, manga.tags.as_deref().unwrap_or(""));
+    let tx = conn.transaction()?;
+    if let Err(e) = tx.execute(
+        "INSERT INTO manga (id, title, alt_titles, cover_url, description, tags, rating, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
          ON CONFLICT(id) DO UPDATE SET
             title=excluded.title,
             alt_titles=excluded.alt_titles,
             cover_url=excluded.cover_url,
             description=excluded.description,
-            tags=excluded.tags,
-            rating=excluded.rating",
-        &[&manga.id, &manga.title, manga.alt_titles.as_deref().unwrap_or(""), manga.cover_url.as_deref().unwrap_or(""), manga.description.as_deref().unwrap_or(""), manga.tags.as_deref().unwrap_or(""), manga.rating.as_deref().unwrap_or("")],
+            tags=coalesce(nullif(excluded.tags, ''), manga.tags),
+            rating=excluded.rating,
+            status=coalesce(excluded.status, manga.status)",
+        params![
+            manga.id,
+            manga.title,
+            manga.alt_titles.as_deref().unwrap_or(""),
+            manga.cover_url.as_deref().unwrap_or(""),
+            manga.description.as_deref().unwrap_or(""),
+            tags::display_list(&tags).unwrap_or_default(),
+            manga.rating.as_deref().unwrap_or(""),
+            manga.status
+        ],
     ) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            error!("Failed to insert manga: {}", e);
-            Err(e)
-        }
+        error!("Failed to insert manga: {}", e);
+        return Err(e);
+    }
+    if !tags.is_empty() {
+        link_tags(&tx, &manga.id, &tags)?;
     }
+    link_people(&tx, &manga.id, &manga.people)?;
+    tx.commit()
 }
 
 pub fn insert_manga_source_data(
-    tx: &Transaction,
+    conn: &Connection,
     manga_source_data: &MangaSourceData,
-) -> Result<i64> {
-    tx.execute(
-        "INSERT OR IGNORE INTO manga_source_data (manga_id, source_id, source_manga_id, source_manga_url) VALUES (?1, ?2, ?3, ?4)",
-        &[&manga_source_data.manga_id, &manga_source_data.source_id.to_string(), &manga_source_data.source_manga_id, &manga_source_data.source_manga_url],
-    )?;
-    Ok(tx.last_insert_rowid())
+) -> Result<i32> {
+    conn.query_row(
+        "INSERT INTO manga_source_data (manga_id, source_id, source_manga_id, source_manga_url)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (manga_id, source_id) DO UPDATE SET
+            source_manga_id = excluded.source_manga_id,
+            source_manga_url = excluded.source_manga_url
+         RETURNING id",
+        params![
+            manga_source_data.manga_id,
+            manga_source_data.source_id,
+            manga_source_data.source_manga_id,
+            manga_source_data.source_manga_url
+        ],
+        |row| row.get(0),
+    )
 }
 
 pub fn insert_chapters(
-    tx: &Transaction,
-    manga_source_data_id: i64,
+    conn: &mut Connection,
+    manga_source_data_id: i32,
     chapters: &[Chapter],
 ) -> Result<()> {
-    let mut stmt = tx.prepare("INSERT OR IGNORE INTO chapters (manga_source_data_id, chapter_number, url) VALUES (?1, ?2, ?3)")?;
-    for chapter in chapters {
-        stmt.execute(&[
-            &manga_source_data_id.to_string(),
-            &chapter.chapter_number,
-            &chapter.url,
-        ])?;
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO chapters (manga_source_data_id, chapter_number, url, number, volume, title,
+                 language, scanlation_group, published_at, page_count, first_seen_at, sort_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT (manga_source_data_id, url) DO UPDATE SET
+                 number = COALESCE(excluded.number, chapters.number),
+                 volume = COALESCE(excluded.volume, chapters.volume),
+                 title = COALESCE(excluded.title, chapters.title),
+                 language = COALESCE(excluded.language, chapters.language),
+                 scanlation_group = COALESCE(excluded.scanlation_group, chapters.scanlation_group),
+                 published_at = COALESCE(excluded.published_at, chapters.published_at),
+                 page_count = COALESCE(excluded.page_count, chapters.page_count),
+                 sort_key = COALESCE(excluded.sort_key, chapters.sort_key)",
+        )?;
+        let now = chrono::Utc::now().timestamp();
+        for chapter in chapters {
+            let mut chapter = chapter.clone();
+            chapter.fill_from_label();
+            stmt.execute(params![
+                manga_source_data_id,
+                chapter.chapter_number,
+                chapter.url,
+                chapter.number,
+                chapter.volume,
+                chapter.title,
+                chapter.language,
+                chapter.scanlation_group,
+                chapter.published_at,
+                chapter.page_count,
+                now,
+                chapter.sort_key
+            ])?;
+        }
     }
-    Ok(())
+    tx.commit()
 }
 
 pub fn set_manga_monitoring(
@@ -485,11 +784,34 @@ pub fn set_manga_monitoring(
 ) -> Result<()> {
     conn.execute(
         "UPDATE manga SET monitored = ?1, check_interval_secs = COALESCE(?2, check_interval_secs), discover_interval_secs = COALESCE(?3, discover_interval_secs) WHERE id = ?4",
-        params![if monitored {1} else {0}, check_interval_secs, discover_interval_secs, manga_id],
+        params![monitored, check_interval_secs, discover_interval_secs, manga_id],
     )?;
     Ok(())
 }
 
+pub fn get_manga_output_format(conn: &Connection, manga_id: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT output_format FROM manga WHERE id = ?1",
+            [manga_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+pub fn set_manga_output_format(
+    conn: &Connection,
+    manga_id: &str,
+    format: Option<&str>,
+) -> Result<u64> {
+    let n = conn.execute(
+        "UPDATE manga SET output_format = ?1 WHERE id = ?2",
+        params![format, manga_id],
+    )?;
+    Ok(n as u64)
+}
+
 pub fn mark_chapter_check(conn: &Connection, manga_id: &str, ts: i64) -> Result<()> {
     conn.execute(
         "UPDATE manga SET last_chapter_check = ?1 WHERE id = ?2",
@@ -505,19 +827,18 @@ pub fn due_for_chapter_check(conn: &Connection, now_ts: i64) -> Result<Vec<Strin
         )",
     )?;
     let rows = stmt.query_map(params![now_ts], |row| row.get(0))?;
-    let mut ids = Vec::new();
-    for r in rows {
-        ids.push(r?);
-    }
-    Ok(ids)
+    rows.collect()
 }
 
 pub fn get_manga_source_data_by_manga_id(
     conn: &Connection,
     manga_id: &str,
 ) -> Result<Vec<MangaSourceData>> {
-    let mut stmt = conn.prepare("SELECT id, manga_id, source_id, source_manga_id, source_manga_url FROM manga_source_data WHERE manga_id = ?1")?;
-    let rows = stmt.query_map(&[manga_id], |row| {
+    let mut stmt = conn.prepare(
+        "SELECT id, manga_id, source_id, source_manga_id, source_manga_url FROM manga_source_data
+         WHERE manga_id = ?1 ORDER BY priority NULLS LAST, id",
+    )?;
+    let rows = stmt.query_map([manga_id], |row| {
         Ok(MangaSourceData {
             manga_id: row.get(1)?,
             source_id: row.get(2)?,
@@ -525,45 +846,1139 @@ pub fn get_manga_source_data_by_manga_id(
             source_manga_url: row.get(4)?,
         })
     })?;
+    rows.collect()
+}
 
-    let mut manga_source_data = Vec::new();
-    for row in rows {
-        manga_source_data.push(row?);
-    }
+pub fn get_manga_source_data_id(
+    conn: &Connection,
+    manga_id: &str,
+    source_id: i32,
+) -> Result<Option<i32>> {
+    conn.query_row(
+        "SELECT id FROM manga_source_data WHERE manga_id = ?1 AND source_id = ?2",
+        params![manga_id, source_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
 
-    Ok(manga_source_data)
+pub fn set_source_priority(conn: &mut Connection, manga_id: &str, source_ids: &[i32]) -> Result<u64> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE manga_source_data SET priority = NULL WHERE manga_id = ?1",
+        [manga_id],
+    )?;
+    let mut ranked = 0;
+    for (priority, source_id) in source_ids.iter().enumerate() {
+        // The first occurrence of a source wins, as with array_position
+        ranked += tx.execute(
+            "UPDATE manga_source_data SET priority = ?3
+             WHERE manga_id = ?1 AND source_id = ?2 AND priority IS NULL",
+            params![manga_id, source_id, priority as i64],
+        )?;
+    }
+    tx.commit()?;
+    Ok(ranked as u64)
 }
 
 pub fn get_manga_by_source(conn: &Connection, source_id: i32) -> Result<Vec<Manga>> {
     let mut stmt = conn.prepare(
-        "SELECT DISTINCT m.id, m.title, m.alt_titles, m.cover_url, m.description, m.tags, m.rating, \
+        "SELECT DISTINCT m.id, m.title, m.alt_titles, m.cover_url, m.description, m.tags, m.rating, m.status, \
          m.monitored, m.check_interval_secs, m.discover_interval_secs, m.last_chapter_check, m.last_discover_check \
          FROM manga m \
          JOIN manga_source_data msd ON m.id = msd.manga_id \
          WHERE msd.source_id = ?1"
     )?;
+    let rows = stmt.query_map([source_id], manga_from_row)?;
+    rows.collect()
+}
+
+const DOWNLOAD_JOB_COLUMNS: &str = "j.id, j.chapter_id, j.state, j.format, j.attempts, j.max_attempts,
+    j.next_attempt_at, j.last_error, j.file_path, j.created_at, j.updated_at,
+    m.id, m.title, c.chapter_number, msd.source_id";
+
+const DOWNLOAD_JOB_JOINS: &str = "FROM download_jobs j
+    JOIN chapters c ON c.id = j.chapter_id
+    JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+    JOIN manga m ON m.id = msd.manga_id";
+
+fn download_job_from_row(row: &Row) -> Result<DownloadJob> {
+    Ok(DownloadJob {
+        id: row.get(0)?,
+        chapter_id: row.get(1)?,
+        state: row.get(2)?,
+        format: row.get(3)?,
+        attempts: row.get(4)?,
+        max_attempts: row.get(5)?,
+        next_attempt_at: row.get(6)?,
+        last_error: row.get(7)?,
+        file_path: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+        manga_id: row.get(11)?,
+        manga_title: row.get(12)?,
+        chapter_number: row.get(13)?,
+        source_id: row.get(14)?,
+    })
+}
+
+pub fn enqueue_download_jobs(
+    conn: &Connection,
+    chapter_ids: &[i32],
+    format: Option<&str>,
+    max_attempts: i32,
+    now_ts: i64,
+) -> Result<u64> {
+    let n = conn.execute(
+        "INSERT OR IGNORE INTO download_jobs (chapter_id, format, max_attempts, created_at, updated_at)
+         SELECT id, ?2, ?3, ?4, ?4 FROM chapters WHERE id IN (SELECT value FROM json_each(?1))",
+        params![json_ids(chapter_ids), format, max_attempts, now_ts],
+    )?;
+    Ok(n as u64)
+}
+
+pub fn get_unscraped_chapter_ids(conn: &Connection, limit: i64) -> Result<Vec<i32>> {
+    let mut stmt = conn.prepare("SELECT id FROM chapters WHERE scraped = 0 ORDER BY id LIMIT ?1")?;
+    let rows = stmt.query_map([limit], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn claim_download_job(conn: &mut Connection, now_ts: i64) -> Result<Option<DownloadJob>> {
+    let tx = conn.transaction()?;
+    let claimed: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM download_jobs
+             WHERE state = 'queued' AND next_attempt_at <= ?1
+             ORDER BY next_attempt_at, id LIMIT 1",
+            [now_ts],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(id) = claimed else {
+        return Ok(None);
+    };
+    tx.execute(
+        "UPDATE download_jobs SET state = 'running', attempts = attempts + 1, updated_at = ?2 WHERE id = ?1",
+        params![id, now_ts],
+    )?;
+    let job = tx
+        .query_row(
+            &format!("SELECT {} {} WHERE j.id = ?1", DOWNLOAD_JOB_COLUMNS, DOWNLOAD_JOB_JOINS),
+            [id],
+            download_job_from_row,
+        )
+        .optional()?;
+    tx.commit()?;
+    Ok(job)
+}
 
-    let rows = stmt.query_map([source_id], |row| {
-        Ok(Manga {
+pub fn complete_download_job(conn: &Connection, job_id: i64, file_path: &str, now_ts: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE download_jobs SET state = 'done', file_path = ?2, last_error = NULL, updated_at = ?3 WHERE id = ?1",
+        params![job_id, file_path, now_ts],
+    )?;
+    conn.execute(
+        "UPDATE chapters SET scraped = 1 WHERE id = (SELECT chapter_id FROM download_jobs WHERE id = ?1)",
+        [job_id],
+    )?;
+    Ok(())
+}
+
+pub fn fail_download_job(
+    conn: &Connection,
+    job_id: i64,
+    error: &str,
+    retry_at: i64,
+    now_ts: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE download_jobs SET
+            state = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'queued' END,
+            next_attempt_at = ?2, last_error = ?3, updated_at = ?4
+         WHERE id = ?1",
+        params![job_id, retry_at, error, now_ts],
+    )?;
+    Ok(())
+}
+
+pub fn requeue_running_download_jobs(conn: &Connection, now_ts: i64) -> Result<u64> {
+    let n = conn.execute(
+        "UPDATE download_jobs SET state = 'queued', attempts = MAX(attempts - 1, 0),
+            next_attempt_at = 0, updated_at = ?1
+         WHERE state = 'running'",
+        [now_ts],
+    )?;
+    Ok(n as u64)
+}
+
+pub fn retry_download_job(conn: &Connection, job_id: i64, now_ts: i64) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE download_jobs SET state = 'queued', attempts = 0, next_attempt_at = 0, updated_at = ?2
+         WHERE id = ?1 AND state = 'failed'",
+        params![job_id, now_ts],
+    )?;
+    Ok(n > 0)
+}
+
+pub fn list_download_jobs(
+    conn: &Connection,
+    state: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<DownloadJob>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} {} WHERE (?1 IS NULL OR j.state = ?1) ORDER BY j.id DESC LIMIT ?2 OFFSET ?3",
+        DOWNLOAD_JOB_COLUMNS, DOWNLOAD_JOB_JOINS
+    ))?;
+    let rows = stmt.query_map(params![state, limit, offset], download_job_from_row)?;
+    rows.collect()
+}
+
+pub fn get_match_candidates(conn: &Connection) -> Result<Vec<MatchCandidate>> {
+    // Unit separator: never part of a provider name or ID
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.title, m.alt_titles, m.cover_url, m.cover_hash,
+            group_concat(p.provider || ':' || p.provider_id, char(31))
+         FROM manga m LEFT JOIN provider_ids p ON p.manga_id = m.id
+         GROUP BY m.id ORDER BY m.id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let provider_ids: Option<String> = row.get(5)?;
+        Ok(MatchCandidate {
             id: row.get(0)?,
             title: row.get(1)?,
             alt_titles: row.get(2)?,
             cover_url: row.get(3)?,
-            description: row.get(4)?,
-            tags: row.get(5)?,
-            rating: row.get(6)?,
-            monitored: row.get(7)?,
-            check_interval_secs: row.get(8)?,
-            discover_interval_secs: row.get(9)?,
-            last_chapter_check: row.get(10)?,
-            last_discover_check: row.get(11)?,
+            cover_hash: row.get(4)?,
+            provider_ids: provider_ids
+                .map(|s| s.split('\u{1f}').map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn set_cover_hash(conn: &Connection, manga_id: &str, hash: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE manga SET cover_hash = ?2 WHERE id = ?1",
+        params![manga_id, hash],
+    )?;
+    Ok(())
+}
+
+pub fn insert_merge_proposal(
+    conn: &Connection,
+    a: (&str, &str),
+    b: (&str, &str),
+    score: f64,
+    reasons: &str,
+    automatic: bool,
+    now_ts: i64,
+) -> Result<Option<i32>> {
+    let (a, b) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    let state = if automatic { "accepted" } else { "pending" };
+    let decided_at = automatic.then_some(now_ts);
+    conn.query_row(
+        "INSERT INTO merge_proposals (manga_a, manga_b, title_a, title_b, score, reasons, state, automatic, created_at, decided_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT (manga_a, manga_b) DO NOTHING
+         RETURNING id",
+        params![a.0, b.0, a.1, b.1, score, reasons, state, automatic, now_ts, decided_at],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+const MERGE_PROPOSAL_COLUMNS: &str = "id, manga_a, manga_b, title_a, title_b, score, reasons, state, automatic, created_at, decided_at";
+
+fn merge_proposal_from_row(row: &Row) -> Result<MergeProposal> {
+    Ok(MergeProposal {
+        id: row.get(0)?,
+        manga_a: row.get(1)?,
+        manga_b: row.get(2)?,
+        title_a: row.get(3)?,
+        title_b: row.get(4)?,
+        score: row.get(5)?,
+        reasons: row.get(6)?,
+        state: row.get(7)?,
+        automatic: row.get(8)?,
+        created_at: row.get(9)?,
+        decided_at: row.get(10)?,
+    })
+}
+
+pub fn list_merge_proposals(
+    conn: &Connection,
+    state: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<MergeProposal>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM merge_proposals WHERE (?1 IS NULL OR state = ?1)
+         ORDER BY score DESC, id LIMIT ?2 OFFSET ?3",
+        MERGE_PROPOSAL_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![state, limit, offset], merge_proposal_from_row)?;
+    rows.collect()
+}
+
+pub fn get_merge_proposal(conn: &Connection, id: i32) -> Result<Option<MergeProposal>> {
+    conn.query_row(
+        &format!("SELECT {} FROM merge_proposals WHERE id = ?1", MERGE_PROPOSAL_COLUMNS),
+        [id],
+        merge_proposal_from_row,
+    )
+    .optional()
+}
+
+pub fn set_merge_proposal_state(
+    conn: &Connection,
+    id: i32,
+    from: &str,
+    to: &str,
+    now_ts: i64,
+) -> Result<bool> {
+    let decided_at = (to != "pending").then_some(now_ts);
+    let n = conn.execute(
+        "UPDATE merge_proposals SET state = ?3, decided_at = ?4 WHERE id = ?1 AND state = ?2",
+        params![id, from, to, decided_at],
+    )?;
+    Ok(n > 0)
+}
+
+/// Rows of `sql` as JSON objects keyed by column name, the way `to_jsonb`
+/// snapshots them on PostgreSQL
+fn rows_as_json(conn: &Connection, sql: &str, manga_id: &str) -> Result<Vec<Json>> {
+    let mut stmt = conn.prepare(sql)?;
+    let names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let rows = stmt.query_map([manga_id], |row| {
+        let mut obj = Map::new();
+        for (i, name) in names.iter().enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null | ValueRef::Blob(_) => Json::Null,
+                ValueRef::Integer(n) => json!(n),
+                ValueRef::Real(f) => json!(f),
+                ValueRef::Text(t) => json!(String::from_utf8_lossy(t)),
+            };
+            obj.insert(name.clone(), value);
+        }
+        Ok(Json::Object(obj))
+    })?;
+    rows.collect()
+}
+
+/// Insert snapshot rows back into `table`; an existing row with the same ID
+/// only gets `update` set back, and without `update` existing rows are kept
+fn restore_rows(conn: &Connection, table: &str, rows: &Json, update: Option<&str>) -> Result<()> {
+    for row in rows.as_array().into_iter().flatten() {
+        let Some(obj) = row.as_object() else { continue };
+        let columns: Vec<&str> = obj.keys().map(String::as_str).collect();
+        let values: Vec<Value> = obj
+            .values()
+            .map(|v| match v {
+                Json::Number(n) if n.is_i64() => Value::Integer(n.as_i64().unwrap_or_default()),
+                Json::Number(n) => Value::Real(n.as_f64().unwrap_or_default()),
+                Json::String(s) => Value::Text(s.clone()),
+                Json::Bool(b) => Value::Integer(*b as i64),
+                _ => Value::Null,
+            })
+            .collect();
+        let placeholders: Vec<String> = (1..=values.len()).map(|i| format!("?{}", i)).collect();
+        let conflict = match update {
+            Some(col) => format!(" ON CONFLICT (id) DO UPDATE SET {0} = excluded.{0}", col),
+            None => " ON CONFLICT DO NOTHING".to_string(),
+        };
+        conn.execute(
+            &format!(
+                "INSERT INTO {} ({}) VALUES ({}){}",
+                table,
+                columns.join(", "),
+                placeholders.join(", "),
+                conflict
+            ),
+            rusqlite::params_from_iter(values),
+        )?;
+    }
+    Ok(())
+}
+
+pub fn merge_manga(conn: &mut Connection, target_id: &str, other_id: &str, now_ts: i64) -> Result<i32> {
+    let tx = conn.transaction()?;
+
+    let load = |id: &str| {
+        tx.query_row(
+            "SELECT title, alt_titles, cover_url, description FROM manga WHERE id = ?1",
+            [id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            },
+        )
+    };
+    let target = load(target_id)?;
+    let other = load(other_id)?;
+
+    let one = |rows: Vec<Json>| rows.into_iter().next().unwrap_or(Json::Null);
+    let snapshot = json!({
+        "target": one(rows_as_json(&tx, "SELECT * FROM manga WHERE id = ?1", target_id)?),
+        "other": one(rows_as_json(&tx, "SELECT * FROM manga WHERE id = ?1", other_id)?),
+        "sources": rows_as_json(&tx, "SELECT * FROM manga_source_data WHERE manga_id = ?1", other_id)?,
+        "chapters": rows_as_json(
+            &tx,
+            "SELECT c.* FROM chapters c JOIN manga_source_data s ON s.id = c.manga_source_data_id WHERE s.manga_id = ?1",
+            other_id,
+        )?,
+        "providers": rows_as_json(&tx, "SELECT * FROM provider_ids WHERE manga_id = ?1", other_id)?,
+        "tags": rows_as_json(&tx, "SELECT * FROM manga_tags WHERE manga_id = ?1", other_id)?,
+        "people": rows_as_json(&tx, "SELECT * FROM manga_people WHERE manga_id = ?1", other_id)?,
+        "library": rows_as_json(&tx, "SELECT * FROM library_entries WHERE manga_id = ?1", other_id)?,
+        "reads": rows_as_json(
+            &tx,
+            "SELECT r.* FROM chapter_reads r
+             JOIN chapters c ON c.id = r.chapter_id
+             JOIN manga_source_data s ON s.id = c.manga_source_data_id WHERE s.manga_id = ?1",
+            other_id,
+        )?,
+    });
+    let audit_id: i32 = tx.query_row(
+        "INSERT INTO manga_audit (action, manga_id, other_manga_id, snapshot, created_at)
+         VALUES ('merge', ?1, ?2, ?3, ?4)
+         RETURNING id",
+        params![target_id, other_id, snapshot.to_string(), now_ts],
+        |row| row.get(0),
+    )?;
+
+    let mut alt_titles: Option<String> = target.1.filter(|s| !s.is_empty());
+    if other.0 != target.0 {
+        crate::helpers::merge_alt_titles(&mut alt_titles, &other.0);
+    }
+    if let Some(titles) = &other.1 {
+        crate::helpers::merge_alt_titles(&mut alt_titles, titles);
+    }
+    tx.execute(
+        "UPDATE manga SET alt_titles = ?2,
+            cover_url = COALESCE(NULLIF(cover_url, ''), ?3),
+            description = COALESCE(NULLIF(description, ''), ?4)
+         WHERE id = ?1",
+        params![target_id, alt_titles, other.2, other.3],
+    )?;
+
+    tx.execute(
+        "UPDATE chapters SET manga_source_data_id = t.id
+         FROM manga_source_data o
+         JOIN manga_source_data t ON t.source_id = o.source_id AND t.manga_id = ?1
+         WHERE o.manga_id = ?2 AND chapters.manga_source_data_id = o.id
+           AND NOT EXISTS (SELECT 1 FROM chapters x WHERE x.manga_source_data_id = t.id AND x.url = chapters.url)",
+        params![target_id, other_id],
+    )?;
+    tx.execute(
+        "UPDATE manga_source_data SET manga_id = ?1
+         WHERE manga_id = ?2
+           AND source_id NOT IN (SELECT source_id FROM manga_source_data WHERE manga_id = ?1)",
+        params![target_id, other_id],
+    )?;
+    tx.execute(
+        "UPDATE provider_ids SET manga_id = ?1
+         WHERE manga_id = ?2
+           AND provider NOT IN (SELECT provider FROM provider_ids WHERE manga_id = ?1)",
+        params![target_id, other_id],
+    )?;
+    tx.execute(
+        "UPDATE library_entries SET manga_id = ?1
+         WHERE manga_id = ?2
+           AND user_id NOT IN (SELECT user_id FROM library_entries WHERE manga_id = ?1)",
+        params![target_id, other_id],
+    )?;
+    tx.execute(
+        "DELETE FROM merge_proposals WHERE state = 'pending' AND (manga_a = ?1 OR manga_b = ?1)",
+        [other_id],
+    )?;
+    // No ON DELETE CASCADE here: drop what the other manga kept explicitly
+    tx.execute(
+        "DELETE FROM chapter_reads WHERE chapter_id IN (
+            SELECT c.id FROM chapters c JOIN manga_source_data s ON s.id = c.manga_source_data_id
+            WHERE s.manga_id = ?1)",
+        [other_id],
+    )?;
+    tx.execute(
+        "DELETE FROM download_jobs WHERE chapter_id IN (
+            SELECT c.id FROM chapters c JOIN manga_source_data s ON s.id = c.manga_source_data_id
+            WHERE s.manga_id = ?1)",
+        [other_id],
+    )?;
+    tx.execute(
+        "DELETE FROM chapters WHERE manga_source_data_id IN (SELECT id FROM manga_source_data WHERE manga_id = ?1)",
+        [other_id],
+    )?;
+    tx.execute("DELETE FROM manga_source_data WHERE manga_id = ?1", [other_id])?;
+    tx.execute("DELETE FROM provider_ids WHERE manga_id = ?1", [other_id])?;
+    tx.execute("DELETE FROM manga_tags WHERE manga_id = ?1", [other_id])?;
+    tx.execute("DELETE FROM manga_people WHERE manga_id = ?1", [other_id])?;
+    tx.execute("DELETE FROM library_entries WHERE manga_id = ?1", [other_id])?;
+    tx.execute("DELETE FROM manga WHERE id = ?1", [other_id])?;
+
+    tx.commit()?;
+    Ok(audit_id)
+}
+
+pub fn split_manga(
+    conn: &mut Connection,
+    manga_id: &str,
+    source_ids: &[i32],
+    title: Option<&str>,
+    now_ts: i64,
+) -> Result<Option<(String, i32)>> {
+    let tx = conn.transaction()?;
+
+    let moved: Vec<i32> = {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM manga_source_data
+             WHERE manga_id = ?1 AND source_id IN (SELECT value FROM json_each(?2)) ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![manga_id, json_ids(source_ids)], |row| row.get(0))?;
+        rows.collect::<Result<_>>()?
+    };
+    if moved.is_empty() {
+        return Ok(None);
+    }
+
+    let new_id = uuid::Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO manga (id, title, cover_url, description)
+         SELECT ?2, COALESCE(?3, title), cover_url, description FROM manga WHERE id = ?1",
+        params![manga_id, new_id, title],
+    )?;
+    tx.execute(
+        "UPDATE manga_source_data SET manga_id = ?2 WHERE id IN (SELECT value FROM json_each(?1))",
+        params![json_ids(&moved), new_id],
+    )?;
+    let audit_id: i32 = tx.query_row(
+        "INSERT INTO manga_audit (action, manga_id, other_manga_id, snapshot, created_at)
+         VALUES ('split', ?1, ?2, ?3, ?4)
+         RETURNING id",
+        params![manga_id, new_id, json!({ "source_ids": moved }).to_string(), now_ts],
+        |row| row.get(0),
+    )?;
+
+    tx.commit()?;
+    Ok(Some((new_id, audit_id)))
+}
+
+const MANGA_AUDIT_COLUMNS: &str = "id, action, manga_id, other_manga_id, created_at, undone_at";
+
+fn manga_audit_from_row(row: &Row) -> Result<MangaAuditEntry> {
+    Ok(MangaAuditEntry {
+        id: row.get(0)?,
+        action: row.get(1)?,
+        manga_id: row.get(2)?,
+        other_manga_id: row.get(3)?,
+        created_at: row.get(4)?,
+        undone_at: row.get(5)?,
+    })
+}
+
+pub fn list_manga_audit(
+    conn: &Connection,
+    manga_id: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<MangaAuditEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM manga_audit
+         WHERE (?1 IS NULL OR manga_id = ?1 OR other_manga_id = ?1)
+         ORDER BY id DESC LIMIT ?2 OFFSET ?3",
+        MANGA_AUDIT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![manga_id, limit, offset], manga_audit_from_row)?;
+    rows.collect()
+}
+
+pub fn undo_manga_audit(conn: &mut Connection, id: i32, now_ts: i64) -> Result<UndoOutcome> {
+    let tx = conn.transaction()?;
+
+    let Some((entry, snapshot)) = tx
+        .query_row(
+            &format!("SELECT {}, snapshot FROM manga_audit WHERE id = ?1", MANGA_AUDIT_COLUMNS),
+            [id],
+            |row| Ok((manga_audit_from_row(row)?, row.get::<_, String>(6)?)),
+        )
+        .optional()?
+    else {
+        return Ok(UndoOutcome::NotFound);
+    };
+    if entry.undone_at.is_some() {
+        return Ok(UndoOutcome::AlreadyUndone);
+    }
+    let later: Option<i32> = tx
+        .query_row(
+            "SELECT 1 FROM manga_audit
+             WHERE id > ?1 AND undone_at IS NULL
+               AND (manga_id IN (?2, ?3) OR other_manga_id IN (?2, ?3))
+             LIMIT 1",
+            params![id, entry.manga_id, entry.other_manga_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if later.is_some() {
+        return Ok(UndoOutcome::Superseded);
+    }
+
+    let snapshot: Json = serde_json::from_str(&snapshot).unwrap_or(Json::Null);
+    if entry.action == "merge" {
+        restore_rows(&tx, "manga", &json!([snapshot["other"]]), None)?;
+        let target = &snapshot["target"];
+        tx.execute(
+            "UPDATE manga SET alt_titles = ?2, cover_url = ?3, description = ?4 WHERE id = ?1",
+            params![
+                entry.manga_id,
+                target["alt_titles"].as_str(),
+                target["cover_url"].as_str(),
+                target["description"].as_str()
+            ],
+        )?;
+        restore_rows(&tx, "manga_source_data", &snapshot["sources"], Some("manga_id"))?;
+        restore_rows(&tx, "chapters", &snapshot["chapters"], Some("manga_source_data_id"))?;
+        restore_rows(&tx, "provider_ids", &snapshot["providers"], Some("manga_id"))?;
+        restore_rows(&tx, "manga_tags", &snapshot["tags"], None)?;
+        restore_rows(&tx, "manga_people", &snapshot["people"], None)?;
+        restore_rows(&tx, "library_entries", &snapshot["library"], Some("manga_id"))?;
+        restore_rows(&tx, "chapter_reads", &snapshot["reads"], None)?;
+    } else {
+        tx.execute(
+            "UPDATE manga_source_data SET manga_id = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+            params![snapshot["source_ids"].to_string(), entry.manga_id],
+        )?;
+        tx.execute(
+            "DELETE FROM provider_ids WHERE manga_id = ?1",
+            [&entry.other_manga_id],
+        )?;
+        tx.execute("DELETE FROM manga_tags WHERE manga_id = ?1", [&entry.other_manga_id])?;
+        tx.execute("DELETE FROM manga_people WHERE manga_id = ?1", [&entry.other_manga_id])?;
+        tx.execute("DELETE FROM library_entries WHERE manga_id = ?1", [&entry.other_manga_id])?;
+        tx.execute("DELETE FROM manga WHERE id = ?1", [&entry.other_manga_id])?;
+    }
+    tx.execute(
+        "UPDATE manga_audit SET undone_at = ?2 WHERE id = ?1",
+        params![id, now_ts],
+    )?;
+
+    tx.commit()?;
+    Ok(UndoOutcome::Undone)
+}
+
+pub fn count_missing_provider(conn: &Connection, provider: MetadataProvider) -> Result<i64> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM manga WHERE {}", provider.missing_condition()),
+        [],
+        |row| row.get(0),
+    )
+}
+
+pub fn get_manga_missing_provider(
+    conn: &Connection,
+    provider: MetadataProvider,
+    limit: Option<i64>,
+) -> Result<Vec<MangaTitles>> {
+    // A negative LIMIT means no limit in SQLite
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, title, COALESCE(alt_titles, '') FROM manga WHERE {} ORDER BY id LIMIT ?1",
+        provider.missing_condition()
+    ))?;
+    let rows = stmt.query_map([limit.unwrap_or(-1)], |row| {
+        Ok(MangaTitles {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            alt_titles: row.get(2)?,
         })
     })?;
+    rows.collect()
+}
+
+pub fn set_provider_id(
+    conn: &mut Connection,
+    manga_id: &str,
+    provider: MetadataProvider,
+    provider_id: &str,
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    let sql = format!("UPDATE manga SET {} = ?1 WHERE id = ?2", provider.column());
+    match provider {
+        MetadataProvider::MangaBaka => tx.execute(&sql, params![provider_id, manga_id])?,
+        MetadataProvider::Mal | MetadataProvider::AniList => {
+            let numeric = provider_id.parse::<i64>().unwrap_or(0);
+            tx.execute(&sql, params![numeric, manga_id])?
+        }
+    };
+    tx.execute(
+        "INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES (?1, ?2, ?3)
+         ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = excluded.provider_id",
+        params![manga_id, provider.name(), provider_id],
+    )?;
+    tx.commit()
+}
 
-    let mut manga_list = Vec::new();
-    for row in rows {
-        manga_list.push(row?);
+pub fn get_provider_links(conn: &Connection) -> Result<Vec<ProviderLinks>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, COALESCE(mangabaka_id, ''), COALESCE(mal_id, 0), COALESCE(anilist_id, 0)
+         FROM manga ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ProviderLinks {
+            manga_id: row.get(0)?,
+            mangabaka_id: row.get(1)?,
+            mal_id: row.get(2)?,
+            anilist_id: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Replace a manga's tag links and its `tags` text with `tags`
+fn link_tags(conn: &Connection, manga_id: &str, tags: &[TagRef]) -> Result<()> {
+    conn.execute("DELETE FROM manga_tags WHERE manga_id = ?1", [manga_id])?;
+    for tag in tags {
+        conn.execute(
+            "INSERT INTO tags (slug, name, category) VALUES (?1, ?2, ?3) ON CONFLICT (slug) DO NOTHING",
+            params![tag.slug, tag.name, tag.category.map(|c| c.as_str())],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO manga_tags (manga_id, tag_id) SELECT ?1, id FROM tags WHERE slug = ?2",
+            params![manga_id, tag.slug],
+        )?;
+    }
+    conn.execute(
+        "UPDATE manga SET tags = ?2 WHERE id = ?1",
+        params![manga_id, tags::display_list(tags).unwrap_or_default()],
+    )?;
+    Ok(())
+}
+
+pub fn set_manga_tags(conn: &mut Connection, manga_id: &str, tags: &[TagRef]) -> Result<()> {
+    let tx = conn.transaction()?;
+    link_tags(&tx, manga_id, tags)?;
+    tx.commit()
+}
+
+fn tag_from_row(row: &Row) -> Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        slug: row.get(1)?,
+        name: row.get(2)?,
+        category: row.get(3)?,
+        manga_count: None,
+    })
+}
+
+pub fn list_tags(conn: &Connection, category: Option<&str>) -> Result<Vec<Tag>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.slug, t.name, t.category, COUNT(mt.manga_id)
+         FROM tags t LEFT JOIN manga_tags mt ON mt.tag_id = t.id
+         WHERE ?1 IS NULL OR t.category = ?1
+         GROUP BY t.id, t.slug, t.name, t.category
+         ORDER BY COUNT(mt.manga_id) DESC, t.name",
+    )?;
+    let rows = stmt.query_map([category], |row| {
+        Ok(Tag {
+            manga_count: Some(row.get(4)?),
+            ..tag_from_row(row)?
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_manga_without_tag_links(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.tags FROM manga m
+         WHERE coalesce(m.tags, '') <> ''
+           AND NOT EXISTS (SELECT 1 FROM manga_tags mt WHERE mt.manga_id = m.id)
+         ORDER BY m.id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Credit `people` on a manga, replacing its credits in the roles `people`
+/// covers and leaving other roles alone
+fn link_people(conn: &Connection, manga_id: &str, people: &[Credit]) -> Result<()> {
+    for role in PersonRole::ALL {
+        if people.iter().any(|c| c.role == role) {
+            conn.execute(
+                "DELETE FROM manga_people WHERE manga_id = ?1 AND role = ?2",
+                params![manga_id, role.as_str()],
+            )?;
+        }
     }
+    for credit in people {
+        let key = people::name_key(&credit.name);
+        conn.execute(
+            "INSERT INTO people (name, name_key) VALUES (?1, ?2) ON CONFLICT (name_key) DO NOTHING",
+            params![credit.name, key],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO manga_people (manga_id, person_id, role)
+             SELECT ?1, id, ?3 FROM people WHERE name_key = ?2",
+            params![manga_id, key, credit.role.as_str()],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn set_manga_people(conn: &mut Connection, manga_id: &str, people: &[Credit]) -> Result<()> {
+    let tx = conn.transaction()?;
+    link_people(&tx, manga_id, people)?;
+    tx.commit()
+}
+
+fn get_manga_people(conn: &Connection, manga_id: &str) -> Result<Vec<Credit>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name, mp.role FROM manga_people mp JOIN people p ON p.id = mp.person_id
+         WHERE mp.manga_id = ?1 ORDER BY p.name, mp.role",
+    )?;
+    let rows = stmt.query_map([manga_id], |row| {
+        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+    })?;
+    Ok(rows
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter_map(|(id, name, role)| {
+            Some(Credit {
+                person_id: Some(id),
+                name,
+                role: PersonRole::parse(&role)?,
+            })
+        })
+        .collect())
+}
+
+pub fn get_person(conn: &Connection, person_id: i32) -> Result<Option<Person>> {
+    let Some(name) = conn
+        .query_row("SELECT name FROM people WHERE id = ?1", [person_id], |row| row.get(0))
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.title, m.cover_url, mp.role
+         FROM manga_people mp JOIN manga m ON m.id = mp.manga_id
+         WHERE mp.person_id = ?1 ORDER BY m.title, m.id, mp.role",
+    )?;
+    let rows = stmt.query_map([person_id], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })?;
+    Ok(Some(Person {
+        id: person_id,
+        name,
+        works: people::group_works(rows.collect::<Result<_>>()?),
+    }))
+}
+
+pub fn update_manga_metadata(
+    conn: &Connection,
+    manga_id: &str,
+    description: Option<&str>,
+    tags: Option<&str>,
+    rating: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE manga SET description = COALESCE(?1, description), tags = COALESCE(?2, tags),
+            rating = COALESCE(?3, rating)
+         WHERE id = ?4",
+        params![description, tags, rating, manga_id],
+    )?;
+    Ok(())
+}
+
+fn user_from_row(row: &Row) -> Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+    })
+}
+
+pub fn create_user(conn: &Connection, name: &str, now_ts: i64) -> Result<Option<User>> {
+    conn.query_row(
+        "INSERT INTO users (name, created_at) VALUES (?1, ?2)
+         ON CONFLICT (name) DO NOTHING
+         RETURNING id, name, created_at",
+        params![name, now_ts],
+        user_from_row,
+    )
+    .optional()
+}
+
+pub fn list_users(conn: &Connection) -> Result<Vec<User>> {
+    let mut stmt = conn.prepare("SELECT id, name, created_at FROM users ORDER BY name")?;
+    let rows = stmt.query_map([], user_from_row)?;
+    rows.collect()
+}
+
+pub fn get_user_by_name(conn: &Connection, name: &str) -> Result<Option<User>> {
+    conn.query_row(
+        "SELECT id, name, created_at FROM users WHERE name = ?1",
+        [name],
+        user_from_row,
+    )
+    .optional()
+}
+
+pub fn set_library_entry(
+    conn: &Connection,
+    user_id: i32,
+    manga_id: &str,
+    status: LibraryStatus,
+    now_ts: i64,
+) -> Result<bool> {
+    let n = conn.execute(
+        "INSERT INTO library_entries (user_id, manga_id, status, added_at, updated_at)
+         SELECT ?1, id, ?3, ?4, ?4 FROM manga WHERE id = ?2
+         ON CONFLICT (user_id, manga_id) DO UPDATE SET status = excluded.status, updated_at = excluded.updated_at",
+        params![user_id, manga_id, status.as_str(), now_ts],
+    )?;
+    Ok(n > 0)
+}
+
+pub fn remove_library_entry(conn: &Connection, user_id: i32, manga_id: &str) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM library_entries WHERE user_id = ?1 AND manga_id = ?2",
+        params![user_id, manga_id],
+    )?;
+    Ok(n > 0)
+}
+
+pub fn get_library(
+    conn: &Connection,
+    user_id: i32,
+    status: Option<LibraryStatus>,
+) -> Result<Vec<LibraryEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT le.manga_id, m.title, m.cover_url, le.status, le.added_at, le.updated_at,
+            COUNT(DISTINCT c.chapter_number) FILTER (WHERE r.completed), MAX(r.updated_at)
+         FROM library_entries le
+         JOIN manga m ON m.id = le.manga_id
+         LEFT JOIN manga_source_data msd ON msd.manga_id = le.manga_id
+         LEFT JOIN chapters c ON c.manga_source_data_id = msd.id
+         LEFT JOIN chapter_reads r ON r.chapter_id = c.id AND r.user_id = le.user_id
+         WHERE le.user_id = ?1 AND (?2 IS NULL OR le.status = ?2)
+         GROUP BY le.id
+         ORDER BY le.updated_at DESC, m.title",
+    )?;
+    let rows = stmt.query_map(params![user_id, status.map(LibraryStatus::as_str)], |row| {
+        Ok((
+            LibraryEntry {
+                manga_id: row.get(0)?,
+                title: row.get(1)?,
+                cover_url: row.get(2)?,
+                status: LibraryStatus::Reading,
+                added_at: row.get(4)?,
+                updated_at: row.get(5)?,
+                chapters_read: row.get(6)?,
+                last_read_at: row.get(7)?,
+            },
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+    Ok(rows
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter_map(|(entry, status)| {
+            Some(LibraryEntry {
+                status: LibraryStatus::parse(&status)?,
+                ..entry
+            })
+        })
+        .collect())
+}
+
+pub fn set_chapter_read(
+    conn: &mut Connection,
+    user_id: i32,
+    chapter_id: i32,
+    page: i32,
+    completed: bool,
+    now_ts: i64,
+) -> Result<bool> {
+    let tx = conn.transaction()?;
+
+    let n = tx.execute(
+        "INSERT INTO chapter_reads (user_id, chapter_id, page, completed, updated_at)
+         SELECT ?1, id, ?3, ?4, ?5 FROM chapters WHERE id = ?2
+         ON CONFLICT (user_id, chapter_id) DO UPDATE
+            SET page = excluded.page, completed = excluded.completed, updated_at = excluded.updated_at",
+        params![user_id, chapter_id, page, completed, now_ts],
+    )?;
+    if n == 0 {
+        return Ok(false);
+    }
+    tx.execute(
+        "INSERT INTO library_entries (user_id, manga_id, status, added_at, updated_at)
+         SELECT ?1, msd.manga_id, 'reading', ?3, ?3
+         FROM chapters c JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         WHERE c.id = ?2
+         ON CONFLICT (user_id, manga_id) DO UPDATE SET
+            status = CASE WHEN library_entries.status = 'plan_to_read' THEN 'reading' ELSE library_entries.status END,
+            updated_at = excluded.updated_at",
+        params![user_id, chapter_id, now_ts],
+    )?;
+
+    tx.commit()?;
+    Ok(true)
+}
+
+pub fn clear_chapter_read(conn: &Connection, user_id: i32, chapter_id: i32) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM chapter_reads WHERE user_id = ?1 AND chapter_id = ?2",
+        params![user_id, chapter_id],
+    )?;
+    Ok(n > 0)
+}
+
+pub fn get_chapter_reads(conn: &Connection, user_id: i32, manga_id: &str) -> Result<Vec<ChapterRead>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.chapter_number, msd.source_id, r.page, r.completed, r.updated_at
+         FROM chapter_reads r
+         JOIN chapters c ON c.id = r.chapter_id
+         JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         WHERE r.user_id = ?1 AND msd.manga_id = ?2
+         ORDER BY c.sort_key NULLS LAST, msd.source_id, c.id",
+    )?;
+    let rows = stmt.query_map(params![user_id, manga_id], |row| {
+        Ok(ChapterRead {
+            chapter_id: row.get(0)?,
+            chapter_number: row.get(1)?,
+            source_id: row.get(2)?,
+            page: row.get(3)?,
+            completed: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_continue_reading(conn: &Connection, user_id: i32, limit: i64) -> Result<Vec<ContinueReading>> {
+    let mut stmt = conn.prepare(
+        "WITH last AS (
+            SELECT msd.manga_id, c.id, c.manga_source_data_id, COALESCE(c.sort_key, 1e308) AS pos,
+                r.completed, r.updated_at,
+                ROW_NUMBER() OVER (PARTITION BY msd.manga_id ORDER BY r.updated_at DESC, c.id DESC) AS n
+            FROM chapter_reads r
+            JOIN chapters c ON c.id = r.chapter_id
+            JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+            JOIN library_entries le ON le.user_id = r.user_id AND le.manga_id = msd.manga_id
+            WHERE r.user_id = ?1 AND le.status = 'reading'
+         ), next AS (
+            SELECT l.manga_id, l.updated_at,
+                CASE WHEN l.completed THEN (
+                    SELECT x.id FROM chapters x
+                    WHERE x.manga_source_data_id = l.manga_source_data_id
+                      AND (COALESCE(x.sort_key, 1e308), x.id) > (l.pos, l.id)
+                      AND NOT EXISTS (SELECT 1 FROM chapter_reads xr
+                                      WHERE xr.user_id = ?1 AND xr.chapter_id = x.id AND xr.completed)
+                    ORDER BY COALESCE(x.sort_key, 1e308), x.id
+                    LIMIT 1
+                ) ELSE l.id END AS chapter_id
+            FROM last l WHERE l.n = 1
+         )
+         SELECT m.id, m.title, m.cover_url, c.id, c.chapter_number, s.id, s.name,
+            COALESCE(r.page, 0), nx.updated_at
+         FROM next nx
+         JOIN manga m ON m.id = nx.manga_id
+         JOIN chapters c ON c.id = nx.chapter_id
+         JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         JOIN sources s ON s.id = msd.source_id
+         LEFT JOIN chapter_reads r ON r.user_id = ?1 AND r.chapter_id = c.id
+         ORDER BY nx.updated_at DESC, m.id
+         LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![user_id, limit], |row| {
+        Ok(ContinueReading {
+            manga_id: row.get(0)?,
+            title: row.get(1)?,
+            cover_url: row.get(2)?,
+            chapter_id: row.get(3)?,
+            chapter_number: row.get(4)?,
+            source_id: row.get(5)?,
+            source_name: row.get(6)?,
+            page: row.get(7)?,
+            last_read_at: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_user(conn: &Connection, user_id: i32) -> Result<Option<User>> {
+    conn.query_row(
+        "SELECT id, name, created_at FROM users WHERE id = ?1",
+        [user_id],
+        user_from_row,
+    )
+    .optional()
+}
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, user_id, created_at, last_used_at, revoked_at";
+
+fn api_key_from_row(row: &Row) -> Result<ApiKey> {
+    Ok(ApiKey {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        prefix: row.get(2)?,
+        scopes: auth::parse_scopes(&row.get::<_, String>(3)?).unwrap_or_default(),
+        user_id: row.get(4)?,
+        created_at: row.get(5)?,
+        last_used_at: row.get(6)?,
+        revoked_at: row.get(7)?,
+    })
+}
+
+pub fn create_api_key(
+    conn: &Connection,
+    name: &str,
+    key_hash: &str,
+    prefix: &str,
+    scopes: &[Scope],
+    user_id: Option<i32>,
+    now_ts: i64,
+) -> Result<ApiKey> {
+    conn.query_row(
+        &format!(
+            "INSERT INTO api_keys (name, key_hash, prefix, scopes, user_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ),
+        params![name, key_hash, prefix, auth::join_scopes(scopes), user_id, now_ts],
+        api_key_from_row,
+    )
+}
+
+pub fn list_api_keys(conn: &Connection) -> Result<Vec<ApiKey>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM api_keys ORDER BY id", API_KEY_COLUMNS))?;
+    let rows = stmt.query_map([], api_key_from_row)?;
+    rows.collect()
+}
+
+pub fn revoke_api_key(conn: &Connection, id: i32, now_ts: i64) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE api_keys SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL",
+        params![id, now_ts],
+    )?;
+    Ok(n > 0)
+}
 
-    Ok(manga_list)
+pub fn authenticate_api_key(conn: &Connection, key_hash: &str, now_ts: i64) -> Result<Option<ApiKey>> {
+    conn.query_row(
+        &format!(
+            "UPDATE api_keys SET last_used_at = ?2 WHERE key_hash = ?1 AND revoked_at IS NULL
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ),
+        params![key_hash, now_ts],
+        api_key_from_row,
+    )
+    .optional()
 }
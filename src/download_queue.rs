@@ -0,0 +1,336 @@
+//! Persistent download queue backed by the `download_jobs` table.
+//!
+//! The `/queue/...` endpoints add one job per chapter; `download_workers`
+//! workers claim due jobs atomically (so they never share one),
+//! download them exactly like `GET /download/...` does, and record the result
+//! (including the file on the chapter that was downloaded, see
+//! [`library::record_download`]).
+//! A failed attempt is queued again with exponential backoff until the job runs
+//! out of attempts; before that, each attempt falls back to the manga's other
+//! sources in priority order. Jobs still marked `running` when the server
+//! starts were interrupted by a restart and are put back in the queue.
+
+use crate::app_state::AppState;
+use crate::chapter_number::{find_same_chapter, ChapterKind, ChapterNumber};
+use crate::comicinfo::ComicInfo;
+use crate::helpers::normalize_chapter_str;
+use crate::library;
+use crate::models::{Chapter, DownloadJob, Manga, QueueRequest};
+use crate::naming::ChapterName;
+use crate::output::OutputFormat;
+use crate::scraper::{self, ChapterDownload};
+use crate::storage::{Storage, StorageError};
+use actix_web::web;
+use chrono::Utc;
+use log::{error, info, warn};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// How long an idle worker waits before polling the queue again.
+const IDLE_POLL: Duration = Duration::from_secs(5);
+/// Upper bound for the retry backoff.
+const MAX_RETRY_DELAY_SECS: u64 = 6 * 60 * 60;
+
+/// Resume interrupted jobs, then start the configured number of workers.
+pub fn spawn_workers(data: web::Data<AppState>) {
+    let workers = data.config.download_workers;
+    actix_web::rt::spawn(async move {
+        match data.storage.requeue_running_download_jobs(Utc::now().timestamp()).await {
+            Ok(0) => {}
+            Ok(n) => info!("Resumed {} interrupted download jobs", n),
+            Err(e) => error!("Failed to resume interrupted download jobs: {}", e),
+        }
+        for worker in 0..workers {
+            actix_web::rt::spawn(run_worker(data.clone(), worker));
+        }
+    });
+}
+
+async fn run_worker(data: web::Data<AppState>, worker: usize) {
+    loop {
+        let job = match data.storage.claim_download_job(Utc::now().timestamp()).await {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                actix_web::rt::time::sleep(IDLE_POLL).await;
+                continue;
+            }
+            Err(e) => {
+                error!("Download worker {}: failed to claim a job: {}", worker, e);
+                actix_web::rt::time::sleep(IDLE_POLL).await;
+                continue;
+            }
+        };
+        info!(
+            "Download worker {}: job {} ({} chapter {}, attempt {}/{})",
+            worker, job.id, job.manga_title, job.chapter_number, job.attempts, job.max_attempts
+        );
+
+        let result = run_job(&data, &job).await;
+        let now = Utc::now().timestamp();
+        let recorded = match result {
+            Ok((chapter_id, file_path)) => {
+                library::record_download(&data, chapter_id, &file_path).await;
+                data.storage.complete_download_job(job.id, &file_path, now).await
+            }
+            Err(e) => {
+                warn!("Download job {} failed: {}", job.id, e);
+                let delay = retry_delay_secs(data.config.download_retry_delay_secs, job.attempts);
+                data.storage.fail_download_job(job.id, &e, now + delay as i64, now).await
+            }
+        };
+        if let Err(e) = recorded {
+            error!(
+                "Failed to record the result of download job {}: {}",
+                job.id, e
+            );
+        }
+    }
+}
+
+/// Copies of a chapter on each of a manga's sources, in source priority order.
+/// A numbered `query` is matched with [`find_same_chapter`], so "Ch. 5" and
+/// "Chapter 5" line up across sources; one without a number ("Oneshot") only
+/// matches the same label.
+pub async fn source_candidates(
+    storage: &dyn Storage,
+    manga_id: &str,
+    query: &str,
+) -> Result<Vec<(i32, Chapter)>, StorageError> {
+    let mut candidates = Vec::new();
+    for msd in storage.get_manga_source_data_by_manga_id(manga_id).await? {
+        let chapters =
+            storage.get_chapters_by_manga_source_data_id(&msd.manga_id, msd.source_id).await?;
+        let found = match ChapterNumber::parse(query).match_key() {
+            Some(_) => find_same_chapter(&chapters, query),
+            None => chapters.iter().find(|c| {
+                normalize_chapter_str(&c.chapter_number) == normalize_chapter_str(query)
+            }),
+        };
+        if let Some(chapter) = found {
+            candidates.push((msd.source_id, chapter.clone()));
+        }
+    }
+    Ok(candidates)
+}
+
+/// Download one job's chapter to disk and return the chapter that was
+/// downloaded with the written path. If the job's source fails, the same
+/// chapter is tried on the manga's other sources in priority order; only a
+/// copy with the same chapter number counts, so a chapter without one has no
+/// fallback.
+async fn run_job(data: &web::Data<AppState>, job: &DownloadJob) -> Result<(i32, String), String> {
+    let chapter = data.storage.get_chapter_with_source(job.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("chapter no longer exists")?;
+    let manga = data.storage.get_manga_by_id(&job.manga_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("manga no longer exists")?;
+
+    let format = match job.format.as_deref().and_then(OutputFormat::parse) {
+        Some(f) => f,
+        None => data.storage.get_manga_output_format(&manga.id)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|f| OutputFormat::parse(&f))
+            .unwrap_or(data.config.output_format),
+    };
+
+    let name = ChapterName::new(&manga.title, chapter.source_id, &chapter.label(), &chapter.url)
+        .with_release(
+            chapter.scanlation_group.as_deref(),
+            chapter.language.as_deref(),
+            chapter.published_at,
+        );
+    let first_error = match download_from(data, &manga, &chapter.url, &name, format).await {
+        Ok(path) => return Ok((chapter.id, path)),
+        Err(e) => e,
+    };
+    if ChapterNumber::parse(&chapter.chapter_number).match_key().is_none() {
+        return Err(first_error);
+    }
+    let fallbacks = source_candidates(data.storage.as_ref(), &manga.id, &chapter.chapter_number)
+        .await
+        .unwrap_or_default();
+    for (source_id, fallback) in fallbacks.iter().filter(|(s, _)| *s != chapter.source_id) {
+        let name = ChapterName::new(&manga.title, *source_id, &fallback.label(), &fallback.url)
+            .with_release(
+                fallback.scanlation_group.as_deref(),
+                fallback.language.as_deref(),
+                fallback.published_at,
+            );
+        match download_from(data, &manga, &fallback.url, &name, format).await {
+            Ok(path) => {
+                info!(
+                    "Download job {}: source {} failed, served by source {}",
+                    job.id, chapter.source_id, source_id
+                );
+                return Ok((fallback.id, path));
+            }
+            Err(e) => warn!(
+                "Download job {}: fallback source {} failed: {}",
+                job.id, source_id, e
+            ),
+        }
+    }
+    Err(first_error)
+}
+
+async fn download_from(
+    data: &web::Data<AppState>,
+    manga: &Manga,
+    chapter_url: &str,
+    name: &ChapterName,
+    format: OutputFormat,
+) -> Result<String, String> {
+    let template = &data.config.chapter_template;
+    if let Some(cu) = &manga.cover_url {
+        let _ = scraper::ensure_cover_downloaded(
+            &data.client,
+            &data.config.download_dir,
+            &template.series_dir(name),
+            cu,
+            name.source_id,
+        )
+        .await;
+    }
+    let comicinfo = ComicInfo::for_chapter(manga, name.source_id, &name.label, chapter_url);
+    let file_path = template.render(name, format);
+    let download = ChapterDownload {
+        source_id: name.source_id,
+        chapter_url,
+        file_path: &file_path,
+        comicinfo: Some(&comicinfo),
+        format,
+    };
+    scraper::download_chapter(
+        &data.client,
+        &data.page_fetcher,
+        &data.config.download_dir,
+        download,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Backoff before the next attempt: `base` doubled for every attempt already
+/// made, capped at six hours.
+pub fn retry_delay_secs(base: u64, attempts: i32) -> u64 {
+    let doublings = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    base.saturating_mul(1u64 << doublings)
+        .min(MAX_RETRY_DELAY_SECS)
+}
+
+/// Chapter IDs to queue for a manga. `chapters` holds each source's chapters in
+/// the manga's source order; every chapter is queued once, from the first
+/// source that has it, unless `source_id` pins a single source. A `from`/`to`
+/// range only matches main-sequence chapters.
+pub fn select_chapters(chapters: Vec<(i32, Vec<Chapter>)>, req: &QueueRequest) -> Vec<i32> {
+    let bound = |s: &Option<String>| s.as_deref().and_then(|s| ChapterNumber::parse(s).number);
+    let (from, to) = (bound(&req.from), bound(&req.to));
+    let mut seen = HashSet::new();
+    let mut ids = Vec::new();
+    for (source_id, list) in chapters {
+        if req.source_id.is_some_and(|s| s != source_id) {
+            continue;
+        }
+        for chapter in list {
+            let parsed = ChapterNumber::parse(&chapter.chapter_number);
+            let in_range = match (parsed.kind, parsed.number) {
+                _ if from.is_none() && to.is_none() => true,
+                (ChapterKind::Main, Some(n)) => {
+                    from.is_none_or(|a| a <= n) && to.is_none_or(|b| n <= b)
+                }
+                _ => false,
+            };
+            if !in_range {
+                continue;
+            }
+            // Mark the chapter as seen even when it is skipped, so a chapter
+            // that is already downloaded is not queued from another source.
+            let dedupe = parsed
+                .match_key()
+                .map(|k| k.to_string())
+                .unwrap_or_else(|| chapter.chapter_number.clone());
+            if seen.insert(dedupe) && !(req.unread_only && chapter.scraped) {
+                ids.push(chapter.id);
+            }
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(id: i32, number: &str, scraped: bool) -> Chapter {
+        Chapter {
+            id,
+            manga_source_data_id: 0,
+            chapter_number: number.to_string(),
+            url: String::new(),
+            scraped,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_and_caps() {
+        assert_eq!(retry_delay_secs(60, 1), 60);
+        assert_eq!(retry_delay_secs(60, 2), 120);
+        assert_eq!(retry_delay_secs(60, 4), 480);
+        assert_eq!(retry_delay_secs(60, 40), MAX_RETRY_DELAY_SECS);
+    }
+
+    #[test]
+    fn test_select_chapters() {
+        let chapters = || {
+            vec![
+                (
+                    2,
+                    vec![
+                        chapter(1, "Chapter 1", true),
+                        chapter(2, "Chapter 2", false),
+                    ],
+                ),
+                (
+                    9,
+                    vec![
+                        chapter(10, "Ch. 1", false),
+                        chapter(11, "Ch. 2", false),
+                        chapter(12, "Ch. 3", false),
+                        chapter(13, "Extra - Beach Episode", false),
+                        chapter(14, "Special - Festival", false),
+                    ],
+                ),
+            ]
+        };
+        let all = select_chapters(chapters(), &QueueRequest::default());
+        assert_eq!(all, vec![1, 2, 12, 13, 14]);
+
+        // Chapter 1 is already downloaded from source 2, so source 9's copy
+        // is not queued either.
+        let req = QueueRequest {
+            unread_only: true,
+            ..Default::default()
+        };
+        assert_eq!(select_chapters(chapters(), &req), vec![2, 12, 13, 14]);
+
+        let req = QueueRequest {
+            from: Some("2".to_string()),
+            unread_only: true,
+            ..Default::default()
+        };
+        assert_eq!(select_chapters(chapters(), &req), vec![2, 12]);
+
+        let req = QueueRequest {
+            source_id: Some(9),
+            to: Some("2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(select_chapters(chapters(), &req), vec![10, 11]);
+    }
+}
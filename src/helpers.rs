@@ -3,7 +3,7 @@
 //! This module provides utility functions used throughout the application:
 //! - Source parsing and identification
 //! - Title normalization and matching
-//! - XML generation for ComicInfo.xml
+//! - XML escaping for ComicInfo.xml
 //! - Chapter number extraction and comparison
 //!
 //! # Examples
@@ -21,72 +21,26 @@
 //! ```
 
 use crate::models::Source;
+use crate::sources::catalogue;
 use std::collections::HashSet;
 
-/// Parse a source name or ID string into a Source enum
+/// Parse a source name or ID string into a Source enum. Names are resolved
+/// through the source catalogue, so slugs, aliases and display names all work.
+#[allow(dead_code)]
 pub fn parse_source(s: &str) -> Option<Source> {
-    let k = s.to_lowercase();
-    if let Ok(n) = k.parse::<i32>() {
-        return match n {
-            1 => Some(Source::MangaDex),
-            2 => Some(Source::FireScans),
-            3 => Some(Source::RizzComic),
-            4 => Some(Source::MyAnimeList),
-            5 => Some(Source::AniList),
-            6 => Some(Source::DrakeComic),
-            7 => Some(Source::KDTNovels),
-            8 => Some(Source::Asmotoon),
-            9 => Some(Source::ResetScans),
-            10 => Some(Source::Kagane),
-            49 => Some(Source::TempleScan),
-            50 => Some(Source::ThunderScans),
-            _ => None,
-        };
-    }
-    match k.as_str() {
-        "mangadex" => Some(Source::MangaDex),
-        "firescans" => Some(Source::FireScans),
-        "rizzcomic" => Some(Source::RizzComic),
-        "myanimelist" | "mal" => Some(Source::MyAnimeList),
-        "anilist" => Some(Source::AniList),
-        "drakecomic" => Some(Source::DrakeComic),
-        "kdtnovels" | "kdt" => Some(Source::KDTNovels),
-        "asmotoon" => Some(Source::Asmotoon),
-        "resetscans" | "reset-scans" => Some(Source::ResetScans),
-        "kagane" => Some(Source::Kagane),
-        "temple-scan" | "templescan" | "templetoons" => Some(Source::TempleScan),
-        "thunderscans" | "thunder-scans" => Some(Source::ThunderScans),
-        _ => None,
-    }
-}
-
-/// Get source ID and base URL for WP-Manga based sources by name
-pub fn wp_manga_source_by_name(name: &str) -> Option<(i32, &'static str)> {
-    match name {
-        "asurascans" => Some((11, "https://asurascans.com")),
-        "kenscans" => Some((25, "https://kenscans.com")),
-        "sirenscans" | "siren-scans" => Some((43, "https://sirenscans.com")),
-        "vortexscans" | "vortex-scans" => Some((56, "https://vortexscans.com")),
-        "witchscans" | "witch-scans" => Some((59, "https://witchscans.com")),
-        "qiscans" | "qi-scans" => Some((38, "https://qiscans.org")),
-        "madarascans" => Some((30, "https://madarascans.com")),
-        "rizzfables" => Some((39, "https://rizzfables.com")),
-        "rokaricomics" | "rokari-comics" => Some((40, "https://rokaricomics.com")),
-        "stonescape" => Some((45, "https://stonescape.xyz")),
-        "manhuaus" => Some((31, "https://manhuaus.com")),
-        "grimscans" => Some((19, "https://grimscans.team")),
-        "hivetoons" => Some((20, "https://hivetoons.com")),
-        "nyxscans" => Some((34, "https://nyxscans.com")),
-        // Free scanlation sites
-        "flamecomics" | "flame-comics" => Some((18, "https://flamecomics.xyz")),
-        "daycomics" | "day-comics" => Some((16, "https://daycomics.com")),
-        "kodokustudio" | "kodoku-studio" => Some((27, "https://kodokustudio.com")),
-        "lunatoons" | "luna-toons" => Some((29, "https://lunatoons.com")),
-        "vastvisual" | "vast-visual" => Some((54, "https://vastvisual.com")),
-        "mavintranslations" | "mavin-translations" => Some((61, "https://mavintranslations.com")),
-        // Free web platforms
-        "tapas" => Some((47, "https://tapas.io")),
-        "webtoon" | "webtoons" => Some((58, "https://www.webtoons.com")),
+    match catalogue::find(s)?.id {
+        1 => Some(Source::MangaDex),
+        2 => Some(Source::FireScans),
+        3 => Some(Source::RizzComic),
+        4 => Some(Source::MyAnimeList),
+        5 => Some(Source::AniList),
+        6 => Some(Source::DrakeComic),
+        7 => Some(Source::KDTNovels),
+        8 => Some(Source::Asmotoon),
+        9 => Some(Source::ResetScans),
+        10 => Some(Source::Kagane),
+        49 => Some(Source::TempleScan),
+        50 => Some(Source::ThunderScans),
         _ => None,
     }
 }
@@ -135,29 +89,13 @@ pub fn xml_escape(s: &str) -> String {
         .replace('\'', "&apos;")
 }
 
-/// Build ComicInfo.xml content
-pub fn build_comicinfo(
-    series: &str,
-    number: &str,
-    summary: Option<&str>,
-    tags: Option<&str>,
-) -> Option<String> {
-    let series_esc = xml_escape(series);
-    let number_esc = xml_escape(number);
-    let mut lines = vec![
-        r#"<?xml version="1.0"?>"#.to_string(),
-        r#"<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema">"#.to_string(),
-        format!(r#"  <Series>{}</Series>"#, series_esc),
-        format!(r#"  <Number>{}</Number>"#, number_esc),
-    ];
-    if let Some(s) = summary {
-        lines.push(format!(r#"  <Summary>{}</Summary>"#, xml_escape(s)));
-    }
-    if let Some(t) = tags {
-        lines.push(format!(r#"  <Tags>{}</Tags>"#, xml_escape(t)));
-    }
-    lines.push(r#"</ComicInfo>"#.to_string());
-    Some(lines.join("\n"))
+/// Reverse of [`xml_escape`]
+pub fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
 }
 
 /// Normalize chapter string for comparison
@@ -191,7 +129,15 @@ pub fn find_best_chapter_match<'a>(
     {
         return Some(ch);
     }
-    // 2) numeric match
+    // 2) same canonical chapter (keeps "Side Story 3" apart from "Chapter 3")
+    if let Some(q_key) = crate::chapter_number::ChapterNumber::parse(query).sort_key() {
+        if let Some(ch) = chapters.iter().find(|c| {
+            crate::chapter_number::ChapterNumber::parse(&c.chapter_number).sort_key() == Some(q_key)
+        }) {
+            return Some(ch);
+        }
+    }
+    // 3) numeric match
     if let Some(q_num) = extract_number(query) {
         if let Some(ch) = chapters
             .iter()
@@ -200,7 +146,7 @@ pub fn find_best_chapter_match<'a>(
             return Some(ch);
         }
     }
-    // 3) contains
+    // 4) contains
     if let Some(ch) = chapters.iter().find(|c| {
         c.chapter_number
             .to_lowercase()
@@ -208,22 +154,13 @@ pub fn find_best_chapter_match<'a>(
     }) {
         return Some(ch);
     }
-    // 4) substring fallback
+    // 5) substring fallback
     chapters
         .iter()
         .find(|c| normalize_chapter_str(&c.chapter_number).contains(&q_norm))
 }
 
-/// Guess source ID from a URL
+/// Guess source ID from a URL by matching its host against the catalogue domains
 pub fn guess_source_id_from_url(u: &str) -> Option<i32> {
-    let u_lower = u.to_lowercase();
-    if u_lower.contains("mangadex.org") {
-        Some(Source::MangaDex as i32)
-    } else if u_lower.contains("firescans") {
-        Some(Source::FireScans as i32)
-    } else if u_lower.contains("rizzcomic") {
-        Some(Source::RizzComic as i32)
-    } else {
-        None
-    }
+    catalogue::detect_url(u).map(|e| e.id)
 }
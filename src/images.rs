@@ -0,0 +1,291 @@
+//! Page image formats: sniffing, validation, archive file names and optional
+//! transcoding.
+//!
+//! Sources serve JPEG, PNG, WebP, GIF and AVIF pages, often with a misleading
+//! URL extension or `Content-Type`. The downloader trusts the magic bytes first
+//! and only falls back to the header; [`validate`] rejects error pages, empty
+//! bodies and images whose header does not decode.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_manga_scraper::images::{page_file_name, ImageFormat};
+//!
+//! let format = ImageFormat::detect(b"RIFF\0\0\0\0WEBPVP8 ", None);
+//! assert_eq!(format, Some(ImageFormat::Webp));
+//! assert_eq!(page_file_name(7, 120, format), "007.webp");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+    Gif,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Avif => "avif",
+        }
+    }
+
+    /// Identify the format from the file signature.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else if data.len() >= 12
+            && &data[4..8] == b"ftyp"
+            && matches!(&data[8..12], b"avif" | b"avis")
+        {
+            Some(ImageFormat::Avif)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Avif => "image/avif",
+        }
+    }
+
+    /// Map an HTTP `Content-Type` to a format, ignoring parameters.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "image/jpeg" | "image/jpg" | "image/pjpeg" => Some(ImageFormat::Jpeg),
+            "image/png" => Some(ImageFormat::Png),
+            "image/webp" => Some(ImageFormat::Webp),
+            "image/gif" => Some(ImageFormat::Gif),
+            "image/avif" => Some(ImageFormat::Avif),
+            _ => None,
+        }
+    }
+
+    /// Magic bytes first, then the `Content-Type` header.
+    pub fn detect(data: &[u8], content_type: Option<&str>) -> Option<Self> {
+        Self::sniff(data).or_else(|| content_type.and_then(Self::from_content_type))
+    }
+
+    /// Whether [`transcode`] can write this format; there is no AVIF encoder.
+    pub fn can_encode(self) -> bool {
+        self != ImageFormat::Avif
+    }
+
+    fn codec(self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+            ImageFormat::Gif => image::ImageFormat::Gif,
+            ImageFormat::Avif => image::ImageFormat::Avif,
+        }
+    }
+}
+
+/// Why a downloaded page was rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum PageError {
+    #[error("HTTP status {0}")]
+    Status(u16),
+    #[error("unexpected content type {0}")]
+    ContentType(String),
+    #[error("only {0} bytes")]
+    TooSmall(usize),
+    #[error("not a recognised image")]
+    UnknownFormat,
+    #[error("{0:?} header does not decode: {1}")]
+    Undecodable(ImageFormat, String),
+}
+
+/// Check that a page body is a real image: the content type (when given) is
+/// not text or JSON, the body is at least `min_bytes`, and the header of the
+/// detected format decodes. AVIF has no decoder here, so its `ftyp` box is
+/// accepted as-is.
+pub fn validate(
+    data: &[u8],
+    content_type: Option<&str>,
+    min_bytes: usize,
+) -> Result<ImageFormat, PageError> {
+    if let Some(ct) = content_type {
+        let mime = ct
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        if !(mime.is_empty() || mime.starts_with("image/") || mime.ends_with("/octet-stream")) {
+            return Err(PageError::ContentType(mime));
+        }
+    }
+    if data.len() < min_bytes {
+        return Err(PageError::TooSmall(data.len()));
+    }
+    let format = ImageFormat::detect(data, content_type).ok_or(PageError::UnknownFormat)?;
+    if format == ImageFormat::Avif {
+        return match ImageFormat::sniff(data) {
+            Some(ImageFormat::Avif) => Ok(format),
+            _ => Err(PageError::Undecodable(
+                format,
+                "missing ftyp box".to_string(),
+            )),
+        };
+    }
+    read_dimensions(data, format).map_err(|e| PageError::Undecodable(format, e.to_string()))?;
+    Ok(format)
+}
+
+fn read_dimensions(data: &[u8], format: ImageFormat) -> image::ImageResult<(u32, u32)> {
+    image::ImageReader::with_format(Cursor::new(data), format.codec()).into_dimensions()
+}
+
+/// Width and height from the image header, without decoding the pixels.
+/// `None` for AVIF and for headers that do not decode.
+pub fn dimensions(data: &[u8], format: ImageFormat) -> Option<(u32, u32)> {
+    if format == ImageFormat::Avif {
+        return None;
+    }
+    read_dimensions(data, format).ok()
+}
+
+/// Archive entry name for a page: the 1-based index zero-padded to at least
+/// three digits (wider for chapters with 1000+ pages) so names sort in reading
+/// order. Unknown formats keep the historical `.jpg` extension.
+pub fn page_file_name(index: usize, total: usize, format: Option<ImageFormat>) -> String {
+    let width = total.to_string().len().max(3);
+    let ext = format.map(ImageFormat::extension).unwrap_or("jpg");
+    format!("{:0width$}.{}", index, ext, width = width)
+}
+
+/// Re-encode a page into `target`, which must be one [`ImageFormat::can_encode`]
+/// accepts. JPEG output drops the alpha channel, and WebP output is lossless.
+pub fn transcode(data: &[u8], target: ImageFormat) -> Result<Vec<u8>, image::ImageError> {
+    let mut img = image::load_from_memory(data)?;
+    if target == ImageFormat::Jpeg {
+        img = image::DynamicImage::ImageRgb8(img.to_rgb8());
+    }
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, target.codec())?;
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_png() -> Vec<u8> {
+        let img = image::DynamicImage::new_rgba8(2, 3);
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(
+            ImageFormat::sniff(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(ImageFormat::sniff(&tiny_png()), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::sniff(b"GIF89a...."), Some(ImageFormat::Gif));
+        assert_eq!(
+            ImageFormat::sniff(b"\0\0\0\x1cftypavif\0\0\0\0"),
+            Some(ImageFormat::Avif)
+        );
+        assert_eq!(ImageFormat::sniff(b"<!DOCTYPE html>"), None);
+    }
+
+    #[test]
+    fn test_detect_prefers_magic_over_content_type() {
+        assert_eq!(
+            ImageFormat::detect(&tiny_png(), Some("image/jpeg")),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::detect(b"????", Some("image/webp; charset=binary")),
+            Some(ImageFormat::Webp)
+        );
+        assert_eq!(ImageFormat::detect(b"????", Some("text/html")), None);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_pages() {
+        let png = tiny_png();
+        assert_eq!(
+            validate(&png, Some("image/png"), 0).unwrap(),
+            ImageFormat::Png
+        );
+        assert!(matches!(
+            validate(b"<html>403</html>", Some("text/html"), 0),
+            Err(PageError::ContentType(_))
+        ));
+        assert!(matches!(
+            validate(b"", None, 1),
+            Err(PageError::TooSmall(0))
+        ));
+        assert!(matches!(
+            validate(b"<html>403</html>", Some("image/jpeg"), 0),
+            Err(PageError::Undecodable(ImageFormat::Jpeg, _))
+        ));
+        assert!(matches!(
+            validate(&png[..20], None, 0),
+            Err(PageError::Undecodable(ImageFormat::Png, _))
+        ));
+        assert!(matches!(
+            validate(b"garbage!", Some("application/octet-stream"), 0),
+            Err(PageError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_dimensions_from_header() {
+        assert_eq!(dimensions(&tiny_png(), ImageFormat::Png), Some((2, 3)));
+        assert_eq!(dimensions(b"garbage!", ImageFormat::Jpeg), None);
+    }
+
+    #[test]
+    fn test_page_file_name_padding() {
+        assert_eq!(page_file_name(1, 20, Some(ImageFormat::Png)), "001.png");
+        assert_eq!(
+            page_file_name(12, 1200, Some(ImageFormat::Webp)),
+            "0012.webp"
+        );
+        assert_eq!(page_file_name(3, 3, None), "003.jpg");
+    }
+
+    #[test]
+    fn test_transcode_png_to_jpeg() {
+        let jpeg = transcode(&tiny_png(), ImageFormat::Jpeg).unwrap();
+        assert_eq!(ImageFormat::sniff(&jpeg), Some(ImageFormat::Jpeg));
+    }
+
+    #[test]
+    fn test_can_encode_matches_transcode() {
+        use ImageFormat::*;
+        for format in [Jpeg, Png, Webp, Gif, Avif] {
+            let result = transcode(&tiny_png(), format);
+            assert_eq!(result.is_ok(), format.can_encode(), "{:?}", format);
+        }
+    }
+}
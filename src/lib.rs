@@ -42,9 +42,26 @@
 //! - [`browser_client`] - Headless Chrome wrapper for browser automation
 //! - [`sources`] - All manga source implementations (90+)
 //! - [`models`] - Data structures (Manga, Chapter, Source enums)
+//! - [`chapter_number`] - Canonical chapter numbers, ordering and gap detection
+//! - [`storage`] - Storage trait with interchangeable PostgreSQL and SQLite backends
 //! - [`db`] - SQLite database operations
+//! - [`search`] - Manga full-text search and list filters
+//! - [`tags`] - Canonical tag dictionary, categories and provider synonyms
+//! - [`people`] - Author, artist and publisher credits
+//! - [`reading`] - Users, libraries and read progress
+//! - [`auth`] - API keys, scopes and the authentication middleware
+//! - [`opds`] - OPDS 1.2 catalog and OPDS-PSE page streaming feeds
+//! - [`migrations`] - Embedded, versioned PostgreSQL schema migrations
 //! - [`scraper`] - Chapter download and ZIP creation
+//! - [`images`] - Page image format detection and transcoding
+//! - [`comicinfo`] - ComicInfo.xml generation
+//! - [`output`] - CBZ, EPUB, PDF and folder chapter writers
 //! - [`crawler`] - Manga discovery and monitoring
+//! - [`library`] - Maintenance jobs over downloaded archives
+//! - [`naming`] - Folder and file naming templates for downloads
+//! - [`bundle`] - Volume bundling of downloaded chapters
+//! - [`download_queue`] - Persistent download queue and its workers
+//! - [`matching`] - Fuzzy cross-source series matching and merge proposals
 //! - [`metadata`] - Metadata aggregation from multiple APIs
 //! - [`helpers`] - Utility functions
 //! - [`app_state`] - Application state for HTTP server
@@ -59,6 +76,7 @@ pub mod config;
 pub mod http_client;
 pub mod metrics;
 pub mod models;
+pub mod chapter_number;
 pub mod scraper;
 pub mod source_utils;
 pub mod sources;
@@ -76,9 +94,48 @@ pub mod db;
 // PostgreSQL database layer
 pub mod pg_db;
 
+// Schema migrations
+pub mod migrations;
+
+// Storage trait over both database layers
+pub mod storage;
+
+// Manga search filters
+pub mod search;
+
+// Canonical tags
+pub mod tags;
+
+// Creator credits
+pub mod people;
+
+// Per-user libraries and read progress
+pub mod reading;
+
+// API keys and scopes
+pub mod auth;
+
+// OPDS catalog feeds
+pub mod opds;
+
 // Crawler for discovering manga
 pub mod crawler;
 
+// Downloaded library maintenance
+pub mod library;
+
+// Download folder and file naming
+pub mod naming;
+
+// Volume bundling
+pub mod bundle;
+
+// Persistent download queue
+pub mod download_queue;
+
+// Cross-source series matching
+pub mod matching;
+
 // Task scheduler
 pub mod scheduler;
 
@@ -88,5 +145,14 @@ pub mod metadata;
 // Helper functions
 pub mod helpers;
 
+// Page image formats
+pub mod images;
+
+// ComicInfo.xml metadata
+pub mod comicinfo;
+
+// Chapter output formats
+pub mod output;
+
 // Application state
 pub mod app_state;
@@ -0,0 +1,1166 @@
+//! Maintenance jobs over the downloaded library in `download_dir`.
+//!
+//! `POST /verify/library` walks every `.cbz` under the download directory,
+//! reopens it and re-validates each page with [`images::validate`], reporting
+//! archives that are corrupt, truncated or contain non-image pages. Progress is
+//! polled with `GET /verify/library/status`.
+//!
+//! `POST /library/scan` links the files on disk to chapter rows. Each chapter
+//! file (or folder) is matched to its manga by the ComicInfo.xml `Series`, the
+//! title in the file name or the series folder, then to a chapter by the path
+//! `chapter_template` gives it, the ComicInfo `Web` URL, or the chapter label in
+//! the file name. Linked chapters get their path, size and SHA-256 recorded and
+//! are marked scraped; files matching no chapter are reported as orphans, and
+//! chapters whose recorded file is gone are marked not downloaded again.
+//! Progress is polled with `GET /library/scan/status`.
+//!
+//! `POST /library/rename` moves every recorded chapter file to the path the
+//! current `chapter_template` gives it and updates the chapter rows, so the
+//! library follows a template change. A file is never moved over an existing
+//! one. Progress is polled with `GET /library/rename/status`.
+
+use crate::app_state::AppState;
+use crate::bundle::read_comicinfo;
+use crate::chapter_number::find_same_chapter;
+use crate::comicinfo::{chapter_web_url, ComicInfo};
+use crate::images;
+use crate::models::{Chapter, ChapterFile, Manga};
+use crate::naming::{ChapterName, Template};
+use crate::output::OutputFormat;
+use crate::scraper::sanitize_filename;
+use crate::search::MangaSearch;
+use crate::sources::catalogue;
+use crate::storage::StorageError;
+use actix_web::web;
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Manga folders that hold something other than chapters.
+pub(crate) const SKIPPED_DIRS: [&str; 3] = ["covers", "artwork", "volumes"];
+
+/// Verification result for one archive; `problems` is empty when it is healthy.
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct ArchiveReport {
+    pub path: String,
+    pub pages: usize,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct VerifyProgress {
+    pub in_progress: bool,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub archives_total: usize,
+    pub archives_checked: usize,
+    pub archives_ok: usize,
+    /// Archives with at least one problem, including leftover `.cbz.tmp` files.
+    pub bad_archives: Vec<ArchiveReport>,
+    pub error: Option<String>,
+}
+
+/// All `.cbz` files (and interrupted `.cbz.tmp` downloads) under `dir`, sorted.
+pub fn find_archives(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_archive(&path) || is_partial_download(&path) {
+                out.push(path);
+            }
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("cbz"))
+}
+
+fn is_partial_download(path: &Path) -> bool {
+    path.to_string_lossy().to_lowercase().ends_with(".cbz.tmp")
+}
+
+/// Reopen an archive and validate every page entry. Reading each entry to the
+/// end also checks its CRC, so truncated or bit-rotted pages are caught.
+pub fn verify_archive(path: &Path, min_page_bytes: usize) -> ArchiveReport {
+    let mut report = ArchiveReport {
+        path: path.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    if is_partial_download(path) {
+        report
+            .problems
+            .push("incomplete download (temporary file left behind)".to_string());
+        return report;
+    }
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            report.problems.push(format!("cannot open: {}", e));
+            return report;
+        }
+    };
+    let mut archive = match zip::ZipArchive::new(file) {
+        Ok(a) => a,
+        Err(e) => {
+            report
+                .problems
+                .push(format!("corrupt or truncated archive: {}", e));
+            return report;
+        }
+    };
+
+    for i in 0..archive.len() {
+        let mut entry = match archive.by_index(i) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.problems.push(format!("entry {}: {}", i, e));
+                continue;
+            }
+        };
+        let name = entry.name().to_string();
+        if entry.is_dir() || name.to_lowercase().ends_with(".xml") {
+            continue;
+        }
+        let mut data = Vec::new();
+        if let Err(e) = entry.read_to_end(&mut data) {
+            report
+                .problems
+                .push(format!("{}: truncated or corrupt ({})", name, e));
+            continue;
+        }
+        match images::validate(&data, None, min_page_bytes) {
+            Ok(_) => report.pages += 1,
+            Err(e) => report.problems.push(format!("{}: {}", name, e)),
+        }
+    }
+    if report.pages == 0 && report.problems.is_empty() {
+        report.problems.push("no page images".to_string());
+    }
+    report
+}
+
+/// Start the library verification job. Returns `false` if one is already running.
+pub fn spawn_verify_library(data: web::Data<AppState>) -> bool {
+    {
+        let mut p = data.library_verify.lock().unwrap();
+        if p.in_progress {
+            return false;
+        }
+        *p = VerifyProgress {
+            in_progress: true,
+            started_at: Some(Utc::now().timestamp()),
+            ..Default::default()
+        };
+    }
+
+    actix_web::rt::spawn(async move {
+        let dir = data.config.download_dir.clone();
+        let min_bytes = data.page_fetcher.min_page_bytes();
+        info!("Library verification started in {}", dir);
+
+        let archives = match tokio::task::spawn_blocking(move || find_archives(Path::new(&dir)))
+            .await
+        {
+            Ok(Ok(archives)) => archives,
+            Ok(Err(e)) => return finish(&data, Some(format!("cannot read download_dir: {}", e))),
+            Err(e) => return finish(&data, Some(e.to_string())),
+        };
+        data.library_verify.lock().unwrap().archives_total = archives.len();
+
+        for path in archives {
+            let report =
+                match tokio::task::spawn_blocking(move || verify_archive(&path, min_bytes)).await {
+                    Ok(report) => report,
+                    Err(e) => {
+                        error!("Archive verification task failed: {}", e);
+                        continue;
+                    }
+                };
+            let mut p = data.library_verify.lock().unwrap();
+            p.archives_checked += 1;
+            if report.problems.is_empty() {
+                p.archives_ok += 1;
+            } else {
+                p.bad_archives.push(report);
+            }
+        }
+        finish(&data, None);
+    });
+    true
+}
+
+fn finish(data: &web::Data<AppState>, error: Option<String>) {
+    let mut p = data.library_verify.lock().unwrap();
+    if let Some(e) = &error {
+        error!("Library verification failed: {}", e);
+    } else {
+        info!(
+            "Library verification finished: {} checked, {} bad",
+            p.archives_checked,
+            p.bad_archives.len()
+        );
+    }
+    p.in_progress = false;
+    p.finished_at = Some(Utc::now().timestamp());
+    p.error = error;
+}
+
+/// A file the scanner could not link to any chapter.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct OrphanFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// A chapter whose recorded file is no longer in `download_dir`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct MissingFile {
+    pub chapter_id: i32,
+    pub path: String,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct ScanProgress {
+    pub in_progress: bool,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub files_total: usize,
+    pub files_scanned: usize,
+    /// Files newly linked to a chapter, or whose size or hash changed.
+    pub files_linked: usize,
+    /// Files already recorded on their chapter as they are.
+    pub files_unchanged: usize,
+    pub orphans: Vec<OrphanFile>,
+    pub missing: Vec<MissingFile>,
+    pub error: Option<String>,
+}
+
+/// A chapter file moved to the path `chapter_template` now gives it.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RenamedFile {
+    pub chapter_id: i32,
+    pub from: String,
+    pub to: String,
+}
+
+/// A chapter file that could not be moved.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RenameFailure {
+    pub chapter_id: i32,
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct RenameProgress {
+    pub in_progress: bool,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub files_total: usize,
+    /// Files already at their templated path.
+    pub files_unchanged: usize,
+    pub renamed: Vec<RenamedFile>,
+    pub failed: Vec<RenameFailure>,
+    pub error: Option<String>,
+}
+
+/// Chapter files under `dir`: archives in any output format, and folder
+/// output (a directory holding `ComicInfo.xml`). Cover, artwork and volume
+/// folders and interrupted downloads are skipped.
+pub fn find_chapter_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if path.join("ComicInfo.xml").is_file() {
+                    out.push(path);
+                } else if !SKIPPED_DIRS.contains(&name) {
+                    pending.push(path);
+                }
+            } else if output_format(&path).is_some() {
+                out.push(path);
+            }
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+/// The output format a chapter file was written in, from its extension.
+fn output_format(path: &Path) -> Option<OutputFormat> {
+    if path.is_dir() {
+        return Some(OutputFormat::Folder);
+    }
+    let ext = path.extension()?.to_str()?;
+    OutputFormat::parse(ext).filter(|f| f.extension() == Some(&*ext.to_ascii_lowercase()))
+}
+
+/// Split a name written by the default [`Template`] (without its extension) into the source ID and the `<Title> - <Label>` rest. The source
+/// name after the ID must be the catalogue's.
+pub fn parse_file_name(stem: &str) -> Option<(i32, &str)> {
+    let (id, rest) = stem.split_once('-')?;
+    let source_id: i32 = id.parse().ok()?;
+    catalogue::get(source_id)?;
+    let source_name = sanitize_filename(catalogue::name_for(source_id));
+    let rest = rest.strip_prefix(source_name.as_str())?.strip_prefix('-')?;
+    Some((source_id, rest))
+}
+
+/// Split `<Title> - <Label>` into its parts. The title is the manga folder's
+/// name when the file starts with it (titles may contain " - " themselves),
+/// otherwise everything before the first " - ".
+pub fn split_title_label<'a>(rest: &'a str, folder: Option<&str>) -> (&'a str, &'a str) {
+    if let Some(label) = folder
+        .and_then(|f| rest.strip_prefix(f))
+        .and_then(|r| r.strip_prefix(" - "))
+    {
+        return (&rest[..rest.len() - label.len() - 3], label);
+    }
+    rest.split_once(" - ").unwrap_or((rest, ""))
+}
+
+/// Size and SHA-256 of a chapter file. Folder output has no hash; its size is
+/// the total of the files in it.
+pub fn file_details(path: &Path) -> std::io::Result<(i64, Option<String>)> {
+    if path.is_dir() {
+        let mut size = 0;
+        for entry in std::fs::read_dir(path)? {
+            let meta = entry?.metadata()?;
+            if meta.is_file() {
+                size += meta.len() as i64;
+            }
+        }
+        return Ok((size, None));
+    }
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut file, &mut hasher)? as i64;
+    Ok((size, Some(format!("{:x}", hasher.finalize()))))
+}
+
+/// `path` relative to `download_dir` with `/` separators, as chapter rows
+/// record it; paths outside `download_dir` are kept whole.
+pub fn relative_path(download_dir: &Path, path: &Path) -> String {
+    match path.strip_prefix(download_dir) {
+        Ok(rel) => rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string_lossy().to_string(),
+    }
+}
+
+/// Record the file a chapter was just downloaded to. Failures are logged; the
+/// next library scan picks the file up again.
+pub async fn record_download(data: &web::Data<AppState>, chapter_id: i32, file_path: &str) {
+    let download_dir = PathBuf::from(&data.config.download_dir);
+    let path = PathBuf::from(file_path);
+    let file = match tokio::task::spawn_blocking(move || {
+        file_details(&path).map(|(size, hash)| ChapterFile {
+            path: relative_path(&download_dir, &path),
+            size,
+            hash,
+        })
+    })
+    .await
+    {
+        Ok(Ok(file)) => file,
+        Ok(Err(e)) => return error!("Cannot read downloaded file {}: {}", file_path, e),
+        Err(e) => return error!("Cannot read downloaded file {}: {}", file_path, e),
+    };
+    let now = Utc::now().timestamp();
+    if let Err(e) = data.storage.set_chapter_file(chapter_id, &file, now).await {
+        error!("Failed to record the file of chapter {}: {}", chapter_id, e);
+    }
+}
+
+/// What the scanner read from one chapter file.
+#[derive(Debug, Clone)]
+pub struct ScannedFile {
+    /// Relative to `download_dir`
+    pub path: String,
+    /// Series folder the file is in, `None` directly under `download_dir`
+    pub folder: Option<String>,
+    pub format: OutputFormat,
+    pub source_id: Option<i32>,
+    pub title: Option<String>,
+    pub label: Option<String>,
+    pub comicinfo: Option<ComicInfo>,
+    pub size: i64,
+    pub hash: Option<String>,
+}
+
+impl ScannedFile {
+    /// Read `path`. The series folder is the one at the template's series
+    /// depth, or the file's own folder when the path is shallower.
+    pub fn read(download_dir: &Path, path: &Path, template: &Template) -> std::io::Result<Self> {
+        let path_str = relative_path(download_dir, path);
+        let dirs: Vec<&str> = path_str.split('/').collect();
+        let dirs = &dirs[..dirs.len() - 1];
+        let folder = dirs
+            .get(template.series_depth() - 1)
+            .or(dirs.last())
+            .map(|d| d.to_string());
+        let format = output_format(path).unwrap_or(OutputFormat::Cbz);
+        let is_dir = path.is_dir();
+        let name = if is_dir {
+            path.file_name()
+        } else {
+            path.file_stem()
+        };
+        let stem = name
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (source_id, title, label) = match parse_file_name(&stem) {
+            Some((source_id, rest)) => {
+                let (title, label) = split_title_label(rest, folder.as_deref());
+                (
+                    Some(source_id),
+                    Some(title.to_string()),
+                    Some(label.to_string()).filter(|l| !l.is_empty()),
+                )
+            }
+            None => (None, None, None),
+        };
+        let comicinfo = if is_dir {
+            std::fs::read_to_string(path.join("ComicInfo.xml"))
+                .ok()
+                .and_then(|xml| ComicInfo::from_xml(&xml))
+        } else if format == OutputFormat::Cbz {
+            read_comicinfo(path).unwrap_or(None)
+        } else {
+            None
+        };
+        let (size, hash) = file_details(path)?;
+        Ok(Self {
+            path: path_str,
+            folder,
+            format,
+            source_id,
+            title,
+            label,
+            comicinfo,
+            size,
+            hash,
+        })
+    }
+
+    /// Titles the file's manga may go by, most specific first.
+    fn titles(&self) -> Vec<&str> {
+        let mut titles: Vec<&str> = Vec::new();
+        let candidates = [
+            self.comicinfo.as_ref().map(|c| c.series.as_str()),
+            self.title.as_deref(),
+            self.folder.as_deref(),
+        ];
+        for title in candidates.into_iter().flatten() {
+            if !title.trim().is_empty() && !titles.contains(&title) {
+                titles.push(title);
+            }
+        }
+        titles
+    }
+}
+
+/// Whether `title` names `manga` once both are made file-name safe, which is
+/// how the downloader turns titles into folder and file names.
+fn same_title(manga: &Manga, title: &str) -> bool {
+    sanitize_filename(manga.title.trim()).to_lowercase()
+        == sanitize_filename(title.trim()).to_lowercase()
+}
+
+/// The chapter `file` holds among the chapters (each with its source ID) of
+/// the manga titled `manga_title`: the one `template` would have written to
+/// the same path, then the one whose public URL is the ComicInfo `Web`, then
+/// the one with the same chapter number as the label in the file name (or the
+/// ComicInfo number) on the file's source. A file with none of these stays
+/// unmatched rather than being linked to a nearby chapter.
+pub fn match_chapter<'a>(
+    template: &Template,
+    manga_title: &str,
+    chapters: &'a [(i32, Chapter)],
+    file: &ScannedFile,
+) -> Option<&'a Chapter> {
+    let by_name = chapters.iter().find(|(source_id, chapter)| {
+        file.source_id.is_none_or(|s| s == *source_id)
+            && template.render(&chapter_name(manga_title, *source_id, chapter), file.format)
+                == file.path
+    });
+    if let Some((_, chapter)) = by_name {
+        return Some(chapter);
+    }
+
+    if let Some(web) = file.comicinfo.as_ref().and_then(|c| c.web.as_deref()) {
+        let by_url = chapters.iter().find(|(source_id, chapter)| {
+            chapter_web_url(*source_id, &chapter.url).as_deref() == Some(web)
+        });
+        if let Some((_, chapter)) = by_url {
+            return Some(chapter);
+        }
+    }
+
+    let query = match (&file.label, &file.comicinfo) {
+        (Some(label), _) => label.clone(),
+        (None, Some(info)) if !info.number.is_empty() => format!("Chapter {}", info.number),
+        _ => return None,
+    };
+    let mut sources: Vec<i32> = Vec::new();
+    for (source_id, _) in chapters {
+        if !sources.contains(source_id) && file.source_id.is_none_or(|s| s == *source_id) {
+            sources.push(*source_id);
+        }
+    }
+    sources.into_iter().find_map(|source_id| {
+        let list: Vec<Chapter> = chapters
+            .iter()
+            .filter(|(s, _)| *s == source_id)
+            .map(|(_, c)| c.clone())
+            .collect();
+        let found = find_same_chapter(&list, &query)?.id;
+        chapters.iter().map(|(_, c)| c).find(|c| c.id == found)
+    })
+}
+
+/// What `template` fills in for `chapter` of `manga_title`.
+pub fn chapter_name(manga_title: &str, source_id: i32, chapter: &Chapter) -> ChapterName {
+    ChapterName::new(manga_title, source_id, &chapter.label(), &chapter.url).with_release(
+        chapter.scanlation_group.as_deref(),
+        chapter.language.as_deref(),
+        chapter.published_at,
+    )
+}
+
+/// Manga and chapter lookups shared by every file of a scan.
+#[derive(Default)]
+struct Lookup {
+    by_source: HashMap<i32, Vec<Manga>>,
+    chapters: HashMap<String, Vec<(i32, Chapter)>>,
+}
+
+impl Lookup {
+    /// The manga a file belongs to: a manga of the file's source with one of
+    /// its titles, otherwise the first search hit with one of them.
+    async fn manga(
+        &mut self,
+        data: &web::Data<AppState>,
+        file: &ScannedFile,
+    ) -> Result<Option<Manga>, StorageError> {
+        let titles = file.titles();
+        if let Some(source_id) = file.source_id {
+            let list = match self.by_source.entry(source_id) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(data.storage.get_manga_by_source(source_id).await?),
+            };
+            for title in &titles {
+                if let Some(manga) = list.iter().find(|m| same_title(m, title)) {
+                    return Ok(Some(manga.clone()));
+                }
+            }
+        }
+        for title in titles {
+            let search = MangaSearch {
+                query: Some(title.to_string()),
+                limit: 20,
+                ..Default::default()
+            };
+            let hits = data.storage.search_manga(&search).await?;
+            if let Some(manga) = hits.into_iter().find(|m| same_title(m, title)) {
+                return Ok(Some(manga));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every chapter of the manga on each of its sources, in source order.
+    async fn chapters(
+        &mut self,
+        data: &web::Data<AppState>,
+        manga_id: &str,
+    ) -> Result<&mut Vec<(i32, Chapter)>, StorageError> {
+        if !self.chapters.contains_key(manga_id) {
+            let mut all = Vec::new();
+            let sources = data
+                .storage
+                .get_manga_source_data_by_manga_id(manga_id)
+                .await?;
+            for msd in sources {
+                let list = data
+                    .storage
+                    .get_chapters_by_manga_source_data_id(manga_id, msd.source_id)
+                    .await?;
+                all.extend(list.into_iter().map(|c| (msd.source_id, c)));
+            }
+            self.chapters.insert(manga_id.to_string(), all);
+        }
+        Ok(self.chapters.get_mut(manga_id).unwrap())
+    }
+}
+
+/// Start the library scan. Returns `false` if one is already running.
+pub fn spawn_scan_library(data: web::Data<AppState>) -> bool {
+    {
+        let mut p = data.library_scan.lock().unwrap();
+        if p.in_progress {
+            return false;
+        }
+        *p = ScanProgress {
+            in_progress: true,
+            started_at: Some(Utc::now().timestamp()),
+            ..Default::default()
+        };
+    }
+
+    actix_web::rt::spawn(async move {
+        let error = scan_library(&data).await.err();
+        let mut p = data.library_scan.lock().unwrap();
+        if let Some(e) = &error {
+            error!("Library scan failed: {}", e);
+        } else {
+            info!(
+                "Library scan finished: {} linked, {} unchanged, {} orphans, {} missing",
+                p.files_linked,
+                p.files_unchanged,
+                p.orphans.len(),
+                p.missing.len()
+            );
+        }
+        p.in_progress = false;
+        p.finished_at = Some(Utc::now().timestamp());
+        p.error = error;
+    });
+    true
+}
+
+async fn scan_library(data: &web::Data<AppState>) -> Result<(), String> {
+    let download_dir = PathBuf::from(&data.config.download_dir);
+    info!("Library scan started in {}", download_dir.display());
+
+    let dir = download_dir.clone();
+    let files = tokio::task::spawn_blocking(move || find_chapter_files(&dir))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("cannot read download_dir: {}", e))?;
+    data.library_scan.lock().unwrap().files_total = files.len();
+
+    let mut lookup = Lookup::default();
+    let mut seen = HashSet::new();
+    for path in files {
+        let dir = download_dir.clone();
+        let rel = relative_path(&download_dir, &path);
+        let template = data.config.chapter_template.clone();
+        let scanned =
+            tokio::task::spawn_blocking(move || ScannedFile::read(&dir, &path, &template))
+                .await
+                .map_err(|e| e.to_string())?;
+        let outcome = match scanned {
+            Ok(file) => {
+                seen.insert(file.path.clone());
+                link_file(data, &mut lookup, &file)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map_err(|reason| OrphanFile {
+                        path: file.path.clone(),
+                        reason,
+                    })
+            }
+            Err(e) => {
+                warn!("Cannot read chapter file {}: {}", rel, e);
+                Err(OrphanFile {
+                    path: rel,
+                    reason: format!("cannot read: {}", e),
+                })
+            }
+        };
+        let mut p = data.library_scan.lock().unwrap();
+        p.files_scanned += 1;
+        match outcome {
+            Ok(true) => p.files_linked += 1,
+            Ok(false) => p.files_unchanged += 1,
+            Err(orphan) => p.orphans.push(orphan),
+        }
+    }
+
+    let recorded = data
+        .storage
+        .get_downloaded_chapters()
+        .await
+        .map_err(|e| e.to_string())?;
+    for (_, chapter) in recorded {
+        let (chapter_id, path) = (chapter.id, chapter.file_path.unwrap_or_default());
+        if seen.contains(&path) || download_dir.join(&path).exists() {
+            continue;
+        }
+        data.storage
+            .clear_chapter_file(chapter_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        data.library_scan
+            .lock()
+            .unwrap()
+            .missing
+            .push(MissingFile { chapter_id, path });
+    }
+    Ok(())
+}
+
+/// Link one file to its chapter. `Ok(true)` if the chapter's file was
+/// recorded or updated, `Ok(false)` if it already was, and the reason it is
+/// an orphan otherwise.
+async fn link_file(
+    data: &web::Data<AppState>,
+    lookup: &mut Lookup,
+    file: &ScannedFile,
+) -> Result<Result<bool, String>, StorageError> {
+    let Some(manga) = lookup.manga(data, file).await? else {
+        return Ok(Err("no manga with this title".to_string()));
+    };
+    let chapters = lookup.chapters(data, &manga.id).await?;
+    let template = &data.config.chapter_template;
+    let Some(chapter) = match_chapter(template, &manga.title, chapters, file) else {
+        return Ok(Err(format!("no matching chapter of {}", manga.title)));
+    };
+    let chapter_id = chapter.id;
+    if chapter.file_path.as_deref() == Some(file.path.as_str())
+        && chapter.file_size == Some(file.size)
+        && chapter.file_hash == file.hash
+    {
+        return Ok(Ok(false));
+    }
+
+    let record = ChapterFile {
+        path: file.path.clone(),
+        size: file.size,
+        hash: file.hash.clone(),
+    };
+    let now = Utc::now().timestamp();
+    data.storage
+        .set_chapter_file(chapter_id, &record, now)
+        .await?;
+    if let Some((_, chapter)) = chapters.iter_mut().find(|(_, c)| c.id == chapter_id) {
+        chapter.scraped = true;
+        chapter.file_path = Some(record.path);
+        chapter.file_size = Some(record.size);
+        chapter.file_hash = record.hash;
+        chapter.downloaded_at = Some(now);
+    }
+    Ok(Ok(true))
+}
+
+/// Move the chapter file at `from` to `to` (both relative to `download_dir`),
+/// creating the new folders and removing the old ones it leaves empty. An
+/// existing file at `to` is never overwritten.
+pub fn move_chapter_file(download_dir: &Path, from: &str, to: &str) -> std::io::Result<()> {
+    let source = download_dir.join(from);
+    let target = download_dir.join(to);
+    if !source.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "file is missing, run a library scan",
+        ));
+    }
+    if target.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to),
+        ));
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&source, &target)?;
+
+    let mut dir = source.parent();
+    while let Some(d) = dir.filter(|d| d.starts_with(download_dir) && *d != download_dir) {
+        if std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+    Ok(())
+}
+
+/// Start moving downloaded chapters to the paths the current
+/// `chapter_template` gives them. Returns `false` if a rename is running.
+pub fn spawn_rename_library(data: web::Data<AppState>) -> bool {
+    {
+        let mut p = data.library_rename.lock().unwrap();
+        if p.in_progress {
+            return false;
+        }
+        *p = RenameProgress {
+            in_progress: true,
+            started_at: Some(Utc::now().timestamp()),
+            ..Default::default()
+        };
+    }
+
+    actix_web::rt::spawn(async move {
+        let error = rename_library(&data).await.err();
+        let mut p = data.library_rename.lock().unwrap();
+        if let Some(e) = &error {
+            error!("Library rename failed: {}", e);
+        } else {
+            info!(
+                "Library rename finished: {} renamed, {} unchanged, {} failed",
+                p.renamed.len(),
+                p.files_unchanged,
+                p.failed.len()
+            );
+        }
+        p.in_progress = false;
+        p.finished_at = Some(Utc::now().timestamp());
+        p.error = error;
+    });
+    true
+}
+
+async fn rename_library(data: &web::Data<AppState>) -> Result<(), String> {
+    let download_dir = PathBuf::from(&data.config.download_dir);
+    let template = &data.config.chapter_template;
+    info!("Library rename started with template {}", template);
+
+    let downloaded = data
+        .storage
+        .get_downloaded_chapters()
+        .await
+        .map_err(|e| e.to_string())?;
+    data.library_rename.lock().unwrap().files_total = downloaded.len();
+
+    for (manga_title, chapter) in downloaded {
+        let from = chapter.file_path.clone().unwrap_or_default();
+        let Some(format) = output_format(&download_dir.join(&from)) else {
+            data.library_rename
+                .lock()
+                .unwrap()
+                .failed
+                .push(RenameFailure {
+                    chapter_id: chapter.id,
+                    path: from,
+                    error: "unknown output format".to_string(),
+                });
+            continue;
+        };
+        let name = ChapterName::new(
+            &manga_title,
+            chapter.source_id,
+            &chapter.label(),
+            &chapter.url,
+        )
+        .with_release(
+            chapter.scanlation_group.as_deref(),
+            chapter.language.as_deref(),
+            chapter.published_at,
+        );
+        let to = template.render(&name, format);
+        if to == from {
+            data.library_rename.lock().unwrap().files_unchanged += 1;
+            continue;
+        }
+
+        let dir = download_dir.clone();
+        let (old, new) = (from.clone(), to.clone());
+        let moved = tokio::task::spawn_blocking(move || move_chapter_file(&dir, &old, &new))
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Err(e) = moved {
+            warn!("Cannot move {} to {}: {}", from, to, e);
+            data.library_rename
+                .lock()
+                .unwrap()
+                .failed
+                .push(RenameFailure {
+                    chapter_id: chapter.id,
+                    path: from,
+                    error: e.to_string(),
+                });
+            continue;
+        }
+        let record = ChapterFile {
+            path: to.clone(),
+            size: chapter.file_size.unwrap_or(0),
+            hash: chapter.file_hash.clone(),
+        };
+        let downloaded_at = chapter
+            .downloaded_at
+            .unwrap_or_else(|| Utc::now().timestamp());
+        data.storage
+            .set_chapter_file(chapter.id, &record, downloaded_at)
+            .await
+            .map_err(|e| e.to_string())?;
+        data.library_rename
+            .lock()
+            .unwrap()
+            .renamed
+            .push(RenamedFile {
+                chapter_id: chapter.id,
+                from,
+                to,
+            });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn png() -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn write_cbz(path: &Path, pages: &[(&str, Vec<u8>)]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in pages {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_verify_archive_reports_bad_pages_and_truncation() {
+        let dir = std::env::temp_dir().join(format!("verify-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("Series")).unwrap();
+
+        let good = dir.join("Series/good.cbz");
+        write_cbz(
+            &good,
+            &[
+                ("ComicInfo.xml", b"<ComicInfo/>".to_vec()),
+                ("001.png", png()),
+            ],
+        );
+        let bad = dir.join("Series/bad.cbz");
+        write_cbz(
+            &bad,
+            &[
+                ("001.png", png()),
+                ("002.jpg", b"<html>403</html>".to_vec()),
+            ],
+        );
+        let truncated = dir.join("Series/truncated.cbz");
+        let bytes = std::fs::read(&good).unwrap();
+        std::fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+        std::fs::write(dir.join("Series/partial.cbz.tmp"), b"").unwrap();
+
+        let found = find_archives(&dir).unwrap();
+        assert_eq!(found.len(), 4);
+
+        let report = verify_archive(&good, 0);
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!(report.pages, 1);
+
+        let report = verify_archive(&bad, 0);
+        assert_eq!(report.pages, 1);
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].starts_with("002.jpg"));
+
+        assert!(!verify_archive(&truncated, 0).problems.is_empty());
+        assert!(!verify_archive(&dir.join("Series/partial.cbz.tmp"), 0)
+            .problems
+            .is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        let stem = "1-MangaDex-Re_Zero - Arc 2 - Vol.2 Ch.15 - The Gate";
+        let (source_id, rest) = parse_file_name(stem).unwrap();
+        assert_eq!(source_id, 1);
+        assert_eq!(rest, "Re_Zero - Arc 2 - Vol.2 Ch.15 - The Gate");
+        assert_eq!(
+            split_title_label(rest, Some("Re_Zero - Arc 2")),
+            ("Re_Zero - Arc 2", "Vol.2 Ch.15 - The Gate")
+        );
+        assert_eq!(
+            split_title_label(rest, None),
+            ("Re_Zero", "Arc 2 - Vol.2 Ch.15 - The Gate")
+        );
+
+        assert_eq!(parse_file_name("1-Comick-Title - Ch.1"), None);
+        assert_eq!(parse_file_name("99999-MangaDex-Title - Ch.1"), None);
+        assert_eq!(parse_file_name("Title - Ch.1"), None);
+    }
+
+    #[test]
+    fn test_move_chapter_file() {
+        let dir = std::env::temp_dir().join(format!("rename-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("Old/covers")).unwrap();
+        std::fs::create_dir_all(dir.join("Gone")).unwrap();
+        std::fs::write(dir.join("Old/a.cbz"), b"a").unwrap();
+        std::fs::write(dir.join("Gone/b.cbz"), b"b").unwrap();
+
+        move_chapter_file(&dir, "Gone/b.cbz", "New/Vol 1/b.cbz").unwrap();
+        assert_eq!(std::fs::read(dir.join("New/Vol 1/b.cbz")).unwrap(), b"b");
+        assert!(!dir.join("Gone").exists());
+
+        // The old folder still holds covers, so it stays
+        move_chapter_file(&dir, "Old/a.cbz", "New/a.cbz").unwrap();
+        assert!(dir.join("Old/covers").is_dir());
+
+        let err = move_chapter_file(&dir, "New/a.cbz", "New/Vol 1/b.cbz").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(dir.join("New/a.cbz").exists());
+        let err = move_chapter_file(&dir, "Old/a.cbz", "New/c.cbz").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_scan_and_match_chapters() {
+        let dir = std::env::temp_dir().join(format!("scan-{}", uuid::Uuid::new_v4()));
+        let series = dir.join("Series");
+        for sub in ["covers", "volumes"] {
+            std::fs::create_dir_all(series.join(sub)).unwrap();
+        }
+        std::fs::write(series.join("covers/cover.jpg"), png()).unwrap();
+        write_cbz(
+            &series.join("volumes/Series - Vol.1.cbz"),
+            &[("001.png", png())],
+        );
+        std::fs::write(series.join("partial.cbz.tmp"), b"").unwrap();
+
+        let chapter = |id: i32, number: &str, url: &str| {
+            let mut chapter = Chapter {
+                id,
+                chapter_number: number.to_string(),
+                url: url.to_string(),
+                ..Default::default()
+            };
+            chapter.fill_from_label();
+            chapter
+        };
+        let chapters = vec![
+            (1, chapter(10, "Chapter 5", "abc")),
+            (1, chapter(11, "Chapter 6", "def")),
+            (1, chapter(12, "Chapter 7", "ghi")),
+            (2, chapter(20, "Chapter 5", "https://example.com/ch-5")),
+        ];
+
+        // Named exactly as the downloader names it
+        let template = Template::default();
+        let name = template.render(
+            &chapter_name("Series", 1, &chapters[0].1),
+            OutputFormat::Cbz,
+        );
+        write_cbz(&dir.join(&name), &[("001.png", png())]);
+        // Renamed, but ComicInfo.xml links it to its source chapter
+        let renamed = series.join("renamed.cbz");
+        let info = ComicInfo {
+            web: Some("https://mangadex.org/chapter/def".to_string()),
+            ..ComicInfo::new("Series", "Chapter 6")
+        };
+        write_cbz(&renamed, &[("ComicInfo.xml", info.to_xml().into_bytes())]);
+        // Folder output labelled differently from the chapter
+        let folder = series.join("1-MangaDex-Series - Chapter 7");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("ComicInfo.xml"), "<ComicInfo/>").unwrap();
+        std::fs::write(folder.join("001.png"), png()).unwrap();
+        // Nothing to match it by
+        write_cbz(&series.join("stray.cbz"), &[("001.png", png())]);
+
+        let found = find_chapter_files(&dir).unwrap();
+        let rel: Vec<String> = found.iter().map(|p| relative_path(&dir, p)).collect();
+        assert_eq!(
+            rel,
+            [
+                name.clone(),
+                "Series/1-MangaDex-Series - Chapter 7".to_string(),
+                "Series/renamed.cbz".to_string(),
+                "Series/stray.cbz".to_string(),
+            ]
+        );
+
+        let read = |path: &Path| ScannedFile::read(&dir, path, &template).unwrap();
+        let exact = read(&dir.join(&name));
+        assert_eq!(exact.source_id, Some(1));
+        assert_eq!(exact.folder.as_deref(), Some("Series"));
+        assert_eq!(exact.titles(), ["Series"]);
+        assert_eq!(
+            exact.size,
+            std::fs::metadata(dir.join(&name)).unwrap().len() as i64
+        );
+        assert_eq!(exact.hash.as_ref().map(String::len), Some(64));
+        assert_eq!(
+            match_chapter(&template, "Series", &chapters, &exact).map(|c| c.id),
+            Some(10)
+        );
+
+        let renamed = read(&renamed);
+        assert_eq!(renamed.source_id, None);
+        assert_eq!(
+            match_chapter(&template, "Series", &chapters, &renamed).map(|c| c.id),
+            Some(11)
+        );
+
+        let folder = read(&folder);
+        assert_eq!(folder.hash, None);
+        assert!(folder.size > 0);
+        assert_eq!(folder.label.as_deref(), Some("Chapter 7"));
+        assert_eq!(
+            match_chapter(&template, "Series", &chapters, &folder).map(|c| c.id),
+            Some(12)
+        );
+
+        let stray = read(&series.join("stray.cbz"));
+        assert_eq!(
+            match_chapter(&template, "Series", &chapters, &stray).map(|c| c.id),
+            None
+        );
+        // Only the same chapter number counts, never the first number found
+        for label in ["Vol.5 Ch.15", "Side Story 6", "Chapter 50"] {
+            let unknown = ScannedFile {
+                label: Some(label.to_string()),
+                ..stray.clone()
+            };
+            assert_eq!(
+                match_chapter(&template, "Series", &chapters, &unknown).map(|c| c.id),
+                None,
+                "{}",
+                label
+            );
+        }
+
+        // A custom layout with the series folder one level down
+        let komga = Template::parse("Manga/{title}/{title} #{chapter:03}").unwrap();
+        let path = dir.join("Manga/Series/Series #006.cbz");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        write_cbz(&path, &[("001.png", png())]);
+        let custom = ScannedFile::read(&dir, &path, &komga).unwrap();
+        assert_eq!(custom.folder.as_deref(), Some("Series"));
+        assert_eq!(custom.source_id, None);
+        assert_eq!(
+            match_chapter(&komga, "Series", &chapters, &custom).map(|c| c.id),
+            Some(11)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
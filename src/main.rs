@@ -1,41 +1,59 @@
 mod app_state;
+mod auth;
 mod browser;
+mod bundle;
+mod chapter_number;
 mod cloudflare_bypass;
+mod comicinfo;
 mod config;
 mod crawler;
 mod db;
+mod download_queue;
 mod pg_db;
 mod helpers;
+mod images;
+mod library;
+mod matching;
 mod metadata;
 mod metrics;
+mod migrations;
 mod models;
+mod naming;
+mod opds;
+mod output;
+mod people;
+mod reading;
 mod scheduler;
 mod scraper;
+mod search;
 mod sources;
+mod storage;
+mod tags;
 
 // Public modules for testing and external use
 pub mod browser_client;
 pub mod http_client;
 pub mod source_utils;
 pub mod sources_browser;
-use crate::sources::{
-    asmotoon, drakecomic, firescans, kagane, kdtnovels, mangadex, reset_scans, rizzcomic,
-    temple_scan, thunderscans,
-};
+use crate::sources::registry::{self, MangaSource};
 // mod mal;
 // mod anilist;
 
 use crate::app_state::{AppState, MetadataProgress};
+use crate::chapter_number::ChapterNumber;
+use crate::comicinfo::ComicInfo;
+use crate::output::OutputFormat;
+use crate::search::MangaSearch;
+use crate::storage::MetadataProvider;
 use crate::helpers::{
-    build_comicinfo, extract_number, find_best_chapter_match, guess_source_id_from_url,
-    merge_alt_titles, normalize_chapter_str, normalize_title, parse_source,
-    wp_manga_source_by_name, xml_escape,
+    extract_number, guess_source_id_from_url,
+    merge_alt_titles, normalize_chapter_str, normalize_title, xml_escape,
 };
 use crate::models::{
     ChapterWithSource, Manga, MangaSourceData, MangaWithSources, PaginatedResponse, PaginationInfo,
     Source, SourceInfo, Stats,
 };
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{delete, get, post, put, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder};
 use log::{error, info};
 use regex::Regex;
 use reqwest::Client;
@@ -44,24 +62,28 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 use uuid::Uuid;
 
-#[get("/import")]
-async fn import(data: web::Data<AppState>) -> impl Responder {
-    info!("Starting import process...");
-    let client = &data.client;
-
-    let mut manga_map: HashMap<String, Manga> = HashMap::new();
-    let mut manga_source_data_map: HashMap<String, Vec<MangaSourceData>> = HashMap::new();
-
-    // Process MangaDex
-    info!("Processing MangaDex...");
-    let mangadex_manga = match mangadex::search_manga(client, "", mangadex::BASE_URL).await {
-        Ok(manga) => manga,
-        Err(e) => {
-            error!("Failed to fetch manga from MangaDex: {}", e);
-            return HttpResponse::InternalServerError().finish();
-        }
-    };
-    for manga_item in mangadex_manga {
+/// Sources walked by `/import`, with whether a listing failure aborts the import.
+const IMPORT_SOURCES: [(Source, bool); 8] = [
+    (Source::MangaDex, true),
+    (Source::FireScans, true),
+    (Source::RizzComic, true),
+    (Source::DrakeComic, true),
+    (Source::Asmotoon, true),
+    (Source::ResetScans, true),
+    // Aggregator — best-effort, may return empty without client-side rendering
+    (Source::Kagane, false),
+    // Metadata-only
+    (Source::KDTNovels, false),
+];
+
+/// Merge a source listing into the import maps, keyed by normalized title.
+fn merge_listing(
+    manga_map: &mut HashMap<String, Manga>,
+    manga_source_data_map: &mut HashMap<String, Vec<MangaSourceData>>,
+    source: &dyn MangaSource,
+    items: Vec<(Manga, String)>,
+) {
+    for (manga_item, series_url) in items {
         let normalized_title = normalize_title(&manga_item.title);
         let current_manga = manga_map
             .entry(normalized_title.clone())
@@ -89,1676 +111,1859 @@ async fn import(data: web::Data<AppState>) -> impl Responder {
         if let Some(tags) = &manga_item.tags {
             merge_alt_titles(&mut current_manga.tags, tags);
         }
+        if !manga_item.people.is_empty() {
+            current_manga.people =
+                crate::people::prefer(&[current_manga.people.clone(), manga_item.people.clone()]);
+        }
 
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::MangaDex as i32,
-            source_manga_id: manga_item.id.clone(), // MangaDex specific ID
-            source_manga_url: format!("https://mangadex.org/title/{}", manga_item.id),
-        };
         manga_source_data_map
-            .entry(normalized_title.clone())
+            .entry(normalized_title)
             .or_default()
-            .push(manga_source_data);
+            .push(source.source_data(&current_manga.id, &series_url));
     }
-    info!("Finished processing MangaDex.");
+}
 
-    // Process Fire Scans
-    info!("Processing Fire Scans...");
-    let firescans_manga = match firescans::search_manga_with_urls(client, "").await {
-        Ok(manga) => manga,
-        Err(e) => {
-            error!("Failed to fetch manga from Fire Scans: {}", e);
-            return HttpResponse::InternalServerError().finish();
+/// Insert merged manga and their source data, optionally fetching chapters for each.
+async fn store_import(
+    data: &web::Data<AppState>,
+    manga_map: &HashMap<String, Manga>,
+    manga_source_data_map: &HashMap<String, Vec<MangaSourceData>>,
+    with_chapters: bool,
+) {
+    info!("Total manga entries to insert: {}", manga_map.len());
+    for (normalized_title, manga) in manga_map {
+        if let Err(e) = data.storage.insert_manga(manga).await {
+            error!("Failed to insert manga {}: {}", manga.title, e);
+            continue;
         }
-    };
-    for (manga_item, series_url) in firescans_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
-
-        if manga_item.title != current_manga.title {
-            merge_alt_titles(&mut current_manga.alt_titles, &manga_item.title);
+        let Some(source_data_list) = manga_source_data_map.get(normalized_title) else {
+            continue;
+        };
+        for manga_source_data in source_data_list {
+            let manga_source_data_id =
+                match data.storage.insert_manga_source_data(manga_source_data).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        error!("Failed to insert manga source data: {}", e);
+                        continue;
+                    }
+                };
+            if !with_chapters {
+                continue;
+            }
+            let source = match registry::get(manga_source_data.source_id) {
+                Some(s) if s.capabilities().chapters => s,
+                _ => continue,
+            };
+            let chapters = match source.get_chapters(&data.client, manga_source_data).await {
+                Ok(chapters) => chapters,
+                Err(e) => {
+                    error!("Failed to get chapters from {}: {}", source.name(), e);
+                    Vec::new()
+                }
+            };
+            if let Err(e) =
+                data.storage.insert_chapters(manga_source_data_id, &chapters).await
+            {
+                error!("Failed to insert chapters: {}", e);
+            }
         }
+    }
+}
 
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
-        }
-        if let Some(alt_titles) = &manga_item.alt_titles {
-            merge_alt_titles(&mut current_manga.alt_titles, alt_titles);
-        }
+#[get("/import")]
+async fn import(data: web::Data<AppState>) -> impl Responder {
+    info!("Starting import process...");
+    let client = &data.client;
+
+    let mut manga_map: HashMap<String, Manga> = HashMap::new();
+    let mut manga_source_data_map: HashMap<String, Vec<MangaSourceData>> = HashMap::new();
 
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::FireScans as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
+    for (src, required) in IMPORT_SOURCES {
+        let Some(source) = registry::get(src as i32) else {
+            continue;
         };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
+        info!("Processing {}...", source.name());
+        let items = match source.search(client, "").await {
+            Ok(items) => items,
+            Err(e) => {
+                error!("Failed to fetch manga from {}: {}", source.name(), e);
+                if required {
+                    return HttpResponse::InternalServerError().finish();
+                }
+                Vec::new()
+            }
+        };
+        merge_listing(&mut manga_map, &mut manga_source_data_map, source, items);
+        info!("Finished processing {}.", source.name());
     }
-    info!("Finished processing Fire Scans.");
-
-    // Process Rizz Comic
-    info!("Processing Rizz Comic...");
-    let rizzcomic_manga = match rizzcomic::search_manga_with_urls(client, "").await {
-        Ok(manga) => manga,
-        Err(e) => {
-            error!("Failed to fetch manga from Rizz Comic: {}", e);
-            return HttpResponse::InternalServerError().finish();
-        }
-    };
-    for (manga_item, series_url) in rizzcomic_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
 
-        if manga_item.title != current_manga.title {
-            merge_alt_titles(&mut current_manga.alt_titles, &manga_item.title);
-        }
+    // Insert/Update merged manga and their source data and chapters into the database
+    info!("Inserting data into the database...");
+    store_import(&data, &manga_map, &manga_source_data_map, true).await;
+    info!("Finished inserting data into the database.");
 
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
-        }
-        if let Some(alt_titles) = &manga_item.alt_titles {
-            merge_alt_titles(&mut current_manga.alt_titles, alt_titles);
-        }
+    info!("Import process finished.");
+    HttpResponse::Ok().finish()
+}
 
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::RizzComic as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
-        };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
+/// Resolve `{source}` for the per-source import endpoints. Metadata-only sources are rejected.
+fn import_target(source: &str) -> Result<&'static dyn MangaSource, HttpResponse> {
+    match registry::find(source) {
+        None => Err(HttpResponse::BadRequest().json(serde_json::json!({"error":"unknown source"}))),
+        Some(s) if !s.capabilities().chapters => Err(HttpResponse::BadRequest()
+            .json(serde_json::json!({"error":"metadata-only source not supported here"}))),
+        Some(s) => Ok(s),
     }
-    info!("Finished processing Rizz Comic.");
+}
 
-    // Process DrakeComic
-    info!("Processing DrakeComic...");
-    let drake_manga = match drakecomic::search_manga_with_urls(client, "").await {
-        Ok(manga) => manga,
-        Err(e) => {
-            error!("Failed to fetch manga from DrakeComic: {}", e);
-            return HttpResponse::InternalServerError().finish();
-        }
+#[get("/import/source/{source}")]
+async fn import_source_endpoint(
+    data: web::Data<AppState>,
+    source: web::Path<String>,
+) -> impl Responder {
+    let target = match import_target(&source) {
+        Ok(s) => s,
+        Err(resp) => return resp,
     };
-    for (manga_item, series_url) in drake_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
-
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
-        }
 
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::DrakeComic as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
-        };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
-    }
-    info!("Finished processing DrakeComic.");
+    let mut manga_map: HashMap<String, Manga> = HashMap::new();
+    let mut manga_source_data_map: HashMap<String, Vec<MangaSourceData>> = HashMap::new();
 
-    // Process Asmotoon
-    info!("Processing Asmotoon...");
-    let asm_manga = match asmotoon::search_manga_with_urls(client, "").await {
-        Ok(manga) => manga,
+    match target.search(&data.client, "").await {
+        Ok(items) => merge_listing(&mut manga_map, &mut manga_source_data_map, target, items),
         Err(e) => {
-            error!("Failed to fetch manga from Asmotoon: {}", e);
+            error!("{} import error: {}", target.name(), e);
             return HttpResponse::InternalServerError().finish();
         }
-    };
-    for (manga_item, series_url) in asm_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
+    }
 
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
-        }
+    store_import(&data, &manga_map, &manga_source_data_map, true).await;
 
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::Asmotoon as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
-        };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
-    }
-    info!("Finished processing Asmotoon.");
+    HttpResponse::Ok()
+        .json(serde_json::json!({"source": source.to_string(), "manga": manga_map.len()}))
+}
+
+#[get("/import/source/{source}/manga")]
+async fn import_source_manga_only(
+    data: web::Data<AppState>,
+    source: web::Path<String>,
+) -> impl Responder {
+    let target = match import_target(&source) {
+        Ok(s) => s,
+        Err(resp) => return resp,
+    };
+
+    let mut manga_map: HashMap<String, Manga> = HashMap::new();
+    let mut manga_source_data_map: HashMap<String, Vec<MangaSourceData>> = HashMap::new();
 
-    // Process Reset-Scans
-    info!("Processing Reset-Scans...");
-    let reset_manga = match reset_scans::search_manga_with_urls(client, "").await {
-        Ok(manga) => manga,
+    match target.search(&data.client, "").await {
+        Ok(items) => merge_listing(&mut manga_map, &mut manga_source_data_map, target, items),
         Err(e) => {
-            error!("Failed to fetch manga from Reset-Scans: {}", e);
+            error!("{} import error: {}", target.name(), e);
             return HttpResponse::InternalServerError().finish();
         }
-    };
-    for (manga_item, series_url) in reset_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
+    }
 
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
-        }
+    store_import(&data, &manga_map, &manga_source_data_map, false).await;
 
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::ResetScans as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
-        };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
-    }
-    info!("Finished processing Reset-Scans.");
+    HttpResponse::Ok().json(
+        serde_json::json!({"source": source.to_string(), "manga": manga_map.len(), "chapters": 0}),
+    )
+}
+
+/// `GET /manga`: list and search manga. See `search::MangaSearch::from_query`
+/// for the parameters; `pagination.total` counts every match, not just the page.
+#[get("/manga")]
+async fn list_manga(
+    data: web::Data<AppState>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let search = match MangaSearch::from_query(&query) {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+    };
 
-    // Process Kagane (aggregator) — best-effort search, may return empty without client-side rendering
-    info!("Processing Kagane (aggregator)...");
-    let kagane_manga = match kagane::search_manga_with_urls(client, "").await {
+    let manga_list = match data.storage.search_manga(&search).await {
         Ok(list) => list,
         Err(e) => {
-            error!("Failed to fetch from Kagane: {}", e);
-            Vec::new()
+            error!("Failed to search manga: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
     };
-    for (manga_item, series_url) in kagane_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
+    let total = match data.storage.count_manga_matching(&search).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to get manga count: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::Kagane as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
-        };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
-    }
-    info!("Finished processing Kagane.");
+    };
+
+    let response = PaginatedResponse {
+        data: manga_list,
+        pagination: PaginationInfo {
+            total: total as i32,
+            limit: search.limit as i32,
+            offset: search.offset as i32,
+            has_more: search.offset + search.limit < total,
+        },
+    };
+
+    HttpResponse::Ok().json(response)
+}
 
-    // Process KDT Novels (metadata-only)
-    info!("Processing KDT Novels...");
-    let kdt_manga = match kdtnovels::search_manga_with_urls(client, "").await {
-        Ok(manga) => manga,
+#[get("/manga/{id}")]
+async fn get_manga(data: web::Data<AppState>, id: web::Path<String>) -> impl Responder {
+    let manga = match data.storage.get_manga_by_id(&id).await {
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Manga not found"}))
+        }
         Err(e) => {
-            error!("Failed to fetch entries from KDT Novels: {}", e);
-            Vec::new()
+            error!("Database error fetching manga: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
     };
-    for (manga_item, series_url) in kdt_manga {
-        let normalized_title = normalize_title(&manga_item.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = manga_item.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
-        if current_manga.cover_url.is_none() && manga_item.cover_url.is_some() {
-            current_manga.cover_url = manga_item.cover_url.clone();
+
+    // Get source data
+    let source_data_list = match data.storage.get_manga_source_data_by_manga_id(&id).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Database error fetching source data: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
-        let manga_source_data = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id: Source::KDTNovels as i32,
-            source_manga_id: series_url.clone(),
-            source_manga_url: series_url,
-        };
-        manga_source_data_map
-            .entry(normalized_title.clone())
-            .or_default()
-            .push(manga_source_data);
-    }
-    info!("Finished processing KDT Novels.");
+    };
 
-    // Insert/Update merged manga and their source data and chapters into the database
-    info!("Inserting data into the database...");
-    info!("Total manga entries to insert: {}", manga_map.len());
-    for (normalized_title, manga) in manga_map {
-        info!(
-            "Inserting manga - ID: {}, Title: {}, Normalized: {}",
-            manga.id, manga.title, normalized_title
-        );
-        if let Err(e) = pg_db::insert_manga(&data.pool, &manga).await {
-            error!("Failed to insert manga: {}", e);
-            continue;
+    // Build sources with names
+    let mut sources = Vec::new();
+    for source_data in source_data_list {
+        let source_name = match data.storage.get_source_name(source_data.source_id).await {
+            Ok(name) => name,
+            Err(_) => format!("Source {}", source_data.source_id),
+        };
+        sources.push(SourceInfo {
+            source_id: source_data.source_id,
+            source_name,
+            source_manga_id: source_data.source_manga_id,
+            source_manga_url: source_data.source_manga_url,
+        });
+    }
+
+    let manga_with_sources = MangaWithSources {
+        id: manga.id,
+        title: manga.title,
+        alt_titles: manga.alt_titles,
+        cover_url: manga.cover_url,
+        description: manga.description,
+        tags: manga.tags,
+        rating: manga.rating,
+        people: manga.people,
+        sources,
+    };
+
+    HttpResponse::Ok().json(manga_with_sources)
+}
+
+#[post("/manga/{id}/monitor")]
+async fn monitor_manga(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::MonitorRequest>,
+) -> impl Responder {
+    let _ = data.storage.set_manga_monitoring(
+        &id,
+        body.monitored,
+        body.check_interval_secs,
+        body.discover_interval_secs,
+    ).await;
+    HttpResponse::Ok().finish()
+}
+
+#[post("/manga/{id}/format")]
+async fn set_output_format(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::OutputFormatRequest>,
+) -> impl Responder {
+    let format = match body.format.as_deref().map(OutputFormat::parse) {
+        Some(None) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "format must be cbz, epub, pdf or folder"}))
+        }
+        Some(Some(f)) => Some(f.as_str()),
+        None => None,
+    };
+    match data.storage.set_manga_output_format(&id, format).await {
+        Ok(0) => HttpResponse::NotFound().json(serde_json::json!({"error": "Manga not found"})),
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({"format": format})),
+        Err(e) => {
+            error!("Failed to set output format for {}: {}", id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}))
         }
-        info!("Successfully inserted manga with ID: {}", manga.id);
+    }
+}
 
-        if let Some(source_data_list) = manga_source_data_map.get(&normalized_title) {
-            info!(
-                "Found {} source data entries for manga {}",
-                source_data_list.len(),
-                manga.id
-            );
-            for manga_source_data in source_data_list {
-                info!(
-                    "Inserting source data - manga_id: {}, source_id: {}, source_manga_id: {}",
-                    manga_source_data.manga_id,
-                    manga_source_data.source_id,
-                    manga_source_data.source_manga_id
-                );
-                let manga_source_data_id =
-                    match pg_db::insert_manga_source_data(&data.pool, &manga_source_data).await {
-                        Ok(id) => id,
-                        Err(e) => {
-                            error!("Failed to insert manga source data: {}", e);
-                            continue;
-                        }
-                    };
-
-                info!(
-                    "Fetching chapters for source_id: {}",
-                    manga_source_data.source_id
-                );
-                let chapters = match manga_source_data.source_id {
-                    x if x == Source::MangaDex as i32 => {
-                        match mangadex::get_chapters(client, &manga_source_data.source_manga_id)
-                            .await
-                        {
-                            Ok(chapters) => chapters,
-                            Err(e) => {
-                                error!("Failed to get chapters from MangaDex: {}", e);
-                                Vec::new()
-                            }
-                        }
-                    }
-                    x if x == Source::FireScans as i32 => {
-                        match firescans::get_chapters(client, &manga_source_data.source_manga_url)
-                            .await
-                        {
-                            Ok(chapters) => chapters,
-                            Err(e) => {
-                                error!("Failed to get chapters from Fire Scans: {}", e);
-                                Vec::new()
-                            }
-                        }
-                    }
-                    x if x == Source::RizzComic as i32 => {
-                        match rizzcomic::get_chapters(client, &manga_source_data.source_manga_url)
-                            .await
-                        {
-                            Ok(chapters) => chapters,
-                            Err(e) => {
-                                error!("Failed to get chapters from Rizz Comic: {}", e);
-                                Vec::new()
-                            }
-                        }
-                    }
-                    x if x == Source::DrakeComic as i32 => {
-                        match drakecomic::get_chapters(client, &manga_source_data.source_manga_url)
-                            .await
-                        {
-                            Ok(chapters) => chapters,
-                            Err(e) => {
-                                error!("Failed to get chapters from DrakeComic: {}", e);
-                                Vec::new()
-                            }
-                        }
-                    }
-                    x if x == Source::Asmotoon as i32 => {
-                        match asmotoon::get_chapters(client, &manga_source_data.source_manga_url)
-                            .await
-                        {
-                            Ok(chapters) => chapters,
-                            Err(e) => {
-                                error!("Failed to get chapters from Asmotoon: {}", e);
-                                Vec::new()
-                            }
-                        }
-                    }
-                    x if x == Source::ResetScans as i32 => {
-                        match reset_scans::get_chapters(client, &manga_source_data.source_manga_url)
-                            .await
-                        {
-                            Ok(chapters) => chapters,
-                            Err(e) => {
-                                error!("Failed to get chapters from Reset-Scans: {}", e);
-                                Vec::new()
-                            }
-                        }
-                    }
-                    x if x == Source::KDTNovels as i32 => Vec::new(), // No chapter scraping for novels yet
-                    _ => Vec::new(),                                  // Should not happen
-                };
+#[post("/manga/{id}/sources/priority")]
+async fn set_source_priority(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::SourcePriorityRequest>,
+) -> impl Responder {
+    match data.storage.set_source_priority(&id, &body.source_ids).await {
+        Ok(0) if !body.source_ids.is_empty() => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "none of these sources are linked to the manga"})),
+        Ok(ranked) => HttpResponse::Ok().json(serde_json::json!({"ranked": ranked})),
+        Err(e) => {
+            error!("Database error setting source priority for {}: {}", id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
 
-                info!("Found {} chapters, inserting into database", chapters.len());
-                if let Err(e) = pg_db::insert_chapters(&data.pool, manga_source_data_id, &chapters).await {
-                    error!("Failed to insert chapters: {}", e);
-                }
-            }
+#[post("/manga/{id}/bundle")]
+async fn bundle_manga(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::BundleRequest>,
+) -> impl Responder {
+    let selection = match bundle::Selection::from_request(&body) {
+        Some(s) => s,
+        None => {
+            return HttpResponse::BadRequest().json(
+                serde_json::json!({"error": "specify a volume, or a chapter range with from <= to"}),
+            )
+        }
+    };
+    let format = match body.format.as_deref().map(OutputFormat::parse) {
+        None => OutputFormat::Cbz,
+        Some(Some(f)) if f.extension().is_some() => f,
+        Some(_) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "format must be cbz, epub or pdf"}))
+        }
+    };
+    let manga = match data.storage.get_manga_by_id(&id).await {
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Manga not found"}))
         }
+        Err(e) => {
+            error!("Database error fetching manga {}: {}", id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}));
+        }
+    };
+    if bundle::spawn_bundle(
+        data.clone(),
+        manga.id,
+        manga.title,
+        selection,
+        format,
+        body.remove_chapters,
+    ) {
+        HttpResponse::Accepted().finish()
+    } else {
+        HttpResponse::Conflict().json(serde_json::json!({"error": "a bundle job is already running"}))
     }
+}
 
-    info!("Finished inserting data into the database.");
+/// Validate an optional `format` for queued jobs; `Ok(None)` means "decide when the job runs"
+fn queue_format(format: Option<&str>) -> Result<Option<String>, HttpResponse> {
+    match format {
+        None => Ok(None),
+        Some(f) => match OutputFormat::parse(f) {
+            Some(f) => Ok(Some(f.as_str().to_string())),
+            None => Err(HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "format must be cbz, epub, pdf or folder"}))),
+        },
+    }
+}
 
-    info!("Import process finished.");
-    HttpResponse::Ok().finish()
+async fn enqueue(data: &web::Data<AppState>, chapter_ids: &[i32], format: Option<&str>) -> HttpResponse {
+    match data.storage.enqueue_download_jobs(
+        chapter_ids,
+        format,
+        data.config.download_max_attempts,
+        chrono::Utc::now().timestamp(),
+    )
+    .await
+    {
+        Ok(queued) => HttpResponse::Accepted().json(serde_json::json!({
+            "selected": chapter_ids.len(),
+            "queued": queued,
+        })),
+        Err(e) => {
+            error!("Database error queueing downloads: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
 }
 
-#[get("/import/source/{source}")]
-async fn import_source_endpoint(
+#[post("/queue/manga/{id}")]
+async fn queue_manga(
     data: web::Data<AppState>,
-    source: web::Path<String>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::QueueRequest>,
 ) -> impl Responder {
-    // Try enum sources first, then generic WP-Manga mapping
-    let src_opt = parse_source(&source);
-    let wp_opt = if src_opt.is_none() {
-        wp_manga_source_by_name(&source.to_lowercase())
-    } else {
-        None
+    let format = match queue_format(body.format.as_deref()) {
+        Ok(f) => f,
+        Err(resp) => return resp,
+    };
+    let sources = match data.storage.get_manga_source_data_by_manga_id(&id).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Database error fetching source data: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}));
+        }
+    };
+    if sources.is_empty() {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "Manga not found or has no sources"}));
+    }
+
+    let mut chapters = Vec::new();
+    for msd in sources {
+        match data.storage.get_chapters_by_manga_source_data_id(&msd.manga_id, msd.source_id).await {
+            Ok(list) => chapters.push((msd.source_id, list)),
+            Err(e) => {
+                error!("Database error fetching chapters: {}", e);
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error"}));
+            }
+        }
+    }
+    let chapter_ids = download_queue::select_chapters(chapters, &body);
+    enqueue(&data, &chapter_ids, format.as_deref()).await
+}
+
+#[post("/queue/unread")]
+async fn queue_unread(
+    data: web::Data<AppState>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let format = match queue_format(query.get("format").map(|s| s.as_str())) {
+        Ok(f) => f,
+        Err(resp) => return resp,
     };
-    if src_opt.is_none() && wp_opt.is_none() {
-        return HttpResponse::BadRequest().json(serde_json::json!({"error":"unknown source"}));
+    let limit = query.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(1000);
+    match data.storage.get_unscraped_chapter_ids(limit).await {
+        Ok(chapter_ids) => enqueue(&data, &chapter_ids, format.as_deref()).await,
+        Err(e) => {
+            error!("Database error fetching unread chapters: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
     }
-    let client = &data.client;
+}
 
-    let mut manga_map: HashMap<String, Manga> = HashMap::new();
-    let mut manga_source_data_map: HashMap<String, Vec<MangaSourceData>> = HashMap::new();
+#[get("/queue")]
+async fn list_queue(
+    data: web::Data<AppState>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let state = query.get("state").map(|s| s.as_str());
+    let limit = query.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(100);
+    let offset = query.get("offset").and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+    match data.storage.list_download_jobs(state, limit, offset).await {
+        Ok(jobs) => HttpResponse::Ok().json(jobs),
+        Err(e) => {
+            error!("Database error listing download jobs: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
 
-    let add_entry = |manga_map: &mut HashMap<String, Manga>,
-                     msd_map: &mut HashMap<String, Vec<MangaSourceData>>,
-                     m: Manga,
-                     source_id: i32,
-                     series_url_or_id: String| {
-        let normalized_title = normalize_title(&m.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = m.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
-        if current_manga.cover_url.is_none() && m.cover_url.is_some() {
-            current_manga.cover_url = m.cover_url.clone();
-        }
-        let msd = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id,
-            source_manga_id: series_url_or_id.clone(),
-            source_manga_url: series_url_or_id,
-        };
-        msd_map.entry(normalized_title).or_default().push(msd);
-    };
-
-    if let Some(src) = src_opt {
-        match src {
-            Source::MangaDex => {
-                match mangadex::search_manga(client, "", mangadex::BASE_URL).await {
-                    Ok(list) => {
-                        for m in list {
-                            add_entry(
-                                &mut manga_map,
-                                &mut manga_source_data_map,
-                                m,
-                                Source::MangaDex as i32,
-                                String::new(),
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        error!("MangaDex import error: {}", e);
-                        return HttpResponse::InternalServerError().finish();
-                    }
-                }
-            }
-            Source::FireScans => match firescans::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::FireScans as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("FireScans import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::RizzComic => match rizzcomic::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::RizzComic as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("RizzComic import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::DrakeComic => match drakecomic::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::DrakeComic as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("DrakeComic import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::Asmotoon => match asmotoon::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::Asmotoon as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("Asmotoon import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::ResetScans => match reset_scans::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::ResetScans as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("ResetScans import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::Kagane => match kagane::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::Kagane as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("Kagane import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::TempleScan => match temple_scan::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::TempleScan as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("TempleScan import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::ThunderScans => match thunderscans::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::ThunderScans as i32,
-                            u,
-                        );
-                    }
-                }
-                Err(e) => {
-                    error!("ThunderScans import error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
-                }
-            },
-            Source::KDTNovels | Source::MyAnimeList | Source::AniList => {
-                return HttpResponse::BadRequest()
-                    .json(serde_json::json!({"error":"metadata-only source not supported here"}))
-            }
+#[post("/queue/{id}/retry")]
+async fn retry_queue_job(data: web::Data<AppState>, id: web::Path<i64>) -> impl Responder {
+    match data.storage.retry_download_job(*id, chrono::Utc::now().timestamp()).await {
+        Ok(true) => HttpResponse::Accepted().finish(),
+        Ok(false) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "no failed job with that id"})),
+        Err(e) => {
+            error!("Database error retrying download job {}: {}", id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
         }
-    } else if let Some((sid, base)) = wp_opt {
-        let s = source.to_lowercase();
-        let res = match s.as_str() {
-            "asurascans" => crate::sources::asurascans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (11, items)),
-            "kenscans" => crate::sources::kenscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (25, items)),
-            "sirenscans" | "siren-scans" => {
-                crate::sources::sirenscans::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (43, items))
-            }
-            "vortexscans" | "vortex-scans" => {
-                crate::sources::vortexscans::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (56, items))
-            }
-            "witchscans" | "witch-scans" => {
-                crate::sources::witchscans::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (59, items))
-            }
-            "qiscans" | "qi-scans" => crate::sources::qiscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (38, items)),
-            "madarascans" => crate::sources::madarascans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (30, items)),
-            "rizzfables" => crate::sources::rizzfables::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (39, items)),
-            "rokaricomics" | "rokari-comics" => {
-                crate::sources::rokaricomics::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (40, items))
-            }
-            "stonescape" => crate::sources::stonescape::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (45, items)),
-            "manhuaus" => crate::sources::manhuaus::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (31, items)),
-            "grimscans" => crate::sources::grimscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (19, items)),
-            "hivetoons" => crate::sources::hivetoons::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (20, items)),
-            "nyxscans" => crate::sources::nyxscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (34, items)),
-            // Free scanlation sites
-            "flamecomics" | "flame-comics" => {
-                crate::sources::flamecomics::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (18, items))
-            }
-            "daycomics" | "day-comics" => {
-                crate::sources::daycomics::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (16, items))
-            }
-            "kodokustudio" | "kodoku-studio" => {
-                crate::sources::kodoku_studio::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (27, items))
-            }
-            "lunatoons" | "luna-toons" => {
-                crate::sources::lunatoons::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (29, items))
-            }
-            "vastvisual" | "vast-visual" => {
-                crate::sources::vast_visual::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (54, items))
-            }
-            "mavintranslations" | "mavin-translations" => {
-                crate::sources::mavintranslations::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (61, items))
-            }
-            // Free web platforms
-            "tapas" => crate::sources::tapas::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (47, items)),
-            "webtoon" | "webtoons" => crate::sources::webtoon::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (58, items)),
-            _ => crate::sources::wp_manga::search_manga_with_urls_base(client, base)
-                .await
-                .map(|items| (sid, items)),
-        };
-        match res {
-            Ok((resolved_sid, items)) => {
-                for (m, u) in items {
-                    add_entry(
-                        &mut manga_map,
-                        &mut manga_source_data_map,
-                        m,
-                        resolved_sid,
-                        u,
-                    );
-                }
+    }
+}
+
+/// Fold another manga (`{"other_id": "..."}`) into this one; undo with `POST /audit/{id}/undo`
+#[post("/manga/{id}/merge")]
+async fn merge_manga(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::MergeRequest>,
+) -> impl Responder {
+    if *id == body.other_id {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "cannot merge a manga into itself"}));
+    }
+    for manga_id in [id.as_str(), body.other_id.as_str()] {
+        match data.storage.get_manga_by_id(manga_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return HttpResponse::NotFound()
+                    .json(serde_json::json!({"error": format!("Manga {} not found", manga_id)}))
             }
             Err(e) => {
-                error!("wp import error {}: {}", s, e);
-                return HttpResponse::InternalServerError().finish();
+                error!("Database error fetching manga {}: {}", manga_id, e);
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error"}));
             }
         }
     }
-
-    // Insert
-    for (key, m) in manga_map.iter() {
-        if let Err(e) = pg_db::insert_manga(&data.pool, m).await {
-            error!("insert manga {}: {}", m.title, e);
+    match data.storage.merge_manga(&id, &body.other_id, chrono::Utc::now().timestamp()).await {
+        Ok(audit_id) => HttpResponse::Ok().json(serde_json::json!({"audit_id": audit_id})),
+        Err(e) => {
+            error!("Failed to merge {} into {}: {}", body.other_id, id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
         }
-        if let Some(msds) = manga_source_data_map.get(key) {
-            for msd in msds {
-                let msd_id = match pg_db::insert_manga_source_data(&data.pool, msd).await {
-                    Ok(id) => id,
-                    Err(e) => {
-                        error!("insert msd: {}", e);
-                        continue;
-                    }
-                };
-                let chapters: Vec<crate::models::Chapter> = match msd.source_id {
-                    x if x == Source::MangaDex as i32 => {
-                        match mangadex::get_chapters(client, &m.id).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::FireScans as i32 => {
-                        match firescans::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::RizzComic as i32 => {
-                        match rizzcomic::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::DrakeComic as i32 => {
-                        match drakecomic::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::Asmotoon as i32 => {
-                        match asmotoon::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::ResetScans as i32 => {
-                        match reset_scans::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::Kagane as i32 => {
-                        match kagane::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::TempleScan as i32 => {
-                        match temple_scan::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    x if x == Source::ThunderScans as i32 => {
-                        match thunderscans::get_chapters(client, &msd.source_manga_url).await {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    11 => match crate::sources::asurascans::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    25 => {
-                        match crate::sources::kenscans::get_chapters(client, &msd.source_manga_url)
-                            .await
-                        {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    43 => match crate::sources::sirenscans::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    56 => match crate::sources::vortexscans::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    59 => match crate::sources::witchscans::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    38 => {
-                        match crate::sources::qiscans::get_chapters(client, &msd.source_manga_url)
-                            .await
-                        {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    30 => match crate::sources::madarascans::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    39 => match crate::sources::rizzfables::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    40 => match crate::sources::rokaricomics::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    45 => match crate::sources::stonescape::get_chapters(
-                        client,
-                        &msd.source_manga_url,
-                    )
-                    .await
-                    {
-                        Ok(c) => c,
-                        Err(_) => Vec::new(),
-                    },
-                    31 => {
-                        match crate::sources::manhuaus::get_chapters(client, &msd.source_manga_url)
-                            .await
-                        {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    19 => {
-                        match crate::sources::grimscans::get_chapters(client, &msd.source_manga_url)
-                            .await
-                        {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    20 => {
-                        match crate::sources::hivetoons::get_chapters(client, &msd.source_manga_url)
-                            .await
-                        {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    34 => {
-                        match crate::sources::nyxscans::get_chapters(client, &msd.source_manga_url)
-                            .await
-                        {
-                            Ok(c) => c,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    _ => Vec::new(),
-                };
-                let _ = pg_db::insert_chapters(&data.pool, msd_id, &chapters).await;
+    }
+}
+
+/// Move the links of `{"source_ids": [...]}` to a new manga; undo with `POST /audit/{id}/undo`
+#[post("/manga/{id}/split")]
+async fn split_manga(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Json<crate::models::SplitRequest>,
+) -> impl Responder {
+    let linked = match data.storage.get_manga_source_data_by_manga_id(&id).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Database error fetching source data for {}: {}", id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}));
+        }
+    };
+    if !linked.is_empty() && linked.iter().all(|msd| body.source_ids.contains(&msd.source_id)) {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "leave at least one source with the original manga"}));
+    }
+    let title = body.title.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    match data.storage.split_manga(&id, &body.source_ids, title, chrono::Utc::now().timestamp()).await {
+        Ok(Some((manga_id, audit_id))) => HttpResponse::Created()
+            .json(serde_json::json!({"manga_id": manga_id, "audit_id": audit_id})),
+        Ok(None) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "none of these sources are linked to the manga"})),
+        Err(e) => {
+            error!("Failed to split manga {}: {}", id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[get("/audit")]
+async fn list_manga_audit(
+    data: web::Data<AppState>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let manga_id = query.get("manga_id").map(|s| s.as_str());
+    let limit = query.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(100);
+    let offset = query.get("offset").and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+    match data.storage.list_manga_audit(manga_id, limit, offset).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            error!("Database error listing manga audit: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[post("/audit/{id}/undo")]
+async fn undo_manga_audit(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    use storage::UndoOutcome;
+    match data.storage.undo_manga_audit(*id, chrono::Utc::now().timestamp()).await {
+        Ok(UndoOutcome::Undone) => HttpResponse::Ok().finish(),
+        Ok(UndoOutcome::NotFound) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "no audit entry with that id"})),
+        Ok(UndoOutcome::AlreadyUndone) => HttpResponse::Conflict()
+            .json(serde_json::json!({"error": "already undone"})),
+        Ok(UndoOutcome::Superseded) => HttpResponse::Conflict().json(serde_json::json!({
+            "error": "a later merge or split of the same manga must be undone first"
+        })),
+        Err(e) => {
+            error!("Failed to undo audit entry {}: {}", id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[post("/matching/run")]
+async fn run_matching(data: web::Data<AppState>) -> impl Responder {
+    if matching::spawn_matching(data.clone()) {
+        HttpResponse::Accepted().finish()
+    } else {
+        HttpResponse::Conflict().json(serde_json::json!({"error": "series matching already running"}))
+    }
+}
+
+#[get("/matching/proposals")]
+async fn list_merge_proposals(
+    data: web::Data<AppState>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let state = query.get("state").map(|s| s.as_str());
+    let limit = query.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(100);
+    let offset = query.get("offset").and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+    match data.storage.list_merge_proposals(state, limit, offset).await {
+        Ok(proposals) => HttpResponse::Ok().json(proposals),
+        Err(e) => {
+            error!("Database error listing merge proposals: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+/// Accept a pending proposal and merge the pair. `?keep=<manga id>` picks the
+/// manga that survives (default `manga_a`).
+#[post("/matching/proposals/{id}/accept")]
+async fn accept_merge_proposal(
+    data: web::Data<AppState>,
+    id: web::Path<i32>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let proposal = match data.storage.get_merge_proposal(*id).await {
+        Ok(Some(p)) if p.state == "pending" => p,
+        Ok(_) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"error": "no pending proposal with that id"}))
+        }
+        Err(e) => {
+            error!("Database error fetching merge proposal {}: {}", id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}));
+        }
+    };
+    let (keep, other) = match query.get("keep").map(|s| s.as_str()) {
+        None => (&proposal.manga_a, &proposal.manga_b),
+        Some(k) if k == proposal.manga_a => (&proposal.manga_a, &proposal.manga_b),
+        Some(k) if k == proposal.manga_b => (&proposal.manga_b, &proposal.manga_a),
+        Some(_) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "keep must be one of the proposal's manga"}))
+        }
+    };
+
+    // Mark it accepted first: the merge drops pending proposals of the merged manga.
+    let now = chrono::Utc::now().timestamp();
+    match data.storage.set_merge_proposal_state(proposal.id, "pending", "accepted", now).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Conflict()
+                .json(serde_json::json!({"error": "proposal was decided meanwhile"}))
+        }
+        Err(e) => {
+            error!("Database error accepting merge proposal {}: {}", id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}));
+        }
+    }
+    match data.storage.merge_manga(keep, other, now).await {
+        Ok(audit_id) => HttpResponse::Ok()
+            .json(serde_json::json!({"kept": keep, "merged": other, "audit_id": audit_id})),
+        Err(e) => {
+            error!("Failed to merge {} into {}: {}", other, keep, e);
+            let _ = data.storage.set_merge_proposal_state(proposal.id, "accepted", "pending", now).await;
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[post("/matching/proposals/{id}/reject")]
+async fn reject_merge_proposal(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    let now = chrono::Utc::now().timestamp();
+    match data.storage.set_merge_proposal_state(*id, "pending", "rejected", now).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "no pending proposal with that id"})),
+        Err(e) => {
+            error!("Database error rejecting merge proposal {}: {}", id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[get("/manga/{id}/chapters")]
+async fn get_chapters(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let manga_source_data_list = match data.storage.get_manga_source_data_by_manga_id(&id).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Database error fetching source data: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
+        }
+    };
+
+    if manga_source_data_list.is_empty() {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "Manga not found or has no sources"}));
+    }
+
+    let mut all_chapters = Vec::new();
+    for manga_source_data in manga_source_data_list {
+        let source_name = match data.storage.get_source_name(manga_source_data.source_id).await {
+            Ok(name) => name,
+            Err(_) => format!("Source {}", manga_source_data.source_id),
+        };
+
+        match data.storage.get_chapters_by_manga_source_data_id(
+            &manga_source_data.manga_id,
+            manga_source_data.source_id,
+        ).await {
+            Ok(chapters) => {
+                for chapter in chapters {
+                    all_chapters.push(ChapterWithSource::new(
+                        chapter,
+                        manga_source_data.source_id,
+                        source_name.clone(),
+                    ));
+                }
+            }
+            Err(e) => {
+                error!("Database error fetching chapters: {}", e);
+                // Continue to next source instead of failing completely
+            }
+        }
+    }
+    if query.get("merged").is_some_and(|m| m == "true") {
+        return HttpResponse::Ok().json(chapter_number::merge_chapters(all_chapters));
+    }
+    HttpResponse::Ok().json(all_chapters)
+}
+
+#[get("/manga/{id}/gaps")]
+async fn get_chapter_gaps(data: web::Data<AppState>, id: web::Path<String>) -> impl Responder {
+    let manga_source_data_list = match data.storage.get_manga_source_data_by_manga_id(&id).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Database error fetching source data: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
+        }
+    };
+    if manga_source_data_list.is_empty() {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "Manga not found or has no sources"}));
+    }
+
+    let mut numbers = Vec::new();
+    for msd in &manga_source_data_list {
+        match data.storage.get_chapters_by_manga_source_data_id(&msd.manga_id, msd.source_id).await {
+            Ok(chapters) => numbers.push((
+                msd.source_id,
+                chapters.iter().map(|c| ChapterNumber::parse(&c.chapter_number)).collect::<Vec<_>>(),
+            )),
+            Err(e) => {
+                error!("Database error fetching chapters: {}", e);
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Internal server error"}));
             }
         }
     }
-
-    HttpResponse::Ok()
-        .json(serde_json::json!({"source": source.to_string(), "manga": manga_map.len()}))
+
+    let mut sources = Vec::new();
+    for ((source_id, missing), (_, parsed)) in chapter_number::find_gaps(&numbers).into_iter().zip(&numbers) {
+        let source_name = data.storage.get_source_name(source_id)
+            .await
+            .unwrap_or_else(|_| format!("Source {}", source_id));
+        sources.push(serde_json::json!({
+            "source_id": source_id,
+            "source_name": source_name,
+            "chapters": parsed.len(),
+            "missing": missing,
+        }));
+    }
+    HttpResponse::Ok().json(serde_json::json!({"manga_id": id.into_inner(), "sources": sources}))
+}
+
+#[get("/chapters/{id}/pages")]
+async fn get_chapter_pages(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    let chapter_id = id.into_inner();
+    let chapter = match data.storage.get_chapter_with_source(chapter_id).await {
+        Ok(Some(chapter)) => chapter,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Chapter not found"}))
+        }
+        Err(e) => {
+            error!("Database error fetching chapter {}: {}", chapter_id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
+        }
+    };
+
+    match resolved_pages(&data, &chapter).await {
+        Ok(pages) => HttpResponse::Ok().json(serde_json::json!({
+            "chapter": chapter,
+            "pages": pages.as_slice(),
+        })),
+        Err(resp) => resp,
+    }
+}
+
+/// A chapter's pages, resolved at most once per `AppState::page_cache` period.
+/// The page count is saved on the chapter for OPDS-PSE links.
+async fn resolved_pages(
+    data: &AppState,
+    chapter: &ChapterWithSource,
+) -> Result<std::sync::Arc<Vec<sources::pages::PageRef>>, HttpResponse> {
+    if let Some(pages) = data.page_cache.get(chapter.id) {
+        return Ok(pages);
+    }
+    let pages = match sources::pages::resolve_pages(&data.client, chapter.source_id, &chapter.url).await {
+        Ok(pages) => pages,
+        Err(e) => {
+            error!("Failed to resolve pages for chapter {}: {}", chapter.id, e);
+            return Err(HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to resolve pages: {}", e)})));
+        }
+    };
+    let count = pages.len() as i32;
+    if count > 0 && chapter.page_count != Some(count) {
+        if let Err(e) = data.storage.set_chapter_page_count(chapter.id, count).await {
+            error!("Failed to save page count of chapter {}: {}", chapter.id, e);
+        }
+    }
+    Ok(data.page_cache.insert(chapter.id, pages))
+}
+
+/// Output format for a download: `?format=` first, then the manga's stored
+/// format, then `output_format` from `config.toml`. Folder output is rejected
+/// for streamed downloads.
+async fn output_format_for(
+    data: &web::Data<AppState>,
+    manga_id: &str,
+    query: &HashMap<String, String>,
+    stream: bool,
+) -> Result<OutputFormat, HttpResponse> {
+    let format = match query.get("format") {
+        Some(f) => OutputFormat::parse(f).ok_or_else(|| {
+            HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "format must be cbz, epub, pdf or folder"}))
+        })?,
+        None => data.storage.get_manga_output_format(manga_id)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|f| OutputFormat::parse(&f))
+            .unwrap_or(data.config.output_format),
+    };
+    if stream && format.extension().is_none() {
+        return Err(HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "folder output cannot be streamed"})));
+    }
+    Ok(format)
+}
+
+#[get("/download/{manga_id}/{chapter_number}")]
+async fn download(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let (manga_id, chapter_number) = path.into_inner();
+    let stream = query.get("stream").map(|s| s == "true").unwrap_or(false);
+
+    // Every source that has the chapter, most preferred first; later ones are
+    // only used if the earlier downloads fail.
+    let candidates = match download_queue::source_candidates(data.storage.as_ref(), &manga_id, &chapter_number).await {
+        Ok(c) => c,
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error"}))
+        }
+    };
+    if candidates.is_empty() {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "Chapter not found"}));
+    }
+
+    let manga = match data.storage.get_manga_by_id(&manga_id).await {
+        Ok(Some(m)) => m,
+        _ => return HttpResponse::InternalServerError().finish(),
+    };
+    let format = match output_format_for(&data, &manga_id, &query, stream).await {
+        Ok(f) => f,
+        Err(resp) => return resp,
+    };
+
+    for (source_id, chapter) in &candidates {
+        let source_id = *source_id;
+        let label = chapter.label();
+        let comicinfo = ComicInfo::for_chapter(&manga, source_id, &label, &chapter.url);
+        if stream {
+            // Stream file directly
+            match scraper::download_chapter_to_memory(
+                &data.client,
+                &data.page_fetcher,
+                source_id,
+                &chapter.url,
+                Some(&comicinfo),
+                format,
+            )
+            .await
+            {
+                Ok(bytes) => {
+                    let filename = format!(
+                        "{} - {}.{}",
+                        manga.title,
+                        label,
+                        format.as_str()
+                    );
+                    return HttpResponse::Ok()
+                        .content_type(format.content_type())
+                        .insert_header((
+                            "Content-Disposition",
+                            format!("attachment; filename=\"{}\"", filename),
+                        ))
+                        .insert_header(("X-Source-Id", source_id.to_string()))
+                        .body(bytes);
+                }
+                Err(e) => error!("Failed to download chapter from source {}: {}", source_id, e),
+            }
+        } else {
+            // Save to disk
+            let name = naming::ChapterName::new(&manga.title, source_id, &label, &chapter.url)
+                .with_release(chapter.scanlation_group.as_deref(), chapter.language.as_deref(), chapter.published_at);
+            let template = &data.config.chapter_template;
+            if let Some(cu) = &manga.cover_url {
+                let _ = scraper::ensure_cover_downloaded(
+                    &data.client,
+                    &data.config.download_dir,
+                    &template.series_dir(&name),
+                    cu,
+                    source_id,
+                )
+                .await;
+            }
+            let file_path = template.render(&name, format);
+            let chapter_download = scraper::ChapterDownload {
+                source_id,
+                chapter_url: &chapter.url,
+                file_path: &file_path,
+                comicinfo: Some(&comicinfo),
+                format,
+            };
+            match scraper::download_chapter(&data.client, &data.page_fetcher, &data.config.download_dir, chapter_download).await {
+                Ok(file_path) => {
+                    library::record_download(&data, chapter.id, &file_path).await;
+                    return HttpResponse::Ok().json(serde_json::json!({"message": "Downloaded successfully", "file": file_path, "source_id": source_id}));
+                }
+                Err(e) => error!("Failed to download chapter from source {}: {}", source_id, e),
+            }
+        }
+    }
+    HttpResponse::InternalServerError().json(serde_json::json!({"error": "Download failed"}))
+}
+
+#[get("/download/byurl")]
+async fn download_by_url(
+    data: web::Data<AppState>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    let manga_id = match query.get("manga_id") {
+        Some(v) => v,
+        None => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error":"manga_id is required"}))
+        }
+    };
+    let url = match query.get("url") {
+        Some(v) => v,
+        None => {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error":"url is required"}))
+        }
+    };
+    let stream = query.get("stream").map(|s| s == "true").unwrap_or(false);
+    let req_source_id = query
+        .get("source_id")
+        .and_then(|s| s.parse::<i32>().ok())
+        .or_else(|| guess_source_id_from_url(url));
+
+    let source_data_list = match data.storage.get_manga_source_data_by_manga_id(manga_id).await {
+        Ok(list) => list,
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error":"Database error"}))
+        }
+    };
+    let (chosen_source_id, _has_msd) = if let Some(sid) = req_source_id {
+        // Prefer requested/guessed source id, even if no msd exists for this manga
+        if source_data_list.iter().any(|s| s.source_id == sid) {
+            (sid, true)
+        } else {
+            (sid, false)
+        }
+    } else if source_data_list.len() == 1 {
+        (source_data_list[0].source_id, true)
+    } else {
+        (0, false)
+    };
+    if chosen_source_id == 0 {
+        return HttpResponse::BadRequest().json(
+            serde_json::json!({"error":"source could not be determined; specify source_id"}),
+        );
+    }
+
+    let format = match output_format_for(&data, manga_id, &query, stream).await {
+        Ok(f) => f,
+        Err(resp) => return resp,
+    };
+
+    if stream {
+        // Metadata is best-effort here; the manga row is not required to stream.
+        let comicinfo = match data.storage.get_manga_by_id(manga_id).await {
+            Ok(Some(manga)) => Some(ComicInfo::for_chapter(&manga, chosen_source_id, "byurl", url)),
+            _ => None,
+        };
+        match scraper::download_chapter_to_memory(
+            &data.client,
+            &data.page_fetcher,
+            chosen_source_id,
+            url,
+            comicinfo.as_ref(),
+            format,
+        )
+        .await
+        {
+            Ok(bytes) => HttpResponse::Ok()
+                .content_type(format.content_type())
+                .insert_header((
+                    "Content-Disposition",
+                    format!(
+                        "attachment; filename=\"{} - byurl.{}\"",
+                        manga_id,
+                        format.as_str()
+                    ),
+                ))
+                .body(bytes),
+            Err(e) => {
+                error!("Failed to download chapter by url: {}", e);
+                HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error":"Download failed"}))
+            }
+        }
+    } else {
+        let manga = match data.storage.get_manga_by_id(manga_id).await {
+            Ok(Some(m)) => m,
+            _ => return HttpResponse::InternalServerError().finish(),
+        };
+        let name = naming::ChapterName::new(&manga.title, chosen_source_id, "byurl", url);
+        let template = &data.config.chapter_template;
+        if let Some(cu) = &manga.cover_url {
+            let _ = scraper::ensure_cover_downloaded(
+                &data.client,
+                &data.config.download_dir,
+                &template.series_dir(&name),
+                cu,
+                chosen_source_id,
+            )
+            .await;
+        }
+        let comicinfo = ComicInfo::for_chapter(&manga, chosen_source_id, "byurl", url);
+        let file_path = template.render(&name, format);
+        let chapter_download = scraper::ChapterDownload {
+            source_id: chosen_source_id,
+            chapter_url: url,
+            file_path: &file_path,
+            comicinfo: Some(&comicinfo),
+            format,
+        };
+        match scraper::download_chapter(&data.client, &data.page_fetcher, &data.config.download_dir, chapter_download)
+            .await
+        {
+            Ok(file_path) => HttpResponse::Ok()
+                .json(serde_json::json!({"message":"Downloaded successfully","file": file_path})),
+            Err(e) => {
+                error!("Failed to download chapter by url: {}", e);
+                HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error":"Download failed"}))
+            }
+        }
+    }
+}
+
+#[get("/sources")]
+async fn get_sources(data: web::Data<AppState>) -> impl Responder {
+    match data.storage.get_per_source_counts().await {
+        Ok(sources) => {
+            let sources_with_chapters: Vec<_> =
+                sources.into_iter().filter(|s| s.chapters > 0).collect();
+            HttpResponse::Ok().json(sources_with_chapters)
+        }
+        Err(e) => {
+            error!("Failed to get sources: {}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve sources"}))
+        }
+    }
+}
+
+/// Every tag with how many manga have it; `?category=genre|theme|format|content_warning`
+/// narrows the list. Tag IDs can be passed to `GET /manga?tags=`.
+#[get("/tags")]
+async fn list_tags(
+    data: web::Data<AppState>,
+    query: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let category = query.get("category").map(|c| c.trim()).filter(|c| !c.is_empty());
+    if let Some(c) = category {
+        if crate::tags::TagCategory::parse(c).is_none() {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": format!("unknown tag category '{}'", c)}));
+        }
+    }
+    match data.storage.list_tags(category).await {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(e) => {
+            error!("Failed to list tags: {}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve tags"}))
+        }
+    }
+}
+
+/// A creator and the manga they are credited on
+#[get("/people/{id}")]
+async fn get_person(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    let id = id.into_inner();
+    match data.storage.get_person(id).await {
+        Ok(Some(person)) => HttpResponse::Ok().json(person),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Person not found"})),
+        Err(e) => {
+            error!("Failed to get person {}: {}", id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve person"}))
+        }
+    }
+}
+
+/// The user a `/me` call is for, or the response to send instead: the owner of
+/// the caller's API key. Only admin keys may act for another user by naming
+/// them in the `X-User` header.
+async fn current_user(req: &HttpRequest, data: &AppState) -> Result<crate::models::User, HttpResponse> {
+    let caller = req.extensions().get::<auth::Caller>().cloned();
+    let key_user = caller.as_ref().and_then(|c| c.key.as_ref()).and_then(|k| k.user_id);
+    if let Some(user_id) = key_user {
+        return match data.storage.get_user(user_id).await {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(HttpResponse::Unauthorized().json(serde_json::json!({"error": "unknown user"}))),
+            Err(e) => {
+                error!("Database error looking up user {}: {}", user_id, e);
+                Err(HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"})))
+            }
+        };
+    }
+    let name = req
+        .headers()
+        .get(reading::USER_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|n| !n.is_empty());
+    let is_admin = caller.as_ref().is_some_and(|c| c.key.is_some() && c.allows(auth::Scope::Admin));
+    let name = match name {
+        Some(name) if is_admin => name,
+        Some(_) => {
+            return Err(HttpResponse::Forbidden().json(serde_json::json!({
+                "error": format!("only admin keys may use the {} header", reading::USER_HEADER)
+            })))
+        }
+        None if is_admin => {
+            return Err(HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": format!("{} header required", reading::USER_HEADER)})))
+        }
+        None => {
+            return Err(HttpResponse::Unauthorized()
+                .json(serde_json::json!({"error": "an API key belonging to a user is required"})))
+        }
+    };
+    match data.storage.get_user_by_name(name).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(HttpResponse::Unauthorized()
+            .json(serde_json::json!({"error": format!("unknown user '{}'", name)}))),
+        Err(e) => {
+            error!("Database error looking up user {}: {}", name, e);
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"})))
+        }
+    }
+}
+
+/// Add a reader: `{"name": "..."}`. Give them a key created with `"user": "<name>"`
+/// to use the `/me` endpoints.
+#[post("/users")]
+async fn create_user(
+    data: web::Data<AppState>,
+    body: web::Json<crate::models::CreateUserRequest>,
+) -> impl Responder {
+    let Some(name) = reading::user_name(&body.name) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("name must be 1-{} characters without control characters", reading::MAX_NAME_LEN)
+        }));
+    };
+    match data.storage.create_user(name, chrono::Utc::now().timestamp()).await {
+        Ok(Some(user)) => HttpResponse::Created().json(user),
+        Ok(None) => HttpResponse::Conflict().json(serde_json::json!({"error": "user name taken"})),
+        Err(e) => {
+            error!("Failed to create user {}: {}", name, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[get("/users")]
+async fn list_users(data: web::Data<AppState>) -> impl Responder {
+    match data.storage.list_users().await {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(e) => {
+            error!("Failed to list users: {}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve users"}))
+        }
+    }
+}
+
+/// The caller's library, most recently active first; `?status=` narrows it
+#[get("/me/library")]
+async fn get_library(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
+    };
+    let status = match query.get("status").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        Some(s) => match reading::LibraryStatus::parse(s) {
+            Some(status) => Some(status),
+            None => {
+                return HttpResponse::BadRequest()
+                    .json(serde_json::json!({"error": format!("unknown library status '{}'", s)}))
+            }
+        },
+        None => None,
+    };
+    match data.storage.get_library(user.id, status).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            error!("Failed to get library of user {}: {}", user.id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve library"}))
+        }
+    }
+}
+
+/// Add a manga to the caller's library or change its status: `{"status": "on_hold"}`
+#[put("/me/library/{manga_id}")]
+async fn set_library_entry(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    manga_id: web::Path<String>,
+    body: web::Json<crate::models::LibraryEntryRequest>,
+) -> impl Responder {
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
+    };
+    match data.storage.set_library_entry(user.id, &manga_id, body.status, chrono::Utc::now().timestamp()).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({"error": "Manga not found"})),
+        Err(e) => {
+            error!("Failed to update library of user {}: {}", user.id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
+
+#[delete("/me/library/{manga_id}")]
+async fn remove_library_entry(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    manga_id: web::Path<String>,
+) -> impl Responder {
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
+    };
+    match data.storage.remove_library_entry(user.id, &manga_id).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "manga is not in your library"})),
+        Err(e) => {
+            error!("Failed to update library of user {}: {}", user.id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
 }
 
-#[get("/import/source/{source}/manga")]
-async fn import_source_manga_only(
+/// The caller's read state for each chapter of a manga they opened
+#[get("/me/manga/{manga_id}/reads")]
+async fn get_chapter_reads(
+    req: HttpRequest,
     data: web::Data<AppState>,
-    source: web::Path<String>,
+    manga_id: web::Path<String>,
 ) -> impl Responder {
-    let src_opt = parse_source(&source);
-    let wp_opt = if src_opt.is_none() {
-        wp_manga_source_by_name(&source.to_lowercase())
-    } else {
-        None
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
     };
-    if src_opt.is_none() && wp_opt.is_none() {
-        return HttpResponse::BadRequest().json(serde_json::json!({"error":"unknown source"}));
+    match data.storage.get_chapter_reads(user.id, &manga_id).await {
+        Ok(reads) => HttpResponse::Ok().json(reads),
+        Err(e) => {
+            error!("Failed to get reads of user {} for {}: {}", user.id, manga_id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve read progress"}))
+        }
     }
-    let client = &data.client;
-
-    let mut manga_map: HashMap<String, Manga> = HashMap::new();
-    let mut manga_source_data_map: HashMap<String, Vec<MangaSourceData>> = HashMap::new();
+}
 
-    let add_entry = |manga_map: &mut HashMap<String, Manga>,
-                     msd_map: &mut HashMap<String, Vec<MangaSourceData>>,
-                     m: Manga,
-                     source_id: i32,
-                     series_url_or_id: String| {
-        let normalized_title = normalize_title(&m.title);
-        let current_manga = manga_map
-            .entry(normalized_title.clone())
-            .or_insert_with(|| {
-                let mut new_manga = m.clone();
-                new_manga.id = Uuid::new_v4().to_string();
-                new_manga
-            });
-        if current_manga.cover_url.is_none() && m.cover_url.is_some() {
-            current_manga.cover_url = m.cover_url.clone();
-        }
-        let msd = MangaSourceData {
-            manga_id: current_manga.id.clone(),
-            source_id,
-            source_manga_id: series_url_or_id.clone(),
-            source_manga_url: series_url_or_id,
-        };
-        msd_map.entry(normalized_title).or_default().push(msd);
-    };
-
-    let scrape_err = |name: &str, e: &dyn std::fmt::Display| {
-        error!("{} import error: {}", name, e);
-        HttpResponse::InternalServerError().finish()
-    };
-
-    let res: Result<(), HttpResponse> = if let Some(src) = src_opt {
-        match src {
-            Source::MangaDex => {
-                match mangadex::search_manga(client, "", mangadex::BASE_URL).await {
-                    Ok(list) => {
-                        for m in list {
-                            add_entry(
-                                &mut manga_map,
-                                &mut manga_source_data_map,
-                                m,
-                                Source::MangaDex as i32,
-                                String::new(),
-                            );
-                        }
-                        Ok(())
-                    }
-                    Err(e) => return scrape_err("MangaDex", &e),
-                }
-            }
-            Source::FireScans => match firescans::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::FireScans as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("FireScans", &e),
-            },
-            Source::RizzComic => match rizzcomic::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::RizzComic as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("RizzComic", &e),
-            },
-            Source::DrakeComic => match drakecomic::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::DrakeComic as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("DrakeComic", &e),
-            },
-            Source::Asmotoon => match asmotoon::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::Asmotoon as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("Asmotoon", &e),
-            },
-            Source::ResetScans => match reset_scans::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::ResetScans as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("ResetScans", &e),
-            },
-            Source::Kagane => match kagane::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::Kagane as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("Kagane", &e),
-            },
-            Source::TempleScan => match temple_scan::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::TempleScan as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("TempleScan", &e),
-            },
-            Source::ThunderScans => match thunderscans::search_manga_with_urls(client, "").await {
-                Ok(items) => {
-                    for (m, u) in items {
-                        add_entry(
-                            &mut manga_map,
-                            &mut manga_source_data_map,
-                            m,
-                            Source::ThunderScans as i32,
-                            u,
-                        );
-                    }
-                    Ok(())
-                }
-                Err(e) => return scrape_err("ThunderScans", &e),
-            },
-            Source::KDTNovels | Source::MyAnimeList | Source::AniList => {
+/// Where to pick up each manga the caller is reading, most recent first
+#[get("/me/continue")]
+async fn continue_reading(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
+    };
+    let limit = match query.get("limit") {
+        None => 20,
+        Some(s) => match s.parse::<i64>() {
+            Ok(l) if (1..=100).contains(&l) => l,
+            _ => {
                 return HttpResponse::BadRequest()
-                    .json(serde_json::json!({"error":"metadata-only source not supported here"}))
+                    .json(serde_json::json!({"error": "limit must be between 1 and 100"}))
             }
+        },
+    };
+    match data.storage.get_continue_reading(user.id, limit).await {
+        Ok(items) => HttpResponse::Ok().json(items),
+        Err(e) => {
+            error!("Failed to get continue reading for user {}: {}", user.id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve continue reading"}))
         }
+    }
+}
+
+/// Mark a chapter read for the caller. `{"page": 12, "completed": false}` saves
+/// a position instead; an empty body marks it finished.
+#[post("/chapters/{id}/read")]
+async fn mark_chapter_read(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    id: web::Path<i32>,
+    body: web::Bytes,
+) -> impl Responder {
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
+    };
+    // Only a missing body means "finished"; malformed JSON is rejected rather
+    // than read as an empty request.
+    let body: crate::models::ReadRequest = if body.iter().all(u8::is_ascii_whitespace) {
+        Default::default()
     } else {
-        let (sid, base) = wp_opt.unwrap();
-        let s = source.to_lowercase();
-        let res = match s.as_str() {
-            "asurascans" => crate::sources::asurascans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (11, items)),
-            "kenscans" => crate::sources::kenscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (25, items)),
-            "sirenscans" | "siren-scans" => {
-                crate::sources::sirenscans::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (43, items))
-            }
-            "vortexscans" | "vortex-scans" => {
-                crate::sources::vortexscans::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (56, items))
-            }
-            "witchscans" | "witch-scans" => {
-                crate::sources::witchscans::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (59, items))
-            }
-            "qiscans" | "qi-scans" => crate::sources::qiscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (38, items)),
-            "madarascans" => crate::sources::madarascans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (30, items)),
-            "rizzfables" => crate::sources::rizzfables::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (39, items)),
-            "rokaricomics" | "rokari-comics" => {
-                crate::sources::rokaricomics::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (40, items))
-            }
-            "stonescape" => crate::sources::stonescape::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (45, items)),
-            "manhuaus" => crate::sources::manhuaus::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (31, items)),
-            "grimscans" => crate::sources::grimscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (19, items)),
-            "hivetoons" => crate::sources::hivetoons::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (20, items)),
-            "nyxscans" => crate::sources::nyxscans::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (34, items)),
-            // Free scanlation sites
-            "flamecomics" | "flame-comics" => {
-                crate::sources::flamecomics::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (18, items))
-            }
-            "daycomics" | "day-comics" => {
-                crate::sources::daycomics::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (16, items))
-            }
-            "kodokustudio" | "kodoku-studio" => {
-                crate::sources::kodoku_studio::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (27, items))
-            }
-            "lunatoons" | "luna-toons" => {
-                crate::sources::lunatoons::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (29, items))
-            }
-            "vastvisual" | "vast-visual" => {
-                crate::sources::vast_visual::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (54, items))
-            }
-            "mavintranslations" | "mavin-translations" => {
-                crate::sources::mavintranslations::search_manga_with_urls(client, "")
-                    .await
-                    .map(|items| (61, items))
-            }
-            // Free web platforms
-            "tapas" => crate::sources::tapas::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (47, items)),
-            "webtoon" | "webtoons" => crate::sources::webtoon::search_manga_with_urls(client, "")
-                .await
-                .map(|items| (58, items)),
-            _ => crate::sources::wp_manga::search_manga_with_urls_base(client, base)
-                .await
-                .map(|items| (sid, items)),
-        };
-        match res {
-            Ok((resolved_sid, items)) => {
-                for (m, u) in items {
-                    add_entry(
-                        &mut manga_map,
-                        &mut manga_source_data_map,
-                        m,
-                        resolved_sid,
-                        u,
-                    );
-                }
-                Ok(())
+        match serde_json::from_slice(&body) {
+            Ok(b) => b,
+            Err(e) => {
+                return HttpResponse::BadRequest()
+                    .json(serde_json::json!({"error": format!("invalid body: {}", e)}))
             }
-            Err(e) => return scrape_err(&s, &e),
         }
     };
-    if res.is_err() {
-        return res.unwrap_err();
+    let page = body.page.unwrap_or(0);
+    if page < 0 {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "page must not be negative"}));
     }
-
-    for (key, m) in manga_map.iter() {
-        let _ = pg_db::insert_manga(&data.pool, m).await;
-        if let Some(msds) = manga_source_data_map.get(key) {
-            for msd in msds {
-                let _ = pg_db::insert_manga_source_data(&data.pool, msd).await;
-            }
+    let completed = body.completed.unwrap_or(true);
+    match data.storage.set_chapter_read(user.id, *id, page, completed, chrono::Utc::now().timestamp()).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({"error": "Chapter not found"})),
+        Err(e) => {
+            error!("Failed to mark chapter {} read for user {}: {}", id, user.id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
         }
     }
-
-    HttpResponse::Ok().json(
-        serde_json::json!({"source": source.to_string(), "manga": manga_map.len(), "chapters": 0}),
-    )
 }
-#[get("/manga")]
-async fn list_manga(
+
+/// Mark a chapter unread for the caller
+#[delete("/chapters/{id}/read")]
+async fn mark_chapter_unread(
+    req: HttpRequest,
     data: web::Data<AppState>,
-    query: web::Query<std::collections::HashMap<String, String>>,
+    id: web::Path<i32>,
 ) -> impl Responder {
-    // Parse pagination parameters
-    let limit = query
-        .get("limit")
-        .and_then(|l| l.parse::<i32>().ok())
-        .unwrap_or(20);
-    let offset = query
-        .get("offset")
-        .and_then(|o| o.parse::<i32>().ok())
-        .unwrap_or(0);
-    let sort_by = query.get("sort").map(|s| s.as_str()).unwrap_or("title");
-    let rating = query.get("rating").map(|r| r.as_str());
-
-    let (manga_list, total) = if let Some(search) = query.get("search") {
-        let tags = query.get("tags").map(|t| t.as_str());
-        match pg_db::search_manga_paginated(
-            &data.pool,
-            search,
-            tags,
-            rating,
-            Some(limit),
-            Some(offset),
-            sort_by,
-        ).await {
-            Ok(list) => {
-                let total = match pg_db::get_manga_count(&data.pool).await {
-                    Ok(c) => c,
-                    Err(e) => {
-                        error!("Failed to get manga count: {}", e);
-                        return HttpResponse::InternalServerError()
-                            .json(serde_json::json!({"error": "Internal server error"}));
-                    }
-                };
-                (list, total)
-            }
-            Err(e) => {
-                error!("Failed to search manga: {}", e);
-                return HttpResponse::InternalServerError()
-                    .json(serde_json::json!({"error": "Internal server error"}));
-            }
+    let user = match current_user(&req, &data).await {
+        Ok(user) => user,
+        Err(resp) => return resp,
+    };
+    match data.storage.clear_chapter_read(user.id, *id).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "chapter is not marked read"})),
+        Err(e) => {
+            error!("Failed to mark chapter {} unread for user {}: {}", id, user.id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
         }
-    } else {
-        match pg_db::get_manga_paginated(&data.pool, Some(limit), Some(offset), sort_by, rating).await {
-            Ok(list) => {
-                let total = match pg_db::get_manga_count(&data.pool).await {
-                    Ok(c) => c,
-                    Err(e) => {
-                        error!("Failed to get manga count: {}", e);
-                        return HttpResponse::InternalServerError()
-                            .json(serde_json::json!({"error": "Internal server error"}));
-                    }
-                };
-                (list, total)
+    }
+}
+
+/// Create an API key: `{"name": "tablet", "scopes": ["read"], "user": "alice"}`.
+/// The response is the only time the key itself is shown.
+#[post("/auth/keys")]
+async fn create_api_key(
+    data: web::Data<AppState>,
+    body: web::Json<crate::models::CreateApiKeyRequest>,
+) -> impl Responder {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "name is required"}));
+    }
+    if body.scopes.is_empty() {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "at least one scope is required"}));
+    }
+    let user_id = match body.user.as_deref().map(str::trim) {
+        Some(user) => match data.storage.get_user_by_name(user).await {
+            Ok(Some(user)) => Some(user.id),
+            Ok(None) => {
+                return HttpResponse::NotFound()
+                    .json(serde_json::json!({"error": format!("unknown user '{}'", user)}))
             }
             Err(e) => {
-                error!("Failed to fetch manga list: {}", e);
+                error!("Database error looking up user {}: {}", user, e);
                 return HttpResponse::InternalServerError()
-                    .json(serde_json::json!({"error": "Internal server error"}));
+                    .json(serde_json::json!({"error": "Database error"}));
             }
-        }
-    };
-
-    let response = PaginatedResponse {
-        data: manga_list,
-        pagination: PaginationInfo {
-            total: total as i32,
-            limit,
-            offset,
-            has_more: offset + limit < (total as i32),
         },
+        None => None,
     };
+    let key = auth::generate_key();
+    match data
+        .storage
+        .create_api_key(name, &auth::hash_key(&key), &auth::key_prefix(&key), &body.scopes, user_id, chrono::Utc::now().timestamp())
+        .await
+    {
+        Ok(api_key) => HttpResponse::Created().json(serde_json::json!({"key": key, "api_key": api_key})),
+        Err(e) => {
+            error!("Failed to create API key {}: {}", name, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
+        }
+    }
+}
 
-    HttpResponse::Ok().json(response)
+#[get("/auth/keys")]
+async fn list_api_keys(data: web::Data<AppState>) -> impl Responder {
+    match data.storage.list_api_keys().await {
+        Ok(keys) => HttpResponse::Ok().json(keys),
+        Err(e) => {
+            error!("Failed to list API keys: {}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve API keys"}))
+        }
+    }
 }
 
-#[get("/manga/{id}")]
-async fn get_manga(data: web::Data<AppState>, id: web::Path<String>) -> impl Responder {
-    let manga = match pg_db::get_manga_by_id(&data.pool, &id).await {
-        Ok(Some(m)) => m,
-        Ok(None) => {
-            return HttpResponse::NotFound().json(serde_json::json!({"error": "Manga not found"}))
+#[delete("/auth/keys/{id}")]
+async fn revoke_api_key(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    match data.storage.revoke_api_key(*id, chrono::Utc::now().timestamp()).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "no active API key with that id"})),
+        Err(e) => {
+            error!("Failed to revoke API key {}: {}", id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Database error"}))
         }
+    }
+}
+
+/// The caller's API key (none when anonymous) and the scopes it grants
+#[get("/auth/whoami")]
+async fn whoami(req: HttpRequest) -> impl Responder {
+    let extensions = req.extensions();
+    let caller = extensions.get::<auth::Caller>();
+    HttpResponse::Ok().json(serde_json::json!({
+        "key": caller.and_then(|c| c.key.as_ref()),
+        "scopes": caller.map(|c| c.scopes.clone()).unwrap_or_default(),
+    }))
+}
+
+fn opds_response(feed: &opds::Feed) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(feed.kind.content_type())
+        .body(feed.to_xml())
+}
+
+/// OPDS catalog root: latest updates, by source, by tag; search is linked
+#[get("/opds")]
+async fn opds_root() -> impl Responder {
+    use opds::{navigation_entry, FeedKind};
+    let now = chrono::Utc::now().timestamp();
+    let mut feed = opds::Feed::new(FeedKind::Navigation, "root", "Tsubaki", "/opds", now);
+    feed.entries = vec![
+        navigation_entry("latest", "Latest updates", Some("Manga with new chapters first".to_string()), "/opds/latest", FeedKind::Navigation, now),
+        navigation_entry("sources", "By source", None, "/opds/sources", FeedKind::Navigation, now),
+        navigation_entry("tags", "By tag", None, "/opds/tags", FeedKind::Navigation, now),
+    ];
+    opds_response(&feed)
+}
+
+#[get("/opds/search.xml")]
+async fn opds_search_description() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(opds::OPENSEARCH_TYPE)
+        .body(opds::opensearch_description())
+}
+
+/// One page of a manga list as an OPDS feed, each series leading to its chapters
+async fn opds_manga_feed(
+    data: &AppState,
+    mut search: MangaSearch,
+    mut feed: opds::Feed,
+    href: &str,
+    page: i64,
+) -> HttpResponse {
+    search.limit = opds::PAGE_SIZE;
+    search.offset = (page - 1) * opds::PAGE_SIZE;
+    let manga_list = match data.storage.search_manga(&search).await {
+        Ok(list) => list,
         Err(e) => {
-            error!("Database error fetching manga: {}", e);
+            error!("Failed to search manga: {}", e);
             return HttpResponse::InternalServerError()
                 .json(serde_json::json!({"error": "Internal server error"}));
         }
     };
-
-    // Get source data
-    let source_data_list = match pg_db::get_manga_source_data_by_manga_id(&data.pool, &id).await {
-        Ok(list) => list,
+    let total = match data.storage.count_manga_matching(&search).await {
+        Ok(c) => c,
         Err(e) => {
-            error!("Database error fetching source data: {}", e);
+            error!("Failed to get manga count: {}", e);
             return HttpResponse::InternalServerError()
                 .json(serde_json::json!({"error": "Internal server error"}));
         }
     };
+    feed.paginate(href, page, search.offset + search.limit < total);
+    feed.entries = manga_list.iter().map(|m| opds::manga_entry(m, feed.updated)).collect();
+    opds_response(&feed)
+}
 
-    // Build sources with names
-    let mut sources = Vec::new();
-    for source_data in source_data_list {
-        let source_name = match pg_db::get_source_name(&data.pool, source_data.source_id).await {
-            Ok(name) => name,
-            Err(_) => format!("Source {}", source_data.source_id),
-        };
-        sources.push(SourceInfo {
-            source_id: source_data.source_id,
-            source_name,
-            source_manga_id: source_data.source_manga_id,
-            source_manga_url: source_data.source_manga_url,
-        });
-    }
-
-    let manga_with_sources = MangaWithSources {
-        id: manga.id,
-        title: manga.title,
-        alt_titles: manga.alt_titles,
-        cover_url: manga.cover_url,
-        description: manga.description,
-        tags: manga.tags,
-        rating: manga.rating,
-        sources,
-    };
-
-    HttpResponse::Ok().json(manga_with_sources)
+/// Search results for `?q=`, as OpenSearch clients send it
+#[get("/opds/search")]
+async fn opds_search(
+    data: web::Data<AppState>,
+    query: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let q = query.get("q").map(|q| q.trim()).unwrap_or_default();
+    let href = format!("/opds/search?q={}", urlencoding::encode(q));
+    let feed = opds::Feed::new(
+        opds::FeedKind::Navigation,
+        "search",
+        &format!("Search: {}", q),
+        &href,
+        chrono::Utc::now().timestamp(),
+    );
+    let search = MangaSearch { query: Some(q.to_string()), ..Default::default() };
+    opds_manga_feed(&data, search, feed, &href, opds::page_param(&query)).await
 }
 
-#[post("/manga/{id}/monitor")]
-async fn monitor_manga(
+#[get("/opds/latest")]
+async fn opds_latest(
     data: web::Data<AppState>,
-    id: web::Path<String>,
-    body: web::Json<crate::models::MonitorRequest>,
+    query: web::Query<HashMap<String, String>>,
 ) -> impl Responder {
-    let _ = pg_db::set_manga_monitoring(
-        &data.pool,
-        &id,
-        body.monitored,
-        body.check_interval_secs,
-        body.discover_interval_secs,
-    ).await;
-    HttpResponse::Ok().finish()
+    let now = chrono::Utc::now().timestamp();
+    let feed = opds::Feed::new(opds::FeedKind::Navigation, "latest", "Latest updates", "/opds/latest", now);
+    let search = MangaSearch { sort: search::SearchSort::Updated, ..Default::default() };
+    opds_manga_feed(&data, search, feed, "/opds/latest", opds::page_param(&query)).await
 }
 
-#[get("/manga/{id}/chapters")]
-async fn get_chapters(data: web::Data<AppState>, id: web::Path<String>) -> impl Responder {
-    let manga_source_data_list = match pg_db::get_manga_source_data_by_manga_id(&data.pool, &id).await {
-        Ok(list) => list,
+#[get("/opds/sources")]
+async fn opds_sources(data: web::Data<AppState>) -> impl Responder {
+    use opds::FeedKind;
+    let sources = match data.storage.get_per_source_counts().await {
+        Ok(sources) => sources,
         Err(e) => {
-            error!("Database error fetching source data: {}", e);
+            error!("Failed to get sources: {}", e);
             return HttpResponse::InternalServerError()
-                .json(serde_json::json!({"error": "Internal server error"}));
+                .json(serde_json::json!({"error": "Failed to retrieve sources"}));
         }
     };
+    let now = chrono::Utc::now().timestamp();
+    let mut feed = opds::Feed::new(FeedKind::Navigation, "sources", "By source", "/opds/sources", now);
+    feed.entries = sources
+        .iter()
+        .filter(|s| s.manga > 0)
+        .map(|s| opds::navigation_entry(
+            &format!("source:{}", s.source_id),
+            &s.source_name,
+            Some(format!("{} manga", s.manga)),
+            &format!("/opds/sources/{}", s.source_id),
+            FeedKind::Navigation,
+            now,
+        ))
+        .collect();
+    opds_response(&feed)
+}
 
-    if manga_source_data_list.is_empty() {
-        return HttpResponse::NotFound()
-            .json(serde_json::json!({"error": "Manga not found or has no sources"}));
-    }
-
-    let mut all_chapters = Vec::new();
-    for manga_source_data in manga_source_data_list {
-        let source_name = match pg_db::get_source_name(&data.pool, manga_source_data.source_id).await {
-            Ok(name) => name,
-            Err(_) => format!("Source {}", manga_source_data.source_id),
-        };
+#[get("/opds/sources/{source_id}")]
+async fn opds_source(
+    data: web::Data<AppState>,
+    source_id: web::Path<i32>,
+    query: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let source_id = source_id.into_inner();
+    let Some(source) = registry::get(source_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Source not found"}));
+    };
+    let href = format!("/opds/sources/{}", source_id);
+    let feed = opds::Feed::new(
+        opds::FeedKind::Navigation,
+        &format!("source:{}", source_id),
+        source.name(),
+        &href,
+        chrono::Utc::now().timestamp(),
+    );
+    let search = MangaSearch { source_id: Some(source_id), ..Default::default() };
+    opds_manga_feed(&data, search, feed, &href, opds::page_param(&query)).await
+}
 
-        match pg_db::get_chapters_by_manga_source_data_id(
-            &data.pool,
-            &manga_source_data.manga_id,
-            manga_source_data.source_id,
-        ).await {
-            Ok(chapters) => {
-                for chapter in chapters {
-                    all_chapters.push(ChapterWithSource {
-                        id: chapter.id,
-                        chapter_number: chapter.chapter_number,
-                        url: chapter.url,
-                        scraped: chapter.scraped,
-                        source_id: manga_source_data.source_id,
-                        source_name: source_name.clone(),
-                    });
-                }
-            }
-            Err(e) => {
-                error!("Database error fetching chapters: {}", e);
-                // Continue to next source instead of failing completely
-            }
+#[get("/opds/tags")]
+async fn opds_tags(data: web::Data<AppState>) -> impl Responder {
+    use opds::FeedKind;
+    let tags = match data.storage.list_tags(None).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            error!("Failed to list tags: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to retrieve tags"}));
         }
-    }
-    HttpResponse::Ok().json(all_chapters)
+    };
+    let now = chrono::Utc::now().timestamp();
+    let mut feed = opds::Feed::new(FeedKind::Navigation, "tags", "By tag", "/opds/tags", now);
+    feed.entries = tags
+        .iter()
+        .filter(|t| t.manga_count.unwrap_or(0) > 0)
+        .map(|t| opds::navigation_entry(
+            &format!("tag:{}", t.id),
+            &t.name,
+            Some(format!("{} manga", t.manga_count.unwrap_or(0))),
+            &format!("/opds/tags/{}", t.id),
+            FeedKind::Navigation,
+            now,
+        ))
+        .collect();
+    opds_response(&feed)
 }
-
-#[get("/download/{manga_id}/{chapter_number}")]
-async fn download(
+
+#[get("/opds/tags/{tag_id}")]
+async fn opds_tag(
     data: web::Data<AppState>,
-    path: web::Path<(String, String)>,
-    query: web::Query<std::collections::HashMap<String, String>>,
+    tag_id: web::Path<i32>,
+    query: web::Query<HashMap<String, String>>,
 ) -> impl Responder {
-    let (manga_id, chapter_number) = path.into_inner();
-    let stream = query.get("stream").map(|s| s == "true").unwrap_or(false);
-
-    let manga_source_data_list = match pg_db::get_manga_source_data_by_manga_id(&data.pool, &manga_id).await {
-        Ok(list) => list,
-        Err(_) => {
+    let tag_id = tag_id.into_inner();
+    let tag = match data.storage.list_tags(None).await {
+        Ok(tags) => tags.into_iter().find(|t| t.id == tag_id),
+        Err(e) => {
+            error!("Failed to list tags: {}", e);
             return HttpResponse::InternalServerError()
-                .json(serde_json::json!({"error": "Database error"}))
+                .json(serde_json::json!({"error": "Failed to retrieve tags"}));
         }
     };
+    let Some(tag) = tag else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Tag not found"}));
+    };
+    let href = format!("/opds/tags/{}", tag_id);
+    let feed = opds::Feed::new(
+        opds::FeedKind::Navigation,
+        &format!("tag:{}", tag_id),
+        &tag.name,
+        &href,
+        chrono::Utc::now().timestamp(),
+    );
+    let search = MangaSearch { include_tags: vec![search::TagFilter::Id(tag_id)], ..Default::default() };
+    opds_manga_feed(&data, search, feed, &href, opds::page_param(&query)).await
+}
 
-    if manga_source_data_list.len() == 1 {
-        let source_data = &manga_source_data_list[0];
-        let chapters = match pg_db::get_chapters_by_manga_source_data_id(
-            &data.pool,
-            &source_data.manga_id,
-            source_data.source_id,
-        ).await {
-            Ok(c) => c,
-            Err(_) => {
-                return HttpResponse::InternalServerError()
-                    .json(serde_json::json!({"error": "Database error"}))
-            }
-        };
-        let chapter = find_best_chapter_match(&chapters, &chapter_number);
-
-        if let Some(chapter) = chapter {
-            if stream {
-                // Stream file directly
-                match scraper::download_chapter_to_memory(
-                    &data.client,
-                    source_data.source_id,
-                    &chapter.url,
-                )
-                .await
-                {
-                    Ok(bytes) => {
-                        let manga = match pg_db::get_manga_by_id(&data.pool, &manga_id).await {
-                            Ok(Some(m)) => m,
-                            _ => return HttpResponse::InternalServerError().finish(),
-                        };
-                        let filename = format!("{} - {}.cbz", manga.title, chapter.chapter_number);
-                        return HttpResponse::Ok()
-                            .content_type("application/x-cbz")
-                            .insert_header((
-                                "Content-Disposition",
-                                format!("attachment; filename=\"{}\"", filename),
-                            ))
-                            .body(bytes);
-                    }
-                    Err(e) => {
-                        error!("Failed to download chapter: {}", e);
-                        return HttpResponse::InternalServerError()
-                            .json(serde_json::json!({"error": "Download failed"}));
-                    }
-                }
-            } else {
-                // Save to disk
-                let manga = match pg_db::get_manga_by_id(&data.pool, &manga_id).await {
-                    Ok(Some(m)) => m,
-                    _ => return HttpResponse::InternalServerError().finish(),
-                };
-                if let Some(cu) = &manga.cover_url {
-                    let _ = scraper::ensure_cover_downloaded(
-                        &data.client,
-                        &data.config.download_dir,
-                        &manga.title,
-                        cu,
-                        source_data.source_id,
-                    )
-                    .await;
-                }
-                let comicinfo = build_comicinfo(
-                    &manga.title,
-                    &chapter.chapter_number,
-                    manga.description.as_deref(),
-                    manga.tags.as_deref(),
-                );
-                match scraper::download_chapter(&data.client, source_data.source_id, &chapter.url, &manga.title, &chapter.chapter_number, &data.config.download_dir, comicinfo.as_deref()).await {
-                    Ok(file_path) => return HttpResponse::Ok().json(serde_json::json!({"message": "Downloaded successfully", "file": file_path})),
-                    Err(e) => {
-                        error!("Failed to download chapter: {}", e);
-                        return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Download failed"}));
-                    }
-                }
-            }
-        } else {
-            return HttpResponse::NotFound()
-                .json(serde_json::json!({"error": "Chapter not found"}));
+/// The caller's read state per chapter of a manga, empty for anonymous callers
+async fn opds_reads(req: &HttpRequest, data: &AppState, manga_id: &str) -> HashMap<i32, crate::models::ChapterRead> {
+    let Ok(user) = current_user(req, data).await else {
+        return HashMap::new();
+    };
+    match data.storage.get_chapter_reads(user.id, manga_id).await {
+        Ok(reads) => reads.into_iter().map(|r| (r.chapter_id, r)).collect(),
+        Err(e) => {
+            error!("Failed to get chapter reads for user {}: {}", user.id, e);
+            HashMap::new()
         }
-    } else {
-        HttpResponse::Ok().json(manga_source_data_list)
     }
 }
 
-#[get("/download/byurl")]
-async fn download_by_url(
+/// Acquisition feed of a manga's chapters in reading order, one per chapter
+/// number from the preferred source
+#[get("/opds/manga/{id}")]
+async fn opds_manga(
+    req: HttpRequest,
     data: web::Data<AppState>,
-    query: web::Query<std::collections::HashMap<String, String>>,
+    id: web::Path<String>,
 ) -> impl Responder {
-    let manga_id = match query.get("manga_id") {
-        Some(v) => v,
-        None => {
-            return HttpResponse::BadRequest()
-                .json(serde_json::json!({"error":"manga_id is required"}))
+    let manga_id = id.into_inner();
+    let manga = match data.storage.get_manga_by_id(&manga_id).await {
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Manga not found"}))
         }
-    };
-    let url = match query.get("url") {
-        Some(v) => v,
-        None => {
-            return HttpResponse::BadRequest().json(serde_json::json!({"error":"url is required"}))
+        Err(e) => {
+            error!("Database error fetching manga: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
     };
-    let stream = query.get("stream").map(|s| s == "true").unwrap_or(false);
-    let req_source_id = query
-        .get("source_id")
-        .and_then(|s| s.parse::<i32>().ok())
-        .or_else(|| guess_source_id_from_url(url));
-
-    let source_data_list = match pg_db::get_manga_source_data_by_manga_id(&data.pool, manga_id).await {
+    let source_data_list = match data.storage.get_manga_source_data_by_manga_id(&manga_id).await {
         Ok(list) => list,
-        Err(_) => {
+        Err(e) => {
+            error!("Database error fetching source data: {}", e);
             return HttpResponse::InternalServerError()
-                .json(serde_json::json!({"error":"Database error"}))
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
     };
-    let (chosen_source_id, _has_msd) = if let Some(sid) = req_source_id {
-        // Prefer requested/guessed source id, even if no msd exists for this manga
-        if source_data_list.iter().any(|s| s.source_id == sid) {
-            (sid, true)
-        } else {
-            (sid, false)
+    let mut all_chapters = Vec::new();
+    for msd in source_data_list {
+        let source_name = data.storage.get_source_name(msd.source_id)
+            .await
+            .unwrap_or_else(|_| format!("Source {}", msd.source_id));
+        match data.storage.get_chapters_by_manga_source_data_id(&msd.manga_id, msd.source_id).await {
+            Ok(chapters) => all_chapters.extend(
+                chapters.into_iter().map(|c| ChapterWithSource::new(c, msd.source_id, source_name.clone())),
+            ),
+            Err(e) => error!("Database error fetching chapters: {}", e),
         }
-    } else if source_data_list.len() == 1 {
-        (source_data_list[0].source_id, true)
-    } else {
-        (0, false)
-    };
-    if chosen_source_id == 0 {
-        return HttpResponse::BadRequest().json(
-            serde_json::json!({"error":"source could not be determined; specify source_id"}),
-        );
     }
+    let reads = opds_reads(&req, &data, &manga_id).await;
+
+    let now = chrono::Utc::now().timestamp();
+    let href = format!("/opds/manga/{}", urlencoding::encode(&manga_id));
+    let mut feed = opds::Feed::new(
+        opds::FeedKind::Acquisition,
+        &format!("manga:{}", manga_id),
+        &manga.title,
+        &href,
+        now,
+    );
+    feed.entries = chapter_number::merge_chapters(all_chapters)
+        .iter()
+        .map(|m| opds::chapter_entry(&manga_id, &m.chapter, reads.get(&m.chapter.id), now))
+        .collect();
+    opds_response(&feed)
+}
 
-    if stream {
-        match scraper::download_chapter_to_memory(&data.client, chosen_source_id, url).await {
-            Ok(bytes) => HttpResponse::Ok()
-                .content_type("application/x-cbz")
-                .insert_header((
-                    "Content-Disposition",
-                    format!("attachment; filename=\"{} - byurl.cbz\"", manga_id),
-                ))
-                .body(bytes),
-            Err(e) => {
-                error!("Failed to download chapter by url: {}", e);
-                HttpResponse::InternalServerError()
-                    .json(serde_json::json!({"error":"Download failed"}))
-            }
-        }
-    } else {
-        let manga = match pg_db::get_manga_by_id(&data.pool, manga_id).await {
-            Ok(Some(m)) => m,
-            _ => return HttpResponse::InternalServerError().finish(),
-        };
-        if let Some(cu) = &manga.cover_url {
-            let _ = scraper::ensure_cover_downloaded(
-                &data.client,
-                &data.config.download_dir,
-                &manga.title,
-                cu,
-                chosen_source_id,
-            )
-            .await;
+/// One chapter with its OPDS-PSE link, resolving its pages to count them
+#[get("/opds/manga/{manga_id}/chapters/{chapter_id}")]
+async fn opds_chapter(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<(String, i32)>,
+) -> impl Responder {
+    let (manga_id, chapter_id) = path.into_inner();
+    let mut chapter = match data.storage.get_chapter_with_source(chapter_id).await {
+        Ok(Some(chapter)) => chapter,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Chapter not found"}))
         }
-        let comicinfo = build_comicinfo(
-            &manga.title,
-            "byurl",
-            manga.description.as_deref(),
-            manga.tags.as_deref(),
-        );
-        match scraper::download_chapter(
-            &data.client,
-            chosen_source_id,
-            url,
-            &manga.title,
-            "byurl",
-            &data.config.download_dir,
-            comicinfo.as_deref(),
-        )
-        .await
-        {
-            Ok(file_path) => HttpResponse::Ok()
-                .json(serde_json::json!({"message":"Downloaded successfully","file": file_path})),
-            Err(e) => {
-                error!("Failed to download chapter by url: {}", e);
-                HttpResponse::InternalServerError()
-                    .json(serde_json::json!({"error":"Download failed"}))
-            }
+        Err(e) => {
+            error!("Database error fetching chapter {}: {}", chapter_id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
         }
+    };
+    let pages = match resolved_pages(&data, &chapter).await {
+        Ok(pages) => pages,
+        Err(resp) => return resp,
+    };
+    if pages.is_empty() {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "No pages found for chapter"}));
     }
+    chapter.page_count = Some(pages.len() as i32);
+    let reads = opds_reads(&req, &data, &manga_id).await;
+
+    let now = chrono::Utc::now().timestamp();
+    let href = format!("/opds/manga/{}/chapters/{}", urlencoding::encode(&manga_id), chapter_id);
+    let mut feed = opds::Feed::new(
+        opds::FeedKind::Acquisition,
+        &format!("chapter:{}", chapter_id),
+        &chapter.label(),
+        &href,
+        now,
+    );
+    feed.entries = vec![opds::chapter_entry(&manga_id, &chapter, reads.get(&chapter_id), now)];
+    opds_response(&feed)
 }
 
-#[get("/sources")]
-async fn get_sources(data: web::Data<AppState>) -> impl Responder {
-    match pg_db::get_per_source_counts(&data.pool).await {
-        Ok(sources) => {
-            let sources_with_chapters: Vec<_> =
-                sources.into_iter().filter(|s| s.chapters > 0).collect();
-            HttpResponse::Ok().json(sources_with_chapters)
+/// OPDS-PSE page image, zero-based. Fetching the last page marks the chapter
+/// read for the caller's user, if there is one.
+#[get("/opds/chapters/{id}/pages/{page}")]
+async fn opds_page(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<(i32, usize)>,
+) -> impl Responder {
+    let (chapter_id, page) = path.into_inner();
+    let chapter = match data.storage.get_chapter_with_source(chapter_id).await {
+        Ok(Some(chapter)) => chapter,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Chapter not found"}))
         }
         Err(e) => {
-            error!("Failed to get sources: {}", e);
-            HttpResponse::InternalServerError()
-                .json(serde_json::json!({"error": "Failed to retrieve sources"}))
+            error!("Database error fetching chapter {}: {}", chapter_id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Internal server error"}));
+        }
+    };
+    let pages = match resolved_pages(&data, &chapter).await {
+        Ok(pages) => pages,
+        Err(resp) => return resp,
+    };
+    let Some(page_ref) = pages.get(page) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Page not found"}));
+    };
+    let image = match data.page_fetcher.fetch_one(&data.client, page_ref).await {
+        Ok(image) => image,
+        Err(e) => {
+            error!("Failed to fetch page {} of chapter {}: {}", page, chapter_id, e);
+            return HttpResponse::BadGateway()
+                .json(serde_json::json!({"error": format!("Failed to fetch page: {}", e)}));
+        }
+    };
+
+    if page + 1 == pages.len() {
+        if let Ok(user) = current_user(&req, &data).await {
+            let now = chrono::Utc::now().timestamp();
+            if let Err(e) = data.storage.set_chapter_read(user.id, chapter_id, page as i32, true, now).await {
+                error!("Failed to mark chapter {} read for user {}: {}", chapter_id, user.id, e);
+            }
         }
     }
+    HttpResponse::Ok()
+        .content_type(image.format.map_or("image/jpeg", |f| f.mime_type()))
+        .insert_header(("Cache-Control", "private, max-age=3600"))
+        .body(image.data)
 }
 
 #[get("/sources/{source_id}/manga")]
 async fn get_source_manga(data: web::Data<AppState>, source_id: web::Path<i32>) -> impl Responder {
     let source_id = source_id.into_inner();
 
-    match pg_db::get_manga_by_source(&data.pool, source_id).await {
+    match data.storage.get_manga_by_source(source_id).await {
         Ok(manga_list) => HttpResponse::Ok().json(manga_list),
         Err(e) => {
             error!("Failed to get manga for source {}: {}", source_id, e);
@@ -1770,7 +1975,7 @@ async fn get_source_manga(data: web::Data<AppState>, source_id: web::Path<i32>)
 
 #[get("/stats")]
 async fn get_stats(data: web::Data<AppState>) -> impl Responder {
-    let total_manga = match pg_db::get_manga_count(&data.pool).await {
+    let total_manga = match data.storage.get_manga_count().await {
         Ok(count) => count,
         Err(e) => {
             error!("Failed to get manga count: {}", e);
@@ -1779,7 +1984,7 @@ async fn get_stats(data: web::Data<AppState>) -> impl Responder {
         }
     };
 
-    let total_chapters = match pg_db::get_chapter_count(&data.pool).await {
+    let total_chapters = match data.storage.get_chapter_count().await {
         Ok(count) => count,
         Err(e) => {
             error!("Failed to get chapter count: {}", e);
@@ -1788,7 +1993,7 @@ async fn get_stats(data: web::Data<AppState>) -> impl Responder {
         }
     };
 
-    let total_sources = match pg_db::get_source_count(&data.pool).await {
+    let total_sources = match data.storage.get_source_count().await {
         Ok(count) => count,
         Err(e) => {
             error!("Failed to get source count: {}", e);
@@ -1802,7 +2007,7 @@ async fn get_stats(data: web::Data<AppState>) -> impl Responder {
         total_chapters: total_chapters as i32,
         total_sources: total_sources as i32,
     };
-    let per_source = match pg_db::get_per_source_counts(&data.pool).await {
+    let per_source = match data.storage.get_per_source_counts().await {
         Ok(v) => v,
         Err(_) => Vec::new(),
     };
@@ -1895,7 +2100,7 @@ async fn download_from_source(
     let (manga_id, chapter_number, source_id) = path.into_inner();
     let stream = query.get("stream").map(|s| s == "true").unwrap_or(false);
 
-    let manga_source_data_list = match pg_db::get_manga_source_data_by_manga_id(&data.pool, &manga_id).await {
+    let manga_source_data_list = match data.storage.get_manga_source_data_by_manga_id(&manga_id).await {
         Ok(list) => list,
         Err(_) => {
             return HttpResponse::InternalServerError()
@@ -1907,8 +2112,7 @@ async fn download_from_source(
         .find(|s| s.source_id == source_id);
 
     if let Some(source_data) = source_data {
-        let chapters = match pg_db::get_chapters_by_manga_source_data_id(
-            &data.pool,
+        let chapters = match data.storage.get_chapters_by_manga_source_data_id(
             &source_data.manga_id,
             source_data.source_id,
         ).await {
@@ -1921,23 +2125,42 @@ async fn download_from_source(
         let chapter = chapters.iter().find(|c| c.chapter_number == chapter_number);
 
         if let Some(chapter) = chapter {
+            let manga = match data.storage.get_manga_by_id(&manga_id).await {
+                Ok(Some(m)) => m,
+                _ => return HttpResponse::InternalServerError().finish(),
+            };
+            let format = match output_format_for(&data, &manga_id, &query, stream).await {
+                Ok(f) => f,
+                Err(resp) => return resp,
+            };
+            let label = chapter.label();
+            let comicinfo = ComicInfo::for_chapter(
+                &manga,
+                source_data.source_id,
+                &label,
+                &chapter.url,
+            );
             if stream {
                 // Stream file directly
                 match scraper::download_chapter_to_memory(
                     &data.client,
+                    &data.page_fetcher,
                     source_data.source_id,
                     &chapter.url,
+                    Some(&comicinfo),
+                    format,
                 )
                 .await
                 {
                     Ok(bytes) => {
-                        let manga = match pg_db::get_manga_by_id(&data.pool, &manga_id).await {
-                            Ok(Some(m)) => m,
-                            _ => return HttpResponse::InternalServerError().finish(),
-                        };
-                        let filename = format!("{} - {}.cbz", manga.title, chapter.chapter_number);
+                        let filename = format!(
+                            "{} - {}.{}",
+                            manga.title,
+                            label,
+                            format.as_str()
+                        );
                         HttpResponse::Ok()
-                            .content_type("application/x-cbz")
+                            .content_type(format.content_type())
                             .insert_header((
                                 "Content-Disposition",
                                 format!("attachment; filename=\"{}\"", filename),
@@ -1952,28 +2175,32 @@ async fn download_from_source(
                 }
             } else {
                 // Save to disk
-                let manga = match pg_db::get_manga_by_id(&data.pool, &manga_id).await {
-                    Ok(Some(m)) => m,
-                    _ => return HttpResponse::InternalServerError().finish(),
-                };
+                let name = naming::ChapterName::new(&manga.title, source_data.source_id, &label, &chapter.url)
+                    .with_release(chapter.scanlation_group.as_deref(), chapter.language.as_deref(), chapter.published_at);
+                let template = &data.config.chapter_template;
                 if let Some(cu) = &manga.cover_url {
                     let _ = scraper::ensure_cover_downloaded(
                         &data.client,
                         &data.config.download_dir,
-                        &manga.title,
+                        &template.series_dir(&name),
                         cu,
                         source_data.source_id,
                     )
                     .await;
                 }
-                let comicinfo = build_comicinfo(
-                    &manga.title,
-                    &chapter.chapter_number,
-                    manga.description.as_deref(),
-                    manga.tags.as_deref(),
-                );
-                match scraper::download_chapter(&data.client, source_data.source_id, &chapter.url, &manga.title, &chapter.chapter_number, &data.config.download_dir, comicinfo.as_deref()).await {
-                    Ok(file_path) => HttpResponse::Ok().json(serde_json::json!({"message": "Downloaded successfully", "file": file_path})),
+                let file_path = template.render(&name, format);
+                let chapter_download = scraper::ChapterDownload {
+                    source_id: source_data.source_id,
+                    chapter_url: &chapter.url,
+                    file_path: &file_path,
+                    comicinfo: Some(&comicinfo),
+                    format,
+                };
+                match scraper::download_chapter(&data.client, &data.page_fetcher, &data.config.download_dir, chapter_download).await {
+                    Ok(file_path) => {
+                        library::record_download(&data, chapter.id, &file_path).await;
+                        HttpResponse::Ok().json(serde_json::json!({"message": "Downloaded successfully", "file": file_path}))
+                    }
                     Err(e) => {
                         error!("Failed to download chapter: {}", e);
                         HttpResponse::InternalServerError().json(serde_json::json!({"error": "Download failed"}))
@@ -1988,11 +2215,96 @@ async fn download_from_source(
     }
 }
 
+/// `--create-api-key <name> [--scopes read,download,admin] [--user <name>]`:
+/// print a new key and exit, for setting up the first admin key
+async fn create_api_key_cli(
+    storage: &dyn storage::Storage,
+    name: &str,
+    scopes: &str,
+    user: Option<&str>,
+) -> std::io::Result<()> {
+    let scopes = auth::parse_scopes(scopes).map_err(std::io::Error::other)?;
+    if scopes.is_empty() {
+        return Err(std::io::Error::other("at least one scope is required"));
+    }
+    let user_id = match user {
+        Some(user) => match storage.get_user_by_name(user).await {
+            Ok(Some(user)) => Some(user.id),
+            Ok(None) => return Err(std::io::Error::other(format!("unknown user '{}'", user))),
+            Err(e) => return Err(std::io::Error::other(e.to_string())),
+        },
+        None => None,
+    };
+    let key = auth::generate_key();
+    storage
+        .create_api_key(name, &auth::hash_key(&key), &auth::key_prefix(&key), &scopes, user_id, chrono::Utc::now().timestamp())
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    println!("{}", key);
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     log4rs::init_file("log4rs.yml", Default::default()).unwrap();
 
-    let pool = crate::pg_db::create_pool();
+    let cfg = match config::Config::try_load() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            return Err(std::io::Error::other(e));
+        }
+    };
+
+    let storage = match storage::open(&cfg.database) {
+        Ok(storage) => storage,
+        Err(e) => {
+            error!("Failed to open database: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    };
+
+    match storage.migrate().await {
+        Ok(applied) if applied.is_empty() => log::info!("Database schema is up to date"),
+        Ok(applied) => log::info!("Applied database migrations {:?}", applied),
+        Err(e) => {
+            error!("Database migration failed: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    }
+    if std::env::args().any(|a| a == "--migrate-only") {
+        return Ok(());
+    }
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).cloned();
+    if let Some(name) = arg("--create-api-key") {
+        let scopes = arg("--scopes").unwrap_or_else(|| "admin".to_string());
+        return create_api_key_cli(storage.as_ref(), &name, &scopes, arg("--user").as_deref()).await;
+    }
+
+    // Source IDs are baked into stored rows and file names; refuse to run
+    // against a sources table that disagrees with the catalogue.
+    if let Err(e) = storage.seed_sources().await {
+        error!("Failed to seed sources table: {}", e);
+    }
+    match storage.check_sources_table().await {
+        Ok(problems) if problems.is_empty() => {}
+        Ok(problems) => {
+            for p in &problems {
+                error!("sources table: {}", p);
+            }
+            error!("Apply migrations/02_source_catalogue.sql to bring the sources table in line");
+            return Err(std::io::Error::other("sources table does not match the source catalogue"));
+        }
+        Err(e) => {
+            error!("Failed to check sources table: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    }
+    if let Err(e) = storage.seed_tags().await {
+        error!("Failed to seed tags table: {}", e);
+    }
+    crate::tags::link_untagged(storage.as_ref()).await;
 
     let client = reqwest::Client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36")
@@ -2001,8 +2313,6 @@ async fn main() -> std::io::Result<()> {
         .build()
         .unwrap();
 
-    let cfg = config::Config::load();
-
     // Create enhanced HTTP client from configuration
     let enhanced_client = cfg
         .bot_detection
@@ -2030,12 +2340,20 @@ async fn main() -> std::io::Result<()> {
     };
 
     let data = web::Data::new(AppState {
-        pool,
+        storage,
         client,
         _enhanced_client: enhanced_client,
         metrics,
+        page_fetcher: scraper::PageFetcher::from_config(&cfg),
+        // Long enough to read a chapter, short enough for expiring image URLs
+        page_cache: sources::pages::PageCache::new(std::time::Duration::from_secs(15 * 60), 256),
         config: cfg,
         crawl_progress: Mutex::new(crawler::CrawlProgress::default()),
+        library_verify: Mutex::new(library::VerifyProgress::default()),
+        library_scan: Mutex::new(library::ScanProgress::default()),
+        library_rename: Mutex::new(library::RenameProgress::default()),
+        bundle_job: Mutex::new(bundle::BundleProgress::default()),
+        matching_job: Mutex::new(matching::MatchingProgress::default()),
         metadata_progress: Mutex::new(MetadataProgress::default()),
         metadata_cancel: Mutex::new(false),
         browser_manager,
@@ -2043,20 +2361,52 @@ async fn main() -> std::io::Result<()> {
 
     // start background scheduler
     scheduler::spawn(data.clone());
+    download_queue::spawn_workers(data.clone());
 
-    // Try to bind to an available port starting at 8080
+    let server_cfg = data.config.server.clone();
+
+    // Bind the configured port, or the first free one from 8080
     let mut last_err: Option<std::io::Error> = None;
-    for port in 8080..=8090 {
+    for port in server_cfg.ports() {
         let data_clone = data.clone();
-        let addr = format!("127.0.0.1:{}", port);
-        match HttpServer::new(move || {
+        let addr = format!("{}:{}", server_cfg.bind, port);
+        let server = HttpServer::new(move || {
             App::new()
                 .app_data(data_clone.clone())
+                .wrap(actix_web::middleware::from_fn(auth::authenticate))
                 .service(import)
             .service(list_manga)
             .service(get_manga)
             .service(get_chapters)
+            .service(get_chapter_gaps)
+            .service(get_chapter_pages)
             .service(get_sources)
+            .service(list_tags)
+            .service(get_person)
+            .service(create_user)
+            .service(list_users)
+            .service(get_library)
+            .service(set_library_entry)
+            .service(remove_library_entry)
+            .service(get_chapter_reads)
+            .service(continue_reading)
+            .service(mark_chapter_read)
+            .service(mark_chapter_unread)
+            .service(create_api_key)
+            .service(list_api_keys)
+            .service(revoke_api_key)
+            .service(whoami)
+            .service(opds_root)
+            .service(opds_search_description)
+            .service(opds_search)
+            .service(opds_latest)
+            .service(opds_sources)
+            .service(opds_source)
+            .service(opds_tags)
+            .service(opds_tag)
+            .service(opds_manga)
+            .service(opds_chapter)
+            .service(opds_page)
             .service(get_source_manga)
             .service(get_stats)
             .service(get_metrics)
@@ -2064,102 +2414,57 @@ async fn main() -> std::io::Result<()> {
             .service(download)
             .service(download_from_source)
             .service(monitor_manga)
+            .service(set_output_format)
+            .service(bundle_manga)
+            .service(set_source_priority)
+            .service(queue_manga)
+            .service(queue_unread)
+            .service(list_queue)
+            .service(retry_queue_job)
+            .service(merge_manga)
+            .service(split_manga)
+            .service(list_manga_audit)
+            .service(undo_manga_audit)
+            .service(run_matching)
+            .service(list_merge_proposals)
+            .service(accept_merge_proposal)
+            .service(reject_merge_proposal)
             .service(import_source_endpoint)
             .service(import_source_manga_only)
             .service(download_by_url)
             .route("/import/source/{source}/quick", web::get().to(|data: web::Data<AppState>, source: web::Path<String>, query: web::Query<std::collections::HashMap<String,String>>| async move {
                 use serde_json::json;
-                // Quick import for WP-Manga: first page only, limited manga and chapters
+                // Quick import: first page only, limited manga and chapters
                 let limit_manga = query.get("limit").and_then(|s| s.parse::<usize>().ok()).unwrap_or(10);
                 let limit_ch = query.get("chapters").and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
-                fn wp_base(name: &str) -> Option<String> {
-                    let n = name.to_lowercase();
-                    match n.as_str() {
-                        "firescans" => Some("https://firescans.xyz".to_string()),
-                        "rizzcomic" => Some("https://rizzcomic.com".to_string()),
-                        "drakecomic" => Some("https://drakecomic.org".to_string()),
-                        "asmotoon" => Some("https://asmotoon.com".to_string()),
-                        "reset-scans" | "resetscans" => Some("https://reset-scans.org".to_string()),
-                        "temple-scan" | "templescan" => Some("https://templetoons.com".to_string()),
-                        "thunderscans" | "thunder-scans" => Some("https://thunderscans.com".to_string()),
-                        _ => None,
-                    }
-                }
-                // Special-case non-WP sources first
-                let sname = source.to_lowercase();
-                if sname == "mangadex" {
-                    let client = &data.client;
-                    let mut manga_added = 0usize; let mut ch_added = 0usize;
-                    let list = match crate::sources::mangadex::search_manga(client, "", crate::sources::mangadex::BASE_URL).await { Ok(v)=>v, Err(e)=>{ return HttpResponse::InternalServerError().json(json!({"error":format!("fetch failed: {}", e)})); } };
-                    for m in list.into_iter().take(limit_manga) {
-                        let mut mm = m.clone(); mm.id = uuid::Uuid::new_v4().to_string();
-                        let _ = pg_db::insert_manga(&data.pool, &mm).await;
-                        let msd = MangaSourceData { manga_id: mm.id.clone(), source_id: Source::MangaDex as i32, source_manga_id: m.id.clone(), source_manga_url: format!("https://mangadex.org/title/{}", m.id) };
-                        let msd_id = match pg_db::insert_manga_source_data(&data.pool, &msd).await { Ok(id)=>id, Err(_)=>continue };
-                        let chs = crate::sources::mangadex::get_chapters(client, &m.id).await.unwrap_or_default();
-                        let chs_limited: Vec<_> = chs.into_iter().take(limit_ch).collect();
-                        let _ = pg_db::insert_chapters(&data.pool, msd_id, &chs_limited).await;
-                        manga_added += 1; ch_added += chs_limited.len();
-                    }
-                    return HttpResponse::Ok().json(json!({"source": source.to_string(), "manga_added": manga_added, "chapters_added": ch_added}));
-                }
-                if sname == "kagane" {
-                    let client = &data.client;
-                    let mut manga_added = 0usize; let mut ch_added = 0usize;
-                    let list = match crate::sources::kagane::search_all_series_with_urls(client).await { Ok(v)=>v, Err(e)=>{ return HttpResponse::InternalServerError().json(json!({"error":format!("fetch failed: {}", e)})); } };
-                    for (m,u) in list.into_iter().take(limit_manga) {
-                        let mut mm = m.clone(); mm.id = uuid::Uuid::new_v4().to_string();
-                        let _ = pg_db::insert_manga(&data.pool, &mm).await;
-                        let msd = MangaSourceData { manga_id: mm.id.clone(), source_id: Source::Kagane as i32, source_manga_id: u.clone(), source_manga_url: u.clone() };
-                        let msd_id = match pg_db::insert_manga_source_data(&data.pool, &msd).await { Ok(id)=>id, Err(_)=>continue };
-                        let chs = crate::sources::kagane::get_chapters(client, &msd.source_manga_url).await.unwrap_or_default();
-                        let chs_limited: Vec<_> = chs.into_iter().take(limit_ch).collect();
-                        let _ = pg_db::insert_chapters(&data.pool, msd_id, &chs_limited).await;
-                        // Also capture external provider links and add as additional sources (no chapters here to keep quick)
-                        for (sid, link) in crate::sources::kagane::extract_provider_links(client, &u).await.into_iter().take(10) {
-                            let extra = MangaSourceData { manga_id: mm.id.clone(), source_id: sid, source_manga_id: link.clone(), source_manga_url: link };
-                            let _ = pg_db::insert_manga_source_data(&data.pool, &extra).await;
-                        }
-                        manga_added += 1; ch_added += chs_limited.len();
-                    }
-                    return HttpResponse::Ok().json(json!({"source": source.to_string(), "manga_added": manga_added, "chapters_added": ch_added}));
-                }
-
-                // WP-Manga flow
-                let base = wp_base(&source).or_else(|| wp_manga_source_by_name(&source).map(|(_,b)| b.to_string()));
-                if base.is_none() { return HttpResponse::BadRequest().json(json!({"error":"unknown or non-wp source"})); }
-                let base = base.unwrap();
+                let target = match registry::find(&source) {
+                    Some(s) if s.capabilities().chapters => s,
+                    _ => return HttpResponse::BadRequest().json(json!({"error":"unknown or unsupported source"})),
+                };
                 let client = &data.client;
                 let mut manga_added = 0usize;
                 let mut ch_added = 0usize;
-                // Choose per-source first-page + chapters strategy
-                let items: Vec<(Manga,String)> = match sname.as_str() {
-                    "firescans" => match crate::sources::firescans::search_manga_first_page(client).await { Ok(v)=>v, Err(e)=>{ return HttpResponse::InternalServerError().json(json!({"error":format!("fetch failed: {}", e)})); } },
-                    "rizzcomic" => match crate::sources::rizzcomic::search_manga_first_page(client).await { Ok(v)=>v, Err(e)=>{ return HttpResponse::InternalServerError().json(json!({"error":format!("fetch failed: {}", e)})); } },
-                    _ => match crate::sources::wp_manga::search_manga_first_page(client, &base).await { Ok(v)=>v, Err(e)=>{ return HttpResponse::InternalServerError().json(json!({"error":format!("fetch failed: {}", e)})); } },
-                };
+                let items = match target.first_page(client).await { Ok(v)=>v, Err(e)=>{ return HttpResponse::InternalServerError().json(json!({"error":format!("fetch failed: {}", e)})); } };
                 for (m,u) in items.into_iter().take(limit_manga) {
                     let mut mm = m.clone(); mm.id = Uuid::new_v4().to_string();
-                    let _ = pg_db::insert_manga(&data.pool, &mm).await;
-                    let sid = if let Some(s) = parse_source(&source) { s as i32 } else if let Some((id,_)) = wp_manga_source_by_name(&source.to_lowercase()) { id } else { 0 };
-                    let msd = MangaSourceData { manga_id: mm.id.clone(), source_id: sid, source_manga_id: u.clone(), source_manga_url: u.clone() };
-                    let msd_id = match pg_db::insert_manga_source_data(&data.pool, &msd).await { Ok(id)=>id, Err(_)=>continue };
-                    let chs = match sname.as_str() {
-                        "firescans" => crate::sources::firescans::get_chapters(client, &u).await.unwrap_or_default(),
-                        "rizzcomic" => crate::sources::rizzcomic::get_chapters(client, &u).await.unwrap_or_default(),
-                        _ => crate::sources::wp_manga::get_chapters_base(client, &base, &u).await.unwrap_or_default(),
-                    };
+                    let _ = data.storage.insert_manga(&mm).await;
+                    let msd = target.source_data(&mm.id, &u);
+                    let msd_id = match data.storage.insert_manga_source_data(&msd).await { Ok(id)=>id, Err(_)=>continue };
+                    let chs = target.get_chapters(client, &msd).await.unwrap_or_default();
                     let chs_limited: Vec<_> = chs.into_iter().take(limit_ch).collect();
-                    let _ = pg_db::insert_chapters(&data.pool, msd_id, &chs_limited).await;
+                    let _ = data.storage.insert_chapters(msd_id, &chs_limited).await;
+                    // Also capture external provider links and add as additional sources (no chapters here to keep quick)
+                    for (sid, link) in target.provider_links(client, &u).await.into_iter().take(10) {
+                        let extra = MangaSourceData { manga_id: mm.id.clone(), source_id: sid, source_manga_id: link.clone(), source_manga_url: link };
+                        let _ = data.storage.insert_manga_source_data(&extra).await;
+                    }
                     manga_added += 1; ch_added += chs_limited.len();
                 }
                 HttpResponse::Ok().json(json!({"source": source.to_string(), "manga_added": manga_added, "chapters_added": ch_added}))
             }))
 .route("/crawl/full", web::get().to(|data: web::Data<AppState>, query: web::Query<std::collections::HashMap<String,String>>| async move {
                 fn name_to_id(name: &str) -> Option<i32> {
-                    if let Some(s) = parse_source(name) { return Some(s as i32); }
-                    if let Some((id,_)) = wp_manga_source_by_name(name) { return Some(id); }
-                    name.parse::<i32>().ok()
+                    registry::find(name).map(|s| s.id())
                 }
                 let include = query.get("include").map(|s| s.split(',').filter_map(|n| name_to_id(n.trim())).collect::<std::collections::HashSet<i32>>());
                 let mut exclude = query.get("exclude").map(|s| s.split(',').filter_map(|n| name_to_id(n.trim())).collect::<std::collections::HashSet<i32>>());
@@ -2174,33 +2479,66 @@ async fn main() -> std::io::Result<()> {
                 let st = crawler::get_progress(data.clone());
                 HttpResponse::Ok().json(serde_json::to_value(&st).unwrap())
             }))
+            .route("/verify/library", web::post().to(|data: web::Data<AppState>| async move {
+                if library::spawn_verify_library(data.clone()) {
+                    HttpResponse::Accepted().finish()
+                } else {
+                    HttpResponse::Conflict().json(serde_json::json!({"error": "library verification already running"}))
+                }
+            }))
+            .route("/verify/library/status", web::get().to(|data: web::Data<AppState>| async move {
+                let st = data.library_verify.lock().unwrap().clone();
+                HttpResponse::Ok().json(serde_json::to_value(&st).unwrap())
+            }))
+            .route("/library/scan", web::post().to(|data: web::Data<AppState>| async move {
+                if library::spawn_scan_library(data.clone()) {
+                    HttpResponse::Accepted().finish()
+                } else {
+                    HttpResponse::Conflict().json(serde_json::json!({"error": "library scan already running"}))
+                }
+            }))
+            .route("/library/scan/status", web::get().to(|data: web::Data<AppState>| async move {
+                let st = data.library_scan.lock().unwrap().clone();
+                HttpResponse::Ok().json(serde_json::to_value(&st).unwrap())
+            }))
+            .route("/library/rename", web::post().to(|data: web::Data<AppState>| async move {
+                if library::spawn_rename_library(data.clone()) {
+                    HttpResponse::Accepted().finish()
+                } else {
+                    HttpResponse::Conflict().json(serde_json::json!({"error": "library rename already running"}))
+                }
+            }))
+            .route("/library/rename/status", web::get().to(|data: web::Data<AppState>| async move {
+                let st = data.library_rename.lock().unwrap().clone();
+                HttpResponse::Ok().json(serde_json::to_value(&st).unwrap())
+            }))
+            .route("/bundle/status", web::get().to(|data: web::Data<AppState>| async move {
+                let st = data.bundle_job.lock().unwrap().clone();
+                HttpResponse::Ok().json(serde_json::to_value(&st).unwrap())
+            }))
+            .route("/matching/status", web::get().to(|data: web::Data<AppState>| async move {
+                let st = data.matching_job.lock().unwrap().clone();
+                HttpResponse::Ok().json(serde_json::to_value(&st).unwrap())
+            }))
             .route("/verify/downloads", web::get().to(|data: web::Data<AppState>| async move {
                 use serde_json::json;
                 use reqwest::Url;
-                let client = match data.pool.get().await {
-                    Ok(c) => c,
-                    Err(_) => return HttpResponse::InternalServerError().finish(),
-                };
-                let rows = match client.query("SELECT id, name FROM sources", &[]).await {
+                let sources = match data.storage.get_per_source_counts().await {
                     Ok(r) => r,
                     Err(_) => return HttpResponse::InternalServerError().finish(),
                 };
                 let mut results = Vec::new();
-                for row in rows {
-                    let sid: i32 = row.get(0);
-                    let sname: String = row.get(1);
-                    let ch_rows = client.query(
-                        "SELECT m.id, c.chapter_number, c.url, msd.source_manga_url FROM manga m JOIN manga_source_data msd ON msd.manga_id=m.id JOIN chapters c ON c.manga_source_data_id=msd.id WHERE msd.source_id = $1 LIMIT 1",
-                        &[&sid]
-                    ).await;
-                    if let Ok(chs) = ch_rows {
-                        if let Some(ch_row) = chs.get(0) {
-                            let mid: String = ch_row.get(0);
-                            let ch: String = ch_row.get(1);
-                            let url: String = ch_row.get(2);
-                            let base: String = ch_row.get(3);
+                for source in sources {
+                    let sid = source.source_id;
+                    let sname = source.source_name;
+                    if let Ok(sample) = data.storage.get_sample_chapter(sid).await {
+                        if let Some(sample) = sample {
+                            let mid = sample.manga_id;
+                            let ch = sample.chapter_number;
+                            let url = sample.url;
+                            let base = sample.series_url;
                             let abs = if Url::parse(&url).is_ok() { url } else { Url::parse(&base).ok().and_then(|b| b.join(&url).ok()).map(|u| u.to_string()).unwrap_or(url) };
-                            let res = scraper::download_chapter_to_memory(&data.client, sid, &abs).await;
+                            let res = scraper::download_chapter_to_memory(&data.client, &data.page_fetcher, sid, &abs, None, OutputFormat::Cbz).await;
                             match res {
                                 Ok(bytes) => results.push(json!({"source_id":sid,"source":sname,"manga_id":mid,"chapter":ch,"ok":true,"bytes":bytes.len()})),
                                 Err(e) => results.push(json!({"source_id":sid,"source":sname,"manga_id":mid,"chapter":ch,"ok":false,"error":e.to_string()})),
@@ -2225,20 +2563,17 @@ async fn main() -> std::io::Result<()> {
                         let mut p = data_clone.metadata_progress.lock().unwrap();
                         *p = MetadataProgress { in_progress: true, started_at: Some(Utc::now().timestamp()), finished_at: None, current_phase: Some("mangabaka_sync".into()), total_pending: None, processed_in_phase: 0, ..Default::default() };
                     }
-                    let db_client = match data_clone.pool.get().await { Ok(c)=>c, Err(e)=>{ error!("get pool: {}", e); return; } };
-                    if let Ok(row) = db_client.query_one("SELECT COUNT(1) FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = ''", &[]).await { let cnt: i64 = row.get(0); let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
-                    let sql = if let Some(l)=limit { format!("SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = '' LIMIT {}", l) } else { "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = ''".to_string() };
-                    let rows_vec = match db_client.query(&sql, &[]).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
+                    if let Ok(cnt) = data_clone.storage.count_missing_provider(MetadataProvider::MangaBaka).await { let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
+                    let rows_vec = match data_clone.storage.get_manga_missing_provider(MetadataProvider::MangaBaka, limit.map(|l| l as i64)).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
                     let mut updated = 0usize;
                     for row in rows_vec {
-                        let manga_id: String = row.get(0);
-                        let title: String = row.get(1);
-                        let alts: String = row.get(2);
+                        let manga_id = row.id;
+                        let title = row.title;
+                        let alts = row.alt_titles;
                         // cancel?
                         if *data_clone.metadata_cancel.lock().unwrap() { let mut p = data_clone.metadata_progress.lock().unwrap(); p.error=Some("cancelled".into()); p.in_progress=false; return; }
                         match crate::metadata::mangabaka::resolve_id(&data_clone.client, &title, &alts).await { Ok(Some(pid)) => {
-                            let _ = db_client.execute("UPDATE manga SET mangabaka_id = $1 WHERE id = $2", &[&pid, &manga_id]).await;
-                            let _ = db_client.execute("INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'mangabaka', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = EXCLUDED.provider_id", &[&manga_id, &pid]).await;
+                            let _ = data_clone.storage.set_provider_id(&manga_id, MetadataProvider::MangaBaka, &pid).await;
                             updated += 1;
                         }, _=>{} }
                         { let mut p = data_clone.metadata_progress.lock().unwrap(); p.processed_in_phase += 1; }
@@ -2260,22 +2595,18 @@ async fn main() -> std::io::Result<()> {
                         let mut p = data_clone.metadata_progress.lock().unwrap();
                         *p = MetadataProgress { in_progress: true, started_at: Some(Utc::now().timestamp()), finished_at: None, current_phase: Some("mal_sync".into()), total_pending: None, processed_in_phase: 0, ..Default::default() };
                     }
-                    // get connection from pool
-                    let db_client = match data_clone.pool.get().await { Ok(c)=>c, Err(e)=>{ error!("get pool: {}", e); return; } };
                     // set pending count
-                    if let Ok(row) = db_client.query_one("SELECT COUNT(1) FROM manga WHERE mal_id IS NULL OR mal_id = 0", &[]).await { let cnt: i64 = row.get(0); let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
+                    if let Ok(cnt) = data_clone.storage.count_missing_provider(MetadataProvider::Mal).await { let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
                     // fetch rows
-                    let sql = if let Some(l)=limit { format!("SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mal_id IS NULL OR mal_id = 0 LIMIT {}", l) } else { "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mal_id IS NULL OR mal_id = 0".to_string() };
-                    let rows_vec = match db_client.query(&sql, &[]).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
+                    let rows_vec = match data_clone.storage.get_manga_missing_provider(MetadataProvider::Mal, limit.map(|l| l as i64)).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
                     let mut updated = 0usize;
                     for row in rows_vec {
-                        let manga_id: String = row.get(0);
-                        let title: String = row.get(1);
-                        let alts: String = row.get(2);
+                        let manga_id = row.id;
+                        let title = row.title;
+                        let alts = row.alt_titles;
                         if *data_clone.metadata_cancel.lock().unwrap() { let mut p = data_clone.metadata_progress.lock().unwrap(); p.error=Some("cancelled".into()); p.in_progress=false; return; }
                         match crate::metadata::mal::resolve_id(&data_clone.client, &title, &alts).await { Ok(Some(mid)) => {
-                            let _ = db_client.execute("UPDATE manga SET mal_id = $1 WHERE id = $2", &[&mid, &manga_id]).await;
-                            let _ = db_client.execute("INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'mal', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = $2", &[&manga_id, &mid.to_string()]).await;
+                            let _ = data_clone.storage.set_provider_id(&manga_id, MetadataProvider::Mal, &mid.to_string()).await;
                             updated += 1;
                         }, _=>{} }
                         { let mut p = data_clone.metadata_progress.lock().unwrap(); p.processed_in_phase += 1; }
@@ -2293,19 +2624,16 @@ async fn main() -> std::io::Result<()> {
                         let mut p = data_clone.metadata_progress.lock().unwrap();
                         *p = MetadataProgress { in_progress: true, started_at: Some(Utc::now().timestamp()), finished_at: None, current_phase: Some("anilist_sync".into()), total_pending: None, processed_in_phase:0, ..Default::default() };
                     }
-                    let db_client = match data_clone.pool.get().await { Ok(c)=>c, Err(e)=>{ error!("get pool: {}", e); return; } };
-                    if let Ok(row) = db_client.query_one("SELECT COUNT(1) FROM manga WHERE anilist_id IS NULL OR anilist_id = 0", &[]).await { let cnt: i64 = row.get(0); let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
-                    let sql = if let Some(l)=limit { format!("SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE anilist_id IS NULL OR anilist_id = 0 LIMIT {}", l) } else { "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE anilist_id IS NULL OR anilist_id = 0".to_string() };
-                    let rows_vec = match db_client.query(&sql, &[]).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
+                    if let Ok(cnt) = data_clone.storage.count_missing_provider(MetadataProvider::AniList).await { let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
+                    let rows_vec = match data_clone.storage.get_manga_missing_provider(MetadataProvider::AniList, limit.map(|l| l as i64)).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
                     let mut updated = 0usize;
                     for row in rows_vec {
-                        let manga_id: String = row.get(0);
-                        let title: String = row.get(1);
-                        let alts: String = row.get(2);
+                        let manga_id = row.id;
+                        let title = row.title;
+                        let alts = row.alt_titles;
                         if *data_clone.metadata_cancel.lock().unwrap() { let mut p = data_clone.metadata_progress.lock().unwrap(); p.error=Some("cancelled".into()); p.in_progress=false; return; }
                         match crate::metadata::anilist::resolve_id(&data_clone.client, &title, &alts).await { Ok(Some(aid)) => {
-                            let _ = db_client.execute("UPDATE manga SET anilist_id = $1 WHERE id = $2", &[&aid, &manga_id]).await;
-                            let _ = db_client.execute("INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'anilist', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = $2", &[&manga_id, &aid.to_string()]).await;
+                            let _ = data_clone.storage.set_provider_id(&manga_id, MetadataProvider::AniList, &aid.to_string()).await;
                             updated += 1;
                         }, _=>{} }
                         { let mut p = data_clone.metadata_progress.lock().unwrap(); p.processed_in_phase += 1; }
@@ -2340,19 +2668,16 @@ async fn main() -> std::io::Result<()> {
                         *p = MetadataProgress { in_progress: true, started_at: Some(Utc::now().timestamp()), finished_at: None, current_phase: Some("mangabaka_sync".into()), total_pending: None, processed_in_phase: 0, ..Default::default() };
                     }
                     // estimate pending
-                    let db_client = match data_clone.pool.get().await { Ok(c)=>c, Err(e)=>{ error!("get pool: {}", e); return; } };
-                    if let Ok(row) = db_client.query_one("SELECT COUNT(1) FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = ''", &[]).await { let cnt: i64 = row.get(0); let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
-                    let sql = if let Some(l)=limit { format!("SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = '' LIMIT {}", l) } else { "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = ''".to_string() };
-                    let rows_vec = match db_client.query(&sql, &[]).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
+                    if let Ok(cnt) = data_clone.storage.count_missing_provider(MetadataProvider::MangaBaka).await { let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
+                    let rows_vec = match data_clone.storage.get_manga_missing_provider(MetadataProvider::MangaBaka, limit.map(|l| l as i64)).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
                     let mut updated_baka = 0usize;
                     for row in rows_vec {
-                        let manga_id: String = row.get(0);
-                        let title: String = row.get(1);
-                        let alts: String = row.get(2);
+                        let manga_id = row.id;
+                        let title = row.title;
+                        let alts = row.alt_titles;
                         if *data_clone.metadata_cancel.lock().unwrap() { let mut p = data_clone.metadata_progress.lock().unwrap(); p.error=Some("cancelled".into()); p.in_progress=false; return; }
                         match crate::metadata::mangabaka::resolve_id(&data_clone.client, &title, &alts).await { Ok(Some(pid)) => {
-                            let _ = db_client.execute("UPDATE manga SET mangabaka_id = $1 WHERE id = $2", &[&pid, &manga_id]).await;
-                            let _ = db_client.execute("INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'mangabaka', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = EXCLUDED.provider_id", &[&manga_id, &pid]).await;
+                            let _ = data_clone.storage.set_provider_id(&manga_id, MetadataProvider::MangaBaka, &pid).await;
                             updated_baka += 1;
                         }, _=>{} }
                         { let mut p = data_clone.metadata_progress.lock().unwrap(); p.processed_in_phase += 1; }
@@ -2360,19 +2685,17 @@ async fn main() -> std::io::Result<()> {
                     { let mut p = data_clone.metadata_progress.lock().unwrap(); p.mangabaka_updated += updated_baka; }
                     // step 2: mal
                     { let mut p = data_clone.metadata_progress.lock().unwrap(); p.current_phase = Some("mal_sync".into()); p.total_pending=None; p.processed_in_phase=0; }
-                    if let Ok(row) = db_client.query_one("SELECT COUNT(1) FROM manga WHERE mal_id IS NULL OR mal_id = 0", &[]).await { let cnt: i64 = row.get(0); let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
+                    if let Ok(cnt) = data_clone.storage.count_missing_provider(MetadataProvider::Mal).await { let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
                     // chunked MAL
-                    let sql = if let Some(l)=limit { format!("SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mal_id IS NULL OR mal_id = 0 LIMIT {}", l) } else { "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mal_id IS NULL OR mal_id = 0".to_string() };
-                    let rows_vec = match db_client.query(&sql, &[]).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
+                    let rows_vec = match data_clone.storage.get_manga_missing_provider(MetadataProvider::Mal, limit.map(|l| l as i64)).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
                     let mut updated_mal = 0usize;
                     for row in rows_vec {
-                        let manga_id: String = row.get(0);
-                        let title: String = row.get(1);
-                        let alts: String = row.get(2);
+                        let manga_id = row.id;
+                        let title = row.title;
+                        let alts = row.alt_titles;
                         if *data_clone.metadata_cancel.lock().unwrap() { let mut p = data_clone.metadata_progress.lock().unwrap(); p.error=Some("cancelled".into()); p.in_progress=false; return; }
                         match crate::metadata::mal::resolve_id(&data_clone.client, &title, &alts).await { Ok(Some(mid)) => {
-                            let _ = db_client.execute("UPDATE manga SET mal_id = $1 WHERE id = $2", &[&mid, &manga_id]).await;
-                            let _ = db_client.execute("INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'mal', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = $2", &[&manga_id, &mid.to_string()]).await;
+                            let _ = data_clone.storage.set_provider_id(&manga_id, MetadataProvider::Mal, &mid.to_string()).await;
                             updated_mal += 1;
                         }, _=>{} }
                         { let mut p = data_clone.metadata_progress.lock().unwrap(); p.processed_in_phase += 1; }
@@ -2380,26 +2703,24 @@ async fn main() -> std::io::Result<()> {
                     { let mut p = data_clone.metadata_progress.lock().unwrap(); p.mal_updated += updated_mal; }
                     // step 3: anilist
                     { let mut p = data_clone.metadata_progress.lock().unwrap(); p.current_phase = Some("anilist_sync".into()); p.total_pending=None; p.processed_in_phase=0; }
-                    if let Ok(row) = db_client.query_one("SELECT COUNT(1) FROM manga WHERE anilist_id IS NULL OR anilist_id = 0", &[]).await { let cnt: i64 = row.get(0); let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
-                    let sql = if let Some(l)=limit { format!("SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE anilist_id IS NULL OR anilist_id = 0 LIMIT {}", l) } else { "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE anilist_id IS NULL OR anilist_id = 0".to_string() };
-                    let rows_vec = match db_client.query(&sql, &[]).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
+                    if let Ok(cnt) = data_clone.storage.count_missing_provider(MetadataProvider::AniList).await { let mut p = data_clone.metadata_progress.lock().unwrap(); p.total_pending = Some(cnt); }
+                    let rows_vec = match data_clone.storage.get_manga_missing_provider(MetadataProvider::AniList, limit.map(|l| l as i64)).await { Ok(r)=>r, Err(e)=>{ error!("query: {}", e); return; } };
                     let mut updated_ani = 0usize;
                     for row in rows_vec {
-                        let manga_id: String = row.get(0);
-                        let title: String = row.get(1);
-                        let alts: String = row.get(2);
+                        let manga_id = row.id;
+                        let title = row.title;
+                        let alts = row.alt_titles;
                         if *data_clone.metadata_cancel.lock().unwrap() { let mut p = data_clone.metadata_progress.lock().unwrap(); p.error=Some("cancelled".into()); p.in_progress=false; return; }
                         match crate::metadata::anilist::resolve_id(&data_clone.client, &title, &alts).await { Ok(Some(aid)) => {
-                            let _ = db_client.execute("UPDATE manga SET anilist_id = $1 WHERE id = $2", &[&aid, &manga_id]).await;
-                            let _ = db_client.execute("INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'anilist', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = $2", &[&manga_id, &aid.to_string()]).await;
+                            let _ = data_clone.storage.set_provider_id(&manga_id, MetadataProvider::AniList, &aid.to_string()).await;
                             updated_ani += 1;
                         }, _=>{} }
                         { let mut p = data_clone.metadata_progress.lock().unwrap(); p.processed_in_phase += 1; }
                     }
                     { let mut p = data_clone.metadata_progress.lock().unwrap(); p.anilist_updated += updated_ani; }
-                    // step 4: merge (fast) - now using PostgreSQL
+                    // step 4: merge (fast)
                     { let mut p = data_clone.metadata_progress.lock().unwrap(); p.current_phase = Some("merge".into()); p.total_pending=None; }
-                    match metadata::aggregate::merge_only(&data_clone.pool, &data_clone.client).await {
+                    match metadata::aggregate::merge_only(data_clone.storage.as_ref(), &data_clone.client).await {
                         Ok(n)=>{
                             let mut p=data_clone.metadata_progress.lock().unwrap();
                             p.merged_updated=n;
@@ -2423,27 +2744,23 @@ async fn main() -> std::io::Result<()> {
             }))
             .route("/verify/source/{source}", web::get().to(|data: web::Data<AppState>, source: web::Path<String>| async move {
                 use serde_json::json;
-                let client = match data.pool.get().await {
-                    Ok(c) => c,
-                    Err(_) => return HttpResponse::InternalServerError().finish(),
-                };
                 // Map source to id
-                let sid = if let Some(s) = parse_source(&source) { s as i32 } else if let Some((id,_)) = wp_manga_source_by_name(&source.to_lowercase()) { id } else { -1 };
-                if sid < 0 { return HttpResponse::BadRequest().json(json!({"error":"unknown source"})); }
+                let sid = match registry::find(&source) { Some(s) => s.id(), None => return HttpResponse::BadRequest().json(json!({"error":"unknown source"})) };
                 // Counts
-                let manga_count: i64 = client.query_one("SELECT COUNT(DISTINCT m.id) FROM manga m JOIN manga_source_data msd ON msd.manga_id=m.id WHERE msd.source_id = $1", &[&sid]).await.ok().and_then(|row| row.get(0)).unwrap_or(0);
-                let chapter_count: i64 = client.query_one("SELECT COUNT(1) FROM chapters c JOIN manga_source_data msd ON c.manga_source_data_id=msd.id WHERE msd.source_id = $1", &[&sid]).await.ok().and_then(|row| row.get(0)).unwrap_or(0);
-                let meta_count: i64 = client.query_one("SELECT COUNT(DISTINCT m.id) FROM manga m JOIN manga_source_data msd ON msd.manga_id=m.id WHERE msd.source_id=$1 AND (COALESCE(m.mangabaka_id,'')<>'' OR COALESCE(m.mal_id,0)>0 OR COALESCE(m.anilist_id,0)>0)", &[&sid]).await.ok().and_then(|row| row.get(0)).unwrap_or(0);
+                let stats = match data.storage.get_source_stats(sid).await {
+                    Ok(s) => s,
+                    Err(_) => return HttpResponse::InternalServerError().finish(),
+                };
                 // One download attempt
-                let rows = client.query("SELECT m.id, c.chapter_number, c.url, msd.source_manga_url FROM manga m JOIN manga_source_data msd ON msd.manga_id=m.id JOIN chapters c ON c.manga_source_data_id=msd.id WHERE msd.source_id = $1 LIMIT 1", &[&sid]).await;
-                let (download_ok, download_error) = if let Ok(rows_vec) = rows {
-                    if let Some(row) = rows_vec.get(0) {
-                        let mid: String = row.get(0);
-                        let ch: String = row.get(1);
-                        let url: String = row.get(2);
-                        let series_url: String = row.get(3);
+                let sample = data.storage.get_sample_chapter(sid).await;
+                let (download_ok, download_error) = if let Ok(sample) = sample {
+                    if let Some(sample) = sample {
+                        let mid = sample.manga_id;
+                        let ch = sample.chapter_number;
+                        let url = sample.url;
+                        let series_url = sample.series_url;
                     let full_url = if url.starts_with("http") { url.clone() } else { reqwest::Url::parse(&series_url).and_then(|b| b.join(&url)).map(|u| u.to_string()).unwrap_or(url.clone()) };
-                        match scraper::download_chapter_to_memory(&data.client, sid, &full_url).await {
+                        match scraper::download_chapter_to_memory(&data.client, &data.page_fetcher, sid, &full_url, None, OutputFormat::Cbz).await {
                             Ok(bytes) => (true, Some(json!({"manga_id":mid,"chapter":ch,"bytes":bytes.len()}))),
                             Err(e) => (false, Some(json!({"manga_id":mid,"chapter":ch,"error":e.to_string()}))),
                         }
@@ -2452,9 +2769,9 @@ async fn main() -> std::io::Result<()> {
                 HttpResponse::Ok().json(json!({
                     "source": source.to_string(),
                     "source_id": sid,
-                    "manga_count": manga_count,
-                    "chapter_count": chapter_count,
-                    "metadata_linked_manga": meta_count,
+                    "manga_count": stats.manga,
+                    "chapter_count": stats.chapters,
+                    "metadata_linked_manga": stats.metadata_linked,
                     "download": { "ok": download_ok, "detail": download_error }
                 }))
             }))
@@ -2466,27 +2783,32 @@ async fn main() -> std::io::Result<()> {
                 // Derive a basic title from slug
                 let slug = url.trim_end_matches('/').rsplit('/').next().unwrap_or("");
                 let title = if slug.is_empty() { "Kagane Series".to_string() } else { slug.replace(['-','_'], " ") };
-                let manga = Manga { id: uuid::Uuid::new_v4().to_string(), title, alt_titles: None, cover_url: None, description: None, tags: None, rating: None, monitored: None, check_interval_secs: None, discover_interval_secs: None, last_chapter_check: None, last_discover_check: None };
-                let _ = pg_db::insert_manga(&data.pool, &manga).await;
+                let manga = Manga { id: uuid::Uuid::new_v4().to_string(), title, alt_titles: None, cover_url: None, description: None, tags: None, rating: None, monitored: None, check_interval_secs: None, discover_interval_secs: None, last_chapter_check: None, last_discover_check: None, status: None, people: Vec::new() };
+                let _ = data.storage.insert_manga(&manga).await;
                 // Kagane source MSD
                 let msd = MangaSourceData { manga_id: manga.id.clone(), source_id: Source::Kagane as i32, source_manga_id: url.clone(), source_manga_url: url.clone() };
-                let msd_id = match pg_db::insert_manga_source_data(&data.pool, &msd).await { Ok(id)=>id, Err(_)=>{ return HttpResponse::InternalServerError().finish(); } };
+                let msd_id = match data.storage.insert_manga_source_data(&msd).await { Ok(id)=>id, Err(_)=>{ return HttpResponse::InternalServerError().finish(); } };
                 // Chapters from Kagane
-                let chs = crate::sources::kagane::get_chapters(client, url).await.unwrap_or_default();
+                let kagane = registry::get(Source::Kagane as i32).expect("Kagane is registered");
+                let chs = kagane.get_chapters(client, &msd).await.unwrap_or_default();
                 let chs_limited: Vec<_> = chs.into_iter().take(limit_ch).collect();
-                let _ = pg_db::insert_chapters(&data.pool, msd_id, &chs_limited).await;
+                let _ = data.storage.insert_chapters(msd_id, &chs_limited).await;
                 // External providers
-                for (sid, link) in crate::sources::kagane::extract_provider_links(client, url).await.into_iter().take(20) {
+                for (sid, link) in kagane.provider_links(client, url).await.into_iter().take(20) {
                     let extra = MangaSourceData { manga_id: manga.id.clone(), source_id: sid, source_manga_id: link.clone(), source_manga_url: link };
-                    let _ = pg_db::insert_manga_source_data(&data.pool, &extra).await;
+                    let _ = data.storage.insert_manga_source_data(&extra).await;
                 }
                 HttpResponse::Ok().json(json!({"ok":true, "manga_id": manga.id, "title": manga.title, "chapters_added": chs_limited.len()}))
             }))
-        })
-        .bind(&addr)
-        {
+        });
+        let bound = match server_cfg.tls_acceptor()? {
+            Some(tls) => server.bind_openssl(&addr, tls),
+            None => server.bind(&addr),
+        };
+        match bound {
             Ok(server) => {
-                info!("Listening on {}", addr);
+                let scheme = if server_cfg.tls_cert.is_some() { "https" } else { "http" };
+                info!("Listening on {}://{}", scheme, addr);
                 return server.run().await;
             }
             Err(e) => {
@@ -2498,7 +2820,7 @@ async fn main() -> std::io::Result<()> {
     Err(last_err.unwrap_or_else(|| {
         std::io::Error::new(
             std::io::ErrorKind::AddrInUse,
-            "No available ports 8080-8090",
+            "No available port to listen on",
         )
     }))
 }
@@ -0,0 +1,512 @@
+//! Fuzzy cross-source series matching.
+//!
+//! The crawler only groups manga whose titles are equal after
+//! `crawler::normalize_title`, so "Solo Leveling" and "Solo Leveling (Official)"
+//! end up as separate rows. `POST /matching/run` compares every pair of manga
+//! that share a title token or an external provider ID and scores them:
+//!
+//! - the same MAL/AniList/MangaBaka ID is a certain match, a different ID for
+//!   the same provider rules the pair out;
+//! - otherwise the best title similarity over both manga's titles and alt
+//!   titles, nudged up or down by how alike their cover images are.
+//!
+//! Pairs scoring at least `matching_min_score` are stored as merge proposals
+//! for review (`/matching/proposals`). When `matching_auto_merge_threshold` is
+//! set, pairs at or above it are merged right away with
+//! [`crate::storage::Storage::merge_manga`] and recorded as automatic proposals; like any merge
+//! they can be undone through `/audit`.
+
+use crate::app_state::AppState;
+use crate::storage::MatchCandidate;
+use actix_web::web;
+use chrono::Utc;
+use image::imageops::FilterType;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Tokens shared by more manga than this ("the", "of", "manga", ...) are too
+/// common to suggest a match on their own.
+const MAX_TOKEN_SHARE: usize = 50;
+/// Words dropped when comparing titles.
+const NOISE_WORDS: &[&str] = &["a", "an", "the", "official"];
+/// Score change when the covers look alike (or clearly differ).
+const COVER_ADJUSTMENT: f64 = 0.15;
+/// Hamming distance between cover hashes at or below which covers look alike.
+const COVER_SAME_DISTANCE: u32 = 8;
+/// Hamming distance at or above which covers are clearly different images.
+const COVER_DIFFERENT_DISTANCE: u32 = 20;
+
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct MatchingProgress {
+    pub in_progress: bool,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub manga_total: usize,
+    pub pairs_total: usize,
+    pub pairs_compared: usize,
+    pub covers_hashed: usize,
+    /// New proposals waiting for review
+    pub proposed: usize,
+    pub auto_merged: usize,
+    pub error: Option<String>,
+}
+
+/// How alike two manga are, with the evidence behind the score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchScore {
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+/// Lowercase `title`, drop bracketed qualifiers such as "(Official)" or
+/// "[Webtoon]", turn punctuation into spaces and drop [`NOISE_WORDS`].
+pub fn normalize_for_match(title: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0usize;
+    for c in title.to_lowercase().chars() {
+        match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                out.push(' ');
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                out.push(' ');
+            }
+            _ if depth > 0 => {}
+            '\'' | '\u{2019}' => {}
+            c if c.is_alphanumeric() => out.push(c),
+            _ => out.push(' '),
+        }
+    }
+    out.split_whitespace()
+        .filter(|t| !NOISE_WORDS.contains(t))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A manga's title followed by its alt titles.
+fn titles(candidate: &MatchCandidate) -> impl Iterator<Item = &str> {
+    std::iter::once(candidate.title.as_str()).chain(
+        candidate
+            .alt_titles
+            .as_deref()
+            .unwrap_or("")
+            .split(", ")
+            .map(str::trim)
+            .filter(|t| !t.is_empty()),
+    )
+}
+
+fn dice<T: Eq + std::hash::Hash>(a: &HashSet<T>, b: &HashSet<T>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+fn bigrams(s: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() == 1 {
+        return HashSet::from([(chars[0], ' ')]);
+    }
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Similarity of two titles from 0 to 1: 1 when they normalize to the same
+/// words, otherwise the mean of word overlap and character-bigram overlap.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_for_match(a), normalize_for_match(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a.replace(' ', "") == b.replace(' ', "") {
+        return 1.0;
+    }
+    let words = |s: &str| s.split(' ').map(str::to_string).collect::<HashSet<_>>();
+    0.5 * dice(&words(&a), &words(&b)) + 0.5 * dice(&bigrams(&a), &bigrams(&b))
+}
+
+/// Best [`title_similarity`] over every title/alt title combination.
+fn best_title_similarity(a: &MatchCandidate, b: &MatchCandidate) -> f64 {
+    let mut best = 0.0f64;
+    for ta in titles(a) {
+        for tb in titles(b) {
+            best = best.max(title_similarity(ta, tb));
+            if best >= 1.0 {
+                return best;
+            }
+        }
+    }
+    best
+}
+
+/// 64-bit difference hash of an image: shrink to 9x8 grayscale and record
+/// whether each pixel is darker than its right neighbour. Re-encoded or
+/// resized copies of the same cover land within a few bits of each other.
+pub fn dhash(bytes: &[u8]) -> Option<u64> {
+    let img = image::load_from_memory(bytes).ok()?;
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn providers(candidate: &MatchCandidate) -> HashMap<&str, &str> {
+    candidate
+        .provider_ids
+        .iter()
+        .filter_map(|p| p.split_once(':'))
+        .collect()
+}
+
+/// Score a pair of manga, or `None` when they carry different IDs for the
+/// same provider and so cannot be the same series.
+pub fn score_pair(a: &MatchCandidate, b: &MatchCandidate) -> Option<MatchScore> {
+    let (pa, pb) = (providers(a), providers(b));
+    let mut shared = Vec::new();
+    for (provider, id) in &pa {
+        match pb.get(provider) {
+            Some(other) if other == id => shared.push(format!("same {} id {}", provider, id)),
+            Some(_) => return None,
+            None => {}
+        }
+    }
+    if !shared.is_empty() {
+        shared.sort();
+        return Some(MatchScore {
+            score: 1.0,
+            reasons: shared,
+        });
+    }
+
+    let title = best_title_similarity(a, b);
+    let mut score = title;
+    let mut reasons = vec![format!("title {:.2}", title)];
+    if let (Some(ha), Some(hb)) = (a.cover_hash, b.cover_hash) {
+        let distance = hamming(ha as u64, hb as u64);
+        if distance <= COVER_SAME_DISTANCE {
+            score += COVER_ADJUSTMENT;
+        } else if distance >= COVER_DIFFERENT_DISTANCE {
+            score -= COVER_ADJUSTMENT;
+        }
+        reasons.push(format!("cover distance {}", distance));
+    }
+    // Only a shared provider ID makes a pair certain.
+    Some(MatchScore {
+        score: score.clamp(0.0, 0.99),
+        reasons,
+    })
+}
+
+/// Index pairs `(i, j)` with `i < j` worth scoring: manga sharing an
+/// uncommon title word or a provider ID.
+pub fn candidate_pairs(candidates: &[MatchCandidate]) -> BTreeSet<(usize, usize)> {
+    let mut index: HashMap<String, BTreeSet<usize>> = HashMap::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        for title in titles(candidate) {
+            for word in normalize_for_match(title)
+                .split(' ')
+                .filter(|w| !w.is_empty())
+            {
+                index.entry(word.to_string()).or_default().insert(i);
+            }
+        }
+        for provider_id in &candidate.provider_ids {
+            index
+                .entry(format!("\0{}", provider_id))
+                .or_default()
+                .insert(i);
+        }
+    }
+
+    let mut pairs = BTreeSet::new();
+    for (key, members) in &index {
+        if members.len() > MAX_TOKEN_SHARE && !key.starts_with('\0') {
+            continue;
+        }
+        let members: Vec<usize> = members.iter().copied().collect();
+        for (n, &i) in members.iter().enumerate() {
+            for &j in &members[n + 1..] {
+                pairs.insert((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+/// Start a matching run. Returns `false` if one is already running.
+pub fn spawn_matching(data: web::Data<AppState>) -> bool {
+    {
+        let mut p = data.matching_job.lock().unwrap();
+        if p.in_progress {
+            return false;
+        }
+        *p = MatchingProgress {
+            in_progress: true,
+            started_at: Some(Utc::now().timestamp()),
+            ..Default::default()
+        };
+    }
+
+    actix_web::rt::spawn(async move {
+        let result = run(&data).await;
+        finish(&data, result.err());
+    });
+    true
+}
+
+async fn run(data: &web::Data<AppState>) -> Result<(), String> {
+    let mut candidates = data.storage.get_match_candidates()
+        .await
+        .map_err(|e| e.to_string())?;
+    let pairs = candidate_pairs(&candidates);
+    {
+        let mut p = data.matching_job.lock().unwrap();
+        p.manga_total = candidates.len();
+        p.pairs_total = pairs.len();
+    }
+    info!(
+        "Series matching started: {} manga, {} pairs",
+        candidates.len(),
+        pairs.len()
+    );
+
+    let min_score = data.config.matching_min_score;
+    let auto_threshold = data.config.matching_auto_merge_threshold;
+    let mut merged_away = HashSet::new();
+    let mut cover_tried = HashSet::new();
+
+    for (i, j) in pairs {
+        if merged_away.contains(&i) || merged_away.contains(&j) {
+            continue;
+        }
+        data.matching_job.lock().unwrap().pairs_compared += 1;
+        let Some(mut found) = score_pair(&candidates[i], &candidates[j]) else {
+            continue;
+        };
+        // Covers only matter when they could tip the decision.
+        let undecided = found.score < 1.0 && found.score + COVER_ADJUSTMENT >= min_score;
+        if undecided && (candidates[i].cover_hash.is_none() || candidates[j].cover_hash.is_none()) {
+            for k in [i, j] {
+                if cover_tried.insert(k) {
+                    hash_cover(data, &mut candidates[k]).await;
+                }
+            }
+            found = match score_pair(&candidates[i], &candidates[j]) {
+                Some(found) => found,
+                None => continue,
+            };
+        }
+        if found.score < min_score {
+            continue;
+        }
+
+        let (a, b) = (&candidates[i], &candidates[j]);
+        let reasons = found.reasons.join("; ");
+        let automatic = auto_threshold.is_some_and(|t| found.score >= t);
+        let now = Utc::now().timestamp();
+        let proposal = match data.storage.insert_merge_proposal(
+            (&a.id, &a.title),
+            (&b.id, &b.title),
+            found.score,
+            &reasons,
+            automatic,
+            now,
+        )
+        .await
+        {
+            Ok(Some(id)) => id,
+            // Proposed before: leave the reviewer's decision alone.
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to record merge proposal {} / {}: {}", a.id, b.id, e);
+                continue;
+            }
+        };
+        if !automatic {
+            data.matching_job.lock().unwrap().proposed += 1;
+            continue;
+        }
+
+        // Keep the manga with more provider IDs; ties keep the first.
+        let (keep, other) = if b.provider_ids.len() > a.provider_ids.len() {
+            (j, i)
+        } else {
+            (i, j)
+        };
+        match data.storage.merge_manga(&candidates[keep].id, &candidates[other].id, now).await
+        {
+            Ok(audit_id) => {
+                info!(
+                    "Merged \"{}\" into \"{}\" ({}; undo with audit entry {})",
+                    candidates[other].title, candidates[keep].title, reasons, audit_id
+                );
+                merged_away.insert(other);
+                data.matching_job.lock().unwrap().auto_merged += 1;
+            }
+            Err(e) => {
+                warn!("Automatic merge of proposal {} failed: {}", proposal, e);
+                let reopened = data.storage.set_merge_proposal_state(
+                    proposal, "accepted", "pending", now,
+                )
+                .await;
+                if reopened.unwrap_or(false) {
+                    data.matching_job.lock().unwrap().proposed += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fetch and hash a manga's cover unless it already has a hash, caching the
+/// result on the manga row. Failures just leave the cover out of the score.
+async fn hash_cover(data: &web::Data<AppState>, candidate: &mut MatchCandidate) {
+    if candidate.cover_hash.is_some() {
+        return;
+    }
+    let Some(url) = candidate.cover_url.as_deref().filter(|u| !u.is_empty()) else {
+        return;
+    };
+    let bytes = match data.client.get(url).send().await {
+        Ok(resp) if resp.status().is_success() => match resp.bytes().await {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    let Ok(Some(hash)) = tokio::task::spawn_blocking(move || dhash(&bytes)).await else {
+        return;
+    };
+    candidate.cover_hash = Some(hash as i64);
+    data.matching_job.lock().unwrap().covers_hashed += 1;
+    if let Err(e) = data.storage.set_cover_hash(&candidate.id, hash as i64).await {
+        warn!("Failed to store cover hash for {}: {}", candidate.id, e);
+    }
+}
+
+fn finish(data: &web::Data<AppState>, error: Option<String>) {
+    let mut p = data.matching_job.lock().unwrap();
+    if let Some(e) = &error {
+        error!("Series matching failed: {}", e);
+    } else {
+        info!(
+            "Series matching finished: {} pairs compared, {} proposed, {} merged",
+            p.pairs_compared, p.proposed, p.auto_merged
+        );
+    }
+    p.in_progress = false;
+    p.finished_at = Some(Utc::now().timestamp());
+    p.error = error;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma};
+    use std::io::Cursor;
+
+    fn candidate(id: &str, title: &str, alt: &str, providers: &[&str]) -> MatchCandidate {
+        MatchCandidate {
+            id: id.to_string(),
+            title: title.to_string(),
+            alt_titles: (!alt.is_empty()).then(|| alt.to_string()),
+            cover_url: None,
+            cover_hash: None,
+            provider_ids: providers.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_for_match() {
+        assert_eq!(
+            normalize_for_match("Solo Leveling (Official)"),
+            "solo leveling"
+        );
+        assert_eq!(
+            normalize_for_match("The Beginning After the End [Webtoon]"),
+            "beginning after end"
+        );
+        assert_eq!(normalize_for_match("Hero's Return!"), "heros return");
+    }
+
+    #[test]
+    fn test_title_similarity() {
+        assert_eq!(
+            title_similarity("Solo Leveling", "Solo Leveling (Official)"),
+            1.0
+        );
+        assert_eq!(title_similarity("Solo-Leveling", "SoloLeveling"), 1.0);
+        assert!(title_similarity("Omniscient Reader", "Omniscient Readers Viewpoint") > 0.5);
+        assert!(title_similarity("Solo Leveling", "Tower of God") < 0.3);
+        assert_eq!(title_similarity("", "Tower of God"), 0.0);
+    }
+
+    #[test]
+    fn test_score_pair_uses_provider_ids_and_alt_titles() {
+        let a = candidate("a", "Na Honjaman Level Up", "", &["mal:121496"]);
+        let b = candidate("b", "Solo Leveling", "", &["mal:121496", "anilist:105398"]);
+        let score = score_pair(&a, &b).unwrap();
+        assert_eq!(score.score, 1.0);
+        assert_eq!(score.reasons, vec!["same mal id 121496"]);
+
+        let c = candidate("c", "Solo Leveling", "", &["mal:1"]);
+        assert_eq!(score_pair(&b, &c), None);
+
+        let d = candidate(
+            "d",
+            "Ore dake Level Up na Ken",
+            "Solo Leveling, 나 혼자만 레벨업",
+            &[],
+        );
+        let score = score_pair(&d, &candidate("e", "Solo Leveling", "", &[])).unwrap();
+        assert_eq!(score.score, 0.99);
+    }
+
+    #[test]
+    fn test_cover_hash_adjusts_score() {
+        let gradient = ImageBuffer::from_fn(90, 80, |x, y| Luma([((x * 2 + y) % 256) as u8]));
+        let mut png = Cursor::new(Vec::new());
+        gradient
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let hash = dhash(png.get_ref()).unwrap();
+        assert_eq!(hamming(hash, hash), 0);
+
+        let mut a = candidate("a", "Tower of God", "", &[]);
+        let mut b = candidate("b", "Tower of Gods", "", &[]);
+        let plain = score_pair(&a, &b).unwrap().score;
+        a.cover_hash = Some(hash as i64);
+        b.cover_hash = Some(hash as i64);
+        assert!(score_pair(&a, &b).unwrap().score > plain);
+        b.cover_hash = Some(!hash as i64);
+        let different = score_pair(&a, &b).unwrap();
+        assert!(different.score < plain);
+        assert_eq!(different.reasons[1], "cover distance 64");
+    }
+
+    #[test]
+    fn test_candidate_pairs() {
+        let candidates = vec![
+            candidate("a", "Solo Leveling", "", &[]),
+            candidate("b", "Tower of God", "", &["mal:1"]),
+            candidate("c", "Solo Leveling (Official)", "", &[]),
+            candidate("d", "Kami no Tou", "", &["mal:1"]),
+        ];
+        let pairs: Vec<_> = candidate_pairs(&candidates).into_iter().collect();
+        assert_eq!(pairs, vec![(0, 2), (1, 3)]);
+    }
+}
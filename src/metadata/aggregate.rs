@@ -1,66 +1,66 @@
 use reqwest::Client;
-use deadpool_postgres::Pool;
+use crate::people;
+use crate::storage::Storage;
+use crate::tags::{self, TagRef};
 use std::error::Error;
 
 // Combine metadata from providers into manga.description, manga.tags, manga.rating
+// and the manga's credits
 #[allow(dead_code)]
-pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Error>> {
+pub async fn sync_all(storage: &dyn Storage, client: &Client) -> Result<usize, Box<dyn Error>> {
     // Ensure provider IDs exist first
-    let _ = super::mangabaka::sync_all(pool, client).await;
-    let _ = super::mal::sync_all(pool, client).await;
-    let _ = super::anilist::sync_all(pool, client).await;
-    merge_only(pool, client).await
+    let _ = super::mangabaka::sync_all(storage, client).await;
+    let _ = super::mal::sync_all(storage, client).await;
+    let _ = super::anilist::sync_all(storage, client).await;
+    merge_only(storage, client).await
 }
 
 // Merge using existing provider IDs without running provider syncs
-pub async fn merge_only(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Error>> {
-    let db_client = pool.get().await.expect("Failed to get connection from pool");
-
-    let rows = db_client.query("SELECT id, COALESCE(mangabaka_id,''), COALESCE(mal_id,0), COALESCE(anilist_id,0), title FROM manga", &[]).await?;
+pub async fn merge_only(storage: &dyn Storage, client: &Client) -> Result<usize, Box<dyn Error>> {
+    let rows = storage.get_provider_links().await?;
 
     let mut updated = 0usize;
-    for row in rows.iter() {
-        let manga_id: String = row.get(0);
-        let _mangabaka_id: String = row.get(1);
-        let mal_id: i64 = row.get(2);
-        let anilist_id: i64 = row.get(3);
-        let _title: String = row.get(4);
+    for row in rows {
+        let manga_id = row.manga_id;
+        let _mangabaka_id = row.mangabaka_id;
+        let mal_id = row.mal_id;
+        let anilist_id = row.anilist_id;
 
         let mut descr: Option<String> = None;
-        let mut tags: Vec<String> = Vec::new();
+        // Canonicalized per provider, so MAL "Sci-Fi" and AniList "Sci Fi" are one tag
+        let mut tags: Vec<TagRef> = Vec::new();
         let mut rating: Option<String> = None;
+        // Per provider; AniList knows who wrote and who drew, MAL only "authors"
+        let (mut baka_credits, mut mal_credits, mut anilist_credits) = (vec![], vec![], vec![]);
         // MangaBaka details
         if !_mangabaka_id.is_empty() {
-            if let Ok((d, g)) = super::mangabaka::fetch_details(client, &_mangabaka_id).await {
+            if let Ok((d, g, c)) = super::mangabaka::fetch_details(client, &_mangabaka_id).await {
                 if descr.is_none() {
                     descr = d;
                 }
-                if !g.is_empty() {
-                    tags.extend(g);
-                }
+                tags.extend(tags::canonicalize_all(Some("mangabaka"), g));
+                baka_credits = c;
             }
         }
         if mal_id > 0 {
-            if let Ok((d, g, r)) = super::mal::fetch_details(client, mal_id).await {
+            if let Ok((d, g, r, c)) = super::mal::fetch_details(client, mal_id).await {
                 if descr.is_none() {
                     descr = d;
                 }
-                if !g.is_empty() {
-                    tags.extend(g);
-                }
+                tags.extend(tags::canonicalize_all(Some("mal"), g));
+                mal_credits = c;
                 if rating.is_none() {
                     rating = r;
                 }
             }
         }
         if anilist_id > 0 {
-            if let Ok((d, g, adult)) = super::anilist::fetch_details(client, anilist_id).await {
+            if let Ok((d, g, adult, c)) = super::anilist::fetch_details(client, anilist_id).await {
                 if descr.is_none() {
                     descr = d;
                 }
-                if !g.is_empty() {
-                    tags.extend(g);
-                }
+                tags.extend(tags::canonicalize_all(Some("anilist"), g));
+                anilist_credits = c;
                 if rating.is_none() {
                     if let Some(is_adult) = adult {
                         if is_adult {
@@ -70,20 +70,23 @@ pub async fn merge_only(pool: &Pool, client: &Client) -> Result<usize, Box<dyn E
                 }
             }
         }
-        tags.sort();
-        tags.dedup();
-        let tags_str = if tags.is_empty() {
-            None
-        } else {
-            Some(tags.join(", "))
-        };
+        let mut seen = std::collections::HashSet::new();
+        tags.retain(|t| seen.insert(t.slug.clone()));
         let desc_str = descr.as_deref().filter(|s| !s.trim().is_empty());
         let rating_str = rating.as_deref();
-        if desc_str.is_some() || tags_str.is_some() || rating_str.is_some() {
-            db_client.execute(
-                "UPDATE manga SET description = COALESCE($1, description), tags = COALESCE($2, tags), rating = COALESCE($3, rating) WHERE id = $4",
-                &[&desc_str, &tags_str, &rating_str, &manga_id],
-            ).await?;
+        let credits = people::prefer(&[anilist_credits, baka_credits, mal_credits]);
+        if !tags.is_empty() {
+            storage.set_manga_tags(&manga_id, &tags).await?;
+        }
+        if !credits.is_empty() {
+            storage.set_manga_people(&manga_id, &credits).await?;
+        }
+        if desc_str.is_some() || rating_str.is_some() {
+            storage
+                .update_manga_metadata(&manga_id, desc_str, None, rating_str)
+                .await?;
+        }
+        if desc_str.is_some() || !tags.is_empty() || rating_str.is_some() || !credits.is_empty() {
             updated += 1;
         }
     }
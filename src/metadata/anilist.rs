@@ -1,5 +1,7 @@
 use reqwest::Client;
-use deadpool_postgres::Pool;
+use crate::models::Credit;
+use crate::people;
+use crate::storage::{MetadataProvider, Storage};
 use serde_json::Value;
 use std::error::Error;
 
@@ -56,34 +58,20 @@ pub async fn resolve_id(
 }
 
 #[allow(dead_code)]
-pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Error>> {
-    let db_client = pool.get().await.expect("Failed to get connection from pool");
-
-    let rows = db_client
-        .query(
-            "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE anilist_id IS NULL OR anilist_id = 0",
-            &[],
-        )
+pub async fn sync_all(storage: &dyn Storage, client: &Client) -> Result<usize, Box<dyn Error>> {
+    let rows = storage
+        .get_manga_missing_provider(MetadataProvider::AniList, None)
         .await?;
 
     let mut updated = 0usize;
     for row in rows {
-        let manga_id: String = row.get(0);
-        let title: String = row.get(1);
-        let alts: String = row.get(2);
+        let manga_id = row.id;
+        let title = row.title;
+        let alts = row.alt_titles;
 
         if let Some(aid) = resolve_id(client, &title, &alts).await? {
-            db_client
-                .execute(
-                    "UPDATE manga SET anilist_id = $1 WHERE id = $2",
-                    &[&aid, &manga_id],
-                )
-                .await?;
-            db_client
-                .execute(
-                    "INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'anilist', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = EXCLUDED.provider_id",
-                    &[&manga_id, &aid.to_string()],
-                )
+            storage
+                .set_provider_id(&manga_id, MetadataProvider::AniList, &aid.to_string())
                 .await?;
             updated += 1;
         }
@@ -94,10 +82,10 @@ pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Err
 pub async fn fetch_details(
     client: &Client,
     anilist_id: i64,
-) -> Result<(Option<String>, Vec<String>, Option<bool>), Box<dyn Error>> {
-    // returns (description, genres, isAdult)
+) -> Result<(Option<String>, Vec<String>, Option<bool>, Vec<Credit>), Box<dyn Error>> {
+    // returns (description, genres, isAdult, credits)
     let query = serde_json::json!({
-        "query": "query ($id: Int) { Media(id: $id, type: MANGA) { id description(asHtml: false) genres isAdult } }",
+        "query": "query ($id: Int) { Media(id: $id, type: MANGA) { id description(asHtml: false) genres isAdult staff(sort: RELEVANCE, perPage: 25) { edges { role node { name { full } } } } } }",
         "variables": {"id": anilist_id}
     });
     let res = client
@@ -106,7 +94,7 @@ pub async fn fetch_details(
         .send()
         .await?;
     if !res.status().is_success() {
-        return Ok((None, vec![], None));
+        return Ok((None, vec![], None, vec![]));
     }
     let txt = res.text().await?;
     let json: Value = serde_json::from_str(&txt)?;
@@ -129,5 +117,17 @@ pub async fn fetch_details(
         })
         .unwrap_or_else(Vec::new);
     let is_adult = media.get("isAdult").and_then(|v| v.as_bool());
-    Ok((desc, genres, Some(is_adult.unwrap_or(false))))
+    let mut credits = Vec::new();
+    if let Some(edges) = media.pointer("/staff/edges").and_then(|v| v.as_array()) {
+        for edge in edges {
+            let role = edge.get("role").and_then(|v| v.as_str()).unwrap_or("");
+            let Some(name) = edge.pointer("/node/name/full").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            for r in people::anilist_roles(role) {
+                credits.extend(people::credit(name, r));
+            }
+        }
+    }
+    Ok((desc, genres, Some(is_adult.unwrap_or(false)), people::dedup(credits)))
 }
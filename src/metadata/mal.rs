@@ -1,5 +1,7 @@
 use reqwest::Client;
-use deadpool_postgres::Pool;
+use crate::models::Credit;
+use crate::people::{self, PersonRole};
+use crate::storage::{MetadataProvider, Storage};
 use serde_json::Value;
 use std::error::Error;
 
@@ -45,30 +47,18 @@ pub async fn resolve_id(
 }
 
 #[allow(dead_code)]
-pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Error>> {
-    let db_client = pool.get().await.expect("Failed to get connection from pool");
-
+pub async fn sync_all(storage: &dyn Storage, client: &Client) -> Result<usize, Box<dyn Error>> {
     // Find manga missing mal_id
-    let rows = db_client.query(
-        "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mal_id IS NULL OR mal_id = 0",
-        &[],
-    ).await?;
+    let rows = storage.get_manga_missing_provider(MetadataProvider::Mal, None).await?;
 
     let mut updated = 0usize;
     for row in rows {
-        let manga_id: String = row.get(0);
-        let title: String = row.get(1);
-        let alts: String = row.get(2);
+        let manga_id = row.id;
+        let title = row.title;
+        let alts = row.alt_titles;
 
         if let Some(mal_id) = resolve_id(client, &title, &alts).await? {
-            db_client.execute(
-                "UPDATE manga SET mal_id = $1 WHERE id = $2",
-                &[&mal_id, &manga_id],
-            ).await?;
-            db_client.execute(
-                "INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'mal', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = $2",
-                &[&manga_id, &mal_id.to_string()],
-            ).await?;
+            storage.set_provider_id(&manga_id, MetadataProvider::Mal, &mal_id.to_string()).await?;
             updated += 1;
         }
     }
@@ -78,12 +68,12 @@ pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Err
 pub async fn fetch_details(
     client: &Client,
     mal_id: i64,
-) -> Result<(Option<String>, Vec<String>, Option<String>), Box<dyn Error>> {
-    // returns (description, genres, rating)
+) -> Result<(Option<String>, Vec<String>, Option<String>, Vec<Credit>), Box<dyn Error>> {
+    // returns (description, genres, rating, credits)
     let url = format!("https://api.jikan.moe/v4/manga/{}", mal_id);
     let res = client.get(&url).send().await?;
     if !res.status().is_success() {
-        return Ok((None, vec![], None));
+        return Ok((None, vec![], None, vec![]));
     }
     let txt = res.text().await?;
     let json: Value = serde_json::from_str(&txt)?;
@@ -104,5 +94,17 @@ pub async fn fetch_details(
         .get("rating")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
-    Ok((desc, genres, rating))
+    // Jikan does not say who drew and who wrote, so every author is credited
+    // as one; serializations are the magazines that published the work
+    let mut credits = Vec::new();
+    for (key, role) in [("authors", PersonRole::Author), ("serializations", PersonRole::Publisher)] {
+        if let Some(arr) = data.get(key).and_then(|v| v.as_array()) {
+            for p in arr {
+                if let Some(n) = p.get("name").and_then(|v| v.as_str()) {
+                    credits.extend(people::credit(&people::given_name_first(n), role));
+                }
+            }
+        }
+    }
+    Ok((desc, genres, rating, credits))
 }
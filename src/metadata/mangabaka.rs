@@ -1,5 +1,7 @@
 use reqwest::Client;
-use deadpool_postgres::Pool;
+use crate::models::Credit;
+use crate::people::{self, PersonRole};
+use crate::storage::{MetadataProvider, Storage};
 use serde_json::Value;
 use std::error::Error;
 
@@ -63,12 +65,12 @@ pub async fn resolve_id(
 pub async fn fetch_details(
     client: &Client,
     pid: &str,
-) -> Result<(Option<String>, Vec<String>), Box<dyn Error>> {
+) -> Result<(Option<String>, Vec<String>, Vec<Credit>), Box<dyn Error>> {
     // best effort; ignore errors
     let url = format!("https://mangabaka.dev/api/manga/{}", pid);
     let res = client.get(&url).send().await?;
     if !res.status().is_success() {
-        return Ok((None, vec![]));
+        return Ok((None, vec![], vec![]));
     }
     let txt = res.text().await?;
     let json: Value = serde_json::from_str(&txt).unwrap_or(Value::Null);
@@ -77,36 +79,45 @@ pub async fn fetch_details(
         .or_else(|| json.get("synopsis"))
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
-    let mut genres = Vec::new();
-    if let Some(arr) = json.get("genres").and_then(|v| v.as_array()) {
+    let genres = names(&json, "genres");
+    let mut credits = Vec::new();
+    for (key, role) in [
+        ("authors", PersonRole::Author),
+        ("artists", PersonRole::Artist),
+        ("publishers", PersonRole::Publisher),
+    ] {
+        credits.extend(names(&json, key).iter().filter_map(|n| people::credit(n, role)));
+    }
+    Ok((desc, genres, people::dedup(credits)))
+}
+
+/// Entries of a list field given either as strings or as `{ "name": ... }`
+fn names(json: &Value, key: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(arr) = json.get(key).and_then(|v| v.as_array()) {
         for g in arr {
             if let Some(n) = g.as_str() {
-                genres.push(n.to_string());
+                out.push(n.to_string());
             } else if let Some(n) = g.get("name").and_then(|v| v.as_str()) {
-                genres.push(n.to_string());
+                out.push(n.to_string());
             }
         }
     }
-    Ok((desc, genres))
+    out
 }
 
 #[allow(dead_code)]
-pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Error>> {
-    let db_client = pool.get().await.expect("Failed to get connection from pool");
-
+pub async fn sync_all(storage: &dyn Storage, client: &Client) -> Result<usize, Box<dyn Error>> {
     // Get manga needing mangabaka_id
-    let rows = db_client
-        .query(
-            "SELECT id, title, COALESCE(alt_titles,'') FROM manga WHERE mangabaka_id IS NULL OR mangabaka_id = ''",
-            &[],
-        )
+    let rows = storage
+        .get_manga_missing_provider(MetadataProvider::MangaBaka, None)
         .await?;
 
     let mut updated = 0usize;
     for row in rows {
-        let manga_id: String = row.get(0);
-        let title: String = row.get(1);
-        let alts: String = row.get(2);
+        let manga_id = row.id;
+        let title = row.title;
+        let alts = row.alt_titles;
 
         let mut candidates: Vec<String> = vec![title.clone()];
         for t in alts.split(", ") {
@@ -161,17 +172,8 @@ pub async fn sync_all(pool: &Pool, client: &Client) -> Result<usize, Box<dyn Err
             }
         }
         if let Some(pid) = found {
-            let _ = db_client
-                .execute(
-                    "UPDATE manga SET mangabaka_id = $1 WHERE id = $2",
-                    &[&pid, &manga_id],
-                )
-                .await?;
-            let _ = db_client
-                .execute(
-                    "INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, 'mangabaka', $2) ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = EXCLUDED.provider_id",
-                    &[&manga_id, &pid],
-                )
+            storage
+                .set_provider_id(&manga_id, MetadataProvider::MangaBaka, &pid)
                 .await?;
             updated += 1;
         }
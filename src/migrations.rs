@@ -0,0 +1,242 @@
+//! Embedded PostgreSQL schema migrations.
+//!
+//! Every file in `migrations/` is compiled into the binary and listed in
+//! [`MIGRATIONS`]. At startup [`run`] applies the ones not yet recorded in the
+//! `schema_migrations` table, in order, each in its own transaction; the
+//! server (or `--migrate-only`) refuses to continue if one fails.
+//!
+//! Migrations are forward-only: add a new numbered file for every schema
+//! change and never edit one that has shipped. A checksum of each applied
+//! file is stored and an edited file stops startup; the one expected edit is
+//! the `sources` seed in `01_init_schema.sql`, which follows the source
+//! catalogue and is re-applied by `pg_db::seed_sources` anyway, so that file
+//! is only logged. The migrations written
+//! before this runner existed are idempotent, so databases created by
+//! Docker's `docker-entrypoint-initdb.d` adopt the runner by replaying them
+//! once.
+
+use deadpool_postgres::Pool;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+
+/// Migrations that may change after they are applied: the `sources` seed in
+/// `01_init_schema.sql` tracks the source catalogue.
+const RESEEDED: [i32; 1] = [1];
+
+/// Key for the advisory lock that keeps two servers from migrating at once.
+const LOCK_KEY: i64 = 0x0074_7375_6261_6b69;
+
+/// One numbered SQL file from `migrations/`.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.sql.as_bytes()))
+    }
+}
+
+macro_rules! migration {
+    ($version:literal, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $file,
+            sql: include_str!(concat!("../migrations/", $file)),
+        }
+    };
+}
+
+/// Every migration, oldest first. New files must be added here too (a unit
+/// test checks the list against the directory).
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "01_init_schema.sql"),
+    migration!(2, "02_source_catalogue.sql"),
+    migration!(3, "03_output_format.sql"),
+    migration!(4, "04_download_jobs.sql"),
+    migration!(5, "05_chapter_metadata.sql"),
+    migration!(6, "06_chapter_sort_key.sql"),
+    migration!(7, "07_source_priority.sql"),
+    migration!(8, "08_merge_proposals.sql"),
+    migration!(9, "09_manga_audit.sql"),
+    migration!(10, "10_column_types.sql"),
+    migration!(11, "11_search.sql"),
+    migration!(12, "12_tags.sql"),
+    migration!(13, "13_people.sql"),
+    migration!(14, "14_reading.sql"),
+    migration!(15, "15_api_keys.sql"),
+    migration!(16, "16_chapter_files.sql"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("cannot connect to the database: {0}")]
+    Connect(#[from] deadpool_postgres::PoolError),
+    #[error("database error: {0}")]
+    Db(#[from] tokio_postgres::Error),
+    #[error("migration {version} ({name}) failed: {source}")]
+    Failed {
+        version: i32,
+        name: &'static str,
+        source: tokio_postgres::Error,
+    },
+    #[error("database is at schema version {0}, newer than this build understands")]
+    UnknownVersion(i32),
+    #[error("migration {version} ({name}) was edited after it was applied; restore it and add a new migration instead")]
+    Changed { version: i32, name: &'static str },
+}
+
+/// Apply pending migrations and return the versions applied by this call.
+pub async fn run(pool: &Pool) -> Result<Vec<i32>, MigrationError> {
+    let mut client = pool.get().await?;
+
+    client
+        .batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at BIGINT NOT NULL
+            )",
+        )
+        .await?;
+    client
+        .execute("SELECT pg_advisory_lock($1)", &[&LOCK_KEY])
+        .await?;
+    let result = apply_pending(&mut client).await;
+    client
+        .execute("SELECT pg_advisory_unlock($1)", &[&LOCK_KEY])
+        .await?;
+    result
+}
+
+async fn apply_pending(client: &mut tokio_postgres::Client) -> Result<Vec<i32>, MigrationError> {
+    let applied: Vec<(i32, String)> = client
+        .query(
+            "SELECT version, checksum FROM schema_migrations ORDER BY version",
+            &[],
+        )
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+    check_applied(&applied)?;
+
+    let mut done = Vec::new();
+    for m in pending(&applied) {
+        info!("Applying migration {} ({})", m.version, m.name);
+        let failed = |source| MigrationError::Failed {
+            version: m.version,
+            name: m.name,
+            source,
+        };
+        let tx = client.transaction().await?;
+        tx.batch_execute(m.sql).await.map_err(failed)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum, applied_at)
+             VALUES ($1, $2, $3, $4)",
+            &[
+                &m.version,
+                &m.name,
+                &m.checksum(),
+                &chrono::Utc::now().timestamp(),
+            ],
+        )
+        .await?;
+        tx.commit().await.map_err(failed)?;
+        done.push(m.version);
+    }
+    Ok(done)
+}
+
+/// Fail on applied migrations this build does not know or whose file was
+/// edited since (except [`RESEEDED`] ones, which are only logged).
+fn check_applied(applied: &[(i32, String)]) -> Result<(), MigrationError> {
+    for (version, checksum) in applied {
+        match MIGRATIONS.iter().find(|m| m.version == *version) {
+            Some(m) if m.checksum() == *checksum => {}
+            Some(m) if RESEEDED.contains(&m.version) => warn!(
+                "Migration {} ({}) changed after it was applied; it is not re-run",
+                m.version, m.name
+            ),
+            Some(m) => {
+                return Err(MigrationError::Changed {
+                    version: m.version,
+                    name: m.name,
+                })
+            }
+            None => return Err(MigrationError::UnknownVersion(*version)),
+        }
+    }
+    Ok(())
+}
+
+/// Migrations whose version is not in `applied`, oldest first.
+fn pending(applied: &[(i32, String)]) -> impl Iterator<Item = &'static Migration> + '_ {
+    MIGRATIONS
+        .iter()
+        .filter(move |m| !applied.iter().any(|(v, _)| *v == m.version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_match_directory() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+        let mut files: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|f| f.ends_with(".sql"))
+            .collect();
+        files.sort();
+        let listed: Vec<&str> = MIGRATIONS.iter().map(|m| m.name).collect();
+        assert_eq!(files, listed, "MIGRATIONS is out of date");
+
+        for (i, m) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(m.version, i as i32 + 1, "{} is out of sequence", m.name);
+            assert!(
+                m.name.starts_with(&format!("{:02}_", m.version)),
+                "{} does not start with its version",
+                m.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_pending_skips_applied() {
+        let applied = vec![(1, String::new()), (2, String::new()), (4, String::new())];
+        let versions: Vec<i32> = pending(&applied).map(|m| m.version).collect();
+        assert_eq!(versions[..2], [3, 5]);
+        assert_eq!(versions.len(), MIGRATIONS.len() - 3);
+    }
+
+    #[test]
+    fn test_check_applied_rejects_edited_migrations() {
+        let mut applied: Vec<(i32, String)> = MIGRATIONS
+            .iter()
+            .map(|m| (m.version, m.checksum()))
+            .collect();
+        assert!(check_applied(&applied).is_ok());
+
+        applied[0].1 = "old sources seed".to_string();
+        assert!(check_applied(&applied).is_ok());
+
+        applied[1].1 = "edited".to_string();
+        assert!(matches!(
+            check_applied(&applied),
+            Err(MigrationError::Changed { version: 2, .. })
+        ));
+
+        applied[1].1 = MIGRATIONS[1].checksum();
+        applied.push((MIGRATIONS.len() as i32 + 1, String::new()));
+        assert!(matches!(
+            check_applied(&applied),
+            Err(MigrationError::UnknownVersion(_))
+        ));
+    }
+}
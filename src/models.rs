@@ -1,3 +1,7 @@
+use crate::auth::Scope;
+use crate::chapter_number::{ChapterKind, ChapterNumber};
+use crate::people::PersonRole;
+use crate::reading::LibraryStatus;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -25,26 +29,89 @@ pub struct Manga {
     pub description: Option<String>,
     pub tags: Option<String>,
     pub rating: Option<String>,
+    /// Publication status as the source reports it, e.g. `ongoing` or `completed`
+    pub status: Option<String>,
     // Monitoring fields
     pub monitored: Option<bool>,
     pub check_interval_secs: Option<i64>,
     pub discover_interval_secs: Option<i64>,
     pub last_chapter_check: Option<i64>,
     pub last_discover_check: Option<i64>,
+    /// Authors, artists and publishers; filled in by `get_manga_by_id` and by
+    /// sources that report them, empty in listings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub people: Vec<Credit>,
     // pub mal_id: Option<i32>,
     // pub anilist_id: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Chapter {
     pub id: i32,
     pub manga_source_data_id: i32,
+    /// The label as the source shows it, e.g. "Vol.2 Ch.15 - Title"
     pub chapter_number: String,
     pub url: String,
     pub scraped: bool,
+    /// Numeric chapter, e.g. `15.5`
+    pub number: Option<f64>,
+    pub volume: Option<String>,
+    pub title: Option<String>,
+    /// ISO 639-1 code of the release
+    pub language: Option<String>,
+    pub scanlation_group: Option<String>,
+    /// Unix timestamp the source published the chapter
+    pub published_at: Option<i64>,
+    pub page_count: Option<i32>,
+    /// Unix timestamp the chapter was first stored
+    pub first_seen_at: Option<i64>,
+    /// Reading order, see [`crate::chapter_number::ChapterNumber::sort_key`]
+    pub sort_key: Option<f64>,
+    /// Downloaded file, relative to `download_dir`
+    pub file_path: Option<String>,
+    pub file_size: Option<i64>,
+    /// SHA-256 of the file, hex encoded
+    pub file_hash: Option<String>,
+    /// Unix timestamp the file was downloaded or linked by the library scanner
+    pub downloaded_at: Option<i64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Chapter {
+    /// Fill `number`, `volume`, `title` and `sort_key` from the label where the
+    /// source did not provide them.
+    pub fn fill_from_label(&mut self) {
+        let mut parsed = ChapterNumber::parse(&self.chapter_number);
+        let (_, volume, title) = crate::comicinfo::parse_chapter_label(&self.chapter_number);
+        if parsed.kind == ChapterKind::Main && self.number.is_some() {
+            parsed.number = self.number;
+        }
+        if self.number.is_none() {
+            self.number = parsed.number;
+        }
+        if self.volume.is_none() {
+            self.volume = volume;
+        }
+        if self.title.is_none() {
+            self.title = title;
+        }
+        if self.sort_key.is_none() {
+            self.sort_key = parsed.sort_key();
+        }
+    }
+
+    /// "Vol.X Ch.Y - Title" built from the stored fields, or the source's label
+    /// when the chapter number is unknown or not a plain main chapter.
+    pub fn label(&self) -> String {
+        chapter_label(
+            &self.chapter_number,
+            self.number,
+            self.volume.as_deref(),
+            self.title.as_deref(),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MangaSourceData {
     pub manga_id: String,
     pub source_id: i32,
@@ -82,6 +149,7 @@ pub struct MangaWithSources {
     pub description: Option<String>,
     pub tags: Option<String>,
     pub rating: Option<String>,
+    pub people: Vec<Credit>,
     pub sources: Vec<SourceInfo>,
 }
 
@@ -101,6 +169,87 @@ pub struct ChapterWithSource {
     pub scraped: bool,
     pub source_id: i32,
     pub source_name: String,
+    pub number: Option<f64>,
+    pub volume: Option<String>,
+    pub title: Option<String>,
+    pub language: Option<String>,
+    pub scanlation_group: Option<String>,
+    pub published_at: Option<i64>,
+    pub page_count: Option<i32>,
+    pub first_seen_at: Option<i64>,
+    pub sort_key: Option<f64>,
+    pub file_path: Option<String>,
+    pub file_size: Option<i64>,
+    pub file_hash: Option<String>,
+    pub downloaded_at: Option<i64>,
+}
+
+impl ChapterWithSource {
+    pub fn new(chapter: Chapter, source_id: i32, source_name: String) -> Self {
+        Self {
+            id: chapter.id,
+            chapter_number: chapter.chapter_number,
+            url: chapter.url,
+            scraped: chapter.scraped,
+            source_id,
+            source_name,
+            number: chapter.number,
+            volume: chapter.volume,
+            title: chapter.title,
+            language: chapter.language,
+            scanlation_group: chapter.scanlation_group,
+            published_at: chapter.published_at,
+            page_count: chapter.page_count,
+            first_seen_at: chapter.first_seen_at,
+            sort_key: chapter.sort_key,
+            file_path: chapter.file_path,
+            file_size: chapter.file_size,
+            file_hash: chapter.file_hash,
+            downloaded_at: chapter.downloaded_at,
+        }
+    }
+
+    /// See [`Chapter::label`].
+    pub fn label(&self) -> String {
+        chapter_label(
+            &self.chapter_number,
+            self.number,
+            self.volume.as_deref(),
+            self.title.as_deref(),
+        )
+    }
+}
+
+/// A chapter's file on disk, as recorded by a download or the library scanner
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChapterFile {
+    /// Relative to `download_dir`
+    pub path: String,
+    pub size: i64,
+    /// SHA-256 of the file, hex encoded; `None` for folder output
+    pub hash: Option<String>,
+}
+
+fn chapter_label(
+    fallback: &str,
+    number: Option<f64>,
+    volume: Option<&str>,
+    title: Option<&str>,
+) -> String {
+    // Seasons, extras and side stories keep the source's label so they never
+    // collide with the main chapter of the same number.
+    let Some(number) = number.filter(|_| ChapterNumber::parse(fallback).is_plain()) else {
+        return fallback.to_string();
+    };
+    let mut label = match volume {
+        Some(v) => format!("Vol.{} Ch.{}", v, number),
+        None => format!("Ch.{}", number),
+    };
+    if let Some(t) = title.map(str::trim).filter(|t| !t.is_empty()) {
+        label.push_str(" - ");
+        label.push_str(t);
+    }
+    label
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -109,3 +258,251 @@ pub struct MonitorRequest {
     pub check_interval_secs: Option<i64>,
     pub discover_interval_secs: Option<i64>,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutputFormatRequest {
+    /// `cbz`, `epub`, `pdf` or `folder`; `null` reverts to the configured default
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleRequest {
+    /// Bundle every downloaded chapter of this volume...
+    pub volume: Option<String>,
+    /// ...or the inclusive chapter range `from`..=`to`
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// `cbz` (default), `epub` or `pdf`
+    pub format: Option<String>,
+    /// Delete the per-chapter archives once the bundle is written
+    #[serde(default)]
+    pub remove_chapters: bool,
+}
+
+/// A row of the `download_jobs` queue, with the chapter and manga it downloads
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadJob {
+    pub id: i64,
+    pub chapter_id: i32,
+    /// `queued`, `running`, `done` or `failed`
+    pub state: String,
+    pub format: Option<String>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+    pub file_path: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub manga_id: String,
+    pub manga_title: String,
+    pub chapter_number: String,
+    pub source_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct QueueRequest {
+    /// Only queue chapters from this source; otherwise one source per chapter number
+    pub source_id: Option<i32>,
+    /// Inclusive chapter range; either end may be left open
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Skip chapters that were already downloaded
+    #[serde(default)]
+    pub unread_only: bool,
+    /// Output format for these jobs (`cbz`, `epub`, `pdf` or `folder`)
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourcePriorityRequest {
+    /// Source IDs, most preferred first
+    pub source_ids: Vec<i32>,
+}
+
+/// A proposed merge of two manga rows found by `matching.rs`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergeProposal {
+    pub id: i32,
+    pub manga_a: String,
+    pub manga_b: String,
+    pub title_a: String,
+    pub title_b: String,
+    pub score: f64,
+    pub reasons: String,
+    /// `pending`, `accepted` or `rejected`
+    pub state: String,
+    pub automatic: bool,
+    pub created_at: i64,
+    pub decided_at: Option<i64>,
+}
+
+/// Body of `POST /manga/{id}/merge`
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    /// Manga folded into this one and deleted
+    pub other_id: String,
+}
+
+/// Body of `POST /manga/{id}/split`
+#[derive(Debug, Deserialize)]
+pub struct SplitRequest {
+    /// Sources whose links (and chapters) move to the new manga
+    pub source_ids: Vec<i32>,
+    /// Title of the new manga; defaults to the original's title
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// A recorded merge or split, undone with `POST /audit/{id}/undo`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MangaAuditEntry {
+    pub id: i32,
+    /// `merge` or `split`
+    pub action: String,
+    /// Merge: the manga kept; split: the manga split
+    pub manga_id: String,
+    /// Merge: the manga merged away; split: the manga created
+    pub other_manga_id: String,
+    pub created_at: i64,
+    pub undone_at: Option<i64>,
+}
+
+/// A canonical tag (see `crate::tags`), listed by `GET /tags`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tag {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    /// `genre`, `theme`, `format`, `content_warning`, or none for tags
+    /// outside the dictionary
+    pub category: Option<String>,
+    /// How many manga have the tag; only set when listing tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manga_count: Option<i64>,
+}
+
+/// A person credited on a manga in one role
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Credit {
+    /// `people.id`; unset until the credit is stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub person_id: Option<i32>,
+    pub name: String,
+    pub role: PersonRole,
+}
+
+/// A creator and the manga they are credited on, for `GET /people/{id}`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Person {
+    pub id: i32,
+    pub name: String,
+    pub works: Vec<PersonWork>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PersonWork {
+    pub manga_id: String,
+    pub title: String,
+    pub cover_url: Option<String>,
+    pub roles: Vec<PersonRole>,
+}
+
+/// A reader sharing the server; see `crate::reading`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub created_at: i64,
+}
+
+/// Body of `POST /users`
+#[derive(Debug, Deserialize)]
+pub struct CreateUserRequest {
+    pub name: String,
+}
+
+/// Body of `PUT /me/library/{manga_id}`
+#[derive(Debug, Deserialize)]
+pub struct LibraryEntryRequest {
+    pub status: LibraryStatus,
+}
+
+/// Body of `POST /chapters/{id}/read`; an empty body marks the chapter finished
+#[derive(Debug, Deserialize, Default)]
+pub struct ReadRequest {
+    /// Last page viewed, zero-based
+    #[serde(default)]
+    pub page: Option<i32>,
+    /// Defaults to true
+    #[serde(default)]
+    pub completed: Option<bool>,
+}
+
+/// A manga in a user's library, for `GET /me/library`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryEntry {
+    pub manga_id: String,
+    pub title: String,
+    pub cover_url: Option<String>,
+    pub status: LibraryStatus,
+    pub added_at: i64,
+    pub updated_at: i64,
+    /// Distinct chapter numbers finished, across sources
+    pub chapters_read: i64,
+    /// When a chapter of the manga was last marked
+    pub last_read_at: Option<i64>,
+}
+
+/// How far a user got in one chapter
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChapterRead {
+    pub chapter_id: i32,
+    pub chapter_number: String,
+    pub source_id: i32,
+    /// Last page viewed, zero-based
+    pub page: i32,
+    pub completed: bool,
+    pub updated_at: i64,
+}
+
+/// The chapter to open next for a manga being read, for `GET /me/continue`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContinueReading {
+    pub manga_id: String,
+    pub title: String,
+    pub cover_url: Option<String>,
+    pub chapter_id: i32,
+    pub chapter_number: String,
+    pub source_id: i32,
+    pub source_name: String,
+    /// Page to resume at, zero-based
+    pub page: i32,
+    pub last_read_at: i64,
+}
+
+/// An API key as `GET /auth/keys` lists it; the key itself is only shown once,
+/// when it is created
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    /// First characters of the key
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    /// The user the key acts as on the `/me` endpoints
+    pub user_id: Option<i32>,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+/// Body of `POST /auth/keys`
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Name of the user the key belongs to
+    #[serde(default)]
+    pub user: Option<String>,
+}
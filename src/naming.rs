@@ -0,0 +1,586 @@
+//! Where downloaded chapters are written inside `download_dir`.
+//!
+//! `chapter_template` in `config.toml` lays out the library, e.g. Komga's
+//! `{title}/{title} v{volume:02} c{chapter:03}` or Kavita's
+//! `{title}/{title}[ Vol.{volume}] Ch.{chapter}`. `/` separates folders and
+//! the output format's extension is appended to the last part. Variables are
+//! listed in [`Variable`]; `{chapter:03}` zero-pads a number to three digits
+//! (`5.5` becomes `005.5`). Text in `[...]` is left out when a variable inside
+//! it is empty (a folder left empty that way is skipped), and `[[`, `]]`, `{{`
+//! and `}}` write a literal bracket or brace.
+//!
+//! Chapters must sit in a folder that uses `{title}`: that series folder also
+//! holds the covers and volume bundles. The file name must use `{chapter}` or
+//! `{label}` so two chapters never get the same name; unnumbered chapters fill
+//! `{chapter}` with their label. Every folder and file name is made path safe
+//! by [`sanitize_component`].
+//!
+//! A new template applies to new downloads; `POST /library/rename` moves the
+//! files already recorded on chapters to their new paths.
+
+use crate::models::Chapter;
+use crate::output::OutputFormat;
+use crate::sources::catalogue;
+use chrono::{DateTime, Datelike};
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt;
+
+/// The layout downloads have always used:
+/// `<Title>/<sourceId>-<SourceName>-<Title> - <Label>.<ext>`.
+pub const DEFAULT_TEMPLATE: &str = "{title}/{source_id}-{source}-{title} - {label}";
+
+/// Longest folder or file name (before the extension) written, in bytes.
+const MAX_COMPONENT_BYTES: usize = 200;
+
+/// Device names Windows reserves in every folder, with or without an extension.
+const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    /// The manga's title
+    Title,
+    /// Volume number, empty when unknown
+    Volume,
+    /// Chapter number such as `15` or `15.5`; the label for unnumbered chapters
+    /// such as `Extra`, so they never share a name
+    Chapter,
+    /// Chapter title, empty when the source has none
+    ChapterTitle,
+    /// The normalised label, e.g. `Vol.2 Ch.15 - The Gate`
+    Label,
+    /// Source name, e.g. `MangaDex`
+    Source,
+    /// Source ID from the catalogue
+    SourceId,
+    /// Scanlation group
+    Group,
+    /// ISO 639-1 language of the release
+    Language,
+    /// Year the source published the chapter
+    Year,
+}
+
+impl Variable {
+    pub const ALL: [Variable; 10] = [
+        Variable::Title,
+        Variable::Volume,
+        Variable::Chapter,
+        Variable::ChapterTitle,
+        Variable::Label,
+        Variable::Source,
+        Variable::SourceId,
+        Variable::Group,
+        Variable::Language,
+        Variable::Year,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Variable::Title => "title",
+            Variable::Volume => "volume",
+            Variable::Chapter => "chapter",
+            Variable::ChapterTitle => "chapter_title",
+            Variable::Label => "label",
+            Variable::Source => "source",
+            Variable::SourceId => "source_id",
+            Variable::Group => "group",
+            Variable::Language => "language",
+            Variable::Year => "year",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Var {
+        var: Variable,
+        width: usize,
+    },
+    /// Left out when any variable in it is empty
+    Optional(Vec<Piece>),
+}
+
+/// A parsed `chapter_template`; see the module docs for the syntax.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+    source: String,
+    /// One entry per folder, then the file name
+    parts: Vec<Vec<Piece>>,
+    /// Leading folders that make up the series folder
+    series_depth: usize,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let err = |msg: &str| Err(format!("chapter_template '{}': {}", template, msg));
+        let mut parts: Vec<Vec<Piece>> = vec![Vec::new()];
+        let mut optional: Option<Vec<Piece>> = None;
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            let piece = match c {
+                '{' | '}' | '[' | ']' if chars.peek() == Some(&c) => {
+                    chars.next();
+                    Piece::Text(c.to_string())
+                }
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => return err("unclosed '{'"),
+                        }
+                    }
+                    match variable(&spec) {
+                        Some(piece) => piece,
+                        None => return err(&format!("unknown variable {{{}}}", spec)),
+                    }
+                }
+                '}' => return err("unmatched '}'"),
+                '[' if optional.is_some() => return err("'[' cannot be nested"),
+                '[' => {
+                    optional = Some(Vec::new());
+                    continue;
+                }
+                ']' => match optional.take() {
+                    Some(group) => Piece::Optional(group),
+                    None => return err("unmatched ']'"),
+                },
+                '/' if optional.is_some() => return err("'/' cannot be inside '[...]'"),
+                '/' => {
+                    parts.push(Vec::new());
+                    continue;
+                }
+                c => Piece::Text(c.to_string()),
+            };
+            push(
+                optional.as_mut().unwrap_or(parts.last_mut().unwrap()),
+                piece,
+            );
+        }
+        if optional.is_some() {
+            return err("unclosed '['");
+        }
+        if parts.iter().any(Vec::is_empty) {
+            return err("empty folder or file name");
+        }
+        let folders = &parts[..parts.len() - 1];
+        let Some(series) = folders.iter().position(|p| uses(p, Variable::Title)) else {
+            return err("chapters must be inside a folder named with {title}");
+        };
+        let file = &parts[parts.len() - 1];
+        if !uses(file, Variable::Chapter) && !uses(file, Variable::Label) {
+            return err("the file name must use {chapter} or {label}");
+        }
+        Ok(Self {
+            source: template.to_string(),
+            series_depth: series + 1,
+            parts,
+        })
+    }
+
+    /// Path of a chapter relative to `download_dir`, `/`-separated, with the
+    /// format's extension.
+    pub fn render(&self, name: &ChapterName, format: OutputFormat) -> String {
+        let (folders, file) = self.parts.split_at(self.parts.len() - 1);
+        let mut file = sanitize_component(&render_pieces(&file[0], name));
+        if let Some(ext) = format.extension() {
+            file.push('.');
+            file.push_str(ext);
+        }
+        let mut path = render_folders(folders, name);
+        path.push(file);
+        path.join("/")
+    }
+
+    /// How many leading folders make up the series folder.
+    pub fn series_depth(&self) -> usize {
+        self.series_depth
+    }
+
+    /// The series folder for `name`, relative to `download_dir`.
+    pub fn series_dir(&self, name: &ChapterName) -> String {
+        render_folders(&self.parts[..self.series_depth], name).join("/")
+    }
+}
+
+/// Folder names, skipping folders made only of optional parts that are empty.
+fn render_folders(folders: &[Vec<Piece>], name: &ChapterName) -> Vec<String> {
+    folders
+        .iter()
+        .map(|p| render_pieces(p, name))
+        .filter(|f| !f.is_empty())
+        .map(|f| sanitize_component(&f))
+        .collect()
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).unwrap()
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Self::parse(&s)
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// `name` or `name:0N` inside braces.
+fn variable(spec: &str) -> Option<Piece> {
+    let (name, width) = match spec.split_once(':') {
+        Some((name, width)) if width.starts_with('0') => (name, width.parse().ok()?),
+        Some(_) => return None,
+        None => (spec, 0),
+    };
+    Some(Piece::Var {
+        var: Variable::parse(name.trim())?,
+        width,
+    })
+}
+
+/// Append `piece`, joining it to a preceding text piece.
+fn push(pieces: &mut Vec<Piece>, piece: Piece) {
+    if let (Some(Piece::Text(last)), Piece::Text(text)) = (pieces.last_mut(), &piece) {
+        last.push_str(text);
+    } else {
+        pieces.push(piece);
+    }
+}
+
+fn uses(pieces: &[Piece], var: Variable) -> bool {
+    pieces.iter().any(|p| match p {
+        Piece::Var { var: v, .. } => *v == var,
+        Piece::Optional(group) => uses(group, var),
+        Piece::Text(_) => false,
+    })
+}
+
+fn render_pieces(pieces: &[Piece], name: &ChapterName) -> String {
+    let mut out = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Var { var, width } => out.push_str(&pad(&name.value(*var), *width)),
+            Piece::Optional(group) => {
+                let filled = group.iter().all(|p| match p {
+                    Piece::Var { var, .. } => !name.value(*var).is_empty(),
+                    _ => true,
+                });
+                if filled {
+                    out.push_str(&render_pieces(group, name));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Zero-pad the integer part of a number to `width` digits; other values are
+/// left alone.
+fn pad(value: &str, width: usize) -> String {
+    let (int, rest) = value.split_at(value.find('.').unwrap_or(value.len()));
+    if int.is_empty() || !int.bytes().all(|b| b.is_ascii_digit()) {
+        return value.to_string();
+    }
+    format!("{:0>width$}{}", int, rest, width = width)
+}
+
+/// Make `s` safe as one folder or file name on Linux, macOS and Windows.
+/// Beyond what [`crate::scraper::sanitize_filename`] replaces, control
+/// characters become spaces, surrounding spaces and trailing dots are trimmed,
+/// a leading dot (a hidden file) becomes `_`, reserved device names such as
+/// `CON` or `COM1` get a `_` prefix, and the name is cut to 200 bytes. The
+/// result is never empty, `.` or `..`.
+pub fn sanitize_component(s: &str) -> String {
+    let replaced: String = s
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let mut out = trim(&replaced);
+    if out.starts_with('.') {
+        out.replace_range(..1, "_");
+    }
+    if is_reserved(&out) {
+        out.insert(0, '_');
+    }
+    if out.len() > MAX_COMPONENT_BYTES {
+        let mut end = MAX_COMPONENT_BYTES;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out = trim(&out[..end]);
+    }
+    if out.is_empty() {
+        return "_".to_string();
+    }
+    out
+}
+
+fn trim(s: &str) -> String {
+    s.trim_start()
+        .trim_end_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+fn is_reserved(name: &str) -> bool {
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or("")
+        .trim_end()
+        .to_uppercase();
+    RESERVED_NAMES.contains(&stem.as_str())
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.as_bytes()[3].is_ascii_digit()
+            && stem.as_bytes()[3] != b'0')
+}
+
+/// The values a [`Template`] fills in for one chapter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterName {
+    pub title: String,
+    pub volume: Option<String>,
+    pub chapter: Option<String>,
+    pub chapter_title: Option<String>,
+    pub label: String,
+    pub source_id: i32,
+    pub group: Option<String>,
+    pub language: Option<String>,
+    pub year: Option<i32>,
+}
+
+impl ChapterName {
+    /// Name parts for the chapter labelled `chapter_label` of `manga_title`.
+    /// The volume and number come from the label, or from the chapter URL's
+    /// slug where the label has none (as for `/download/byurl`).
+    pub fn new(manga_title: &str, source_id: i32, chapter_label: &str, chapter_url: &str) -> Self {
+        let chapter = chapter_from_label(chapter_label, chapter_url);
+        Self {
+            title: manga_title.trim().to_string(),
+            volume: chapter.volume.clone(),
+            chapter: chapter.number.map(|n| n.to_string()),
+            chapter_title: chapter.title.clone(),
+            label: chapter.label(),
+            source_id,
+            ..Default::default()
+        }
+    }
+
+    /// Add the release details a source reported for the chapter.
+    pub fn with_release(
+        mut self,
+        group: Option<&str>,
+        language: Option<&str>,
+        published_at: Option<i64>,
+    ) -> Self {
+        self.group = group.map(str::to_string);
+        self.language = language.map(str::to_string);
+        self.year = published_at
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .map(|d| d.year());
+        self
+    }
+
+    pub fn value(&self, var: Variable) -> String {
+        let text = |v: &Option<String>| v.as_deref().unwrap_or("").trim().to_string();
+        match var {
+            Variable::Title => self.title.clone(),
+            Variable::Volume => text(&self.volume),
+            Variable::Chapter => match text(&self.chapter) {
+                number if number.is_empty() => self.label.clone(),
+                number => number,
+            },
+            Variable::ChapterTitle => text(&self.chapter_title),
+            Variable::Label => self.label.clone(),
+            Variable::Source => catalogue::name_for(self.source_id).to_string(),
+            Variable::SourceId => self.source_id.to_string(),
+            Variable::Group => text(&self.group),
+            Variable::Language => text(&self.language),
+            Variable::Year => self.year.map(|y| y.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+/// A chapter parsed from its label, with the number and volume taken from
+/// the URL's slug when the label does not carry them.
+fn chapter_from_label(chapter_label: &str, chapter_url: &str) -> Chapter {
+    let mut chapter = Chapter {
+        chapter_number: chapter_label.to_string(),
+        ..Default::default()
+    };
+    chapter.fill_from_label();
+
+    let lower_url = chapter_url.to_lowercase();
+    if chapter.number.is_none() {
+        chapter.number = Regex::new(r"chapter[-/](\d+(?:\.\d+)?)")
+            .unwrap()
+            .captures(&lower_url)
+            .and_then(|cap| cap[1].parse().ok());
+    }
+    if chapter.volume.is_none() {
+        chapter.volume = Regex::new(r"vol(?:ume)?[-/](\d+)")
+            .unwrap()
+            .captures(&lower_url)
+            .map(|cap| cap[1].to_string());
+    }
+    chapter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(label: &str) -> ChapterName {
+        ChapterName::new("Solo Leveling", 1, label, "")
+    }
+
+    #[test]
+    fn test_chapter_label() {
+        assert_eq!(
+            name("Vol.2 Chapter 15 - The Gate").label,
+            "Vol.2 Ch.15 - The Gate"
+        );
+        assert_eq!(name("Chapter 7").label, "Ch.7");
+        let by_url = ChapterName::new(
+            "x",
+            1,
+            "byurl",
+            "https://example.com/volume-3/chapter-12.5/",
+        );
+        assert_eq!(by_url.label, "Vol.3 Ch.12.5");
+        assert_eq!(by_url.chapter.as_deref(), Some("12.5"));
+        assert_eq!(by_url.volume.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_default_template_keeps_existing_names() {
+        let template = Template::default();
+        assert_eq!(
+            template.render(&name("Vol.2 Chapter 15 - The Gate"), OutputFormat::Cbz),
+            "Solo Leveling/1-MangaDex-Solo Leveling - Vol.2 Ch.15 - The Gate.cbz"
+        );
+        let odd = ChapterName::new("Re:Zero", 1, "Chapter 3", "");
+        assert_eq!(
+            template.render(&odd, OutputFormat::Folder),
+            "Re_Zero/1-MangaDex-Re_Zero - Ch.3"
+        );
+        assert_eq!(template.series_dir(&odd), "Re_Zero");
+    }
+
+    #[test]
+    fn test_library_server_layouts() {
+        let komga = Template::parse("{title}/{title} v{volume:02} c{chapter:03}").unwrap();
+        let kavita = Template::parse("{title}/{title}[ Vol.{volume}] Ch.{chapter}").unwrap();
+        let with_volume = name("Vol.1 Chapter 3");
+        let without = name("Chapter 5.5");
+        assert_eq!(
+            komga.render(&with_volume, OutputFormat::Cbz),
+            "Solo Leveling/Solo Leveling v01 c003.cbz"
+        );
+        assert_eq!(
+            kavita.render(&with_volume, OutputFormat::Cbz),
+            "Solo Leveling/Solo Leveling Vol.1 Ch.3.cbz"
+        );
+        assert_eq!(
+            kavita.render(&without, OutputFormat::Epub),
+            "Solo Leveling/Solo Leveling Ch.5.5.epub"
+        );
+
+        let nested = Template::parse(
+            "Manga/{title}/[Volume {volume}]/[{year} ]{title} #{chapter:03}[ [[{group}]]][ ({language})]",
+        )
+        .unwrap();
+        let release = name("Chapter 12").with_release(Some("Team: A/B"), None, Some(1_700_000_000));
+        assert_eq!(
+            nested.render(&release, OutputFormat::Pdf),
+            "Manga/Solo Leveling/2023 Solo Leveling #012 [Team_ A_B].pdf"
+        );
+        assert_eq!(
+            nested.render(&name("Vol.4 Chapter 30"), OutputFormat::Folder),
+            "Manga/Solo Leveling/Volume 4/Solo Leveling #030"
+        );
+        assert_eq!(nested.series_dir(&release), "Manga/Solo Leveling");
+    }
+
+    #[test]
+    fn test_unnumbered_chapters_get_distinct_names() {
+        let template = Template::parse("{title}/{title}[ c{chapter:03}]").unwrap();
+        let extra = template.render(&name("Extra"), OutputFormat::Cbz);
+        let special = template.render(&name("Special"), OutputFormat::Cbz);
+        assert_eq!(extra, "Solo Leveling/Solo Leveling cExtra.cbz");
+        assert_eq!(special, "Solo Leveling/Solo Leveling cSpecial.cbz");
+        assert_eq!(
+            template.render(&name("Chapter 7"), OutputFormat::Cbz),
+            "Solo Leveling/Solo Leveling c007.cbz"
+        );
+    }
+
+    #[test]
+    fn test_template_errors() {
+        for bad in [
+            "{title} - {chapter}",
+            "{source}/{chapter}",
+            "{title}/{volume}",
+            "{title}/{chapter",
+            "{title}/{chapter}}",
+            "{title}/{nope}",
+            "{title}/{chapter:3}",
+            "{title}/[[{chapter}]",
+            "{title}/[a[{chapter}]]",
+            "{title}/[{volume}/]{chapter}",
+            "{title}//{chapter}",
+            "{title}/{chapter}/{title}",
+            "{title}/Ch.{label}/{volume}",
+        ] {
+            assert!(Template::parse(bad).is_err(), "{}", bad);
+        }
+        #[derive(Deserialize)]
+        struct Config {
+            chapter_template: Template,
+        }
+        let parsed: Config = toml::from_str("chapter_template = '{title}/{label}'").unwrap();
+        let parsed = parsed.chapter_template;
+        assert!(toml::from_str::<Config>("chapter_template = '{label}'").is_err());
+        assert_eq!(parsed.to_string(), "{title}/{label}");
+    }
+
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(
+            sanitize_component("a/b\\c:d*e?f\"g<h>i|j"),
+            "a_b_c_d_e_f_g_h_i_j"
+        );
+        assert_eq!(sanitize_component("  Title...  "), "Title");
+        assert_eq!(sanitize_component("line\nbreak"), "line break");
+        assert_eq!(sanitize_component(".hack"), "_hack");
+        assert_eq!(sanitize_component(".."), "_");
+        assert_eq!(sanitize_component(""), "_");
+        assert_eq!(sanitize_component("con"), "_con");
+        assert_eq!(sanitize_component("COM1.cbz"), "_COM1.cbz");
+        assert_eq!(sanitize_component("Common"), "Common");
+        let long = sanitize_component(&"é".repeat(150));
+        assert!(long.len() <= MAX_COMPONENT_BYTES);
+        assert!(long.chars().all(|c| c == 'é'));
+    }
+}
@@ -0,0 +1,508 @@
+//! OPDS 1.2 catalog for reading apps such as Panels, Chunky and KOReader.
+//!
+//! `GET /opds` is the root navigation feed: latest updates, manga by source
+//! and by tag, and search through an OpenSearch description. Manga lists link
+//! each series to `/opds/manga/{id}`, an acquisition feed with one entry per
+//! chapter (merged across sources as in `GET /manga/{id}/chapters?merged=true`)
+//! whose CBZ link is the streaming download endpoint.
+//!
+//! Chapters can also be read page by page with OPDS-PSE (Page Streaming
+//! Extension). PSE needs the page count up front, so a chapter whose count is
+//! not known yet links to `/opds/manga/{id}/chapters/{chapter_id}` instead,
+//! which resolves its pages and answers with the streaming link. Pages are
+//! zero-based, like `chapter_reads.page`, and `pse:lastRead` is the reader's
+//! saved position.
+//!
+//! Apps sign in with HTTP Basic, an API key as the password (see
+//! [`crate::auth`]).
+
+use crate::helpers::xml_escape;
+use crate::images::ImageFormat;
+use crate::models::{ChapterRead, ChapterWithSource, Manga};
+use crate::people::{self, PersonRole};
+use chrono::{DateTime, SecondsFormat};
+use std::collections::HashMap;
+
+/// Manga per page of a feed
+pub const PAGE_SIZE: i64 = 50;
+
+pub const OPENSEARCH_TYPE: &str = "application/opensearchdescription+xml";
+/// The registered CBZ type, which apps recognise more reliably than the
+/// `application/x-cbz` the download endpoints answer with
+const CBZ_TYPE: &str = "application/vnd.comicbook+zip";
+const ACQUISITION_REL: &str = "http://opds-spec.org/acquisition";
+const PSE_STREAM_REL: &str = "http://vaemendis.net/opds-pse/stream";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedKind {
+    /// Links to other feeds
+    Navigation,
+    /// Entries that can be downloaded or read
+    Acquisition,
+}
+
+impl FeedKind {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedKind::Navigation => "application/atom+xml;profile=opds-catalog;kind=navigation",
+            FeedKind::Acquisition => "application/atom+xml;profile=opds-catalog;kind=acquisition",
+        }
+    }
+}
+
+/// An Atom `<link>`, with the OPDS-PSE attributes on page streaming links
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Link {
+    pub rel: String,
+    pub kind: String,
+    pub href: String,
+    pub title: Option<String>,
+    /// Number of pages (`pse:count`)
+    pub pse_count: Option<i32>,
+    /// Last page read, zero-based (`pse:lastRead`)
+    pub pse_last_read: Option<i32>,
+    pub pse_last_read_date: Option<i64>,
+}
+
+impl Link {
+    pub fn new(rel: &str, kind: &str, href: impl Into<String>) -> Self {
+        Link {
+            rel: rel.to_string(),
+            kind: kind.to_string(),
+            href: href.into(),
+            ..Default::default()
+        }
+    }
+
+    fn to_xml(&self) -> String {
+        let mut attrs = vec![
+            ("rel", self.rel.clone()),
+            ("type", self.kind.clone()),
+            ("href", self.href.clone()),
+        ];
+        if let Some(title) = &self.title {
+            attrs.push(("title", title.clone()));
+        }
+        if let Some(count) = self.pse_count {
+            attrs.push(("pse:count", count.to_string()));
+        }
+        if let Some(page) = self.pse_last_read {
+            attrs.push(("pse:lastRead", page.to_string()));
+        }
+        if let Some(at) = self.pse_last_read_date {
+            attrs.push(("pse:lastReadDate", timestamp(at)));
+        }
+        let attrs: Vec<String> = attrs
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, xml_escape(value)))
+            .collect();
+        format!("<link {}/>", attrs.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    /// Unix timestamp
+    pub updated: i64,
+    pub content: Option<String>,
+    pub authors: Vec<String>,
+    pub categories: Vec<String>,
+    pub links: Vec<Link>,
+}
+
+impl Entry {
+    fn push_xml(&self, lines: &mut Vec<String>) {
+        lines.push("  <entry>".to_string());
+        lines.push(format!("    <id>{}</id>", xml_escape(&self.id)));
+        lines.push(format!("    <title>{}</title>", xml_escape(&self.title)));
+        lines.push(format!(
+            "    <updated>{}</updated>",
+            timestamp(self.updated)
+        ));
+        for author in &self.authors {
+            lines.push(format!(
+                "    <author><name>{}</name></author>",
+                xml_escape(author)
+            ));
+        }
+        for category in &self.categories {
+            let category = xml_escape(category);
+            lines.push(format!(
+                "    <category term=\"{0}\" label=\"{0}\"/>",
+                category
+            ));
+        }
+        if let Some(content) = &self.content {
+            lines.push(format!(
+                "    <content type=\"text\">{}</content>",
+                xml_escape(content)
+            ));
+        }
+        for link in &self.links {
+            lines.push(format!("    {}", link.to_xml()));
+        }
+        lines.push("  </entry>".to_string());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub kind: FeedKind,
+    pub id: String,
+    pub title: String,
+    pub updated: i64,
+    pub links: Vec<Link>,
+    pub entries: Vec<Entry>,
+}
+
+impl Feed {
+    /// An empty feed at `href`, linking back to the catalog root and to search
+    pub fn new(kind: FeedKind, id: &str, title: &str, href: &str, updated: i64) -> Self {
+        let navigation = FeedKind::Navigation.content_type();
+        Feed {
+            kind,
+            id: format!("urn:tsubaki:{}", id),
+            title: title.to_string(),
+            updated,
+            links: vec![
+                Link::new("self", kind.content_type(), href),
+                Link::new("start", navigation, "/opds"),
+                Link::new("search", OPENSEARCH_TYPE, "/opds/search.xml"),
+            ],
+            entries: Vec::new(),
+        }
+    }
+
+    /// `previous` and `next` links for page `page` (1-based) of `href`
+    pub fn paginate(&mut self, href: &str, page: i64, has_more: bool) {
+        let separator = if href.contains('?') { '&' } else { '?' };
+        let kind = self.kind.content_type();
+        if page > 1 {
+            let previous = format!("{}{}page={}", href, separator, page - 1);
+            self.links.push(Link::new("previous", kind, previous));
+        }
+        if has_more {
+            let next = format!("{}{}page={}", href, separator, page + 1);
+            self.links.push(Link::new("next", kind, next));
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:opds="http://opds-spec.org/2010/catalog" xmlns:pse="http://vaemendis.net/opds-pse/ns">"#.to_string(),
+            format!("  <id>{}</id>", xml_escape(&self.id)),
+            format!("  <title>{}</title>", xml_escape(&self.title)),
+            format!("  <updated>{}</updated>", timestamp(self.updated)),
+            "  <author><name>Tsubaki</name></author>".to_string(),
+        ];
+        for link in &self.links {
+            lines.push(format!("  {}", link.to_xml()));
+        }
+        for entry in &self.entries {
+            entry.push_xml(&mut lines);
+        }
+        lines.push("</feed>".to_string());
+        lines.join("\n")
+    }
+}
+
+/// OpenSearch description pointing apps at `/opds/search`
+pub fn opensearch_description() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName>Tsubaki</ShortName>
+  <Description>Search manga by title, alternative title, tag or description</Description>
+  <InputEncoding>UTF-8</InputEncoding>
+  <OutputEncoding>UTF-8</OutputEncoding>
+  <Url type="{}" template="/opds/search?q={{searchTerms}}"/>
+</OpenSearchDescription>"#,
+        xml_escape(FeedKind::Navigation.content_type())
+    )
+}
+
+/// The 1-based `page` query parameter, 1 when missing or invalid
+pub fn page_param(query: &HashMap<String, String>) -> i64 {
+    query
+        .get("page")
+        .and_then(|p| p.trim().parse::<i64>().ok())
+        .filter(|p| *p > 0)
+        .unwrap_or(1)
+}
+
+/// An entry leading to another feed
+pub fn navigation_entry(
+    id: &str,
+    title: &str,
+    content: Option<String>,
+    href: &str,
+    kind: FeedKind,
+    updated: i64,
+) -> Entry {
+    Entry {
+        id: format!("urn:tsubaki:{}", id),
+        title: title.to_string(),
+        updated,
+        content,
+        links: vec![Link::new("subsection", kind.content_type(), href)],
+        ..Default::default()
+    }
+}
+
+/// A series, leading to the acquisition feed of its chapters
+pub fn manga_entry(manga: &Manga, updated: i64) -> Entry {
+    let mut links = vec![Link::new(
+        "subsection",
+        FeedKind::Acquisition.content_type(),
+        format!("/opds/manga/{}", urlencoding::encode(&manga.id)),
+    )];
+    if let Some(cover) = manga.cover_url.as_deref().filter(|u| !u.is_empty()) {
+        let kind = image_type(cover);
+        links.push(Link::new("http://opds-spec.org/image", kind, cover));
+        links.push(Link::new(
+            "http://opds-spec.org/image/thumbnail",
+            kind,
+            cover,
+        ));
+    }
+    Entry {
+        id: format!("urn:tsubaki:manga:{}", manga.id),
+        title: manga.title.clone(),
+        updated,
+        content: manga.description.clone().filter(|d| !d.trim().is_empty()),
+        authors: people::names(&manga.people, PersonRole::Author)
+            .map(|names| names.split(", ").map(str::to_string).collect())
+            .unwrap_or_default(),
+        categories: manga
+            .tags
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect(),
+        links,
+    }
+}
+
+/// A chapter with its CBZ download and, when the page count is known, its
+/// OPDS-PSE streaming link; otherwise a link to the feed that resolves it
+pub fn chapter_entry(
+    manga_id: &str,
+    chapter: &ChapterWithSource,
+    read: Option<&ChapterRead>,
+    updated: i64,
+) -> Entry {
+    let manga = urlencoding::encode(manga_id);
+    let mut links = vec![Link::new(
+        ACQUISITION_REL,
+        CBZ_TYPE,
+        format!(
+            "/download/{}/{}/{}?stream=true&format=cbz",
+            manga,
+            urlencoding::encode(&chapter.chapter_number),
+            chapter.source_id
+        ),
+    )];
+    match chapter.page_count.filter(|n| *n > 0) {
+        Some(count) => links.push(stream_link(chapter.id, count, read)),
+        None => links.push(Link::new(
+            "subsection",
+            FeedKind::Acquisition.content_type(),
+            format!("/opds/manga/{}/chapters/{}", manga, chapter.id),
+        )),
+    }
+
+    let mut about = vec![chapter.source_name.clone()];
+    about.extend(chapter.scanlation_group.clone());
+    about.extend(chapter.language.clone());
+    if read.is_some_and(|r| r.completed) {
+        about.push("read".to_string());
+    }
+    Entry {
+        id: format!("urn:tsubaki:chapter:{}", chapter.id),
+        title: chapter.label(),
+        updated: chapter
+            .published_at
+            .or(chapter.first_seen_at)
+            .unwrap_or(updated),
+        content: Some(about.join(" · ")),
+        links,
+        ..Default::default()
+    }
+}
+
+/// OPDS-PSE link streaming `count` pages of a chapter
+pub fn stream_link(chapter_id: i32, count: i32, read: Option<&ChapterRead>) -> Link {
+    Link {
+        pse_count: Some(count),
+        pse_last_read: read.map(|r| r.page.clamp(0, count - 1)),
+        pse_last_read_date: read.map(|r| r.updated_at),
+        ..Link::new(
+            PSE_STREAM_REL,
+            "image/jpeg",
+            format!("/opds/chapters/{}/pages/{{pageNumber}}", chapter_id),
+        )
+    }
+}
+
+/// Media type of an image by the extension of its URL, JPEG when unknown
+fn image_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let ext = path
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    [
+        ImageFormat::Png,
+        ImageFormat::Webp,
+        ImageFormat::Gif,
+        ImageFormat::Avif,
+    ]
+    .into_iter()
+    .find(|f| f.extension() == ext)
+    .unwrap_or(ImageFormat::Jpeg)
+    .mime_type()
+}
+
+/// RFC 3339 time of a Unix timestamp, as Atom wants it
+fn timestamp(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chapter;
+
+    fn chapter(page_count: Option<i32>) -> ChapterWithSource {
+        let chapter = Chapter {
+            id: 7,
+            chapter_number: "Chapter 3 & 4".to_string(),
+            page_count,
+            first_seen_at: Some(0),
+            ..Default::default()
+        };
+        ChapterWithSource::new(chapter, 1, "MangaDex".to_string())
+    }
+
+    #[test]
+    fn test_feed_xml() {
+        let mut feed = Feed::new(
+            FeedKind::Navigation,
+            "latest",
+            "Latest <updates>",
+            "/opds/latest",
+            0,
+        );
+        feed.paginate("/opds/latest", 2, true);
+        feed.entries.push(navigation_entry(
+            "sources",
+            "By source",
+            None,
+            "/opds/sources",
+            FeedKind::Navigation,
+            0,
+        ));
+        let xml = feed.to_xml();
+        assert!(xml.contains("<title>Latest &lt;updates&gt;</title>"));
+        assert!(xml.contains("<updated>1970-01-01T00:00:00Z</updated>"));
+        assert!(xml.contains(r#"rel="previous" type="application/atom+xml;profile=opds-catalog;kind=navigation" href="/opds/latest?page=1""#));
+        assert!(xml.contains(r#"href="/opds/latest?page=3""#));
+        assert!(xml.contains(r#"<link rel="subsection""#));
+        assert!(xml.trim_end().ends_with("</feed>"));
+
+        let mut search = Feed::new(
+            FeedKind::Navigation,
+            "search",
+            "Search",
+            "/opds/search?q=a",
+            0,
+        );
+        search.paginate("/opds/search?q=a", 1, false);
+        assert!(!search.to_xml().contains("page="));
+    }
+
+    #[test]
+    fn test_manga_entry() {
+        let manga = Manga {
+            id: "m 1".to_string(),
+            title: "Solo".to_string(),
+            cover_url: Some("https://cdn.example.com/c.webp?v=2".to_string()),
+            tags: Some("Action, Fantasy".to_string()),
+            alt_titles: None,
+            description: None,
+            rating: None,
+            status: None,
+            monitored: None,
+            check_interval_secs: None,
+            discover_interval_secs: None,
+            last_chapter_check: None,
+            last_discover_check: None,
+            people: vec![
+                people::credit("Chugong", PersonRole::Author).unwrap(),
+                people::credit("DUBU", PersonRole::Artist).unwrap(),
+            ],
+        };
+        let entry = manga_entry(&manga, 0);
+        assert_eq!(entry.authors, vec!["Chugong"]);
+        assert_eq!(entry.categories, vec!["Action", "Fantasy"]);
+        assert_eq!(entry.links[0].href, "/opds/manga/m%201");
+        assert_eq!(entry.links[1].kind, "image/webp");
+    }
+
+    #[test]
+    fn test_chapter_entry() {
+        let entry = chapter_entry("m1", &chapter(None), None, 0);
+        assert_eq!(
+            entry.links[0].href,
+            "/download/m1/Chapter%203%20%26%204/1?stream=true&format=cbz"
+        );
+        assert_eq!(entry.links[1].href, "/opds/manga/m1/chapters/7");
+        assert_eq!(entry.links[1].pse_count, None);
+
+        let read = ChapterRead {
+            chapter_id: 7,
+            chapter_number: "3".to_string(),
+            source_id: 1,
+            page: 30,
+            completed: true,
+            updated_at: 60,
+        };
+        let entry = chapter_entry("m1", &chapter(Some(20)), Some(&read), 0);
+        let stream = &entry.links[1];
+        assert_eq!(stream.rel, PSE_STREAM_REL);
+        assert_eq!(stream.href, "/opds/chapters/7/pages/{pageNumber}");
+        assert_eq!(
+            (stream.pse_count, stream.pse_last_read),
+            (Some(20), Some(19))
+        );
+        assert_eq!(entry.content.as_deref(), Some("MangaDex · read"));
+
+        let mut lines = Vec::new();
+        entry.push_xml(&mut lines);
+        let xml = lines.join("\n");
+        assert!(xml.contains(
+            r#"pse:count="20" pse:lastRead="19" pse:lastReadDate="1970-01-01T00:01:00Z""#
+        ));
+        assert!(xml.contains("Chapter 3 &amp; 4"));
+    }
+
+    #[test]
+    fn test_page_param() {
+        let query = |v: &str| HashMap::from([("page".to_string(), v.to_string())]);
+        assert_eq!(page_param(&HashMap::new()), 1);
+        assert_eq!(page_param(&query("3")), 3);
+        assert_eq!(page_param(&query("0")), 1);
+        assert_eq!(page_param(&query("x")), 1);
+        assert_eq!(image_type("https://a/cover.PNG"), "image/png");
+        assert_eq!(image_type("https://a/cover"), "image/jpeg");
+    }
+}
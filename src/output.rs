@@ -0,0 +1,673 @@
+//! Chapter output formats: CBZ, fixed-layout EPUB, PDF and a plain folder.
+//!
+//! The downloader hands every writer the same [`Chapter`]: the fetched pages in
+//! reading order plus the [`ComicInfo`] built for them. CBZ and folder output
+//! embed `ComicInfo.xml` as-is; EPUB maps it onto OPF metadata and PDF onto the
+//! document information dictionary. The format is picked per request
+//! (`?format=`), per manga (`POST /manga/{id}/format`) or by `output_format` in
+//! `config.toml`.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_manga_scraper::output::OutputFormat;
+//!
+//! let format = OutputFormat::parse("EPUB").unwrap();
+//! assert_eq!(format.extension(), Some("epub"));
+//! assert_eq!(OutputFormat::parse("folder").unwrap().extension(), None);
+//! ```
+
+use crate::comicinfo::{ComicInfo, ComicPage, ReadingStyle};
+use crate::helpers::xml_escape;
+use crate::images::{self, ImageFormat};
+use crate::scraper::PageImage;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::io::{Cursor, Seek, Write};
+use std::path::Path;
+use zip::write::{FileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Cbz,
+    Epub,
+    Pdf,
+    /// Loose page files and `ComicInfo.xml` in a directory.
+    Folder,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cbz" | "zip" => Some(OutputFormat::Cbz),
+            "epub" => Some(OutputFormat::Epub),
+            "pdf" => Some(OutputFormat::Pdf),
+            "folder" | "dir" => Some(OutputFormat::Folder),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputFormat::Cbz => "cbz",
+            OutputFormat::Epub => "epub",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Folder => "folder",
+        }
+    }
+
+    /// File extension, `None` for folder output.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Folder => None,
+            other => Some(other.as_str()),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Cbz => "application/x-cbz",
+            OutputFormat::Epub => "application/epub+zip",
+            OutputFormat::Pdf => "application/pdf",
+            OutputFormat::Folder => "inode/directory",
+        }
+    }
+
+    pub fn writer(self) -> &'static dyn ChapterWriter {
+        match self {
+            OutputFormat::Cbz => &CbzWriter,
+            OutputFormat::Epub => &EpubWriter,
+            OutputFormat::Pdf => &PdfWriter,
+            OutputFormat::Folder => &FolderWriter,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutputError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("page {0} could not be converted: {1}")]
+    Image(usize, image::ImageError),
+    #[error("{0} output cannot be streamed")]
+    NotStreamable(&'static str),
+}
+
+/// A fetched chapter ready to be written. `comicinfo` already carries the
+/// measured pages (see [`ComicInfo::with_pages`]).
+pub struct Chapter {
+    pub comicinfo: Option<ComicInfo>,
+    pub pages: Vec<PageImage>,
+}
+
+impl Chapter {
+    /// Measure the pages and attach them to `comicinfo`.
+    pub fn new(comicinfo: Option<&ComicInfo>, pages: Vec<PageImage>) -> Self {
+        let comicinfo = comicinfo.map(|info| {
+            let measured = pages
+                .iter()
+                .map(|page| ComicPage::measure(&page.data, page.format))
+                .collect();
+            info.clone().with_pages(measured)
+        });
+        Self { comicinfo, pages }
+    }
+
+    fn page_name(&self, i: usize) -> String {
+        images::page_file_name(i + 1, self.pages.len(), self.pages[i].format)
+    }
+
+    fn page_dimensions(&self, i: usize) -> Option<(u32, u32)> {
+        let page = self.comicinfo.as_ref()?.pages.get(i)?;
+        Some((page.width?, page.height?))
+    }
+
+    fn right_to_left(&self) -> bool {
+        self.comicinfo
+            .as_ref()
+            .is_some_and(|c| c.reading_style == Some(ReadingStyle::RightToLeft))
+    }
+}
+
+/// One output format. Single-file formats only implement [`to_bytes`]; the
+/// default [`write_to`] writes those bytes through a `.tmp` file so an
+/// interrupted download never leaves a truncated file behind.
+///
+/// [`to_bytes`]: ChapterWriter::to_bytes
+/// [`write_to`]: ChapterWriter::write_to
+pub trait ChapterWriter: Sync {
+    fn to_bytes(&self, chapter: &Chapter) -> Result<Vec<u8>, OutputError>;
+
+    fn write_to(&self, chapter: &Chapter, path: &Path) -> Result<(), OutputError> {
+        let bytes = self.to_bytes(chapter)?;
+        let tmp = tmp_path(path);
+        if let Err(e) = std::fs::write(&tmp, bytes) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn tmp_path(path: &Path) -> std::path::PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+pub struct CbzWriter;
+
+impl ChapterWriter for CbzWriter {
+    fn to_bytes(&self, chapter: &Chapter) -> Result<Vec<u8>, OutputError> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        write_cbz(&mut zip, chapter)?;
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+/// Write ComicInfo.xml (if any) and the pages to `zip` in reading order,
+/// named `001.webp`, `002.webp`, ... after their detected format.
+fn write_cbz<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    chapter: &Chapter,
+) -> zip::result::ZipResult<()> {
+    if let Some(info) = &chapter.comicinfo {
+        zip.start_file("ComicInfo.xml", FileOptions::default())?;
+        zip.write_all(info.to_xml().as_bytes())?;
+    }
+    for (i, page) in chapter.pages.iter().enumerate() {
+        zip.start_file(chapter.page_name(i), FileOptions::default())?;
+        zip.write_all(&page.data)?;
+    }
+    Ok(())
+}
+
+pub struct FolderWriter;
+
+impl ChapterWriter for FolderWriter {
+    fn to_bytes(&self, _chapter: &Chapter) -> Result<Vec<u8>, OutputError> {
+        Err(OutputError::NotStreamable("folder"))
+    }
+
+    fn write_to(&self, chapter: &Chapter, path: &Path) -> Result<(), OutputError> {
+        let tmp = tmp_path(path);
+        let res = (|| -> std::io::Result<()> {
+            std::fs::create_dir_all(&tmp)?;
+            if let Some(info) = &chapter.comicinfo {
+                std::fs::write(tmp.join("ComicInfo.xml"), info.to_xml())?;
+            }
+            for (i, page) in chapter.pages.iter().enumerate() {
+                std::fs::write(tmp.join(chapter.page_name(i)), &page.data)?;
+            }
+            if path.exists() {
+                std::fs::remove_dir_all(path)?;
+            }
+            std::fs::rename(&tmp, path)
+        })();
+        if res.is_err() {
+            let _ = std::fs::remove_dir_all(&tmp);
+        }
+        Ok(res?)
+    }
+}
+
+/// Fixed-layout EPUB 3: one XHTML page per image, sized to the image, with the
+/// spine direction taken from the reading style.
+pub struct EpubWriter;
+
+/// Page box used when an image's dimensions are unknown (AVIF).
+const FALLBACK_PAGE_SIZE: (u32, u32) = (800, 1200);
+
+impl ChapterWriter for EpubWriter {
+    fn to_bytes(&self, chapter: &Chapter) -> Result<Vec<u8>, OutputError> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        // `mimetype` must come first and be stored uncompressed.
+        zip.start_file(
+            "mimetype",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(b"application/epub+zip")?;
+        zip.start_file("META-INF/container.xml", FileOptions::default())?;
+        zip.write_all(EPUB_CONTAINER.as_bytes())?;
+        zip.start_file("OEBPS/content.opf", FileOptions::default())?;
+        zip.write_all(epub_package(chapter).as_bytes())?;
+        zip.start_file("OEBPS/nav.xhtml", FileOptions::default())?;
+        zip.write_all(epub_nav(chapter).as_bytes())?;
+        for (i, page) in chapter.pages.iter().enumerate() {
+            let (w, h) = chapter.page_dimensions(i).unwrap_or(FALLBACK_PAGE_SIZE);
+            let name = chapter.page_name(i);
+            zip.start_file(
+                format!("OEBPS/pages/{}.xhtml", i + 1),
+                FileOptions::default(),
+            )?;
+            zip.write_all(
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+<title>Page {n}</title>
+<meta name="viewport" content="width={w}, height={h}"/>
+<style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: {w}px; height: {h}px; }}</style>
+</head>
+<body><img src="../images/{name}" alt="Page {n}"/></body>
+</html>
+"#,
+                    n = i + 1,
+                    w = w,
+                    h = h,
+                    name = name
+                )
+                .as_bytes(),
+            )?;
+            zip.start_file(format!("OEBPS/images/{}", name), FileOptions::default())?;
+            zip.write_all(&page.data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+const EPUB_CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn media_type(format: Option<ImageFormat>) -> &'static str {
+    format.map_or("image/jpeg", ImageFormat::mime_type)
+}
+
+/// Display title: `Series - Title` or `Series - Chapter N`.
+fn book_title(info: &ComicInfo) -> String {
+    match &info.title {
+        Some(title) => format!("{} - {}", info.series, title),
+        None => format!("{} - Chapter {}", info.series, info.number),
+    }
+}
+
+fn epub_package(chapter: &Chapter) -> String {
+    let info = chapter.comicinfo.clone().unwrap_or_default();
+    let mut meta = String::new();
+    let mut dc = |name: &str, value: Option<&str>| {
+        if let Some(v) = value {
+            let _ = writeln!(meta, "    <dc:{0}>{1}</dc:{0}>", name, xml_escape(v));
+        }
+    };
+    dc("title", Some(&book_title(&info)));
+    dc(
+        "language",
+        Some(info.language_iso.as_deref().unwrap_or("en")),
+    );
+    dc("creator", info.writer.as_deref());
+    dc("contributor", info.penciller.as_deref());
+    dc("publisher", info.publisher.as_deref());
+    dc("description", info.summary.as_deref());
+    dc("source", info.web.as_deref());
+    dc("date", info.year.map(|y| y.to_string()).as_deref());
+    for genre in info.genre.iter().flat_map(|g| g.split(',')) {
+        dc("subject", Some(genre.trim()));
+    }
+    let _ = writeln!(
+        meta,
+        "    <dc:identifier id=\"bookid\">urn:uuid:{}</dc:identifier>",
+        uuid::Uuid::new_v4()
+    );
+    let _ = writeln!(
+        meta,
+        "    <meta property=\"belongs-to-collection\" id=\"series\">{}</meta>\n    <meta refines=\"#series\" property=\"collection-type\">series</meta>\n    <meta refines=\"#series\" property=\"group-position\">{}</meta>",
+        xml_escape(&info.series),
+        xml_escape(&info.number)
+    );
+    let _ = writeln!(
+        meta,
+        "    <meta property=\"dcterms:modified\">{}</meta>",
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    );
+
+    let mut manifest = String::from(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    let mut spine = String::new();
+    for (i, page) in chapter.pages.iter().enumerate() {
+        let n = i + 1;
+        let cover = if i == 0 {
+            " properties=\"cover-image\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            manifest,
+            "    <item id=\"img{n}\" href=\"images/{}\" media-type=\"{}\"{cover}/>\n    <item id=\"page{n}\" href=\"pages/{n}.xhtml\" media-type=\"application/xhtml+xml\"/>",
+            chapter.page_name(i),
+            media_type(page.format),
+        );
+        let _ = writeln!(spine, "    <itemref idref=\"page{n}\"/>");
+    }
+    if !chapter.pages.is_empty() {
+        let _ = writeln!(meta, "    <meta name=\"cover\" content=\"img1\"/>");
+    }
+    let direction = if chapter.right_to_left() {
+        "rtl"
+    } else {
+        "ltr"
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="bookid" prefix="rendition: http://www.idpf.org/vocab/rendition/#">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{meta}    <meta property="rendition:layout">pre-paginated</meta>
+    <meta property="rendition:spread">none</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine page-progression-direction="{direction}">
+{spine}  </spine>
+</package>
+"#
+    )
+}
+
+fn epub_nav(chapter: &Chapter) -> String {
+    let title = chapter
+        .comicinfo
+        .as_ref()
+        .map(book_title)
+        .unwrap_or_else(|| "Chapter".to_string());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>{title}</title></head>
+<body>
+<nav epub:type="toc"><ol><li><a href="pages/1.xhtml">{title}</a></li></ol></nav>
+</body>
+</html>
+"#,
+        title = xml_escape(&title)
+    )
+}
+
+/// PDF with one page per image at 72 dpi. JPEG pages are embedded as-is;
+/// other formats are re-encoded to JPEG first.
+pub struct PdfWriter;
+
+impl ChapterWriter for PdfWriter {
+    fn to_bytes(&self, chapter: &Chapter) -> Result<Vec<u8>, OutputError> {
+        let mut pdf = PdfBuilder::default();
+        // Objects 1-3 are the catalog, page tree and info dictionary; each page
+        // then takes three objects (page, image, content stream).
+        let page_ids: Vec<usize> = (0..chapter.pages.len()).map(|i| 4 + i * 3).collect();
+
+        let viewer = if chapter.right_to_left() {
+            " /ViewerPreferences << /Direction /R2L >>"
+        } else {
+            ""
+        };
+        pdf.object(format!("<< /Type /Catalog /Pages 2 0 R{} >>", viewer).as_bytes());
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+        pdf.object(
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                page_ids.len()
+            )
+            .as_bytes(),
+        );
+        pdf.object(pdf_info(chapter.comicinfo.as_ref()).as_bytes());
+
+        for (i, page) in chapter.pages.iter().enumerate() {
+            let jpeg = match page.format {
+                Some(ImageFormat::Jpeg) => page.data.clone(),
+                _ => images::transcode(&page.data, ImageFormat::Jpeg)
+                    .map_err(|e| OutputError::Image(i + 1, e))?,
+            };
+            let (w, h, components) =
+                jpeg_header(&jpeg).unwrap_or((FALLBACK_PAGE_SIZE.0, FALLBACK_PAGE_SIZE.1, 3));
+            let color_space = match components {
+                1 => "/DeviceGray",
+                4 => "/DeviceCMYK",
+                _ => "/DeviceRGB",
+            };
+            let id = page_ids[i];
+            pdf.object(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {w} {h}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                    id + 1,
+                    id + 2
+                )
+                .as_bytes(),
+            );
+            pdf.stream(
+                &format!(
+                    "<< /Type /XObject /Subtype /Image /Width {w} /Height {h} /ColorSpace {color_space} /BitsPerComponent 8 /Filter /DCTDecode"
+                ),
+                &jpeg,
+            );
+            pdf.stream("<<", format!("q {w} 0 0 {h} 0 0 cm /Im0 Do Q").as_bytes());
+        }
+        Ok(pdf.finish())
+    }
+}
+
+/// Minimal PDF serialiser: numbered objects written in order plus the xref table.
+#[derive(Default)]
+struct PdfBuilder {
+    out: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfBuilder {
+    fn start(&mut self) {
+        if self.out.is_empty() {
+            self.out.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+        }
+        self.offsets.push(self.out.len());
+        let _ = writeln!(self.out, "{} 0 obj", self.offsets.len());
+    }
+
+    fn object(&mut self, body: &[u8]) {
+        self.start();
+        self.out.extend_from_slice(body);
+        self.out.extend_from_slice(b"\nendobj\n");
+    }
+
+    /// `dict_head` is an unterminated dictionary; `/Length` and `>>` are appended.
+    fn stream(&mut self, dict_head: &str, data: &[u8]) {
+        self.start();
+        let _ = write!(
+            self.out,
+            "{} /Length {} >>\nstream\n",
+            dict_head,
+            data.len()
+        );
+        self.out.extend_from_slice(data);
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.out.len();
+        let _ = write!(
+            self.out,
+            "xref\n0 {}\n0000000000 65535 f \n",
+            self.offsets.len() + 1
+        );
+        for offset in &self.offsets {
+            let _ = writeln!(self.out, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            self.out,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            xref
+        );
+        self.out
+    }
+}
+
+/// PDF text string as UTF-16BE hex, so titles in any script survive.
+fn pdf_text(s: &str) -> String {
+    let mut hex = String::from("<FEFF");
+    for unit in s.encode_utf16() {
+        let _ = write!(hex, "{:04X}", unit);
+    }
+    hex.push('>');
+    hex
+}
+
+fn pdf_info(info: Option<&ComicInfo>) -> String {
+    let mut dict = String::from("<< /Producer (rust_manga_scraper)");
+    if let Some(info) = info {
+        let _ = write!(dict, " /Title {}", pdf_text(&book_title(info)));
+        let entries = [
+            ("Author", info.writer.as_deref()),
+            ("Subject", info.summary.as_deref()),
+            ("Keywords", info.genre.as_deref()),
+            ("Creator", info.publisher.as_deref()),
+        ];
+        for (key, value) in entries {
+            if let Some(v) = value {
+                let _ = write!(dict, " /{} {}", key, pdf_text(v));
+            }
+        }
+    }
+    let _ = write!(
+        dict,
+        " /CreationDate (D:{}Z) >>",
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    dict
+}
+
+/// Width, height and colour component count from a JPEG's SOF marker.
+fn jpeg_header(data: &[u8]) -> Option<(u32, u32, u8)> {
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof && pos + 10 <= data.len() {
+            let h = u16::from_be_bytes([data[pos + 5], data[pos + 6]]) as u32;
+            let w = u16::from_be_bytes([data[pos + 7], data[pos + 8]]) as u32;
+            return Some((w, h, data[pos + 9]));
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn png() -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(4, 6)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn chapter(formats: &[Option<ImageFormat>]) -> Chapter {
+        let pages = formats
+            .iter()
+            .map(|&format| PageImage {
+                data: if format == Some(ImageFormat::Png) {
+                    png()
+                } else {
+                    b"x".to_vec()
+                },
+                format,
+            })
+            .collect();
+        Chapter::new(Some(&ComicInfo::new("Series & Co", "Chapter 2")), pages)
+    }
+
+    #[test]
+    fn test_cbz_keeps_page_order() {
+        let ch = chapter(&[Some(ImageFormat::Webp), Some(ImageFormat::Png), None]);
+        let data = CbzWriter.to_bytes(&ch).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 4);
+        for (i, expected) in ["ComicInfo.xml", "001.webp", "002.png", "003.jpg"]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(archive.by_index(i).unwrap().name(), *expected);
+        }
+        let mut xml = String::new();
+        archive
+            .by_name("ComicInfo.xml")
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        assert!(xml.contains("<PageCount>3</PageCount>"));
+        assert!(xml.contains(r#"ImageWidth="4" ImageHeight="6""#));
+    }
+
+    #[test]
+    fn test_epub_layout() {
+        let data = EpubWriter
+            .to_bytes(&chapter(&[Some(ImageFormat::Png)]))
+            .unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        let first = archive.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        drop(first);
+
+        let mut opf = String::new();
+        archive
+            .by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        assert!(opf.contains("<dc:title>Series &amp; Co - Chapter 2</dc:title>"));
+        assert!(opf.contains("pre-paginated"));
+        assert!(opf
+            .contains(r#"href="images/001.png" media-type="image/png" properties="cover-image""#));
+        assert!(archive.by_name("OEBPS/pages/1.xhtml").is_ok());
+    }
+
+    #[test]
+    fn test_pdf_structure() {
+        let pdf = PdfWriter
+            .to_bytes(&chapter(&[Some(ImageFormat::Png)]))
+            .unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(text.contains("/Count 1"));
+        assert!(text.contains("/MediaBox [0 0 4 6]"));
+        assert!(text.contains("/Filter /DCTDecode"));
+        assert!(text.trim_end().ends_with("%%EOF"));
+
+        // Every xref offset points at the start of its object.
+        let xref = pdf.windows(5).rposition(|w| w == b"xref\n").unwrap();
+        let table = String::from_utf8_lossy(&pdf[xref..]).to_string();
+        for (n, line) in table.lines().skip(3).take(6).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", n + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_folder_cannot_stream() {
+        assert!(matches!(
+            FolderWriter.to_bytes(&chapter(&[None])),
+            Err(OutputError::NotStreamable(_))
+        ));
+        assert_eq!(OutputFormat::parse("nope"), None);
+    }
+}
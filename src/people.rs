@@ -0,0 +1,214 @@
+//! Creators credited on a manga: authors, artists and publishers.
+//!
+//! Credits come from MangaDex `author`/`artist` relationships when a manga is
+//! scraped and from MAL, AniList and MangaBaka during the metadata merge. They
+//! are stored in `people` and `manga_people` (one row per manga, person and
+//! role) and end up in the ComicInfo `Writer` and `Penciller` fields.
+//!
+//! Providers write names differently: MAL gives "Oda, Eiichiro", MangaDex
+//! "Oda Eiichiro", AniList "Eiichiro Oda". [`name_key`] ignores word order and
+//! punctuation, so all three are one person.
+
+use crate::models::{Credit, PersonWork};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersonRole {
+    /// Story; ComicInfo `Writer`
+    Author,
+    /// Art; ComicInfo `Penciller`
+    Artist,
+    /// Publisher, or for MAL the magazine serializing the work
+    Publisher,
+}
+
+impl PersonRole {
+    pub const ALL: [PersonRole; 3] = [
+        PersonRole::Author,
+        PersonRole::Artist,
+        PersonRole::Publisher,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PersonRole::Author => "author",
+            PersonRole::Artist => "artist",
+            PersonRole::Publisher => "publisher",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == s)
+    }
+}
+
+/// Identity of a name for `people.name_key`: its lowercased words, sorted.
+pub fn name_key(name: &str) -> String {
+    let mut words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    words.sort_unstable();
+    words.join(" ")
+}
+
+/// A credit for `name`, or `None` if the name is blank.
+pub fn credit(name: &str, role: PersonRole) -> Option<Credit> {
+    let name = name.trim();
+    if name_key(name).is_empty() {
+        return None;
+    }
+    Some(Credit {
+        person_id: None,
+        name: name.to_string(),
+        role,
+    })
+}
+
+/// "Family, Given" (MAL's format) as "Given Family"; other names unchanged.
+pub fn given_name_first(name: &str) -> String {
+    match name.split_once(',') {
+        Some((family, given)) if !given.contains(',') && !given.trim().is_empty() => {
+            format!("{} {}", given.trim(), family.trim())
+        }
+        _ => name.trim().to_string(),
+    }
+}
+
+/// Roles of an AniList staff edge: "Story & Art" is both, "Story (ch. 1-20)"
+/// is an author. Translators, letterers and assistants get no role.
+pub fn anilist_roles(role: &str) -> Vec<PersonRole> {
+    let role = role.split('(').next().unwrap_or_default();
+    let mut roles = Vec::new();
+    for part in role.split(['&', ',']) {
+        let r = match part.trim().to_lowercase().as_str() {
+            "story" | "original story" | "original creator" | "original work" => PersonRole::Author,
+            "art" | "original art" | "illustration" => PersonRole::Artist,
+            _ => continue,
+        };
+        if !roles.contains(&r) {
+            roles.push(r);
+        }
+    }
+    roles
+}
+
+/// Credits without repeats of the same person in the same role.
+pub fn dedup(credits: Vec<Credit>) -> Vec<Credit> {
+    let mut out: Vec<Credit> = Vec::new();
+    for c in credits {
+        let key = name_key(&c.name);
+        if !out
+            .iter()
+            .any(|o| o.role == c.role && name_key(&o.name) == key)
+        {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Combine provider credits: for each role, the first list that credits
+/// anyone in that role wins. Order the lists from most to least reliable.
+pub fn prefer(lists: &[Vec<Credit>]) -> Vec<Credit> {
+    let mut out = Vec::new();
+    for role in PersonRole::ALL {
+        if let Some(list) = lists.iter().find(|l| l.iter().any(|c| c.role == role)) {
+            out.extend(list.iter().filter(|c| c.role == role).cloned());
+        }
+    }
+    dedup(out)
+}
+
+/// Names credited in `role`, joined with ", " (for ComicInfo).
+pub fn names(credits: &[Credit], role: PersonRole) -> Option<String> {
+    let names: Vec<&str> = credits
+        .iter()
+        .filter(|c| c.role == role)
+        .map(|c| c.name.as_str())
+        .collect();
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(", "))
+    }
+}
+
+/// Fold `(manga_id, title, cover_url, role)` rows, sorted by manga, into one
+/// work per manga.
+pub fn group_works(rows: Vec<(String, String, Option<String>, String)>) -> Vec<PersonWork> {
+    let mut works: Vec<PersonWork> = Vec::new();
+    for (manga_id, title, cover_url, role) in rows {
+        let Some(role) = PersonRole::parse(&role) else {
+            continue;
+        };
+        match works.last_mut() {
+            Some(w) if w.manga_id == manga_id => w.roles.push(role),
+            _ => works.push(PersonWork {
+                manga_id,
+                title,
+                cover_url: cover_url.filter(|u| !u.is_empty()),
+                roles: vec![role],
+            }),
+        }
+    }
+    works
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(name: &str, role: PersonRole) -> Credit {
+        credit(name, role).unwrap()
+    }
+
+    #[test]
+    fn test_name_key() {
+        assert_eq!(name_key("Oda, Eiichiro"), name_key("Eiichiro Oda"));
+        assert_eq!(name_key("ODA Eiichiro"), "eiichiro oda");
+        assert_eq!(given_name_first("Oda, Eiichiro"), "Eiichiro Oda");
+        assert_eq!(given_name_first("Chugong"), "Chugong");
+        assert!(credit(" - ", PersonRole::Author).is_none());
+    }
+
+    #[test]
+    fn test_anilist_roles() {
+        use PersonRole::*;
+        assert_eq!(anilist_roles("Story & Art"), vec![Author, Artist]);
+        assert_eq!(anilist_roles("Story (ch. 1-20)"), vec![Author]);
+        assert_eq!(anilist_roles("Original Creator"), vec![Author]);
+        assert!(anilist_roles("Translator (English)").is_empty());
+        assert!(anilist_roles("Assistant").is_empty());
+    }
+
+    #[test]
+    fn test_prefer() {
+        use PersonRole::*;
+        let anilist = vec![c("Chugong", Author), c("DUBU", Artist)];
+        let mal = vec![
+            c("Chugong", Author),
+            c("Jang, Sung-Rak", Author),
+            c("D&C Media", Publisher),
+        ];
+        let merged = prefer(&[anilist, mal]);
+        let got: Vec<(&str, PersonRole)> =
+            merged.iter().map(|c| (c.name.as_str(), c.role)).collect();
+        assert_eq!(
+            got,
+            vec![
+                ("Chugong", Author),
+                ("DUBU", Artist),
+                ("D&C Media", Publisher)
+            ]
+        );
+        assert_eq!(names(&merged, Artist).as_deref(), Some("DUBU"));
+        assert_eq!(names(&merged, Author).as_deref(), Some("Chugong"));
+        assert_eq!(
+            dedup(vec![c("Oda Eiichiro", Author), c("Eiichiro Oda", Author)]).len(),
+            1
+        );
+    }
+}
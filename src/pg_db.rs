@@ -1,18 +1,36 @@
-use crate::models::{Chapter, Manga, MangaSourceData};
+use crate::config::DatabaseConfig;
+use crate::auth::{self, Scope};
+use crate::models::{
+    ApiKey, Chapter, ChapterFile, ChapterRead, ChapterWithSource, ContinueReading, Credit,
+    DownloadJob, LibraryEntry, Manga, MangaSourceData, Person, Tag, User,
+};
+use crate::people::{self, PersonRole};
+use crate::reading::LibraryStatus;
+use crate::search::{self, MangaSearch, SearchSort, TagFilter};
+use crate::sources::catalogue;
+use crate::tags::{self, TagRef};
+use crate::storage::{
+    MangaTitles, MatchCandidate, MetadataProvider, PerSourceCounts, ProviderLinks, SampleChapter,
+    SourceStats, StorageError, UndoOutcome,
+};
 use deadpool_postgres::{Config, ManagerConfig, Pool, RecyclingMethod, Runtime};
 use log::{error, info};
 use tokio_postgres::{NoTls, Error as PgError};
 
-/// Creates and returns a PostgreSQL connection pool
-pub fn create_pool() -> Pool {
+/// Creates and returns a PostgreSQL connection pool for `[database]` in config.toml
+pub fn create_pool(db: &DatabaseConfig) -> Pool {
     info!("Creating PostgreSQL connection pool...");
 
     let mut cfg = Config::new();
-    cfg.host = Some("localhost".to_string());
-    cfg.port = Some(5432);
-    cfg.dbname = Some("manga_scraper".to_string());
-    cfg.user = Some("manga_admin".to_string());
-    cfg.password = Some("manga_password".to_string());
+    if let Some(url) = &db.url {
+        cfg.url = Some(url.clone());
+    } else {
+        cfg.host = Some(db.host.clone());
+        cfg.port = Some(db.port);
+        cfg.dbname = Some(db.dbname.clone());
+        cfg.user = Some(db.user.clone());
+        cfg.password = Some(db.password.clone());
+    }
     cfg.manager = Some(ManagerConfig {
         recycling_method: RecyclingMethod::Fast,
     });
@@ -25,71 +43,60 @@ pub fn create_pool() -> Pool {
     pool
 }
 
-/// Get paginated manga list with optional filtering
-pub async fn get_manga_paginated(
-    pool: &Pool,
-    limit: Option<i32>,
-    offset: Option<i32>,
-    sort_by: &str,
-    rating_filter: Option<&str>,
-) -> Result<Vec<Manga>, PgError> {
-    let client = pool.get().await.expect("Failed to get connection from pool");
+/// Insert any catalogue sources missing from the `sources` table. Existing rows
+/// are left alone; `check_sources_table` reports those that disagree.
+pub async fn seed_sources(pool: &Pool) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+    client.batch_execute(&catalogue::seed_sql()).await?;
+    Ok(())
+}
 
-    let sort_expr = match sort_by {
-        "rating" => "CASE lower(coalesce(rating,'')) WHEN 'safe' THEN 1 WHEN 'suggestive' THEN 2 WHEN 'erotica' THEN 3 WHEN 'pornographic' THEN 4 ELSE 5 END, title",
-        _ => "title",
-    };
+/// Insert or update the tag dictionary rows of the `tags` table. Tags created
+/// for labels outside the dictionary are left alone.
+pub async fn seed_tags(pool: &Pool) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+    client.batch_execute(&tags::seed_sql()).await?;
+    Ok(())
+}
 
-    let mut query = format!(
-        "SELECT id, title, alt_titles, cover_url, description, tags, rating, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check FROM manga"
-    );
+/// Compare the `sources` table with the source catalogue and describe every
+/// disagreement (missing IDs, wrong names, IDs the catalogue does not know).
+/// An empty result means the table is consistent.
+pub async fn check_sources_table(pool: &Pool) -> Result<Vec<String>, StorageError> {
+    let client = pool.get().await?;
 
-    let limit_val = limit.unwrap_or(100);
-    let offset_val = offset.unwrap_or(0);
-
-    if let Some(r) = rating_filter {
-        query.push_str(&format!(" WHERE lower(rating) = lower('{}') ORDER BY {} LIMIT $1 OFFSET $2", r, sort_expr));
-        let rows = client.query(&query, &[&limit_val, &offset_val]).await?;
-
-        let manga_list: Vec<Manga> = rows.iter().map(|row| Manga {
-            id: row.get(0),
-            title: row.get(1),
-            alt_titles: row.get(2),
-            cover_url: row.get(3),
-            description: row.get(4),
-            tags: row.get(5),
-            rating: row.get(6),
-            monitored: row.get(7),
-            check_interval_secs: row.get(8),
-            discover_interval_secs: row.get(9),
-            last_chapter_check: row.get(10),
-            last_discover_check: row.get(11),
-        }).collect();
-
-        return Ok(manga_list);
-    } else {
-        query.push_str(&format!(" ORDER BY {} LIMIT $1 OFFSET $2", sort_expr));
-        let rows = client.query(&query, &[&limit_val, &offset_val]).await?;
-
-        let manga_list: Vec<Manga> = rows.iter().map(|row| Manga {
-            id: row.get(0),
-            title: row.get(1),
-            alt_titles: row.get(2),
-            cover_url: row.get(3),
-            description: row.get(4),
-            tags: row.get(5),
-            rating: row.get(6),
-            monitored: row.get(7),
-            check_interval_secs: row.get(8),
-            discover_interval_secs: row.get(9),
-            last_chapter_check: row.get(10),
-            last_discover_check: row.get(11),
-        }).collect();
-
-        Ok(manga_list)
+    let rows = client.query("SELECT id, name FROM sources ORDER BY id", &[]).await?;
+    let db: Vec<(i32, String)> = rows.iter().map(|r| (r.get(0), r.get(1))).collect();
+    Ok(catalogue::table_problems(&db))
+}
+
+const MANGA_COLUMNS: &str = "m.id, m.title, m.alt_titles, m.cover_url, m.description, m.tags, m.rating, m.status,
+    m.monitored, m.check_interval_secs, m.discover_interval_secs, m.last_chapter_check, m.last_discover_check";
+
+fn manga_from_row(row: &tokio_postgres::Row) -> Manga {
+    Manga {
+        id: row.get(0),
+        title: row.get(1),
+        alt_titles: row.get(2),
+        cover_url: row.get(3),
+        description: row.get(4),
+        tags: row.get(5),
+        rating: row.get(6),
+        status: row.get(7),
+        monitored: row.get(8),
+        check_interval_secs: row.get(9),
+        discover_interval_secs: row.get(10),
+        last_chapter_check: row.get(11),
+        last_discover_check: row.get(12),
+        people: Vec::new(),
     }
 }
 
+/// When a manga last got a new chapter, for `SearchSort::Updated`
+const UPDATED_ORDER: &str = "(SELECT MAX(c.first_seen_at) FROM manga_source_data msd
+    JOIN chapters c ON c.manga_source_data_id = msd.id WHERE msd.manga_id = m.id)";
+const RATING_ORDER: &str = "CASE lower(coalesce(m.rating,'')) WHEN 'safe' THEN 1 WHEN 'suggestive' THEN 2 WHEN 'erotica' THEN 3 WHEN 'pornographic' THEN 4 ELSE 5 END";
+
 /// Get total manga count
 pub async fn get_manga_count(pool: &Pool) -> Result<i64, PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
@@ -117,14 +124,6 @@ pub async fn get_source_count(pool: &Pool) -> Result<i64, PgError> {
     Ok(count)
 }
 
-#[derive(Debug, serde::Serialize)]
-pub struct PerSourceCounts {
-    pub source_id: i32,
-    pub source_name: String,
-    pub manga: i64,
-    pub chapters: i64,
-}
-
 /// Get per-source manga and chapter counts
 pub async fn get_per_source_counts(pool: &Pool) -> Result<Vec<PerSourceCounts>, PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
@@ -148,6 +147,47 @@ pub async fn get_per_source_counts(pool: &Pool) -> Result<Vec<PerSourceCounts>,
     Ok(counts)
 }
 
+/// Manga, chapter and metadata-linked manga counts for one source
+pub async fn get_source_stats(pool: &Pool, source_id: i32) -> Result<SourceStats, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_one(
+        "SELECT
+            (SELECT COUNT(DISTINCT msd.manga_id) FROM manga_source_data msd WHERE msd.source_id = $1),
+            (SELECT COUNT(*) FROM chapters c JOIN manga_source_data msd ON c.manga_source_data_id = msd.id
+             WHERE msd.source_id = $1),
+            (SELECT COUNT(DISTINCT m.id) FROM manga m JOIN manga_source_data msd ON msd.manga_id = m.id
+             WHERE msd.source_id = $1
+               AND (COALESCE(m.mangabaka_id, '') <> '' OR COALESCE(m.mal_id, 0) > 0 OR COALESCE(m.anilist_id, 0) > 0))",
+        &[&source_id],
+    ).await?;
+
+    Ok(SourceStats {
+        manga: row.get(0),
+        chapters: row.get(1),
+        metadata_linked: row.get(2),
+    })
+}
+
+/// Any one chapter stored for a source
+pub async fn get_sample_chapter(pool: &Pool, source_id: i32) -> Result<Option<SampleChapter>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt(
+        "SELECT msd.manga_id, c.chapter_number, c.url, msd.source_manga_url
+         FROM manga_source_data msd JOIN chapters c ON c.manga_source_data_id = msd.id
+         WHERE msd.source_id = $1 LIMIT 1",
+        &[&source_id],
+    ).await?;
+
+    Ok(row.map(|row| SampleChapter {
+        manga_id: row.get(0),
+        chapter_number: row.get(1),
+        url: row.get(2),
+        series_url: row.get(3),
+    }))
+}
+
 /// Get source name by ID
 pub async fn get_source_name(pool: &Pool, source_id: i32) -> Result<String, PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
@@ -157,110 +197,133 @@ pub async fn get_source_name(pool: &Pool, source_id: i32) -> Result<String, PgEr
     Ok(name)
 }
 
-/// Search manga with pagination and filtering
-pub async fn search_manga_paginated(
-    pool: &Pool,
-    query: &str,
-    tags: Option<&str>,
-    rating: Option<&str>,
-    limit: Option<i32>,
-    offset: Option<i32>,
-    sort_by: &str,
-) -> Result<Vec<Manga>, PgError> {
-    let client = pool.get().await.expect("Failed to get connection from pool");
-
-    let search_pattern = format!("%{}%", query);
-    let sort_expr = match sort_by {
-        "rating" => "CASE lower(coalesce(rating,'')) WHEN 'safe' THEN 1 WHEN 'suggestive' THEN 2 WHEN 'erotica' THEN 3 WHEN 'pornographic' THEN 4 ELSE 5 END, title",
-        _ => "title",
+type SqlParam = Box<dyn tokio_postgres::types::ToSql + Sync + Send>;
+
+/// WHERE clause for a manga search over `manga m`, with its parameters, plus
+/// the relevance expression (which reuses the same parameters)
+fn search_clause(search: &MangaSearch) -> (String, String, Vec<SqlParam>) {
+    let mut params: Vec<SqlParam> = Vec::new();
+    let mut conditions: Vec<String> = Vec::new();
+    let mut relevance = "0".to_string();
+    let next = |params: &mut Vec<SqlParam>, value: SqlParam| {
+        params.push(value);
+        format!("${}", params.len())
     };
 
-    let mut where_clauses: Vec<String> = vec!["(title ILIKE $1 OR alt_titles ILIKE $2)".to_string()];
-    let mut param_count = 2;
-
-    if tags.is_some() {
-        param_count += 1;
-        where_clauses.push(format!("tags ILIKE ${}", param_count));
+    if let Some(text) = search.text() {
+        let tsquery = next(&mut params, Box::new(search::prefix_tsquery(text)));
+        let q = next(&mut params, Box::new(text.to_string()));
+        conditions.push(format!(
+            "(m.search_vector @@ to_tsquery('simple', {tsq}::text) OR m.title % {q}::text OR {q}::text <% coalesce(m.alt_titles, ''))",
+            tsq = tsquery, q = q
+        ));
+        relevance = format!(
+            "coalesce(ts_rank(m.search_vector, to_tsquery('simple', {tsq}::text)), 0)
+             + similarity(m.title, {q}::text) + 0.5 * word_similarity({q}::text, coalesce(m.alt_titles, ''))",
+            tsq = tsquery, q = q
+        );
     }
-
-    if rating.is_some() {
-        param_count += 1;
-        where_clauses.push(format!("lower(rating) = lower(${})", param_count));
+    if let Some(status) = &search.status {
+        let p = next(&mut params, Box::new(status.clone()));
+        conditions.push(format!("lower(m.status) = lower({})", p));
     }
-
-    let sql = format!(
-        "SELECT id, title, alt_titles, cover_url, description, tags, rating, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check FROM manga WHERE {} ORDER BY {} LIMIT ${} OFFSET ${}",
-        where_clauses.join(" AND "),
-        sort_expr,
-        param_count + 1,
-        param_count + 2
-    );
-
-    let limit_val = limit.unwrap_or(100);
-    let offset_val = offset.unwrap_or(0);
-
-    let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![&search_pattern, &search_pattern];
-
-    let tag_pattern;
-    if let Some(tag_filter) = tags {
-        tag_pattern = format!("%{}%", tag_filter);
-        params.push(&tag_pattern);
+    if let Some(source_id) = search.source_id {
+        let p = next(&mut params, Box::new(source_id));
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM manga_source_data msd WHERE msd.manga_id = m.id AND msd.source_id = {})",
+            p
+        ));
     }
-
-    if let Some(r) = &rating {
-        params.push(r);
+    let tags = search.include_tags.iter().map(|t| ("", t));
+    for (negate, tag) in tags.chain(search.exclude_tags.iter().map(|t| ("NOT ", t))) {
+        let (column, p) = match tag {
+            TagFilter::Id(id) => ("t.id", next(&mut params, Box::new(*id))),
+            TagFilter::Slug(slug) => ("t.slug", next(&mut params, Box::new(slug.clone()))),
+        };
+        conditions.push(format!(
+            "{}EXISTS (SELECT 1 FROM manga_tags mt JOIN tags t ON t.id = mt.tag_id
+             WHERE mt.manga_id = m.id AND {} = {})",
+            negate, column, p
+        ));
+    }
+    if let Some(rating) = &search.rating {
+        let p = next(&mut params, Box::new(rating.clone()));
+        conditions.push(format!("lower(m.rating) = lower({})", p));
+    }
+    if let Some(has_unread) = search.has_unread {
+        conditions.push(format!(
+            "{}EXISTS (SELECT 1 FROM manga_source_data msd JOIN chapters c ON c.manga_source_data_id = msd.id
+             WHERE msd.manga_id = m.id AND NOT c.scraped)",
+            if has_unread { "" } else { "NOT " }
+        ));
     }
 
-    params.push(&limit_val);
-    params.push(&offset_val);
+    let clause = if conditions.is_empty() {
+        "TRUE".to_string()
+    } else {
+        conditions.join(" AND ")
+    };
+    (clause, relevance, params)
+}
 
-    let rows = client.query(&sql, &params[..]).await?;
+/// One page of a manga search; see `search.rs` for how text is matched
+pub async fn search_manga(pool: &Pool, search: &MangaSearch) -> Result<Vec<Manga>, StorageError> {
+    let client = pool.get().await?;
 
-    let manga_list: Vec<Manga> = rows.iter().map(|row| Manga {
-        id: row.get(0),
-        title: row.get(1),
-        alt_titles: row.get(2),
-        cover_url: row.get(3),
-        description: row.get(4),
-        tags: row.get(5),
-        rating: row.get(6),
-        monitored: row.get(7),
-        check_interval_secs: row.get(8),
-        discover_interval_secs: row.get(9),
-        last_chapter_check: row.get(10),
-        last_discover_check: row.get(11),
-    }).collect();
+    let (clause, relevance, mut params) = search_clause(search);
+    let order = match search.sort {
+        _ if search.by_relevance() => format!("{} DESC, m.title", relevance),
+        SearchSort::Rating => format!("{}, m.title", RATING_ORDER),
+        SearchSort::Updated => format!("{} DESC NULLS LAST, m.title", UPDATED_ORDER),
+        _ => "m.title".to_string(),
+    };
+    params.push(Box::new(search.limit));
+    params.push(Box::new(search.offset));
+    let sql = format!(
+        "SELECT {} FROM manga m WHERE {} ORDER BY {} LIMIT ${} OFFSET ${}",
+        MANGA_COLUMNS, clause, order, params.len() - 1, params.len()
+    );
 
-    Ok(manga_list)
+    let refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
+        params.iter().map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync)).collect();
+    let rows = client.query(&sql, &refs).await?;
+    Ok(rows.iter().map(manga_from_row).collect())
+}
+
+/// Number of manga a search matches across all pages
+pub async fn count_manga_matching(pool: &Pool, search: &MangaSearch) -> Result<i64, StorageError> {
+    let client = pool.get().await?;
+
+    let (clause, _, params) = search_clause(search);
+    let refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
+        params.iter().map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync)).collect();
+    let row = client.query_one(&format!("SELECT COUNT(*) FROM manga m WHERE {}", clause), &refs).await?;
+    Ok(row.get(0))
 }
 
 /// Get manga by ID
 pub async fn get_manga_by_id(pool: &Pool, manga_id: &str) -> Result<Option<Manga>, PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
 
-    let rows = client.query(
-        "SELECT id, title, alt_titles, cover_url, description, tags, rating, monitored, check_interval_secs, discover_interval_secs, last_chapter_check, last_discover_check FROM manga WHERE id = $1",
+    let Some(row) = client.query_opt(
+        &format!("SELECT {} FROM manga m WHERE m.id = $1", MANGA_COLUMNS),
         &[&manga_id]
-    ).await?;
+    ).await? else {
+        return Ok(None);
+    };
 
-    if let Some(row) = rows.get(0) {
-        Ok(Some(Manga {
-            id: row.get(0),
-            title: row.get(1),
-            alt_titles: row.get(2),
-            cover_url: row.get(3),
-            description: row.get(4),
-            tags: row.get(5),
-            rating: row.get(6),
-            monitored: row.get(7),
-            check_interval_secs: row.get(8),
-            discover_interval_secs: row.get(9),
-            last_chapter_check: row.get(10),
-            last_discover_check: row.get(11),
-        }))
-    } else {
-        Ok(None)
-    }
+    let mut manga = manga_from_row(&row);
+    let rows = client.query(
+        "SELECT p.id, p.name, mp.role::text FROM manga_people mp JOIN people p ON p.id = mp.person_id
+         WHERE mp.manga_id = $1 ORDER BY p.name, mp.role",
+        &[&manga_id],
+    ).await?;
+    manga.people = rows.iter().filter_map(|row| Some(Credit {
+        person_id: Some(row.get(0)),
+        name: row.get(1),
+        role: PersonRole::parse(row.get(2))?,
+    })).collect();
+    Ok(Some(manga))
 }
 
 /// Get chapters by manga ID and source ID
@@ -281,51 +344,175 @@ pub async fn get_chapters_by_manga_source_data_id(
 
     // Then get chapters
     let rows = client.query(
-        "SELECT id, manga_source_data_id, chapter_number, url, scraped FROM chapters WHERE manga_source_data_id = $1",
+        &format!(
+            "SELECT {} FROM chapters c WHERE c.manga_source_data_id = $1
+             ORDER BY c.sort_key NULLS LAST, c.id",
+            CHAPTER_COLUMNS
+        ),
         &[&manga_source_data_id]
     ).await?;
 
-    let chapters: Vec<Chapter> = rows.iter().map(|row| Chapter {
+    let chapters: Vec<Chapter> = rows.iter().map(chapter_from_row).collect();
+
+    Ok(chapters)
+}
+
+const CHAPTER_COLUMNS: &str = "c.id, c.manga_source_data_id, c.chapter_number, c.url, c.scraped,
+    c.number, c.volume, c.title, c.language, c.scanlation_group, c.published_at, c.page_count,
+    c.first_seen_at, c.sort_key, c.file_path, c.file_size, c.file_hash, c.downloaded_at";
+
+fn chapter_from_row(row: &tokio_postgres::Row) -> Chapter {
+    Chapter {
         id: row.get(0),
         manga_source_data_id: row.get(1),
         chapter_number: row.get(2),
         url: row.get(3),
         scraped: row.get(4),
-    }).collect();
+        number: row.get(5),
+        volume: row.get(6),
+        title: row.get(7),
+        language: row.get(8),
+        scanlation_group: row.get(9),
+        published_at: row.get(10),
+        page_count: row.get(11),
+        first_seen_at: row.get(12),
+        sort_key: row.get(13),
+        file_path: row.get(14),
+        file_size: row.get(15),
+        file_hash: row.get(16),
+        downloaded_at: row.get(17),
+    }
+}
 
-    Ok(chapters)
+/// Get a single chapter by ID together with the source it belongs to
+pub async fn get_chapter_with_source(
+    pool: &Pool,
+    chapter_id: i32,
+) -> Result<Option<ChapterWithSource>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        &format!(
+            "SELECT {}, msd.source_id, s.name
+             FROM chapters c
+             JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+             JOIN sources s ON s.id = msd.source_id
+             WHERE c.id = $1",
+            CHAPTER_COLUMNS
+        ),
+        &[&chapter_id]
+    ).await?;
+
+    Ok(rows.first().map(|row| {
+        ChapterWithSource::new(chapter_from_row(row), row.get(18), row.get(19))
+    }))
+}
+
+/// Record how many pages a chapter has, once its pages have been resolved
+pub async fn set_chapter_page_count(pool: &Pool, chapter_id: i32, page_count: i32) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE chapters SET page_count = $2 WHERE id = $1",
+        &[&chapter_id, &page_count],
+    ).await?;
+    Ok(())
+}
+
+/// Record the file a chapter was downloaded to and mark it scraped
+pub async fn set_chapter_file(
+    pool: &Pool,
+    chapter_id: i32,
+    file: &ChapterFile,
+    now_ts: i64,
+) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE chapters SET scraped = TRUE, file_path = $2, file_size = $3, file_hash = $4, downloaded_at = $5
+         WHERE id = $1",
+        &[&chapter_id, &file.path, &file.size, &file.hash, &now_ts],
+    ).await?;
+    Ok(())
+}
+
+/// Forget a chapter's file (it is no longer on disk) and mark it not scraped
+pub async fn clear_chapter_file(pool: &Pool, chapter_id: i32) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE chapters SET scraped = FALSE, file_path = NULL, file_size = NULL, file_hash = NULL, downloaded_at = NULL
+         WHERE id = $1",
+        &[&chapter_id],
+    ).await?;
+    Ok(())
+}
+
+/// Every chapter with a recorded file, as `(chapter_id, file_path)`
+pub async fn get_downloaded_chapters(
+    pool: &Pool,
+) -> Result<Vec<(String, ChapterWithSource)>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        &format!(
+            "SELECT {}, msd.source_id, s.name, m.title
+             FROM chapters c
+             JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+             JOIN sources s ON s.id = msd.source_id
+             JOIN manga m ON m.id = msd.manga_id
+             WHERE c.file_path IS NOT NULL
+             ORDER BY c.id",
+            CHAPTER_COLUMNS
+        ),
+        &[],
+    ).await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let chapter = ChapterWithSource::new(chapter_from_row(row), row.get(18), row.get(19));
+            (row.get(20), chapter)
+        })
+        .collect())
 }
 
 /// Insert manga (upsert)
 pub async fn insert_manga(pool: &Pool, manga: &Manga) -> Result<(), PgError> {
-    let client = pool.get().await.expect("Failed to get connection from pool");
-
-    match client.execute(
-        "INSERT INTO manga (id, title, alt_titles, cover_url, description, tags, rating)
-         VALUES ($1, $2, $3, $4, $5, $6, $7)
+    let mut client = pool.get().await.expect("Failed to get connection from pool");
+    let tx = client.transaction().await?;
+
+    // Tags are stored in canonical form; a manga scraped without tags keeps its own
+    let tags = tags::parse_list(None, manga.tags.as_deref().unwrap_or(""));
+    if let Err(e) = tx.execute(
+        "INSERT INTO manga (id, title, alt_titles, cover_url, description, tags, rating, status)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
          ON CONFLICT(id) DO UPDATE SET
             title = EXCLUDED.title,
             alt_titles = EXCLUDED.alt_titles,
             cover_url = EXCLUDED.cover_url,
             description = EXCLUDED.description,
-            tags = EXCLUDED.tags,
-            rating = EXCLUDED.rating",
+            tags = COALESCE(NULLIF(EXCLUDED.tags, ''), manga.tags),
+            rating = EXCLUDED.rating,
+            status = COALESCE(EXCLUDED.status, manga.status)",
         &[
             &manga.id,
             &manga.title,
             &manga.alt_titles.as_deref().unwrap_or(""),
             &manga.cover_url.as_deref().unwrap_or(""),
             &manga.description.as_deref().unwrap_or(""),
-            &manga.tags.as_deref().unwrap_or(""),
+            &tags::display_list(&tags).unwrap_or_default(),
             &manga.rating.as_deref().unwrap_or(""),
+            &manga.status,
         ],
     ).await {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            error!("Failed to insert manga: {}", e);
-            Err(e)
-        }
+        error!("Failed to insert manga: {}", e);
+        return Err(e);
+    }
+    if !tags.is_empty() {
+        link_tags(&tx, &manga.id, &tags).await?;
     }
+    link_people(&tx, &manga.id, &manga.people).await?;
+    tx.commit().await
 }
 
 /// Insert manga source data
@@ -361,19 +548,41 @@ pub async fn insert_chapters(
 ) -> Result<(), PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
 
+    // Re-imports keep first_seen_at and only fill in details the source now provides.
     let stmt = client.prepare(
-        "INSERT INTO chapters (manga_source_data_id, chapter_number, url)
-         VALUES ($1, $2, $3)
-         ON CONFLICT (manga_source_data_id, url) DO NOTHING"
+        "INSERT INTO chapters (manga_source_data_id, chapter_number, url, number, volume, title,
+             language, scanlation_group, published_at, page_count, first_seen_at, sort_key)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+         ON CONFLICT (manga_source_data_id, url) DO UPDATE SET
+             number = COALESCE(EXCLUDED.number, chapters.number),
+             volume = COALESCE(EXCLUDED.volume, chapters.volume),
+             title = COALESCE(EXCLUDED.title, chapters.title),
+             language = COALESCE(EXCLUDED.language, chapters.language),
+             scanlation_group = COALESCE(EXCLUDED.scanlation_group, chapters.scanlation_group),
+             published_at = COALESCE(EXCLUDED.published_at, chapters.published_at),
+             page_count = COALESCE(EXCLUDED.page_count, chapters.page_count),
+             sort_key = COALESCE(EXCLUDED.sort_key, chapters.sort_key)"
     ).await?;
 
+    let now = chrono::Utc::now().timestamp();
     for chapter in chapters {
+        let mut chapter = chapter.clone();
+        chapter.fill_from_label();
         client.execute(
             &stmt,
             &[
                 &manga_source_data_id,
                 &chapter.chapter_number,
                 &chapter.url,
+                &chapter.number,
+                &chapter.volume,
+                &chapter.title,
+                &chapter.language,
+                &chapter.scanlation_group,
+                &chapter.published_at,
+                &chapter.page_count,
+                &now,
+                &chapter.sort_key,
             ],
         ).await?;
     }
@@ -391,19 +600,43 @@ pub async fn set_manga_monitoring(
 ) -> Result<(), PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
 
-    let monitored_val = if monitored { 1 } else { 0 };
-
     client.execute(
         "UPDATE manga SET monitored = $1,
          check_interval_secs = COALESCE($2, check_interval_secs),
          discover_interval_secs = COALESCE($3, discover_interval_secs)
          WHERE id = $4",
-        &[&monitored_val, &check_interval_secs, &discover_interval_secs, &manga_id],
+        &[&monitored, &check_interval_secs, &discover_interval_secs, &manga_id],
     ).await?;
 
     Ok(())
 }
 
+/// Preferred output format for a manga's downloads, if one was set
+pub async fn get_manga_output_format(pool: &Pool, manga_id: &str) -> Result<Option<String>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt("SELECT output_format FROM manga WHERE id = $1", &[&manga_id])
+        .await?;
+    Ok(row.and_then(|r| r.get(0)))
+}
+
+/// Set (or clear with `None`) a manga's preferred output format
+pub async fn set_manga_output_format(
+    pool: &Pool,
+    manga_id: &str,
+    format: Option<&str>,
+) -> Result<u64, StorageError> {
+    let client = pool.get().await?;
+
+    Ok(client
+        .execute(
+            "UPDATE manga SET output_format = $1 WHERE id = $2",
+            &[&format, &manga_id],
+        )
+        .await?)
+}
+
 /// Mark chapter check timestamp
 pub async fn mark_chapter_check(pool: &Pool, manga_id: &str, ts: i64) -> Result<(), PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
@@ -421,7 +654,7 @@ pub async fn due_for_chapter_check(pool: &Pool, now_ts: i64) -> Result<Vec<Strin
     let client = pool.get().await.expect("Failed to get connection from pool");
 
     let rows = client.query(
-        "SELECT id FROM manga WHERE monitored = 1 AND check_interval_secs IS NOT NULL AND (
+        "SELECT id FROM manga WHERE monitored AND check_interval_secs IS NOT NULL AND (
             last_chapter_check IS NULL OR ($1 - last_chapter_check) >= check_interval_secs
         )",
         &[&now_ts],
@@ -439,7 +672,8 @@ pub async fn get_manga_source_data_by_manga_id(
     let client = pool.get().await.expect("Failed to get connection from pool");
 
     let rows = client.query(
-        "SELECT id, manga_id, source_id, source_manga_id, source_manga_url FROM manga_source_data WHERE manga_id = $1",
+        "SELECT id, manga_id, source_id, source_manga_id, source_manga_url FROM manga_source_data
+         WHERE manga_id = $1 ORDER BY priority NULLS LAST, id",
         &[&manga_id]
     ).await?;
 
@@ -453,33 +687,1216 @@ pub async fn get_manga_source_data_by_manga_id(
     Ok(manga_source_data)
 }
 
+/// ID of the link between a manga and a source, if there is one
+pub async fn get_manga_source_data_id(
+    pool: &Pool,
+    manga_id: &str,
+    source_id: i32,
+) -> Result<Option<i32>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt(
+        "SELECT id FROM manga_source_data WHERE manga_id = $1 AND source_id = $2",
+        &[&manga_id, &source_id],
+    ).await?;
+    Ok(row.map(|r| r.get(0)))
+}
+
+/// Set the order in which a manga's sources serve chapters. Listed sources get
+/// priorities 0, 1, 2, ...; unlisted ones fall back to insertion order after
+/// them. Returns how many linked sources were ranked.
+pub async fn set_source_priority(
+    pool: &Pool,
+    manga_id: &str,
+    source_ids: &[i32],
+) -> Result<u64, StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE manga_source_data SET priority = array_position($2::INTEGER[], source_id) - 1
+         WHERE manga_id = $1",
+        &[&manga_id, &source_ids],
+    ).await?;
+    let row = client.query_one(
+        "SELECT COUNT(*) FROM manga_source_data WHERE manga_id = $1 AND priority IS NOT NULL",
+        &[&manga_id],
+    ).await?;
+    Ok(row.get::<_, i64>(0) as u64)
+}
+
 /// Get all manga from a specific source
 pub async fn get_manga_by_source(pool: &Pool, source_id: i32) -> Result<Vec<Manga>, PgError> {
     let client = pool.get().await.expect("Failed to get connection from pool");
 
     let rows = client.query(
-        "SELECT DISTINCT m.id, m.title, m.alt_titles, m.cover_url, m.description, m.tags, m.rating,
-         m.monitored, m.check_interval_secs, m.discover_interval_secs, m.last_chapter_check, m.last_discover_check
-         FROM manga m
-         JOIN manga_source_data msd ON m.id = msd.manga_id
-         WHERE msd.source_id = $1",
+        &format!(
+            "SELECT {} FROM manga m
+             WHERE EXISTS (SELECT 1 FROM manga_source_data msd WHERE msd.manga_id = m.id AND msd.source_id = $1)",
+            MANGA_COLUMNS
+        ),
         &[&source_id]
     ).await?;
 
-    let manga_list: Vec<Manga> = rows.iter().map(|row| Manga {
+    Ok(rows.iter().map(manga_from_row).collect())
+}
+
+const DOWNLOAD_JOB_COLUMNS: &str = "j.id, j.chapter_id, j.state, j.format, j.attempts, j.max_attempts,
+    j.next_attempt_at, j.last_error, j.file_path, j.created_at, j.updated_at,
+    m.id, m.title, c.chapter_number, msd.source_id";
+
+const DOWNLOAD_JOB_JOINS: &str = "FROM download_jobs j
+    JOIN chapters c ON c.id = j.chapter_id
+    JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+    JOIN manga m ON m.id = msd.manga_id";
+
+fn download_job_from_row(row: &tokio_postgres::Row) -> DownloadJob {
+    DownloadJob {
+        id: row.get(0),
+        chapter_id: row.get(1),
+        state: row.get(2),
+        format: row.get(3),
+        attempts: row.get(4),
+        max_attempts: row.get(5),
+        next_attempt_at: row.get(6),
+        last_error: row.get(7),
+        file_path: row.get(8),
+        created_at: row.get(9),
+        updated_at: row.get(10),
+        manga_id: row.get(11),
+        manga_title: row.get(12),
+        chapter_number: row.get(13),
+        source_id: row.get(14),
+    }
+}
+
+/// Queue download jobs for chapters. Chapters that already have a queued or
+/// running job are skipped; returns how many jobs were added.
+pub async fn enqueue_download_jobs(
+    pool: &Pool,
+    chapter_ids: &[i32],
+    format: Option<&str>,
+    max_attempts: i32,
+    now_ts: i64,
+) -> Result<u64, StorageError> {
+    let client = pool.get().await?;
+
+    Ok(client.execute(
+        "INSERT INTO download_jobs (chapter_id, format, max_attempts, created_at, updated_at)
+         SELECT id, $2, $3, $4, $4 FROM chapters WHERE id = ANY($1)
+         ON CONFLICT DO NOTHING",
+        &[&chapter_ids, &format, &max_attempts, &now_ts],
+    ).await?)
+}
+
+/// IDs of chapters that have never been downloaded, oldest first
+pub async fn get_unscraped_chapter_ids(pool: &Pool, limit: i64) -> Result<Vec<i32>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        "SELECT id FROM chapters WHERE scraped = FALSE ORDER BY id LIMIT $1",
+        &[&limit],
+    ).await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Atomically take the next due queued job and mark it running. Concurrent
+/// workers never receive the same job.
+pub async fn claim_download_job(pool: &Pool, now_ts: i64) -> Result<Option<DownloadJob>, StorageError> {
+    let client = pool.get().await?;
+
+    let claimed = client.query_opt(
+        "UPDATE download_jobs SET state = 'running', attempts = attempts + 1, updated_at = $1
+         WHERE id = (
+             SELECT id FROM download_jobs
+             WHERE state = 'queued' AND next_attempt_at <= $1
+             ORDER BY next_attempt_at, id
+             FOR UPDATE SKIP LOCKED
+             LIMIT 1
+         )
+         RETURNING id",
+        &[&now_ts],
+    ).await?;
+    let Some(row) = claimed else {
+        return Ok(None);
+    };
+    let id: i64 = row.get(0);
+
+    let row = client.query_opt(
+        &format!("SELECT {} {} WHERE j.id = $1", DOWNLOAD_JOB_COLUMNS, DOWNLOAD_JOB_JOINS),
+        &[&id],
+    ).await?;
+    Ok(row.as_ref().map(download_job_from_row))
+}
+
+/// Mark a job done and its chapter downloaded
+pub async fn complete_download_job(
+    pool: &Pool,
+    job_id: i64,
+    file_path: &str,
+    now_ts: i64,
+) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE download_jobs SET state = 'done', file_path = $2, last_error = NULL, updated_at = $3 WHERE id = $1",
+        &[&job_id, &file_path, &now_ts],
+    ).await?;
+    client.execute(
+        "UPDATE chapters SET scraped = TRUE WHERE id = (SELECT chapter_id FROM download_jobs WHERE id = $1)",
+        &[&job_id],
+    ).await?;
+
+    Ok(())
+}
+
+/// Record a failed attempt: the job is queued again from `retry_at`, or marked
+/// failed once it has used all its attempts
+pub async fn fail_download_job(
+    pool: &Pool,
+    job_id: i64,
+    error: &str,
+    retry_at: i64,
+    now_ts: i64,
+) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE download_jobs SET
+            state = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'queued' END,
+            next_attempt_at = $2, last_error = $3, updated_at = $4
+         WHERE id = $1",
+        &[&job_id, &retry_at, &error, &now_ts],
+    ).await?;
+
+    Ok(())
+}
+
+/// Put jobs left running by a previous process back in the queue. The
+/// interrupted attempt is not counted. Returns how many jobs were resumed.
+pub async fn requeue_running_download_jobs(pool: &Pool, now_ts: i64) -> Result<u64, StorageError> {
+    let client = pool.get().await?;
+
+    Ok(client.execute(
+        "UPDATE download_jobs SET state = 'queued', attempts = GREATEST(attempts - 1, 0),
+            next_attempt_at = 0, updated_at = $1
+         WHERE state = 'running'",
+        &[&now_ts],
+    ).await?)
+}
+
+/// Give a failed job a fresh set of attempts. Returns `false` if the job does
+/// not exist or has not failed.
+pub async fn retry_download_job(pool: &Pool, job_id: i64, now_ts: i64) -> Result<bool, StorageError> {
+    let client = pool.get().await?;
+
+    let n = client.execute(
+        "UPDATE download_jobs SET state = 'queued', attempts = 0, next_attempt_at = 0, updated_at = $2
+         WHERE id = $1 AND state = 'failed'",
+        &[&job_id, &now_ts],
+    ).await?;
+    Ok(n > 0)
+}
+
+/// List download jobs, newest first, optionally filtered by state
+pub async fn list_download_jobs(
+    pool: &Pool,
+    state: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<DownloadJob>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        &format!(
+            "SELECT {} {} WHERE ($1::text IS NULL OR j.state = $1) ORDER BY j.id DESC LIMIT $2 OFFSET $3",
+            DOWNLOAD_JOB_COLUMNS, DOWNLOAD_JOB_JOINS
+        ),
+        &[&state, &limit, &offset],
+    ).await?;
+
+    Ok(rows.iter().map(download_job_from_row).collect())
+}
+
+pub async fn get_match_candidates(pool: &Pool) -> Result<Vec<MatchCandidate>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        "SELECT m.id, m.title, m.alt_titles, m.cover_url, m.cover_hash,
+            COALESCE(array_agg(p.provider || ':' || p.provider_id) FILTER (WHERE p.provider IS NOT NULL), '{}')
+         FROM manga m LEFT JOIN provider_ids p ON p.manga_id = m.id
+         GROUP BY m.id ORDER BY m.id",
+        &[],
+    ).await?;
+
+    Ok(rows.iter().map(|row| MatchCandidate {
         id: row.get(0),
         title: row.get(1),
         alt_titles: row.get(2),
         cover_url: row.get(3),
-        description: row.get(4),
-        tags: row.get(5),
-        rating: row.get(6),
-        monitored: row.get(7),
-        check_interval_secs: row.get(8),
-        discover_interval_secs: row.get(9),
-        last_chapter_check: row.get(10),
-        last_discover_check: row.get(11),
-    }).collect();
+        cover_hash: row.get(4),
+        provider_ids: row.get(5),
+    }).collect())
+}
+
+pub async fn set_cover_hash(pool: &Pool, manga_id: &str, hash: i64) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE manga SET cover_hash = $2 WHERE id = $1",
+        &[&manga_id, &hash],
+    ).await?;
+
+    Ok(())
+}
+
+/// Record a proposed merge of `(id, title)` pairs `a` and `b`; `automatic`
+/// proposals are recorded as already accepted. Returns `None` if the pair was
+/// proposed before, whatever became of it.
+pub async fn insert_merge_proposal(
+    pool: &Pool,
+    a: (&str, &str),
+    b: (&str, &str),
+    score: f64,
+    reasons: &str,
+    automatic: bool,
+    now_ts: i64,
+) -> Result<Option<i32>, StorageError> {
+    let client = pool.get().await?;
+
+    let (a, b) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    let state = if automatic { "accepted" } else { "pending" };
+    let decided_at = automatic.then_some(now_ts);
+    let row = client.query_opt(
+        "INSERT INTO merge_proposals (manga_a, manga_b, title_a, title_b, score, reasons, state, automatic, created_at, decided_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         ON CONFLICT (manga_a, manga_b) DO NOTHING
+         RETURNING id",
+        &[&a.0, &b.0, &a.1, &b.1, &score, &reasons, &state, &automatic, &now_ts, &decided_at],
+    ).await?;
+
+    Ok(row.map(|r| r.get(0)))
+}
+
+const MERGE_PROPOSAL_COLUMNS: &str = "id, manga_a, manga_b, title_a, title_b, score, reasons, state, automatic, created_at, decided_at";
+
+fn merge_proposal_from_row(row: &tokio_postgres::Row) -> crate::models::MergeProposal {
+    crate::models::MergeProposal {
+        id: row.get(0),
+        manga_a: row.get(1),
+        manga_b: row.get(2),
+        title_a: row.get(3),
+        title_b: row.get(4),
+        score: row.get(5),
+        reasons: row.get(6),
+        state: row.get(7),
+        automatic: row.get(8),
+        created_at: row.get(9),
+        decided_at: row.get(10),
+    }
+}
+
+/// List merge proposals, most confident first, optionally filtered by state
+pub async fn list_merge_proposals(
+    pool: &Pool,
+    state: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<crate::models::MergeProposal>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        &format!(
+            "SELECT {} FROM merge_proposals WHERE ($1::text IS NULL OR state = $1)
+             ORDER BY score DESC, id LIMIT $2 OFFSET $3",
+            MERGE_PROPOSAL_COLUMNS
+        ),
+        &[&state, &limit, &offset],
+    ).await?;
 
-    Ok(manga_list)
+    Ok(rows.iter().map(merge_proposal_from_row).collect())
+}
+
+pub async fn get_merge_proposal(
+    pool: &Pool,
+    id: i32,
+) -> Result<Option<crate::models::MergeProposal>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt(
+        &format!("SELECT {} FROM merge_proposals WHERE id = $1", MERGE_PROPOSAL_COLUMNS),
+        &[&id],
+    ).await?;
+
+    Ok(row.as_ref().map(merge_proposal_from_row))
+}
+
+/// Move a proposal from state `from` to `to` (`decided_at` is cleared when it
+/// goes back to pending). Returns `false` if it was not in state `from`.
+pub async fn set_merge_proposal_state(
+    pool: &Pool,
+    id: i32,
+    from: &str,
+    to: &str,
+    now_ts: i64,
+) -> Result<bool, StorageError> {
+    let client = pool.get().await?;
+
+    let decided_at = (to != "pending").then_some(now_ts);
+    let n = client.execute(
+        "UPDATE merge_proposals SET state = $3, decided_at = $4 WHERE id = $1 AND state = $2",
+        &[&id, &from, &to, &decided_at],
+    ).await?;
+    Ok(n > 0)
+}
+
+/// Fold manga `other_id` into `target_id` in one transaction: source links,
+/// chapters and provider IDs move over (a source or provider both already have
+/// keeps the target's copy, plus any chapters only `other` had), titles are
+/// combined into `alt_titles`, and `other` is deleted along with its pending
+/// merge proposals, tags and credits (the target keeps its own). Library
+/// entries move to the target for readers who had not added it themselves.
+/// Returns the `manga_audit` entry that can undo it.
+pub async fn merge_manga(
+    pool: &Pool,
+    target_id: &str,
+    other_id: &str,
+    now_ts: i64,
+) -> Result<i32, StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+
+    let load = "SELECT title, alt_titles, cover_url, description FROM manga WHERE id = $1 FOR UPDATE";
+    let target = tx.query_one(load, &[&target_id]).await?;
+    let other = tx.query_one(load, &[&other_id]).await?;
+
+    let audit_id: i32 = tx.query_one(
+        "INSERT INTO manga_audit (action, manga_id, other_manga_id, snapshot, created_at)
+         SELECT 'merge', $1, $2, jsonb_build_object(
+            'target', (SELECT to_jsonb(m) FROM manga m WHERE m.id = $1),
+            'other', (SELECT to_jsonb(m) FROM manga m WHERE m.id = $2),
+            'sources', (SELECT COALESCE(jsonb_agg(to_jsonb(s)), '[]') FROM manga_source_data s WHERE s.manga_id = $2),
+            'chapters', (SELECT COALESCE(jsonb_agg(to_jsonb(c)), '[]') FROM chapters c
+                         JOIN manga_source_data s ON s.id = c.manga_source_data_id WHERE s.manga_id = $2),
+            'providers', (SELECT COALESCE(jsonb_agg(to_jsonb(p)), '[]') FROM provider_ids p WHERE p.manga_id = $2),
+            'tags', (SELECT COALESCE(jsonb_agg(to_jsonb(t)), '[]') FROM manga_tags t WHERE t.manga_id = $2),
+            'people', (SELECT COALESCE(jsonb_agg(to_jsonb(p)), '[]') FROM manga_people p WHERE p.manga_id = $2),
+            'library', (SELECT COALESCE(jsonb_agg(to_jsonb(l)), '[]') FROM library_entries l WHERE l.manga_id = $2),
+            'reads', (SELECT COALESCE(jsonb_agg(to_jsonb(r)), '[]') FROM chapter_reads r
+                      JOIN chapters c ON c.id = r.chapter_id
+                      JOIN manga_source_data s ON s.id = c.manga_source_data_id WHERE s.manga_id = $2)
+         ), $3
+         RETURNING id",
+        &[&target_id, &other_id, &now_ts],
+    ).await?.get(0);
+
+    let mut alt_titles: Option<String> = target.get::<_, Option<String>>(1).filter(|s| !s.is_empty());
+    let other_title: String = other.get(0);
+    if other_title != target.get::<_, String>(0) {
+        crate::helpers::merge_alt_titles(&mut alt_titles, &other_title);
+    }
+    if let Some(titles) = other.get::<_, Option<String>>(1) {
+        crate::helpers::merge_alt_titles(&mut alt_titles, &titles);
+    }
+    tx.execute(
+        "UPDATE manga SET alt_titles = $2,
+            cover_url = COALESCE(NULLIF(cover_url, ''), $3),
+            description = COALESCE(NULLIF(description, ''), $4)
+         WHERE id = $1",
+        &[
+            &target_id,
+            &alt_titles,
+            &other.get::<_, Option<String>>(2),
+            &other.get::<_, Option<String>>(3),
+        ],
+    ).await?;
+
+    // Sources both manga have: keep the target's link, adopt chapters it lacks.
+    tx.execute(
+        "UPDATE chapters c SET manga_source_data_id = t.id
+         FROM manga_source_data o
+         JOIN manga_source_data t ON t.source_id = o.source_id AND t.manga_id = $1
+         WHERE o.manga_id = $2 AND c.manga_source_data_id = o.id
+           AND NOT EXISTS (SELECT 1 FROM chapters x WHERE x.manga_source_data_id = t.id AND x.url = c.url)",
+        &[&target_id, &other_id],
+    ).await?;
+    tx.execute(
+        "UPDATE manga_source_data SET manga_id = $1
+         WHERE manga_id = $2
+           AND source_id NOT IN (SELECT source_id FROM manga_source_data WHERE manga_id = $1)",
+        &[&target_id, &other_id],
+    ).await?;
+    tx.execute(
+        "UPDATE provider_ids SET manga_id = $1
+         WHERE manga_id = $2
+           AND provider NOT IN (SELECT provider FROM provider_ids WHERE manga_id = $1)",
+        &[&target_id, &other_id],
+    ).await?;
+    // Readers following only the other manga now follow the target
+    tx.execute(
+        "UPDATE library_entries SET manga_id = $1
+         WHERE manga_id = $2
+           AND user_id NOT IN (SELECT user_id FROM library_entries WHERE manga_id = $1)",
+        &[&target_id, &other_id],
+    ).await?;
+    tx.execute(
+        "DELETE FROM merge_proposals WHERE state = 'pending' AND (manga_a = $1 OR manga_b = $1)",
+        &[&other_id],
+    ).await?;
+    tx.execute("DELETE FROM manga WHERE id = $1", &[&other_id]).await?;
+
+    tx.commit().await?;
+    Ok(audit_id)
+}
+
+/// Move the source links `source_ids` of `manga_id` (with their chapters) to a
+/// new manga titled `title`, which also gets the original's cover and
+/// description. Returns the new manga's ID and its `manga_audit` entry, or
+/// `None` if none of those sources are linked to `manga_id`.
+pub async fn split_manga(
+    pool: &Pool,
+    manga_id: &str,
+    source_ids: &[i32],
+    title: Option<&str>,
+    now_ts: i64,
+) -> Result<Option<(String, i32)>, StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+
+    let moved: Vec<i32> = tx.query(
+        "SELECT id FROM manga_source_data WHERE manga_id = $1 AND source_id = ANY($2) ORDER BY id FOR UPDATE",
+        &[&manga_id, &source_ids],
+    ).await?.iter().map(|row| row.get(0)).collect();
+    if moved.is_empty() {
+        return Ok(None);
+    }
+
+    let new_id = uuid::Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO manga (id, title, cover_url, description)
+         SELECT $2, COALESCE($3, title), cover_url, description FROM manga WHERE id = $1",
+        &[&manga_id, &new_id, &title],
+    ).await?;
+    tx.execute(
+        "UPDATE manga_source_data SET manga_id = $2 WHERE id = ANY($1)",
+        &[&moved, &new_id],
+    ).await?;
+    let audit_id: i32 = tx.query_one(
+        "INSERT INTO manga_audit (action, manga_id, other_manga_id, snapshot, created_at)
+         VALUES ('split', $1, $2, jsonb_build_object('source_ids', to_jsonb($3::int[])), $4)
+         RETURNING id",
+        &[&manga_id, &new_id, &moved, &now_ts],
+    ).await?.get(0);
+
+    tx.commit().await?;
+    Ok(Some((new_id, audit_id)))
+}
+
+const MANGA_AUDIT_COLUMNS: &str = "id, action, manga_id, other_manga_id, created_at, undone_at";
+
+fn manga_audit_from_row(row: &tokio_postgres::Row) -> crate::models::MangaAuditEntry {
+    crate::models::MangaAuditEntry {
+        id: row.get(0),
+        action: row.get(1),
+        manga_id: row.get(2),
+        other_manga_id: row.get(3),
+        created_at: row.get(4),
+        undone_at: row.get(5),
+    }
+}
+
+/// Merge and split history, newest first, optionally only entries involving `manga_id`
+pub async fn list_manga_audit(
+    pool: &Pool,
+    manga_id: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<crate::models::MangaAuditEntry>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        &format!(
+            "SELECT {} FROM manga_audit
+             WHERE ($1::text IS NULL OR manga_id = $1 OR other_manga_id = $1)
+             ORDER BY id DESC LIMIT $2 OFFSET $3",
+            MANGA_AUDIT_COLUMNS
+        ),
+        &[&manga_id, &limit, &offset],
+    ).await?;
+
+    Ok(rows.iter().map(manga_audit_from_row).collect())
+}
+
+/// Reverse a merge or split. A merge recreates the merged-away manga with its
+/// source links, chapters and provider IDs and restores the kept manga's alt
+/// titles, cover and description; a split moves the source links back and
+/// deletes the manga it created.
+pub async fn undo_manga_audit(pool: &Pool, id: i32, now_ts: i64) -> Result<UndoOutcome, StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+
+    let Some(entry) = tx.query_opt(
+        &format!("SELECT {} FROM manga_audit WHERE id = $1 FOR UPDATE", MANGA_AUDIT_COLUMNS),
+        &[&id],
+    ).await? else {
+        return Ok(UndoOutcome::NotFound);
+    };
+    let entry = manga_audit_from_row(&entry);
+    if entry.undone_at.is_some() {
+        return Ok(UndoOutcome::AlreadyUndone);
+    }
+    let later = tx.query_opt(
+        "SELECT 1 FROM manga_audit
+         WHERE id > $1 AND undone_at IS NULL
+           AND (manga_id IN ($2, $3) OR other_manga_id IN ($2, $3))
+         LIMIT 1",
+        &[&id, &entry.manga_id, &entry.other_manga_id],
+    ).await?;
+    if later.is_some() {
+        return Ok(UndoOutcome::Superseded);
+    }
+
+    if entry.action == "merge" {
+        let snapshot = "(SELECT snapshot FROM manga_audit WHERE id = $1)";
+        // Generated columns (search_vector) cannot be inserted, so list the rest
+        let columns: String = tx.query_one(
+            "SELECT string_agg(quote_ident(column_name), ', ' ORDER BY ordinal_position)
+             FROM information_schema.columns
+             WHERE table_schema = current_schema() AND table_name = 'manga' AND is_generated = 'NEVER'",
+            &[],
+        ).await?.get(0);
+        tx.execute(
+            &format!(
+                "INSERT INTO manga ({cols}) SELECT {cols} FROM jsonb_populate_record(NULL::manga, {}->'other')",
+                snapshot,
+                cols = columns
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "UPDATE manga m SET alt_titles = t.alt_titles, cover_url = t.cover_url, description = t.description
+                 FROM jsonb_populate_record(NULL::manga, {}->'target') t WHERE m.id = t.id",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO manga_source_data SELECT * FROM jsonb_populate_recordset(NULL::manga_source_data, {}->'sources')
+                 ON CONFLICT (id) DO UPDATE SET manga_id = EXCLUDED.manga_id",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO chapters SELECT * FROM jsonb_populate_recordset(NULL::chapters, {}->'chapters')
+                 ON CONFLICT (id) DO UPDATE SET manga_source_data_id = EXCLUDED.manga_source_data_id",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO provider_ids SELECT * FROM jsonb_populate_recordset(NULL::provider_ids, {}->'providers')
+                 ON CONFLICT (id) DO UPDATE SET manga_id = EXCLUDED.manga_id",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        // Older snapshots lack the later keys ("tags", "people", "library",
+        // "reads"); the recordsets are then empty
+        tx.execute(
+            &format!(
+                "INSERT INTO manga_tags SELECT * FROM jsonb_populate_recordset(NULL::manga_tags, {}->'tags')
+                 ON CONFLICT DO NOTHING",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO manga_people SELECT * FROM jsonb_populate_recordset(NULL::manga_people, {}->'people')
+                 ON CONFLICT DO NOTHING",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO library_entries SELECT * FROM jsonb_populate_recordset(NULL::library_entries, {}->'library')
+                 ON CONFLICT (id) DO UPDATE SET manga_id = EXCLUDED.manga_id",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO chapter_reads SELECT * FROM jsonb_populate_recordset(NULL::chapter_reads, {}->'reads')
+                 ON CONFLICT DO NOTHING",
+                snapshot
+            ),
+            &[&id],
+        ).await?;
+    } else {
+        tx.execute(
+            "UPDATE manga_source_data SET manga_id = $2
+             WHERE id IN (SELECT jsonb_array_elements_text(snapshot->'source_ids')::int FROM manga_audit WHERE id = $1)",
+            &[&id, &entry.manga_id],
+        ).await?;
+        tx.execute("DELETE FROM manga WHERE id = $1", &[&entry.other_manga_id]).await?;
+    }
+    tx.execute("UPDATE manga_audit SET undone_at = $2 WHERE id = $1", &[&id, &now_ts]).await?;
+
+    tx.commit().await?;
+    Ok(UndoOutcome::Undone)
+}
+
+/// Number of manga without an ID from `provider`
+pub async fn count_missing_provider(pool: &Pool, provider: MetadataProvider) -> Result<i64, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_one(
+        &format!("SELECT COUNT(*) FROM manga WHERE {}", provider.missing_condition()),
+        &[],
+    ).await?;
+    Ok(row.get(0))
+}
+
+/// Titles of manga without an ID from `provider`, at most `limit` of them
+pub async fn get_manga_missing_provider(
+    pool: &Pool,
+    provider: MetadataProvider,
+    limit: Option<i64>,
+) -> Result<Vec<MangaTitles>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        &format!(
+            "SELECT id, title, COALESCE(alt_titles, '') FROM manga WHERE {} ORDER BY id LIMIT $1",
+            provider.missing_condition()
+        ),
+        &[&limit],
+    ).await?;
+
+    Ok(rows.iter().map(|row| MangaTitles {
+        id: row.get(0),
+        title: row.get(1),
+        alt_titles: row.get(2),
+    }).collect())
+}
+
+/// Store a manga's ID at `provider` on the manga row and in `provider_ids`.
+/// MAL and AniList IDs are numeric; anything else is stored as 0 on the row.
+pub async fn set_provider_id(
+    pool: &Pool,
+    manga_id: &str,
+    provider: MetadataProvider,
+    provider_id: &str,
+) -> Result<(), StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+
+    let sql = format!("UPDATE manga SET {} = $1 WHERE id = $2", provider.column());
+    match provider {
+        MetadataProvider::MangaBaka => tx.execute(&sql, &[&provider_id, &manga_id]).await?,
+        MetadataProvider::Mal | MetadataProvider::AniList => {
+            let numeric = provider_id.parse::<i64>().unwrap_or(0);
+            tx.execute(&sql, &[&numeric, &manga_id]).await?
+        }
+    };
+    tx.execute(
+        "INSERT INTO provider_ids (manga_id, provider, provider_id) VALUES ($1, $2, $3)
+         ON CONFLICT (manga_id, provider) DO UPDATE SET provider_id = EXCLUDED.provider_id",
+        &[&manga_id, &provider.name(), &provider_id],
+    ).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Provider IDs of every manga, for merging provider metadata
+pub async fn get_provider_links(pool: &Pool) -> Result<Vec<ProviderLinks>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        "SELECT id, COALESCE(mangabaka_id, ''), COALESCE(mal_id, 0), COALESCE(anilist_id, 0)
+         FROM manga ORDER BY id",
+        &[],
+    ).await?;
+
+    Ok(rows.iter().map(|row| ProviderLinks {
+        manga_id: row.get(0),
+        mangabaka_id: row.get(1),
+        mal_id: row.get(2),
+        anilist_id: row.get(3),
+    }).collect())
+}
+
+/// Overwrite description, tags and rating with provider metadata; `None`
+/// leaves a field as it is
+pub async fn update_manga_metadata(
+    pool: &Pool,
+    manga_id: &str,
+    description: Option<&str>,
+    tags: Option<&str>,
+    rating: Option<&str>,
+) -> Result<(), StorageError> {
+    let client = pool.get().await?;
+
+    client.execute(
+        "UPDATE manga SET description = COALESCE($1, description), tags = COALESCE($2, tags),
+            rating = COALESCE($3, rating)
+         WHERE id = $4",
+        &[&description, &tags, &rating, &manga_id],
+    ).await?;
+
+    Ok(())
+}
+
+/// Replace a manga's tag links and its `tags` text with `tags`, creating rows
+/// for tags outside the dictionary
+async fn link_tags(
+    tx: &deadpool_postgres::Transaction<'_>,
+    manga_id: &str,
+    tags: &[TagRef],
+) -> Result<(), PgError> {
+    tx.execute("DELETE FROM manga_tags WHERE manga_id = $1", &[&manga_id]).await?;
+    for tag in tags {
+        tx.execute(
+            "INSERT INTO tags (slug, name, category) VALUES ($1, $2, $3) ON CONFLICT (slug) DO NOTHING",
+            &[&tag.slug, &tag.name, &tag.category.map(|c| c.as_str())],
+        ).await?;
+        tx.execute(
+            "INSERT INTO manga_tags (manga_id, tag_id) SELECT $1, id FROM tags WHERE slug = $2
+             ON CONFLICT DO NOTHING",
+            &[&manga_id, &tag.slug],
+        ).await?;
+    }
+    tx.execute(
+        "UPDATE manga SET tags = $2 WHERE id = $1",
+        &[&manga_id, &tags::display_list(tags).unwrap_or_default()],
+    ).await?;
+    Ok(())
+}
+
+/// Set a manga's tags (already canonical), replacing the ones it had
+pub async fn set_manga_tags(pool: &Pool, manga_id: &str, tags: &[TagRef]) -> Result<(), StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+    link_tags(&tx, manga_id, tags).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+fn tag_from_row(row: &tokio_postgres::Row) -> Tag {
+    Tag {
+        id: row.get(0),
+        slug: row.get(1),
+        name: row.get(2),
+        category: row.get(3),
+        manga_count: None,
+    }
+}
+
+/// Every tag, optionally only those of one category, with how many manga have
+/// it; most used first
+pub async fn list_tags(pool: &Pool, category: Option<&str>) -> Result<Vec<Tag>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        "SELECT t.id, t.slug, t.name, t.category::text, COUNT(mt.manga_id)
+         FROM tags t LEFT JOIN manga_tags mt ON mt.tag_id = t.id
+         WHERE $1::text IS NULL OR t.category = $1
+         GROUP BY t.id
+         ORDER BY COUNT(mt.manga_id) DESC, t.name",
+        &[&category],
+    ).await?;
+    Ok(rows.iter().map(|row| Tag {
+        manga_count: Some(row.get(4)),
+        ..tag_from_row(row)
+    }).collect())
+}
+
+/// `(id, tags)` of manga with a `tags` text but no `manga_tags` links yet
+pub async fn get_manga_without_tag_links(pool: &Pool) -> Result<Vec<(String, String)>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        "SELECT m.id, m.tags FROM manga m
+         WHERE coalesce(m.tags, '') <> ''
+           AND NOT EXISTS (SELECT 1 FROM manga_tags mt WHERE mt.manga_id = m.id)
+         ORDER BY m.id",
+        &[],
+    ).await?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Credit `people` on a manga, replacing its credits in the roles `people`
+/// covers and leaving other roles alone. People are matched by `name_key`.
+async fn link_people(
+    tx: &deadpool_postgres::Transaction<'_>,
+    manga_id: &str,
+    people: &[Credit],
+) -> Result<(), PgError> {
+    for role in PersonRole::ALL {
+        if people.iter().any(|c| c.role == role) {
+            tx.execute(
+                "DELETE FROM manga_people WHERE manga_id = $1 AND role = $2",
+                &[&manga_id, &role.as_str()],
+            ).await?;
+        }
+    }
+    for credit in people {
+        let key = people::name_key(&credit.name);
+        tx.execute(
+            "INSERT INTO people (name, name_key) VALUES ($1, $2) ON CONFLICT (name_key) DO NOTHING",
+            &[&credit.name, &key],
+        ).await?;
+        tx.execute(
+            "INSERT INTO manga_people (manga_id, person_id, role)
+             SELECT $1, id, $3 FROM people WHERE name_key = $2
+             ON CONFLICT DO NOTHING",
+            &[&manga_id, &key, &credit.role.as_str()],
+        ).await?;
+    }
+    Ok(())
+}
+
+/// Set a manga's credits from provider metadata; see `link_people`
+pub async fn set_manga_people(pool: &Pool, manga_id: &str, people: &[Credit]) -> Result<(), StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+    link_people(&tx, manga_id, people).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// A person and every manga they are credited on, by title
+pub async fn get_person(pool: &Pool, person_id: i32) -> Result<Option<Person>, StorageError> {
+    let client = pool.get().await?;
+
+    let Some(row) = client.query_opt("SELECT name FROM people WHERE id = $1", &[&person_id]).await? else {
+        return Ok(None);
+    };
+    let rows = client.query(
+        "SELECT m.id, m.title, m.cover_url, mp.role::text
+         FROM manga_people mp JOIN manga m ON m.id = mp.manga_id
+         WHERE mp.person_id = $1 ORDER BY m.title, m.id, mp.role",
+        &[&person_id],
+    ).await?;
+    Ok(Some(Person {
+        id: person_id,
+        name: row.get(0),
+        works: people::group_works(rows.iter().map(|r| (r.get(0), r.get(1), r.get(2), r.get(3))).collect()),
+    }))
+}
+
+fn user_from_row(row: &tokio_postgres::Row) -> User {
+    User {
+        id: row.get(0),
+        name: row.get(1),
+        created_at: row.get(2),
+    }
+}
+
+/// Add a user; `None` if the name is taken
+pub async fn create_user(pool: &Pool, name: &str, now_ts: i64) -> Result<Option<User>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt(
+        "INSERT INTO users (name, created_at) VALUES ($1, $2)
+         ON CONFLICT (name) DO NOTHING
+         RETURNING id, name, created_at",
+        &[&name, &now_ts],
+    ).await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+pub async fn list_users(pool: &Pool) -> Result<Vec<User>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query("SELECT id, name, created_at FROM users ORDER BY name", &[]).await?;
+    Ok(rows.iter().map(user_from_row).collect())
+}
+
+pub async fn get_user_by_name(pool: &Pool, name: &str) -> Result<Option<User>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt("SELECT id, name, created_at FROM users WHERE name = $1", &[&name]).await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+/// Put a manga in a user's library or change its status; false if there is
+/// no such manga
+pub async fn set_library_entry(
+    pool: &Pool,
+    user_id: i32,
+    manga_id: &str,
+    status: LibraryStatus,
+    now_ts: i64,
+) -> Result<bool, StorageError> {
+    let client = pool.get().await?;
+
+    let n = client.execute(
+        "INSERT INTO library_entries (user_id, manga_id, status, added_at, updated_at)
+         SELECT $1, id, $3, $4, $4 FROM manga WHERE id = $2
+         ON CONFLICT (user_id, manga_id) DO UPDATE SET status = EXCLUDED.status, updated_at = EXCLUDED.updated_at",
+        &[&user_id, &manga_id, &status.as_str(), &now_ts],
+    ).await?;
+    Ok(n > 0)
+}
+
+/// Take a manga out of a user's library; its read chapters stay marked
+pub async fn remove_library_entry(pool: &Pool, user_id: i32, manga_id: &str) -> Result<bool, StorageError> {
+    let client = pool.get().await?;
+
+    let n = client.execute(
+        "DELETE FROM library_entries WHERE user_id = $1 AND manga_id = $2",
+        &[&user_id, &manga_id],
+    ).await?;
+    Ok(n > 0)
+}
+
+/// A user's library, most recently active first, optionally one status only
+pub async fn get_library(
+    pool: &Pool,
+    user_id: i32,
+    status: Option<LibraryStatus>,
+) -> Result<Vec<LibraryEntry>, StorageError> {
+    let client = pool.get().await?;
+
+    let status = status.map(LibraryStatus::as_str);
+    let rows = client.query(
+        "SELECT le.manga_id, m.title, m.cover_url, le.status::text, le.added_at, le.updated_at,
+            COUNT(DISTINCT c.chapter_number) FILTER (WHERE r.completed), MAX(r.updated_at)
+         FROM library_entries le
+         JOIN manga m ON m.id = le.manga_id
+         LEFT JOIN manga_source_data msd ON msd.manga_id = le.manga_id
+         LEFT JOIN chapters c ON c.manga_source_data_id = msd.id
+         LEFT JOIN chapter_reads r ON r.chapter_id = c.id AND r.user_id = le.user_id
+         WHERE le.user_id = $1 AND ($2::text IS NULL OR le.status = $2)
+         GROUP BY le.id, m.id
+         ORDER BY le.updated_at DESC, m.title",
+        &[&user_id, &status],
+    ).await?;
+    Ok(rows.iter().filter_map(|row| Some(LibraryEntry {
+        manga_id: row.get(0),
+        title: row.get(1),
+        cover_url: row.get(2),
+        status: LibraryStatus::parse(row.get(3))?,
+        added_at: row.get(4),
+        updated_at: row.get(5),
+        chapters_read: row.get(6),
+        last_read_at: row.get(7),
+    })).collect())
+}
+
+/// Record where a user is in a chapter. The chapter's manga joins their
+/// library as `reading` (or moves there from `plan_to_read`). False if there
+/// is no such chapter.
+pub async fn set_chapter_read(
+    pool: &Pool,
+    user_id: i32,
+    chapter_id: i32,
+    page: i32,
+    completed: bool,
+    now_ts: i64,
+) -> Result<bool, StorageError> {
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+
+    let n = tx.execute(
+        "INSERT INTO chapter_reads (user_id, chapter_id, page, completed, updated_at)
+         SELECT $1, id, $3, $4, $5 FROM chapters WHERE id = $2
+         ON CONFLICT (user_id, chapter_id) DO UPDATE
+            SET page = EXCLUDED.page, completed = EXCLUDED.completed, updated_at = EXCLUDED.updated_at",
+        &[&user_id, &chapter_id, &page, &completed, &now_ts],
+    ).await?;
+    if n == 0 {
+        return Ok(false);
+    }
+    tx.execute(
+        "INSERT INTO library_entries (user_id, manga_id, status, added_at, updated_at)
+         SELECT $1, msd.manga_id, 'reading', $3, $3
+         FROM chapters c JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         WHERE c.id = $2
+         ON CONFLICT (user_id, manga_id) DO UPDATE SET
+            status = CASE WHEN library_entries.status = 'plan_to_read' THEN 'reading' ELSE library_entries.status END,
+            updated_at = EXCLUDED.updated_at",
+        &[&user_id, &chapter_id, &now_ts],
+    ).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Mark a chapter unread again
+pub async fn clear_chapter_read(pool: &Pool, user_id: i32, chapter_id: i32) -> Result<bool, StorageError> {
+    let client = pool.get().await?;
+
+    let n = client.execute(
+        "DELETE FROM chapter_reads WHERE user_id = $1 AND chapter_id = $2",
+        &[&user_id, &chapter_id],
+    ).await?;
+    Ok(n > 0)
+}
+
+/// A user's read state for every chapter of a manga they opened, in reading order
+pub async fn get_chapter_reads(pool: &Pool, user_id: i32, manga_id: &str) -> Result<Vec<ChapterRead>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(
+        "SELECT c.id, c.chapter_number, msd.source_id, r.page, r.completed, r.updated_at
+         FROM chapter_reads r
+         JOIN chapters c ON c.id = r.chapter_id
+         JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         WHERE r.user_id = $1 AND msd.manga_id = $2
+         ORDER BY c.sort_key NULLS LAST, msd.source_id, c.id",
+        &[&user_id, &manga_id],
+    ).await?;
+    Ok(rows.iter().map(|row| ChapterRead {
+        chapter_id: row.get(0),
+        chapter_number: row.get(1),
+        source_id: row.get(2),
+        page: row.get(3),
+        completed: row.get(4),
+        updated_at: row.get(5),
+    }).collect())
+}
+
+/// For each manga the user is `reading`, the chapter to open next: the last
+/// chapter they opened if unfinished, else the next unfinished chapter of the
+/// same source. Manga they are caught up on are left out. Most recent first.
+pub async fn get_continue_reading(pool: &Pool, user_id: i32, limit: i64) -> Result<Vec<ContinueReading>, StorageError> {
+    let client = pool.get().await?;
+
+    // Chapters without a sort key come last, as in chapter lists
+    let rows = client.query(
+        "WITH last AS (
+            SELECT msd.manga_id, c.id, c.manga_source_data_id, COALESCE(c.sort_key, 1e308) AS pos,
+                r.completed, r.updated_at,
+                ROW_NUMBER() OVER (PARTITION BY msd.manga_id ORDER BY r.updated_at DESC, c.id DESC) AS n
+            FROM chapter_reads r
+            JOIN chapters c ON c.id = r.chapter_id
+            JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+            JOIN library_entries le ON le.user_id = r.user_id AND le.manga_id = msd.manga_id
+            WHERE r.user_id = $1 AND le.status = 'reading'
+         ), next AS (
+            SELECT l.manga_id, l.updated_at,
+                CASE WHEN l.completed THEN (
+                    SELECT x.id FROM chapters x
+                    WHERE x.manga_source_data_id = l.manga_source_data_id
+                      AND (COALESCE(x.sort_key, 1e308), x.id) > (l.pos, l.id)
+                      AND NOT EXISTS (SELECT 1 FROM chapter_reads xr
+                                      WHERE xr.user_id = $1 AND xr.chapter_id = x.id AND xr.completed)
+                    ORDER BY COALESCE(x.sort_key, 1e308), x.id
+                    LIMIT 1
+                ) ELSE l.id END AS chapter_id
+            FROM last l WHERE l.n = 1
+         )
+         SELECT m.id, m.title, m.cover_url, c.id, c.chapter_number, s.id, s.name,
+            COALESCE(r.page, 0), nx.updated_at
+         FROM next nx
+         JOIN manga m ON m.id = nx.manga_id
+         JOIN chapters c ON c.id = nx.chapter_id
+         JOIN manga_source_data msd ON msd.id = c.manga_source_data_id
+         JOIN sources s ON s.id = msd.source_id
+         LEFT JOIN chapter_reads r ON r.user_id = $1 AND r.chapter_id = c.id
+         ORDER BY nx.updated_at DESC, m.id
+         LIMIT $2",
+        &[&user_id, &limit],
+    ).await?;
+    Ok(rows.iter().map(|row| ContinueReading {
+        manga_id: row.get(0),
+        title: row.get(1),
+        cover_url: row.get(2),
+        chapter_id: row.get(3),
+        chapter_number: row.get(4),
+        source_id: row.get(5),
+        source_name: row.get(6),
+        page: row.get(7),
+        last_read_at: row.get(8),
+    }).collect())
+}
+
+pub async fn get_user(pool: &Pool, user_id: i32) -> Result<Option<User>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt("SELECT id, name, created_at FROM users WHERE id = $1", &[&user_id]).await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, user_id, created_at, last_used_at, revoked_at";
+
+fn api_key_from_row(row: &tokio_postgres::Row) -> ApiKey {
+    ApiKey {
+        id: row.get(0),
+        name: row.get(1),
+        prefix: row.get(2),
+        // Unknown scopes grant nothing
+        scopes: auth::parse_scopes(row.get(3)).unwrap_or_default(),
+        user_id: row.get(4),
+        created_at: row.get(5),
+        last_used_at: row.get(6),
+        revoked_at: row.get(7),
+    }
+}
+
+/// Store a new key by its hash
+pub async fn create_api_key(
+    pool: &Pool,
+    name: &str,
+    key_hash: &str,
+    prefix: &str,
+    scopes: &[Scope],
+    user_id: Option<i32>,
+    now_ts: i64,
+) -> Result<ApiKey, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_one(
+        &format!(
+            "INSERT INTO api_keys (name, key_hash, prefix, scopes, user_id, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ),
+        &[&name, &key_hash, &prefix, &auth::join_scopes(scopes), &user_id, &now_ts],
+    ).await?;
+    Ok(api_key_from_row(&row))
+}
+
+pub async fn list_api_keys(pool: &Pool) -> Result<Vec<ApiKey>, StorageError> {
+    let client = pool.get().await?;
+
+    let rows = client.query(&format!("SELECT {} FROM api_keys ORDER BY id", API_KEY_COLUMNS), &[]).await?;
+    Ok(rows.iter().map(api_key_from_row).collect())
+}
+
+/// Revoke a key; false if there is no such key or it is already revoked
+pub async fn revoke_api_key(pool: &Pool, id: i32, now_ts: i64) -> Result<bool, StorageError> {
+    let client = pool.get().await?;
+
+    let n = client.execute(
+        "UPDATE api_keys SET revoked_at = $2 WHERE id = $1 AND revoked_at IS NULL",
+        &[&id, &now_ts],
+    ).await?;
+    Ok(n > 0)
+}
+
+/// The unrevoked key with this hash, marked as used now
+pub async fn authenticate_api_key(pool: &Pool, key_hash: &str, now_ts: i64) -> Result<Option<ApiKey>, StorageError> {
+    let client = pool.get().await?;
+
+    let row = client.query_opt(
+        &format!(
+            "UPDATE api_keys SET last_used_at = $2 WHERE key_hash = $1 AND revoked_at IS NULL
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ),
+        &[&key_hash, &now_ts],
+    ).await?;
+    Ok(row.as_ref().map(api_key_from_row))
 }
@@ -0,0 +1,93 @@
+//! Readers, their libraries and where they are in each chapter.
+//!
+//! Several people can share one server. Each is a row in `users`; the
+//! `/me/...` endpoints answer for the user the caller's API key belongs to
+//! (see [`crate::auth`]); admin keys may instead name a user in the
+//! [`USER_HEADER`] request header, and anonymous callers have no user. A
+//! user's library (`library_entries`) holds the manga they follow with a
+//! [`LibraryStatus`], and `chapter_reads` keeps one row per chapter they
+//! opened: the last page viewed (zero-based, like OPDS-PSE) and whether they
+//! finished it.
+//!
+//! Marking a chapter read adds its manga to the library as `reading`, or
+//! moves it there from `plan_to_read`. `GET /me/continue` lists, for every
+//! manga being read, the chapter to open next: the last one opened if it is
+//! unfinished, otherwise the next unfinished chapter of the same source.
+
+use serde::{Deserialize, Serialize};
+
+/// Request header naming the user a `/me/...` call is made for
+pub const USER_HEADER: &str = "X-User";
+
+/// Longest user name `POST /users` accepts
+pub const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryStatus {
+    Reading,
+    Completed,
+    OnHold,
+    Dropped,
+    PlanToRead,
+}
+
+impl LibraryStatus {
+    pub const ALL: [LibraryStatus; 5] = [
+        LibraryStatus::Reading,
+        LibraryStatus::Completed,
+        LibraryStatus::OnHold,
+        LibraryStatus::Dropped,
+        LibraryStatus::PlanToRead,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LibraryStatus::Reading => "reading",
+            LibraryStatus::Completed => "completed",
+            LibraryStatus::OnHold => "on_hold",
+            LibraryStatus::Dropped => "dropped",
+            LibraryStatus::PlanToRead => "plan_to_read",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|st| st.as_str() == s)
+    }
+}
+
+/// A trimmed user name, or `None` if it is empty, too long or contains
+/// control characters (it travels in a header)
+pub fn user_name(name: &str) -> Option<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN || name.chars().any(char::is_control)
+    {
+        return None;
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_status() {
+        for status in LibraryStatus::ALL {
+            assert_eq!(LibraryStatus::parse(status.as_str()), Some(status));
+            assert_eq!(
+                serde_json::to_value(status).unwrap(),
+                serde_json::json!(status.as_str())
+            );
+        }
+        assert_eq!(LibraryStatus::parse("on-hold"), None);
+    }
+
+    #[test]
+    fn test_user_name() {
+        assert_eq!(user_name("  alice "), Some("alice"));
+        assert_eq!(user_name(""), None);
+        assert_eq!(user_name("a\nb"), None);
+        assert_eq!(user_name(&"x".repeat(MAX_NAME_LEN + 1)), None);
+    }
+}
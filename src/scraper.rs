@@ -1,14 +1,17 @@
-use crate::models::Source;
+use crate::comicinfo::ComicInfo;
+use crate::images::{self, ImageFormat, PageError};
+use crate::output::{Chapter, OutputFormat};
+use crate::sources::catalogue;
+use crate::sources::pages::{self, PageRef};
 use headless_chrome::{Browser, LaunchOptions};
-use regex::Regex;
-use reqwest::{Client, Url};
-use scraper::{Html, Selector};
+use reqwest::Client;
 use serde::Deserialize;
-use std::fs::File;
-use std::io::{copy, Cursor};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
-use zip::write::{FileOptions, ZipWriter};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -26,332 +29,269 @@ pub struct AtHomeChapter {
     pub data_saver: Vec<String>,
 }
 
-fn sanitize_filename(s: &str) -> String {
+pub(crate) fn sanitize_filename(s: &str) -> String {
     s.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_")
 }
 
 fn source_name_from_id(source_id: i32) -> &'static str {
-    match source_id {
-        x if x == Source::MangaDex as i32 => "MangaDex",
-        x if x == Source::FireScans as i32 => "FireScans",
-        x if x == Source::RizzComic as i32 => "RizzComic",
-        x if x == Source::DrakeComic as i32 => "DrakeComic",
-        x if x == Source::Asmotoon as i32 => "Asmotoon",
-        x if x == Source::ResetScans as i32 => "ResetScans",
-        x if x == Source::Kagane as i32 => "Kagane",
-        x if x == Source::KDTNovels as i32 => "KDTNovels",
-        _ => "Unknown",
-    }
+    catalogue::name_for(source_id)
 }
 
-fn format_chapter_label(chapter_number: &str, chapter_url: &str) -> String {
-    // Try to construct "Vol.X Ch.Y - Title" if possible
-    let mut vol: Option<String> = None;
-    let mut ch: Option<String> = None;
-    let mut title: Option<String> = None;
+/// One chapter for [`download_chapter`]: where its pages come from and the
+/// file they are written to.
+#[derive(Debug, Clone, Copy)]
+pub struct ChapterDownload<'a> {
+    pub source_id: i32,
+    pub chapter_url: &'a str,
+    /// Relative to the download directory, see [`crate::naming::Template::render`]
+    pub file_path: &'a str,
+    pub comicinfo: Option<&'a ComicInfo>,
+    pub format: OutputFormat,
+}
 
-    let num_re = Regex::new(r"(?i)(?:ch(?:apter)?\s*)?(\d+(?:\.\d+)?)").unwrap();
-    let vol_re = Regex::new(r"(?i)(?:vol(?:ume)?\s*)?(\d+)").unwrap();
+/// Download a chapter to its `file_path` under `base_dir` and return the path
+/// written.
+pub async fn download_chapter(
+    client: &Client,
+    fetcher: &PageFetcher,
+    base_dir: &str,
+    download: ChapterDownload<'_>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    // Fetch every page before touching the disk, so a failed page never
+    // leaves a partial archive behind.
+    let pages = pages::resolve_pages(client, download.source_id, download.chapter_url).await?;
+    let images = fetcher.fetch_all(client, &pages).await?;
+    let chapter = Chapter::new(download.comicinfo, images);
+
+    let file_path = Path::new(base_dir).join(download.file_path);
+    if let Some(dir) = file_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    download.format.writer().write_to(&chapter, &file_path)?;
 
-    // From chapter_number text
-    if let Some(cap) = num_re.captures(chapter_number) {
-        ch = cap.get(1).map(|m| m.as_str().to_string());
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+/// A downloaded page image and its detected format (`None` if unrecognised).
+#[derive(Debug, Clone)]
+pub struct PageImage {
+    pub data: Vec<u8>,
+    pub format: Option<ImageFormat>,
+}
+
+/// Fetch one resolved page with the referer and headers its source expects,
+/// rejecting error statuses and bodies that fail [`images::validate`].
+pub async fn fetch_page(
+    client: &Client,
+    page: &PageRef,
+    min_bytes: usize,
+) -> Result<PageImage, Box<dyn std::error::Error>> {
+    let mut request = client.get(&page.url).header("Referer", page.referer.as_str());
+    for (name, value) in &page.headers {
+        request = request.header(name.as_str(), value.as_str());
     }
-    if let Some(cap) = vol_re.captures(chapter_number) {
-        vol = cap.get(1).map(|m| m.as_str().to_string());
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(PageError::Status(response.status().as_u16()).into());
     }
-    if chapter_number.contains('-') {
-        let parts: Vec<&str> = chapter_number.splitn(2, '-').collect();
-        if parts.len() == 2 {
-            let t = parts[1].trim();
-            if !t.is_empty() {
-                title = Some(t.to_string());
-            }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let data = response.bytes().await?.to_vec();
+    let format = images::validate(&data, content_type.as_deref(), min_bytes)?;
+    Ok(PageImage {
+        data,
+        format: Some(format),
+    })
+}
+
+/// Caps how many page requests run at once against each image host. One
+/// limiter is shared by every download in the process, so parallel chapter
+/// downloads from the same CDN still respect the cap.
+pub struct HostLimiter {
+    per_host: usize,
+    hosts: std::sync::Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(per_host: usize) -> Self {
+        Self {
+            per_host: per_host.max(1),
+            hosts: std::sync::Mutex::new(HashMap::new()),
         }
     }
 
-    // From URL slug
-    let lower_url = chapter_url.to_lowercase();
-    if ch.is_none() {
-        if let Some(cap) = Regex::new(r"chapter[-/](\d+(?:\.\d+)?)")
-            .unwrap()
-            .captures(&lower_url)
-        {
-            ch = cap.get(1).map(|m| m.as_str().to_string());
-        }
+    fn for_url(&self, url: &str) -> Arc<Semaphore> {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+            .clone()
     }
-    if vol.is_none() {
-        if let Some(cap) = Regex::new(r"vol(?:ume)?[-/](\d+)")
-            .unwrap()
-            .captures(&lower_url)
-        {
-            vol = cap.get(1).map(|m| m.as_str().to_string());
+}
+
+/// Download settings shared by every chapter download: the per-host limiter,
+/// page validation and retry policy, and the optional transcode target from
+/// `config.toml`.
+pub struct PageFetcher {
+    limiter: HostLimiter,
+    retry: PageRetry,
+    transcode_to: Option<ImageFormat>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PageRetry {
+    retries: usize,
+    initial_delay: Duration,
+    max_delay: Duration,
+    min_bytes: usize,
+}
+
+impl PageFetcher {
+    pub fn from_config(cfg: &crate::config::Config) -> Self {
+        let bot = &cfg.bot_detection;
+        Self {
+            limiter: HostLimiter::new(bot.max_concurrent_pages_per_host),
+            retry: PageRetry {
+                retries: bot.max_retries,
+                initial_delay: Duration::from_millis(bot.initial_retry_delay_ms),
+                max_delay: Duration::from_millis(bot.max_retry_delay_ms),
+                min_bytes: cfg.min_page_bytes,
+            },
+            transcode_to: cfg.transcode_pages,
         }
     }
 
-    // Build label
-    let mut parts: Vec<String> = Vec::new();
-    if let Some(v) = vol {
-        parts.push(format!("Vol.{}", v));
+    /// Smallest body accepted as a page image.
+    pub fn min_page_bytes(&self) -> usize {
+        self.retry.min_bytes
     }
-    if let Some(cn) = ch {
-        parts.push(format!("Ch.{}", cn));
+
+    /// Fetch all pages in parallel, bounded per host, and return them in
+    /// reading order. Bad pages are retried with backoff; any page that is
+    /// still bad fails the whole chapter, as does a chapter with no pages.
+    pub async fn fetch_all(
+        &self,
+        client: &Client,
+        pages: &[PageRef],
+    ) -> Result<Vec<PageImage>, Box<dyn std::error::Error>> {
+        if pages.is_empty() {
+            return Err("no pages found for chapter".into());
+        }
+        let mut tasks = JoinSet::new();
+        for (slot, page) in pages.iter().enumerate() {
+            let client = client.clone();
+            let page = page.clone();
+            let permits = self.limiter.for_url(&page.url);
+            let retry = self.retry;
+            let transcode_to = self.transcode_to;
+            tasks.spawn(async move {
+                let image = fetch_with_retry(&client, &page, &permits, retry).await;
+                let image = match (image, transcode_to) {
+                    (Ok(image), Some(target)) => Ok(transcode_page(image, target).await),
+                    (image, _) => image,
+                };
+                (slot, image)
+            });
+        }
+
+        let mut images: Vec<Option<PageImage>> = vec![None; pages.len()];
+        while let Some(joined) = tasks.join_next().await {
+            let (slot, image) = joined?;
+            images[slot] =
+                Some(image.map_err(|e| format!("page {}: {}", pages[slot].index, e))?);
+        }
+        Ok(images.into_iter().flatten().collect())
     }
-    let base = if parts.is_empty() {
-        chapter_number.to_string()
-    } else {
-        parts.join(" ")
-    };
-    if let Some(t) = title {
-        format!("{} - {}", base, t)
-    } else {
-        base
+
+    /// Fetch a single page with the same host limit, retries and transcoding
+    /// as [`fetch_all`](Self::fetch_all), for readers streaming page by page.
+    pub async fn fetch_one(&self, client: &Client, page: &PageRef) -> Result<PageImage, String> {
+        let permits = self.limiter.for_url(&page.url);
+        let image = fetch_with_retry(client, page, &permits, self.retry).await?;
+        Ok(match self.transcode_to {
+            Some(target) => transcode_page(image, target).await,
+            None => image,
+        })
     }
 }
 
-pub async fn download_chapter(
+/// Fetch one page, retrying failed or invalid responses with exponential
+/// backoff. The host permit is only held while a request is in flight.
+async fn fetch_with_retry(
     client: &Client,
-    source_id: i32,
-    chapter_url: &str,
-    manga_title: &str,
-    chapter_number: &str,
-    base_dir: &str,
-    comicinfo_xml: Option<&str>,
-) -> Result<String, Box<dyn std::error::Error>> {
-    // Root download directory
-    std::fs::create_dir_all(base_dir)?;
-
-    // Per-manga folder with subfolders for covers and artwork
-    let sanitized_title = sanitize_filename(manga_title);
-    let manga_dir: PathBuf = [base_dir, &sanitized_title].iter().collect();
-    std::fs::create_dir_all(&manga_dir)?;
-    std::fs::create_dir_all(manga_dir.join("covers"))?;
-    std::fs::create_dir_all(manga_dir.join("artwork"))?;
-
-    // Build filename: <sourceId>-<SourceName>-<MangaTitle> - <ChapterLabel>.cbz
-    let label = format_chapter_label(chapter_number, chapter_url);
-    let sanitized_chapter = sanitize_filename(&label);
-    let source_name = sanitize_filename(source_name_from_id(source_id));
-    let file_name = format!(
-        "{}-{}-{} - {}.cbz",
-        source_id, source_name, sanitized_title, sanitized_chapter
-    );
-
-    let file_path = manga_dir.join(&file_name);
-    let tmp_path = file_path.with_extension("cbz.tmp");
-    let file = File::create(&tmp_path)?;
-    let mut zip = ZipWriter::new(file);
-    if let Some(xml) = comicinfo_xml {
-        use std::io::Write;
-        zip.start_file("ComicInfo.xml", FileOptions::default())?;
-        zip.write_all(xml.as_bytes())?;
-    }
-
-    match source_id {
-        x if x == Source::MangaDex as i32 => {
-            // Get the at-home server URL
-            let url = format!("https://api.mangadex.org/at-home/server/{}", chapter_url);
-            let response = client
-                .get(&url)
-                .header("User-Agent", "rust_manga_scraper/0.1.0")
-                .send()
-                .await?
-                .json::<AtHomeServer>()
-                .await?;
-
-            // Download each page and add it to the zip file
-            for (i, page) in response.chapter.data.iter().enumerate() {
-                let page_url = format!(
-                    "{}/data/{}/{}",
-                    response.base_url, response.chapter.hash, page
+    page: &PageRef,
+    permits: &Arc<Semaphore>,
+    retry: PageRetry,
+) -> Result<PageImage, String> {
+    let mut delay = retry.initial_delay;
+    let mut attempt = 0;
+    loop {
+        let result = {
+            let _permit = permits.acquire().await;
+            fetch_page(client, page, retry.min_bytes)
+                .await
+                .map_err(|e| e.to_string())
+        };
+        match result {
+            Ok(image) => return Ok(image),
+            Err(e) if attempt < retry.retries => {
+                attempt += 1;
+                log::warn!(
+                    "Page {} ({}) failed: {}; retry {}/{}",
+                    page.index,
+                    page.url,
+                    e,
+                    attempt,
+                    retry.retries
                 );
-                let response = client
-                    .get(&page_url)
-                    .header("Referer", "https://mangadex.org/")
-                    .header("User-Agent", "rust_manga_scraper/0.1.0")
-                    .send()
-                    .await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(retry.max_delay);
             }
+            Err(e) => return Err(e),
         }
-        x if x == Source::FireScans as i32
-            || x == Source::RizzComic as i32
-            || x == Source::DrakeComic as i32
-            || x == Source::Asmotoon as i32
-            || x == Source::ResetScans as i32
-            || x == Source::TempleScan as i32
-            || x == Source::ThunderScans as i32 =>
-        {
-            let response = client
-                .get(chapter_url)
-                .header("User-Agent", "rust_manga_scraper/0.1.0")
-                .send()
-                .await?
-                .text()
-                .await?;
-            let document = Html::parse_document(&response);
-            let selector = Selector::parse("div.reading-content img").unwrap();
-            let mut image_list: Vec<String> = Vec::new();
-
-            for element in document.select(&selector) {
-                if let Some(image_url) = element
-                    .value()
-                    .attr("src")
-                    .or_else(|| element.value().attr("data-src"))
-                {
-                    image_list.push(image_url.to_string());
-                }
-            }
-            if image_list.is_empty() {
-                // Regex fallback: scrape direct image URLs from HTML
-                let re = Regex::new(r#"https?://[^"'\s>]+\.(?:jpg|jpeg|png)"#).unwrap();
-                let mut seen = std::collections::HashSet::new();
-                for cap in re.captures_iter(&response) {
-                    let url = cap.get(0).unwrap().as_str().to_string();
-                    if seen.insert(url.clone()) {
-                        image_list.push(url);
-                    }
-                }
-            }
+    }
+}
 
-            // Resolve relative image URLs and set a reasonable Referer per origin
-            let origin = Url::parse(chapter_url)
-                .ok()
-                .and_then(|u| Some(format!("{}://{}", u.scheme(), u.host_str()?)))
-                .unwrap_or_default();
-            for (i, src) in image_list.iter().enumerate() {
-                let full_url = if let Ok(base) = Url::parse(chapter_url) {
-                    base.join(src)
-                        .map(|u| u.to_string())
-                        .unwrap_or_else(|_| src.clone())
-                } else {
-                    src.clone()
-                };
-                let response = client
-                    .get(&full_url)
-                    .header(
-                        "Referer",
-                        if !origin.is_empty() {
-                            origin.as_str()
-                        } else {
-                            chapter_url
-                        },
-                    )
-                    .header("User-Agent", "rust_manga_scraper/0.1.0")
-                    .send()
-                    .await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
-        }
-        x if x == Source::Kagane as i32 => {
-            // Kagane is a Next.js app; attempt to extract all readable images from the reader area.
-            let response = client
-                .get(chapter_url)
-                .header("User-Agent", "rust_manga_scraper/0.1.0")
-                .send()
-                .await?
-                .text()
-                .await?;
-            let document = Html::parse_document(&response);
-            let selectors = vec![
-                Selector::parse("div[data-reader] img").unwrap(),
-                Selector::parse("div.reading-content img").unwrap(),
-                Selector::parse("main img").unwrap(),
-            ];
-            let mut image_list: Vec<String> = Vec::new();
-            'outer: for selector in selectors {
-                for element in document.select(&selector) {
-                    if let Some(image_url) = element
-                        .value()
-                        .attr("src")
-                        .or_else(|| element.value().attr("data-src"))
-                    {
-                        // Filter likely page images
-                        if image_url.contains("http")
-                            && (image_url.ends_with(".jpg")
-                                || image_url.ends_with(".jpeg")
-                                || image_url.ends_with(".png"))
-                        {
-                            image_list.push(image_url.to_string());
-                        }
-                    }
-                }
-                if !image_list.is_empty() {
-                    break 'outer;
-                }
-            }
-            for (i, image_url) in image_list.iter().enumerate() {
-                let response = client
-                    .get(image_url)
-                    .header("Referer", "https://mangadex.org/")
-                    .header("User-Agent", "rust_manga_scraper/0.1.0")
-                    .send()
-                    .await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
+/// Re-encode a page off the async runtime. Pages already in the target format
+/// are left alone, and a page that fails to transcode is kept as served.
+async fn transcode_page(image: PageImage, target: ImageFormat) -> PageImage {
+    if image.format == Some(target) {
+        return image;
+    }
+    let data = image.data.clone();
+    match tokio::task::spawn_blocking(move || images::transcode(&data, target)).await {
+        Ok(Ok(data)) => PageImage {
+            data,
+            format: Some(target),
+        },
+        Ok(Err(e)) => {
+            log::warn!("Keeping page as served, transcode to {:?} failed: {}", target, e);
+            image
         }
-        _ => {
-            // Generic HTML reader fallback
-            let response = client.get(chapter_url).send().await?.text().await?;
-            let document = Html::parse_document(&response);
-            let selector = Selector::parse("div.reading-content img").unwrap();
-            let mut image_list: Vec<String> = Vec::new();
-            for element in document.select(&selector) {
-                if let Some(image_url) = element
-                    .value()
-                    .attr("src")
-                    .or_else(|| element.value().attr("data-src"))
-                {
-                    image_list.push(image_url.to_string());
-                }
-            }
-            if image_list.is_empty() {
-                let re = Regex::new(r#"https?://[^"'\s>]+\.(?:jpg|jpeg|png)"#).unwrap();
-                let mut seen = std::collections::HashSet::new();
-                for cap in re.captures_iter(&response) {
-                    let url = cap.get(0).unwrap().as_str().to_string();
-                    if seen.insert(url.clone()) {
-                        image_list.push(url);
-                    }
-                }
-            }
-            for (i, image_url) in image_list.iter().enumerate() {
-                let response = client.get(image_url).send().await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
+        Err(e) => {
+            log::warn!("Keeping page as served, transcode task failed: {}", e);
+            image
         }
     }
-
-    let res = zip.finish();
-    if let Err(e) = res {
-        // Cleanup partial file on error
-        let _ = std::fs::remove_file(&tmp_path);
-        return Err(Box::new(e));
-    }
-    // Move temp file to final destination
-    std::fs::rename(&tmp_path, &file_path)?;
-
-    Ok(file_path.to_string_lossy().to_string())
 }
 
+/// Save a manga's cover from `source_id` under `covers/` in its series folder
+/// (`series_dir`, relative to `base_dir`), unless it is already there.
 pub async fn ensure_cover_downloaded(
     client: &Client,
     base_dir: &str,
-    manga_title: &str,
+    series_dir: &str,
     cover_url: &str,
     source_id: i32,
 ) -> Result<Option<String>, Box<dyn std::error::Error>> {
-    let sanitized_title = sanitize_filename(manga_title);
-    let manga_dir: PathBuf = [base_dir, &sanitized_title].iter().collect();
-    let covers_dir = manga_dir.join("covers");
+    let covers_dir = Path::new(base_dir).join(series_dir).join("covers");
     std::fs::create_dir_all(&covers_dir)?;
     // Determine ext
     let ext = cover_url
@@ -384,15 +324,14 @@ pub async fn ensure_cover_downloaded(
     Ok(Some(path.to_string_lossy().to_string()))
 }
 
-/// Download chapter using headless browser for Cloudflare-protected sites
-async fn download_chapter_with_browser(
-    client: &Client,
+/// Render a chapter page in the headless browser for Cloudflare-protected sites
+/// and return the resulting HTML.
+pub(crate) async fn fetch_html_with_browser(
     chapter_url: &str,
-) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+) -> Result<String, Box<dyn std::error::Error>> {
     use crate::cloudflare_bypass::{
         get_fingerprint_spoofing_script, CloudflareConfig, SessionManager,
     };
-    use std::io::Cursor;
 
     // Load Cloudflare bypass configuration
     let cf_config = CloudflareConfig::load().unwrap_or_else(|e| {
@@ -624,388 +563,37 @@ async fn download_chapter_with_browser(
     }
 
     // Get HTML content
-    let html = tab.get_content()?;
-    let document = Html::parse_document(&html);
-
-    // Try multiple selectors to find images
-    let selectors = vec![
-        "div.reading-content img",
-        "div.read-content img",
-        "div.page-break img",
-        "div#readerarea img",
-        "div.reader-area img",
-        "div.chapter-content img",
-        "div.entry-content img",
-        "main img[src*='.jpg'], main img[src*='.png'], main img[src*='.webp']",
-        "img[loading='lazy']",
-    ];
-
-    let mut image_list = Vec::new();
-    for selector_str in selectors {
-        if let Ok(selector) = Selector::parse(selector_str) {
-            for element in document.select(&selector) {
-                if let Some(image_url) = element
-                    .value()
-                    .attr("src")
-                    .or_else(|| element.value().attr("data-src"))
-                    .or_else(|| element.value().attr("data-lazy-src"))
-                {
-                    let url_lower = image_url.to_lowercase();
-                    if (url_lower.contains(".jpg")
-                        || url_lower.contains(".jpeg")
-                        || url_lower.contains(".png")
-                        || url_lower.contains(".webp"))
-                        && !url_lower.contains("logo")
-                        && !url_lower.contains("icon")
-                        && !url_lower.contains("avatar")
-                        && !url_lower.contains("banner")
-                    {
-                        image_list.push(image_url.to_string());
-                    }
-                }
-            }
-            if !image_list.is_empty() {
-                break;
-            }
-        }
-    }
-
-    // If no images found via CSS, try JSON parsing
-    if image_list.is_empty() {
-        // Pattern 1: "chapterImages":[...]
-        if let Some(json_start) = html.find("\"chapterImages\":[") {
-            if let Some(json_end) = html[json_start..].find("]") {
-                let json_str = &html[json_start..json_start + json_end + 1];
-                if let Some(array_start) = json_str.find("[") {
-                    let array_str = &json_str[array_start..];
-                    if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(array_str) {
-                        if let Some(images) = json_value.as_array() {
-                            for img in images {
-                                if let Some(url) = img.get("url").and_then(|u| u.as_str()) {
-                                    image_list.push(url.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        // Pattern 2: "sources":[{"images":[...]}]  (RizzComic, Rokari, etc.)
-        if image_list.is_empty() {
-            if let Some(sources_start) = html.find("\"sources\":[") {
-                // Find the end of the sources array (look for the closing bracket)
-                let search_range = &html[sources_start
-                    ..sources_start + std::cmp::min(50000, html.len() - sources_start)];
-                if let Some(array_end) = search_range.rfind("]") {
-                    let json_str = &html[sources_start..sources_start + array_end + 1];
-                    if let Some(array_start) = json_str.find("[") {
-                        let array_str = &json_str[array_start..];
-                        if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(array_str)
-                        {
-                            if let Some(sources) = json_value.as_array() {
-                                for source_obj in sources {
-                                    if let Some(images_array) =
-                                        source_obj.get("images").and_then(|i| i.as_array())
-                                    {
-                                        for img_url in images_array {
-                                            if let Some(url) = img_url.as_str() {
-                                                image_list.push(url.to_string());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Create ZIP archive
-    let buffer = Cursor::new(Vec::new());
-    let mut zip = ZipWriter::new(buffer);
-
-    let origin = reqwest::Url::parse(chapter_url)
-        .ok()
-        .and_then(|u| Some(format!("{}://{}", u.scheme(), u.host_str()?)))
-        .unwrap_or_default();
-    let base = reqwest::Url::parse(chapter_url).ok();
-
-    for (i, image_url) in image_list.iter().enumerate() {
-        let full_url = if let Some(ref b) = base {
-            b.join(image_url)
-                .map(|u| u.to_string())
-                .unwrap_or(image_url.clone())
-        } else {
-            image_url.clone()
-        };
-
-        let response = client
-            .get(&full_url)
-            .header(
-                "Referer",
-                if !origin.is_empty() {
-                    origin.as_str()
-                } else {
-                    chapter_url
-                },
-            )
-            .header(
-                "User-Agent",
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36",
-            )
-            .send()
-            .await?;
-
-        let mut cursor = Cursor::new(response.bytes().await?);
-        zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-        copy(&mut cursor, &mut zip)?;
-    }
-
-    let cursor = zip.finish()?;
-    Ok(cursor.into_inner())
+    Ok(tab.get_content()?)
 }
 
+/// Download a chapter straight into memory in `format` (for `stream=true`).
 pub async fn download_chapter_to_memory(
     client: &Client,
+    fetcher: &PageFetcher,
     source_id: i32,
     chapter_url: &str,
+    comicinfo: Option<&ComicInfo>,
+    format: OutputFormat,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    use std::io::Cursor;
-
-    // Check if this source requires browser automation (Cloudflare-protected)
-    let cloudflare_sources = vec![3, 9, 20, 38, 39, 40, 59]; // RizzComic, ResetScans, HiveToons, QiScans, RizzFables, RokariComics, WitchScans
-    if cloudflare_sources.contains(&source_id) {
-        return download_chapter_with_browser(client, chapter_url).await;
-    }
-
-    let buffer = Cursor::new(Vec::new());
-    let mut zip = ZipWriter::new(buffer);
-
-    match source_id {
-        x if x == Source::MangaDex as i32 => {
-            let url = format!("https://api.mangadex.org/at-home/server/{}", chapter_url);
-            let response = client
-                .get(&url)
-                .header("User-Agent", "rust_manga_scraper/0.1.0")
-                .send()
-                .await?
-                .json::<AtHomeServer>()
-                .await?;
-
-            for (i, page) in response.chapter.data.iter().enumerate() {
-                let page_url = format!(
-                    "{}/data/{}/{}",
-                    response.base_url, response.chapter.hash, page
-                );
-                let response = client
-                    .get(&page_url)
-                    .header("Referer", "https://mangadex.org/")
-                    .header("User-Agent", "rust_manga_scraper/0.1.0")
-                    .send()
-                    .await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
-        }
-        x if x == Source::FireScans as i32 || x == Source::RizzComic as i32 => {
-            let response = client
-                .get(chapter_url)
-                .header("User-Agent", "rust_manga_scraper/0.1.0")
-                .send()
-                .await?
-                .text()
-                .await?;
-            let document = Html::parse_document(&response);
-            let selector = Selector::parse("div.reading-content > div.page-break > img").unwrap();
-            let mut image_list = Vec::new();
-
-            for element in document.select(&selector) {
-                let image_url = element.value().attr("src").unwrap().to_string();
-                image_list.push(image_url);
-            }
-
-            // Resolve relative image URLs and set a reasonable Referer per origin
-            let origin = reqwest::Url::parse(chapter_url)
-                .ok()
-                .and_then(|u| Some(format!("{}://{}", u.scheme(), u.host_str()?)))
-                .unwrap_or_default();
-            for (i, src) in image_list.iter().enumerate() {
-                let full_url = if let Ok(base) = reqwest::Url::parse(chapter_url) {
-                    base.join(src)
-                        .map(|u| u.to_string())
-                        .unwrap_or_else(|_| src.clone())
-                } else {
-                    src.clone()
-                };
-                let response = client
-                    .get(&full_url)
-                    .header(
-                        "Referer",
-                        if !origin.is_empty() {
-                            origin.as_str()
-                        } else {
-                            chapter_url
-                        },
-                    )
-                    .header("User-Agent", "rust_manga_scraper/0.1.0")
-                    .send()
-                    .await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
-        }
-        x if x == Source::Kagane as i32 => {
-            let response = client.get(chapter_url).send().await?.text().await?;
-            let document = Html::parse_document(&response);
-            let selectors = vec![
-                Selector::parse("div[data-reader] img").unwrap(),
-                Selector::parse("div.reading-content img").unwrap(),
-                Selector::parse("main img").unwrap(),
-            ];
-            let mut image_list: Vec<String> = Vec::new();
-            'outer: for selector in selectors {
-                for element in document.select(&selector) {
-                    if let Some(image_url) = element
-                        .value()
-                        .attr("src")
-                        .or_else(|| element.value().attr("data-src"))
-                    {
-                        if image_url.contains("http")
-                            && (image_url.ends_with(".jpg")
-                                || image_url.ends_with(".jpeg")
-                                || image_url.ends_with(".png"))
-                        {
-                            image_list.push(image_url.to_string());
-                        }
-                    }
-                }
-                if !image_list.is_empty() {
-                    break 'outer;
-                }
-            }
-            for (i, image_url) in image_list.iter().enumerate() {
-                let response = client.get(image_url).send().await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
-        }
-        _ => {
-            // Generic HTML reader fallback to memory with multiple selectors
-            let response = client.get(chapter_url).send().await?.text().await?;
-            let document = Html::parse_document(&response);
-
-            // Try multiple selectors in order
-            let selectors = vec![
-                "div.reading-content img",
-                "div.read-content img",
-                "div.page-break img",
-                "div#readerarea img",
-                "div.reader-area img",
-                "div.chapter-content img",
-                "div.entry-content img",
-                "main img[src*='.jpg'], main img[src*='.png'], main img[src*='.webp']",
-                "img[loading='lazy']",
-            ];
-
-            let mut image_list = Vec::new();
-            for selector_str in selectors {
-                if let Ok(selector) = Selector::parse(selector_str) {
-                    for element in document.select(&selector) {
-                        if let Some(image_url) = element
-                            .value()
-                            .attr("src")
-                            .or_else(|| element.value().attr("data-src"))
-                            .or_else(|| element.value().attr("data-lazy-src"))
-                        {
-                            // Filter out obviously non-chapter images
-                            let url_lower = image_url.to_lowercase();
-                            if (url_lower.contains(".jpg")
-                                || url_lower.contains(".jpeg")
-                                || url_lower.contains(".png")
-                                || url_lower.contains(".webp"))
-                                && !url_lower.contains("logo")
-                                && !url_lower.contains("icon")
-                                && !url_lower.contains("avatar")
-                                && !url_lower.contains("banner")
-                            {
-                                image_list.push(image_url.to_string());
-                            }
-                        }
-                    }
-                    // If we found images with this selector, use them
-                    if !image_list.is_empty() {
-                        break;
-                    }
-                }
-            }
-
-            // If no images found via CSS selectors, try parsing Next.js JSON data
-            if image_list.is_empty() {
-                // Look for JSON data in script tags (Next.js pattern)
-                if let Some(json_start) = response.find("\"chapterImages\":[") {
-                    if let Some(json_end) = response[json_start..].find("]") {
-                        let json_str = &response[json_start..json_start + json_end + 1];
-                        // Extract the array part
-                        if let Some(array_start) = json_str.find("[") {
-                            let array_str = &json_str[array_start..];
-                            // Try to parse as JSON
-                            if let Ok(json_value) =
-                                serde_json::from_str::<serde_json::Value>(array_str)
-                            {
-                                if let Some(images) = json_value.as_array() {
-                                    for img in images {
-                                        if let Some(url) = img.get("url").and_then(|u| u.as_str()) {
-                                            image_list.push(url.to_string());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    let pages = pages::resolve_pages(client, source_id, chapter_url).await?;
+    let images = fetcher.fetch_all(client, &pages).await?;
+    let chapter = Chapter::new(comicinfo, images);
+    Ok(format.writer().to_bytes(&chapter)?)
+}
 
-            let origin = reqwest::Url::parse(chapter_url)
-                .ok()
-                .and_then(|u| Some(format!("{}://{}", u.scheme(), u.host_str()?)))
-                .unwrap_or_default();
-            let base = reqwest::Url::parse(chapter_url).ok();
-
-            for (i, image_url) in image_list.iter().enumerate() {
-                let full_url = if let Some(ref b) = base {
-                    b.join(image_url)
-                        .map(|u| u.to_string())
-                        .unwrap_or(image_url.clone())
-                } else {
-                    image_url.clone()
-                };
-                let response = client
-                    .get(&full_url)
-                    .header(
-                        "Referer",
-                        if !origin.is_empty() {
-                            origin.as_str()
-                        } else {
-                            chapter_url
-                        },
-                    )
-                    .header("User-Agent", "rust_manga_scraper/0.1.0")
-                    .send()
-                    .await?;
-                let mut cursor = Cursor::new(response.bytes().await?);
-                zip.start_file(format!("page_{}.jpg", i + 1), FileOptions::default())?;
-                copy(&mut cursor, &mut zip)?;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_limiter_shares_permits_per_host() {
+        let limiter = HostLimiter::new(2);
+        let a = limiter.for_url("https://cdn.example.com/1.jpg");
+        let b = limiter.for_url("https://cdn.example.com/2.jpg");
+        let c = limiter.for_url("https://other.example.com/1.jpg");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(a.available_permits(), 2);
+        assert_eq!(HostLimiter::new(0).for_url("x").available_permits(), 1);
     }
-
-    let cursor = zip.finish()?;
-
-    Ok(cursor.into_inner())
 }
@@ -0,0 +1,249 @@
+//! Manga search for `GET /manga`.
+//!
+//! [`MangaSearch`] holds the free-text query and filters; each storage backend
+//! turns it into one query for the page and one for the filtered total, so
+//! `pagination.total` always counts what the filters match.
+//!
+//! On PostgreSQL the text is matched against `manga.search_vector`, a weighted
+//! document (title A, alt titles B, tags C, description D) where every query
+//! word matches as a prefix, and by pg_trgm similarity on the title and alt
+//! titles so misspelt queries still find the series. Results sort by relevance
+//! when there is a query. SQLite has no trigram index: every word must appear
+//! in one of the same fields, and title hits rank first.
+//!
+//! Tag filters match `manga_tags` links by tag ID or canonical slug, so
+//! `tags=Sci Fi` and `tags=sci-fi` find the same manga.
+
+use crate::sources::registry;
+use crate::tags;
+use std::collections::HashMap;
+
+/// Page size when `limit` is not given.
+pub const DEFAULT_LIMIT: i64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchSort {
+    /// Best match first; by title when there is no query
+    #[default]
+    Relevance,
+    Title,
+    /// Safe first, then suggestive, erotica and pornographic
+    Rating,
+    /// Most recently found new chapter first
+    Updated,
+}
+
+impl SearchSort {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "relevance" => Some(Self::Relevance),
+            "title" => Some(Self::Title),
+            "rating" => Some(Self::Rating),
+            "updated" => Some(Self::Updated),
+            _ => None,
+        }
+    }
+}
+
+/// A tag to filter on: its `tags.id`, or the canonical slug of a name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagFilter {
+    Id(i32),
+    Slug(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct MangaSearch {
+    /// Free text; empty lists everything the filters match
+    pub query: Option<String>,
+    /// Publication status, e.g. `ongoing` (case-insensitive)
+    pub status: Option<String>,
+    /// Only manga linked to this source
+    pub source_id: Option<i32>,
+    /// Tags the manga must all have
+    pub include_tags: Vec<TagFilter>,
+    /// Tags the manga must not have
+    pub exclude_tags: Vec<TagFilter>,
+    pub rating: Option<String>,
+    /// Only manga with (`true`) or without (`false`) chapters not downloaded yet
+    pub has_unread: Option<bool>,
+    pub sort: SearchSort,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Default for MangaSearch {
+    fn default() -> Self {
+        MangaSearch {
+            query: None,
+            status: None,
+            source_id: None,
+            include_tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            rating: None,
+            has_unread: None,
+            sort: SearchSort::default(),
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+        }
+    }
+}
+
+impl MangaSearch {
+    /// Read the `GET /manga` query string: `q` (or `search`), `status`,
+    /// `source` (name or ID), `tags` and `exclude_tags` (comma-separated tag
+    /// IDs or names),
+    /// `rating`, `has_unread`, `sort`, `limit` and `offset`. Unknown sources
+    /// and malformed values are errors rather than silently ignored filters.
+    pub fn from_query(params: &HashMap<String, String>) -> Result<Self, String> {
+        let get = |key: &str| params.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let mut search = MangaSearch {
+            query: get("q").or_else(|| get("search")).map(str::to_string),
+            status: get("status").map(str::to_string),
+            include_tags: get("tags").map(parse_tags).unwrap_or_default(),
+            exclude_tags: get("exclude_tags").map(parse_tags).unwrap_or_default(),
+            rating: get("rating").map(str::to_string),
+            ..Default::default()
+        };
+        if let Some(source) = get("source") {
+            let id = match source.parse::<i32>() {
+                Ok(id) => registry::get(id).map(|s| s.id()),
+                Err(_) => registry::find(source).map(|s| s.id()),
+            };
+            search.source_id = Some(id.ok_or_else(|| format!("unknown source '{}'", source))?);
+        }
+        if let Some(v) = get("has_unread") {
+            search.has_unread = Some(match v {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => return Err("has_unread must be true or false".to_string()),
+            });
+        }
+        if let Some(v) = get("sort") {
+            search.sort = SearchSort::parse(v).ok_or_else(|| format!("unknown sort '{}'", v))?;
+        }
+        if let Some(v) = get("limit") {
+            search.limit = v
+                .parse::<i64>()
+                .ok()
+                .filter(|l| *l > 0)
+                .ok_or("limit must be a positive number")?;
+        }
+        if let Some(v) = get("offset") {
+            search.offset = v
+                .parse::<i64>()
+                .ok()
+                .filter(|o| *o >= 0)
+                .ok_or("offset must not be negative")?;
+        }
+        Ok(search)
+    }
+
+    /// The free-text query, if it has anything to match
+    pub fn text(&self) -> Option<&str> {
+        self.query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+    }
+
+    /// Whether results are ordered by match quality
+    pub fn by_relevance(&self) -> bool {
+        self.sort == SearchSort::Relevance && self.text().is_some()
+    }
+}
+
+/// Lowercased words of a query, split on anything that is not a letter or digit.
+pub fn query_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// `to_tsquery` input requiring every word of `query` as a prefix
+/// ("one pie" becomes `one:* & pie:*`), or `None` when it has no words.
+pub fn prefix_tsquery(query: &str) -> Option<String> {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+        return None;
+    }
+    Some(
+        terms
+            .iter()
+            .map(|t| format!("{}:*", t))
+            .collect::<Vec<_>>()
+            .join(" & "),
+    )
+}
+
+/// Split a comma-separated list of tag IDs and names; names are matched by
+/// their canonical slug.
+pub fn parse_tags(list: &str) -> Vec<TagFilter> {
+    list.split(',')
+        .map(str::trim)
+        .filter_map(|t| match t.parse::<i32>() {
+            Ok(id) => Some(TagFilter::Id(id)),
+            Err(_) => tags::canonicalize(None, t).map(|tag| TagFilter::Slug(tag.slug)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_prefix_tsquery() {
+        assert_eq!(prefix_tsquery("One Pie").as_deref(), Some("one:* & pie:*"));
+        assert_eq!(
+            prefix_tsquery("re:zero (kara)").as_deref(),
+            Some("re:* & zero:* & kara:*")
+        );
+        assert_eq!(prefix_tsquery(" !& "), None);
+    }
+
+    #[test]
+    fn test_from_query() {
+        let search = MangaSearch::from_query(&params(&[
+            ("search", "solo"),
+            ("tags", "Action, Sci Fi,"),
+            ("exclude_tags", "12"),
+            ("source", "1"),
+            ("has_unread", "true"),
+            ("limit", "5"),
+        ]))
+        .unwrap();
+        assert_eq!(search.text(), Some("solo"));
+        assert_eq!(
+            search.include_tags,
+            vec![
+                TagFilter::Slug("action".to_string()),
+                TagFilter::Slug("sci-fi".to_string())
+            ]
+        );
+        assert_eq!(search.exclude_tags, vec![TagFilter::Id(12)]);
+        assert_eq!(search.source_id, Some(1));
+        assert_eq!(search.has_unread, Some(true));
+        assert_eq!((search.limit, search.offset), (5, 0));
+        assert!(search.by_relevance());
+
+        let search = MangaSearch::from_query(&params(&[("q", "  "), ("sort", "rating")])).unwrap();
+        assert_eq!(search.text(), None);
+        assert_eq!(search.sort, SearchSort::Rating);
+        assert!(!MangaSearch::default().by_relevance());
+        assert_eq!(SearchSort::parse("Updated"), Some(SearchSort::Updated));
+
+        assert!(MangaSearch::from_query(&params(&[("source", "nowhere")])).is_err());
+        assert!(MangaSearch::from_query(&params(&[("has_unread", "maybe")])).is_err());
+        assert!(MangaSearch::from_query(&params(&[("limit", "0")])).is_err());
+    }
+}
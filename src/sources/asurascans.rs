@@ -89,6 +89,8 @@ pub async fn search_manga_with_urls(
                     discover_interval_secs: None,
                     last_chapter_check: None,
                     last_discover_check: None,
+                    status: None,
+                    people: Vec::new(),
                 };
 
                 results.push((manga, series_url));
@@ -151,6 +153,7 @@ pub async fn get_chapters(
             chapter_number: num.to_string(),
             url,
             scraped: false,
+            ..Default::default()
         })
         .collect();
 
@@ -0,0 +1,340 @@
+//! Authoritative source-ID catalogue.
+//!
+//! Every numeric source ID used by the scraper is defined here exactly once,
+//! together with its slug, display name, base URL, known domains, engine family
+//! and flags. The `sources` seed rows, [`registry`](super::registry) metadata,
+//! download file names and URL detection are all derived from this table, and
+//! `Storage::check_sources_table` refuses to start when the database disagrees.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_manga_scraper::sources::catalogue;
+//!
+//! let entry = catalogue::detect_url("https://reset-scans.org/manga/foo/").unwrap();
+//! assert_eq!(entry.id, 9);
+//! assert_eq!(catalogue::name_for(2), "FireScans");
+//! ```
+
+use reqwest::Url;
+
+/// How a source is scraped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineFamily {
+    /// MangaDex JSON API.
+    MangaDexApi,
+    /// Tracking sites (MAL, AniList); no chapters.
+    MetadataApi,
+    /// WordPress Madara / MangaStream themed scanlation sites.
+    WpManga,
+    /// Next.js apps with page data embedded in `__NEXT_DATA__` / RSC payloads.
+    NextJs,
+    /// One-off HTML scrapers.
+    Html,
+    /// Commercial publishers and paid platforms (previews only).
+    Publisher,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceFlags {
+    /// Walked by the full crawl.
+    pub crawl: bool,
+    /// Chapter pages sit behind Cloudflare and need the headless browser.
+    pub requires_browser: bool,
+    /// Listings only; no chapter scraping.
+    pub metadata_only: bool,
+}
+
+impl SourceFlags {
+    pub const NONE: Self = Self {
+        crawl: false,
+        requires_browser: false,
+        metadata_only: false,
+    };
+    pub const CRAWL: Self = Self {
+        crawl: true,
+        ..Self::NONE
+    };
+    pub const CRAWL_BROWSER: Self = Self {
+        crawl: true,
+        requires_browser: true,
+        metadata_only: false,
+    };
+    pub const METADATA: Self = Self {
+        metadata_only: true,
+        ..Self::NONE
+    };
+}
+
+#[derive(Debug)]
+pub struct SourceEntry {
+    pub id: i32,
+    /// Lowercase alphanumeric key used in URLs (`/import/source/{slug}`).
+    pub slug: &'static str,
+    /// Extra names accepted by [`find`].
+    pub aliases: &'static [&'static str],
+    /// Display name; also the `sources.name` column and the download file prefix.
+    pub name: &'static str,
+    pub base_url: &'static str,
+    /// Hostnames (without `www.`) that belong to this source, current one first.
+    pub domains: &'static [&'static str],
+    pub engine: EngineFamily,
+    pub flags: SourceFlags,
+}
+
+macro_rules! catalogue {
+    ($($id:literal => ($slug:literal, $name:literal, $base:literal, [$($domain:literal),*], $engine:ident, $flags:ident $(, aliases [$($alias:literal),*])?),)*) => {
+        pub static CATALOGUE: &[SourceEntry] = &[
+            $(SourceEntry {
+                id: $id,
+                slug: $slug,
+                aliases: &[$($($alias),*)?],
+                name: $name,
+                base_url: $base,
+                domains: &[$($domain),*],
+                engine: EngineFamily::$engine,
+                flags: SourceFlags::$flags,
+            },)*
+        ];
+    };
+}
+
+catalogue! {
+    1 => ("mangadex", "MangaDex", "https://mangadex.org", ["mangadex.org"], MangaDexApi, CRAWL),
+    2 => ("firescans", "FireScans", "https://firescans.xyz", ["firescans.xyz", "firescans.com"], WpManga, CRAWL),
+    3 => ("rizzcomic", "RizzComic", "https://rizzcomic.com", ["rizzcomic.com"], WpManga, CRAWL_BROWSER),
+    4 => ("myanimelist", "MyAnimeList", "https://myanimelist.net", ["myanimelist.net"], MetadataApi, METADATA, aliases ["mal"]),
+    5 => ("anilist", "AniList", "https://anilist.co", ["anilist.co"], MetadataApi, METADATA),
+    6 => ("drakecomic", "DrakeComic", "https://drakecomic.org", ["drakecomic.org", "drakecomic.com"], WpManga, CRAWL),
+    7 => ("kdtnovels", "KDTNovels", "https://kdtnovels.com", ["kdtnovels.com", "kdt-novels.com"], Html, METADATA, aliases ["kdt"]),
+    8 => ("asmotoon", "Asmotoon", "https://asmotoon.com", ["asmotoon.com"], WpManga, CRAWL),
+    9 => ("resetscans", "ResetScans", "https://reset-scans.org", ["reset-scans.org", "reset-scans.us", "reset-scans.com"], WpManga, CRAWL_BROWSER),
+    10 => ("kagane", "Kagane", "https://kagane.org", ["kagane.org"], NextJs, CRAWL),
+    11 => ("asurascans", "AsuraScans", "https://asuracomic.net", ["asuracomic.net", "asurascans.com", "asuratoon.com"], WpManga, CRAWL),
+    12 => ("booklive", "BookLive", "https://booklive.jp", ["booklive.jp"], Publisher, NONE),
+    13 => ("comikey", "Comikey", "https://comikey.com", ["comikey.com"], Publisher, NONE),
+    14 => ("denpa", "DENPA", "https://denpa.pub", ["denpa.pub"], Publisher, NONE, aliases ["denpabooks"]),
+    15 => ("darkhorse", "Dark Horse", "https://www.darkhorse.com", ["darkhorse.com"], Publisher, NONE, aliases ["darkhorsecomics"]),
+    16 => ("daycomics", "DayComics", "https://daycomics.com", ["daycomics.com"], Html, NONE),
+    17 => ("fakku", "FAKKU", "https://www.fakku.net", ["fakku.net"], Publisher, NONE),
+    18 => ("flamecomics", "FlameComics", "https://flamecomics.xyz", ["flamecomics.xyz", "flamecomics.com"], NextJs, NONE),
+    19 => ("grimscans", "GrimScans", "https://grimscans.com", ["grimscans.com", "grimscans.team"], WpManga, CRAWL),
+    20 => ("hivetoons", "HiveToons", "https://hivetoons.org", ["hivetoons.org", "hivetoons.com"], WpManga, CRAWL_BROWSER),
+    21 => ("inkr", "INKR", "https://comics.inkr.com", ["inkr.com"], Publisher, NONE, aliases ["inkrcomics"]),
+    22 => ("irodori", "Irodori", "https://irodoricomics.com", ["irodoricomics.com"], Publisher, NONE, aliases ["irodoricomics"]),
+    23 => ("jnovel", "J-Novel", "https://j-novel.club", ["j-novel.club"], Publisher, NONE, aliases ["jnovelclub"]),
+    24 => ("kana", "Kana", "https://www.mangakana.com", ["mangakana.com", "kana.fr"], Publisher, NONE),
+    25 => ("kenscans", "KenScans", "https://kencomics.com", ["kencomics.com", "kenscans.com"], WpManga, CRAWL),
+    26 => ("kodansha", "Kodansha", "https://kodansha.us", ["kodansha.us"], Publisher, NONE, aliases ["kodanshacomics"]),
+    27 => ("kodokustudio", "KodokuStudio", "https://kodokustudio.com", ["kodokustudio.com"], Html, NONE),
+    28 => ("lezhin", "Lezhin", "https://www.lezhinus.com", ["lezhinus.com", "lezhin.com"], Publisher, NONE),
+    29 => ("lunatoons", "LunaToons", "https://lunatoons.com", ["lunatoons.com"], Html, NONE),
+    30 => ("madarascans", "MadaraScans", "https://madarascans.com", ["madarascans.com", "madaradex.org"], WpManga, CRAWL),
+    31 => ("manhuaus", "Manhuaus", "https://manhuaus.com", ["manhuaus.com"], WpManga, CRAWL),
+    32 => ("manta", "Manta", "https://manta.net", ["manta.net"], Publisher, NONE),
+    33 => ("medibang", "MediBang", "https://medibang.com", ["medibang.com"], Html, NONE),
+    34 => ("nyxscans", "NyxScans", "https://nyxscans.com", ["nyxscans.com"], WpManga, CRAWL),
+    35 => ("onepeace", "One Peace", "https://onepeacebooks.com", ["onepeacebooks.com"], Publisher, NONE, aliases ["onepeacebooks"]),
+    36 => ("others", "Others", "https://example.com/others", [], Html, NONE),
+    37 => ("pocket", "Pocket", "https://www.pocketcomics.com", ["pocketcomics.com"], Publisher, NONE, aliases ["pocketcomics"]),
+    38 => ("qiscans", "QiScans", "https://qiscans.org", ["qiscans.org", "qiscans.com"], WpManga, CRAWL_BROWSER),
+    39 => ("rizzfables", "RizzFables", "https://rizzfables.com", ["rizzfables.com"], WpManga, CRAWL_BROWSER),
+    40 => ("rokaricomics", "RokariComics", "https://rokaricomics.com", ["rokaricomics.com"], WpManga, CRAWL_BROWSER),
+    41 => ("sevenseas", "Seven Seas", "https://sevenseasentertainment.com", ["sevenseasentertainment.com"], Publisher, NONE),
+    42 => ("shueisha", "Shueisha", "https://www.shonenjump.com", ["shonenjump.com", "shueisha.co.jp"], Publisher, NONE),
+    43 => ("sirenscans", "SirenScans", "https://sirenscans.com", ["sirenscans.com"], WpManga, CRAWL),
+    44 => ("squareenix", "Square Enix", "https://squareenixmangaandbooks.square-enix-games.com", ["square-enix-games.com", "square-enix-books.com"], Publisher, NONE, aliases ["squareenixmanga"]),
+    45 => ("stonescape", "StoneScape", "https://stonescape.xyz", ["stonescape.xyz"], WpManga, CRAWL),
+    46 => ("tokyopop", "TOKYOPOP", "https://www.tokyopop.com", ["tokyopop.com"], Publisher, NONE),
+    47 => ("tapas", "Tapas", "https://tapas.io", ["tapas.io"], Html, NONE),
+    48 => ("tappytoon", "Tappytoon", "https://www.tappytoon.com", ["tappytoon.com"], Publisher, NONE),
+    49 => ("templescan", "TempleScan", "https://templetoons.com", ["templetoons.com", "templescan.net"], WpManga, CRAWL, aliases ["templetoons"]),
+    50 => ("thunderscans", "ThunderScans", "https://en-thunderscans.com", ["en-thunderscans.com", "thunderscans.com"], WpManga, CRAWL),
+    51 => ("titan", "Titan", "https://titan-comics.com", ["titan-comics.com"], Publisher, NONE, aliases ["titanmanga"]),
+    52 => ("toomics", "Toomics", "https://www.toomics.com", ["toomics.com"], Publisher, NONE),
+    53 => ("udon", "UDON", "https://www.udonentertainment.com", ["udonentertainment.com"], Publisher, NONE, aliases ["udonentertainment"]),
+    54 => ("vastvisual", "VastVisual", "https://vastvisual.com", ["vastvisual.com"], Html, NONE),
+    55 => ("viz", "VIZ", "https://www.viz.com", ["viz.com"], Publisher, NONE, aliases ["vizmedia"]),
+    56 => ("vortexscans", "VortexScans", "https://vortexscans.org", ["vortexscans.org", "vortexscans.com"], WpManga, CRAWL),
+    57 => ("webcomics", "Webcomics", "https://www.webcomicsapp.com", ["webcomicsapp.com"], Html, NONE),
+    58 => ("webtoon", "Webtoon", "https://www.webtoons.com", ["webtoons.com"], Html, NONE, aliases ["webtoons"]),
+    59 => ("witchscans", "WitchScans", "https://witchscans.com", ["witchscans.com"], WpManga, CRAWL_BROWSER),
+    60 => ("yenpress", "Yen Press", "https://yenpress.com", ["yenpress.com"], Publisher, NONE),
+    61 => ("mavintranslations", "MavinTranslations", "https://mavintranslations.com", ["mavintranslations.com"], Html, NONE),
+}
+
+/// Look up a source by ID.
+pub fn get(id: i32) -> Option<&'static SourceEntry> {
+    CATALOGUE.iter().find(|e| e.id == id)
+}
+
+/// Display name for a source ID, `"Unknown"` if it is not catalogued.
+pub fn name_for(id: i32) -> &'static str {
+    get(id).map(|e| e.name).unwrap_or("Unknown")
+}
+
+fn compact(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Look up a source by numeric ID, slug, alias or display name. Matching ignores
+/// case and punctuation, so `qi-scans`, `QiScans` and `38` all resolve to QiScans.
+pub fn find(key: &str) -> Option<&'static SourceEntry> {
+    let key = key.trim();
+    if let Ok(id) = key.parse::<i32>() {
+        return get(id);
+    }
+    let k = compact(key);
+    if k.is_empty() {
+        return None;
+    }
+    CATALOGUE
+        .iter()
+        .find(|e| e.slug == k || compact(e.name) == k || e.aliases.iter().any(|a| *a == k))
+}
+
+/// Identify the source a URL belongs to by its hostname.
+pub fn detect_url(url: &str) -> Option<&'static SourceEntry> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    CATALOGUE.iter().find(|e| {
+        e.domains
+            .iter()
+            .any(|d| host == *d || host.ends_with(&format!(".{}", d)))
+    })
+}
+
+/// `(id, name, url)` rows for the `sources` table.
+pub fn seed_rows() -> String {
+    CATALOGUE
+        .iter()
+        .map(|e| {
+            format!(
+                "    ({}, '{}', '{}')",
+                e.id,
+                e.name.replace('\'', "''"),
+                e.base_url
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+/// Seed statement for the `sources` table, as used in `migrations/01_init_schema.sql`.
+pub fn seed_sql() -> String {
+    format!(
+        "INSERT INTO sources (id, name, url) VALUES\n{}\nON CONFLICT (id) DO NOTHING;",
+        seed_rows()
+    )
+}
+
+/// Describe every way the `(id, name)` rows of a `sources` table disagree
+/// with the catalogue (missing IDs, wrong names, IDs the catalogue does not
+/// know). An empty result means the table is consistent.
+pub fn table_problems(rows: &[(i32, String)]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for entry in CATALOGUE {
+        match rows.iter().find(|(id, _)| *id == entry.id) {
+            None => problems.push(format!("source {} ({}) is missing", entry.id, entry.name)),
+            Some((_, name)) if name != entry.name => problems.push(format!(
+                "source {} is named '{}' but the catalogue says '{}'",
+                entry.id, name, entry.name
+            )),
+            Some(_) => {}
+        }
+    }
+    for (id, name) in rows {
+        if get(*id).is_none() {
+            problems.push(format!("source {} ('{}') is not in the catalogue", id, name));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Source;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_ids_slugs_and_names_are_unique() {
+        let mut ids = HashSet::new();
+        let mut keys = HashSet::new();
+        for e in CATALOGUE {
+            assert!(ids.insert(e.id), "duplicate id {}", e.id);
+            assert!(keys.insert(e.slug.to_string()), "duplicate slug {}", e.slug);
+            assert_eq!(compact(e.slug), e.slug, "slug {} is not compact", e.slug);
+            for a in e.aliases {
+                assert!(keys.insert(a.to_string()), "duplicate alias {}", a);
+            }
+        }
+        let names: HashSet<_> = CATALOGUE.iter().map(|e| e.name).collect();
+        assert_eq!(names.len(), CATALOGUE.len());
+    }
+
+    #[test]
+    fn test_source_enum_matches_catalogue() {
+        let expected = [
+            (Source::MangaDex, "mangadex"),
+            (Source::FireScans, "firescans"),
+            (Source::RizzComic, "rizzcomic"),
+            (Source::MyAnimeList, "myanimelist"),
+            (Source::AniList, "anilist"),
+            (Source::DrakeComic, "drakecomic"),
+            (Source::KDTNovels, "kdtnovels"),
+            (Source::Asmotoon, "asmotoon"),
+            (Source::ResetScans, "resetscans"),
+            (Source::Kagane, "kagane"),
+            (Source::TempleScan, "templescan"),
+            (Source::ThunderScans, "thunderscans"),
+        ];
+        for (src, slug) in expected {
+            assert_eq!(get(src as i32).unwrap().slug, slug);
+        }
+    }
+
+    #[test]
+    fn test_detect_url() {
+        assert_eq!(detect_url("https://mangadex.org/title/abc").unwrap().id, 1);
+        assert_eq!(detect_url("https://www.webtoons.com/en/x").unwrap().id, 58);
+        assert_eq!(
+            detect_url("https://en-thunderscans.com/foo").unwrap().id,
+            50
+        );
+        assert_eq!(
+            detect_url("https://cdn.asuracomic.net/a.jpg").unwrap().id,
+            11
+        );
+        assert!(detect_url("https://example.org/").is_none());
+        assert!(detect_url("not a url").is_none());
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("qi-scans").unwrap().id, 38);
+        assert_eq!(find("mal").unwrap().id, 4);
+        assert_eq!(find("Yen Press").unwrap().id, 60);
+        assert_eq!(find("7").unwrap().slug, "kdtnovels");
+        assert!(find("").is_none());
+        assert!(find("mangakakalot").is_none());
+    }
+
+    #[test]
+    fn test_migrations_seed_matches_catalogue() {
+        let init = include_str!("../../migrations/01_init_schema.sql");
+        assert!(
+            init.contains(&seed_sql()),
+            "01_init_schema.sql seed is out of date"
+        );
+        let fix = include_str!("../../migrations/02_source_catalogue.sql");
+        assert!(
+            fix.contains(&seed_rows()),
+            "02_source_catalogue.sql is out of date"
+        );
+    }
+}
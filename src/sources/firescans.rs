@@ -38,6 +38,8 @@ pub async fn search_manga(client: &Client, title: &str) -> Result<Vec<Manga>, re
             discover_interval_secs: None,
             last_chapter_check: None,
             last_discover_check: None,
+            status: None,
+            people: Vec::new(),
         };
         let encoded = format!("::URL::{}", url);
         manga.alt_titles = Some(encoded);
@@ -116,6 +118,8 @@ pub async fn search_manga_with_urls(
                                     discover_interval_secs: None,
                                     last_chapter_check: None,
                                     last_discover_check: None,
+                                    status: None,
+                                    people: Vec::new(),
                                 },
                                 series_url,
                             ));
@@ -172,6 +176,8 @@ pub async fn search_manga_with_urls(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             series_url,
                         ));
@@ -232,6 +238,8 @@ pub async fn search_manga_first_page(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             series_url,
                         ));
@@ -320,6 +328,7 @@ pub async fn get_chapters(
                         chapter_number: label,
                         url: abs,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -343,6 +352,7 @@ pub async fn get_chapters(
                             chapter_number: label,
                             url: href.to_string(),
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -374,6 +384,7 @@ pub async fn get_chapters(
                                 chapter_number: label,
                                 url: href.to_string(),
                                 scraped: false,
+                                ..Default::default()
                             });
                             any = true;
                         }
@@ -418,6 +429,7 @@ pub async fn get_chapters(
                         chapter_number: format!("Chapter {}", n),
                         url: chapter_url,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -484,6 +496,7 @@ pub async fn get_chapters(
                         chapter_number: label,
                         url: abs,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -523,6 +536,7 @@ pub async fn get_chapters(
                                             chapter_number: lbl,
                                             url: abs,
                                             scraped: false,
+                                            ..Default::default()
                                         });
                                     }
                                 }
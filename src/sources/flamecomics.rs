@@ -136,6 +136,8 @@ pub async fn search_manga_with_urls(
                     discover_interval_secs: None,
                     last_chapter_check: None,
                     last_discover_check: None,
+                    status: None,
+                    people: Vec::new(),
                 };
 
                 let series_url = format!("{}/series/{}", BASE_URL, series.series_id);
@@ -201,6 +203,9 @@ pub async fn get_chapters(
                 chapter_number: chapter_num.to_string(),
                 url: chapter_url,
                 scraped: false,
+                number: ch.chapter.parse().ok(),
+                title: ch.title.filter(|t| !t.trim().is_empty()),
+                ..Default::default()
             });
         }
     }
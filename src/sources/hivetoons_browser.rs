@@ -112,6 +112,8 @@ pub fn search_manga_with_urls_browser(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             url,
                         ));
@@ -196,6 +198,7 @@ pub fn get_chapters_browser(
         chapter_number: "Chapter 0".to_string(),
         url: format!("{}/chapter-0", series_url.trim_end_matches('/')),
         scraped: false,
+        ..Default::default()
     }])
 }
 
@@ -77,6 +77,8 @@ pub async fn search_manga_with_urls(
                                     discover_interval_secs: None,
                                     last_chapter_check: None,
                                     last_discover_check: None,
+                                    status: None,
+                                    people: Vec::new(),
                                 },
                                 series_url,
                             ));
@@ -141,6 +143,8 @@ pub async fn search_manga_with_urls(
                     discover_interval_secs: None,
                     last_chapter_check: None,
                     last_discover_check: None,
+                    status: None,
+                    people: Vec::new(),
                 };
                 out.push((m, format!("{}{}", BASE_URL, href)));
             }
@@ -217,6 +221,7 @@ pub async fn get_chapters(
                                 },
                                 url: u,
                                 scraped: false,
+                                ..Default::default()
                             });
                         }
                     }
@@ -250,6 +255,7 @@ pub async fn get_chapters(
                             },
                             url: abs,
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -270,6 +276,7 @@ pub async fn get_chapters(
                     chapter_number: rel.to_string(),
                     url,
                     scraped: false,
+                    ..Default::default()
                 });
             }
         }
@@ -334,6 +341,8 @@ pub async fn search_all_series_with_urls(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         };
                         out.push((m, format!("{}{}", BASE_URL, href)));
                         items_in_page += 1;
@@ -374,6 +383,8 @@ pub async fn search_all_series_with_urls(
                                                 discover_interval_secs: None,
                                                 last_chapter_check: None,
                                                 last_discover_check: None,
+                                                status: None,
+                                                people: Vec::new(),
                                             },
                                             series_url,
                                         ));
@@ -420,6 +431,8 @@ pub async fn search_all_series_with_urls(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         url,
                     ));
@@ -465,6 +478,8 @@ pub async fn search_all_series_with_urls(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         loc,
                     ));
@@ -476,6 +491,14 @@ pub async fn search_all_series_with_urls(
     Ok(out)
 }
 
+/// Source ID for a provider link, via the catalogue domains. Links back to Kagane
+/// itself and to metadata-only sites are not providers.
+fn provider_source_id(url: &str) -> Option<i32> {
+    crate::sources::catalogue::detect_url(url)
+        .filter(|e| e.id != crate::models::Source::Kagane as i32 && !e.flags.metadata_only)
+        .map(|e| e.id)
+}
+
 /// Extract external provider links from a Kagane series page and map them to known source IDs.
 pub async fn extract_provider_links(client: &Client, series_url: &str) -> Vec<(i32, String)> {
     let mut out: Vec<(i32, String)> = Vec::new();
@@ -499,34 +522,6 @@ pub async fn extract_provider_links(client: &Client, series_url: &str) -> Vec<(i
         Err(_) => return out,
     };
 
-    // Map of hostname substrings to source IDs (must align with DB 'sources' table)
-    let patterns: Vec<(&str, i32)> = vec![
-        ("mangadex.org", crate::models::Source::MangaDex as i32),
-        ("firescans", crate::models::Source::FireScans as i32),
-        ("rizzcomic", crate::models::Source::RizzComic as i32),
-        ("drakecomic", crate::models::Source::DrakeComic as i32),
-        ("asmotoon", crate::models::Source::Asmotoon as i32),
-        ("reset-scans", crate::models::Source::ResetScans as i32),
-        ("resetscans", crate::models::Source::ResetScans as i32),
-        ("templescan", crate::models::Source::TempleScan as i32),
-        ("thunderscans", crate::models::Source::ThunderScans as i32),
-        // WP-Manga group (IDs as per main mapping)
-        ("asurascans.com", 11),
-        ("kenscans.com", 25),
-        ("sirenscans.com", 43),
-        ("vortexscans", 56),
-        ("witchscans", 59),
-        ("qiscans", 38),
-        ("madarascans", 30),
-        ("rizzfables", 39),
-        ("rokaricomics", 40),
-        ("stonescape.xyz", 45),
-        ("manhuaus.com", 31),
-        ("grimscans.team", 19),
-        ("hivetoons.com", 20),
-        ("nyxscans.com", 34),
-    ];
-
     let mut seen = std::collections::HashSet::new();
     for a in doc.select(&a_sel) {
         if let Some(href) = a.value().attr("href") {
@@ -534,9 +529,9 @@ pub async fn extract_provider_links(client: &Client, series_url: &str) -> Vec<(i
             if !(h.starts_with("http://") || h.starts_with("https://")) {
                 continue;
             }
-            if let Some((_, sid)) = patterns.iter().find(|(pat, _)| h.contains(*pat)) {
+            if let Some(sid) = provider_source_id(&h) {
                 if seen.insert((sid, h.clone())) {
-                    out.push((*sid, href.to_string()));
+                    out.push((sid, href.to_string()));
                 }
             }
         }
@@ -546,9 +541,9 @@ pub async fn extract_provider_links(client: &Client, series_url: &str) -> Vec<(i
     for cap in re.captures_iter(&text) {
         let url = cap.get(0).unwrap().as_str().to_string();
         let l = url.to_lowercase();
-        if let Some((_, sid)) = patterns.iter().find(|(pat, _)| l.contains(*pat)) {
+        if let Some(sid) = provider_source_id(&l) {
             if seen.insert((sid, l.clone())) {
-                out.push((*sid, url));
+                out.push((sid, url));
             }
         }
     }
@@ -1,4 +1,5 @@
-use crate::models::{Chapter, Manga};
+use crate::models::{Chapter, Credit, Manga};
+use crate::people::{self, PersonRole};
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -78,6 +79,8 @@ pub async fn search_manga(
         .query(&[
             ("title", title),
             ("includes[]", "cover_art"),
+            ("includes[]", "author"),
+            ("includes[]", "artist"),
             ("limit", "25"),
         ])
         .send()
@@ -162,6 +165,8 @@ pub async fn search_manga(
                 description: Some(manga_description),
                 tags: Some(manga_tags),
                 rating: Some(manga_data.attributes.content_rating.clone()),
+                status: Some(manga_data.attributes.status.clone()),
+                people: credits(&manga_data.relationships),
                 monitored: None,
                 check_interval_secs: None,
                 discover_interval_secs: None,
@@ -189,7 +194,7 @@ pub async fn search_all_manga(
         }
 
         let url = format!(
-            "{}/manga?limit={}&offset={}&includes[]=cover_art",
+            "{}/manga?limit={}&offset={}&includes[]=cover_art&includes[]=author&includes[]=artist",
             base_url, limit, offset
         );
         let mut attempt = 0;
@@ -241,6 +246,24 @@ pub async fn search_all_manga(
     Ok(out)
 }
 
+/// Authors and artists from the `author`/`artist` relationships (requested
+/// with `includes[]` so their names are expanded)
+fn credits(relationships: &[Relationship]) -> Vec<Credit> {
+    let credits = relationships
+        .iter()
+        .filter_map(|r| {
+            let role = match r.rel_type.as_str() {
+                "author" => PersonRole::Author,
+                "artist" => PersonRole::Artist,
+                _ => return None,
+            };
+            let name = r.attributes.as_ref()?.get("name")?.as_str()?;
+            people::credit(name, role)
+        })
+        .collect();
+    people::dedup(credits)
+}
+
 fn map_mangadex(manga_data: MangaData) -> Manga {
     let mut all_titles: Vec<String> = Vec::new();
     for title_text in manga_data.attributes.title.values() {
@@ -314,6 +337,8 @@ fn map_mangadex(manga_data: MangaData) -> Manga {
         description: Some(manga_description),
         tags: Some(manga_tags),
         rating: Some(manga_data.attributes.content_rating.clone()),
+        status: Some(manga_data.attributes.status.clone()),
+        people: credits(&manga_data.relationships),
         monitored: None,
         check_interval_secs: None,
         discover_interval_secs: None,
@@ -334,8 +359,9 @@ pub async fn get_chapters(
         let resp = client
             .get(&url)
             .query(&[
-                ("limit", &limit.to_string()),
-                ("offset", &offset.to_string()),
+                ("limit", limit.to_string().as_str()),
+                ("offset", offset.to_string().as_str()),
+                ("includes[]", "scanlation_group"),
             ])
             .send()
             .await?;
@@ -345,19 +371,42 @@ pub async fn get_chapters(
         if arr.is_empty() {
             break;
         }
-        for chapter_data in arr {
-            out.push(Chapter {
-                id: 0,
-                manga_source_data_id: 0,
-                chapter_number: chapter_data["attributes"]["chapter"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string(),
-                url: chapter_data["id"].as_str().unwrap_or_default().to_string(),
-                scraped: false,
-            });
-        }
+        out.extend(arr.iter().map(chapter_from_feed));
         offset += limit;
     }
     Ok(out)
 }
+
+/// A chapter from the `/manga/{id}/feed` endpoint, with its attributes and the
+/// name of the first scanlation group (requires `includes[]=scanlation_group`).
+fn chapter_from_feed(chapter_data: &serde_json::Value) -> Chapter {
+    let attrs = &chapter_data["attributes"];
+    let text = |v: &serde_json::Value| {
+        v.as_str()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    let scanlation_group = chapter_data["relationships"]
+        .as_array()
+        .and_then(|rels| {
+            rels.iter()
+                .find(|r| r["type"] == "scanlation_group")
+                .and_then(|r| text(&r["attributes"]["name"]))
+        });
+    Chapter {
+        chapter_number: attrs["chapter"].as_str().unwrap_or_default().to_string(),
+        url: chapter_data["id"].as_str().unwrap_or_default().to_string(),
+        number: attrs["chapter"].as_str().and_then(|c| c.parse().ok()),
+        volume: text(&attrs["volume"]),
+        title: text(&attrs["title"]),
+        language: text(&attrs["translatedLanguage"]),
+        scanlation_group,
+        published_at: attrs["publishAt"]
+            .as_str()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.timestamp()),
+        page_count: attrs["pages"].as_i64().map(|p| p as i32),
+        ..Default::default()
+    }
+}
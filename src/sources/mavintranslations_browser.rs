@@ -105,6 +105,8 @@ pub fn search_manga_with_urls_browser(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             url,
                         ));
@@ -185,6 +187,7 @@ pub fn get_chapters_browser(
                             chapter_number,
                             url: url.clone(),
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
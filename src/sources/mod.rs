@@ -93,3 +93,8 @@ pub mod wp_manga;
 
 // Browser utilities
 pub mod browser_utils;
+
+// Unified source catalogue, trait, registry and page extraction
+pub mod catalogue;
+pub mod pages;
+pub mod registry;
@@ -76,6 +76,8 @@ pub async fn search_manga_with_urls(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             url,
                         ));
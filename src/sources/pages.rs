@@ -0,0 +1,407 @@
+//! Page-image extraction shared by [`MangaSource::get_pages`](super::registry::MangaSource::get_pages).
+//!
+//! Each extractor returns absolute image URLs in reading order; [`resolve_pages`]
+//! turns them into [`PageRef`]s carrying the headers needed to fetch each image.
+//! Fetching the images and packaging them is left to the caller (see `scraper.rs`).
+
+use super::registry::{self, SourceError};
+use crate::scraper::AtHomeServer;
+use regex::Regex;
+use reqwest::{Client, Url};
+use scraper::{Html, Selector};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const USER_AGENT: &str = "rust_manga_scraper/0.1.0";
+/// Image CDNs of browser-only sources reject non-browser user agents.
+const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36";
+
+/// One chapter page: where to fetch it and which request headers to send.
+#[derive(Debug, Clone, Serialize)]
+pub struct PageRef {
+    pub url: String,
+    pub referer: String,
+    /// Extra request headers besides `Referer` (currently `User-Agent`).
+    pub headers: BTreeMap<String, String>,
+    /// 1-based position in reading order.
+    pub index: usize,
+}
+
+/// Resolve a chapter's pages through its registered source. Sources flagged
+/// `requires_browser` are rendered in the headless browser first; unknown
+/// source IDs fall back to the generic extractor.
+pub async fn resolve_pages(
+    client: &Client,
+    source_id: i32,
+    chapter_url: &str,
+) -> Result<Vec<PageRef>, SourceError> {
+    let (urls, referer, user_agent) = match registry::get(source_id) {
+        Some(source) if source.capabilities().requires_browser => {
+            let html = crate::scraper::fetch_html_with_browser(chapter_url).await?;
+            (
+                generic_pages(&html, chapter_url),
+                source.page_referer(chapter_url),
+                BROWSER_USER_AGENT,
+            )
+        }
+        Some(source) => (
+            source.get_pages(client, chapter_url).await?,
+            source.page_referer(chapter_url),
+            USER_AGENT,
+        ),
+        None => {
+            let html = fetch_html(client, chapter_url).await?;
+            (
+                generic_pages(&html, chapter_url),
+                origin_of(chapter_url).unwrap_or_else(|| chapter_url.to_string()),
+                USER_AGENT,
+            )
+        }
+    };
+    Ok(page_refs(urls, &referer, user_agent))
+}
+
+/// When a chapter's pages were resolved, and the pages
+type CachedPages = (Instant, Arc<Vec<PageRef>>);
+
+/// Resolved page lists by chapter ID, kept for a while so a reader fetching a
+/// chapter one page at a time (OPDS-PSE) resolves it once, not once per page.
+/// Image URLs from most sources expire, hence the time limit.
+pub struct PageCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<i32, CachedPages>>,
+}
+
+impl PageCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The pages of a chapter if they were resolved less than `ttl` ago.
+    pub fn get(&self, chapter_id: i32) -> Option<Arc<Vec<PageRef>>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&chapter_id)
+            .filter(|(at, _)| at.elapsed() < self.ttl)
+            .map(|(_, pages)| pages.clone())
+    }
+
+    /// Remember a chapter's pages, dropping expired entries and, when full,
+    /// the oldest one.
+    pub fn insert(&self, chapter_id: i32, pages: Vec<PageRef>) -> Arc<Vec<PageRef>> {
+        let pages = Arc::new(pages);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        if entries.len() >= self.capacity && !entries.contains_key(&chapter_id) {
+            let oldest = entries.iter().min_by_key(|(_, (at, _))| *at).map(|(id, _)| *id);
+            if let Some(id) = oldest {
+                entries.remove(&id);
+            }
+        }
+        entries.insert(chapter_id, (Instant::now(), pages.clone()));
+        pages
+    }
+}
+
+fn page_refs(urls: Vec<String>, referer: &str, user_agent: &str) -> Vec<PageRef> {
+    urls.into_iter()
+        .enumerate()
+        .map(|(i, url)| PageRef {
+            url,
+            referer: referer.to_string(),
+            headers: BTreeMap::from([("User-Agent".to_string(), user_agent.to_string())]),
+            index: i + 1,
+        })
+        .collect()
+}
+
+/// Resolve page URLs through the MangaDex at-home API. `chapter_id` is the
+/// MangaDex chapter UUID stored in the chapter's `url` column.
+pub async fn mangadex_pages(client: &Client, chapter_id: &str) -> Result<Vec<String>, SourceError> {
+    let url = format!("https://api.mangadex.org/at-home/server/{}", chapter_id);
+    let server = client
+        .get(&url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?
+        .json::<AtHomeServer>()
+        .await?;
+    Ok(server
+        .chapter
+        .data
+        .iter()
+        .map(|page| format!("{}/data/{}/{}", server.base_url, server.chapter.hash, page))
+        .collect())
+}
+
+/// Fetch a chapter page as HTML.
+pub async fn fetch_html(client: &Client, chapter_url: &str) -> Result<String, SourceError> {
+    Ok(client
+        .get(chapter_url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?
+        .text()
+        .await?)
+}
+
+/// WP-Manga / Madara readers: `div.reading-content img`, falling back to a
+/// regex scan for direct image links.
+pub fn reader_content_pages(html: &str, chapter_url: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("div.reading-content img").unwrap();
+    let mut image_list: Vec<String> = Vec::new();
+    for element in document.select(&selector) {
+        if let Some(image_url) = element
+            .value()
+            .attr("src")
+            .or_else(|| element.value().attr("data-src"))
+        {
+            image_list.push(image_url.trim().to_string());
+        }
+    }
+    if image_list.is_empty() {
+        image_list = regex_pages(html);
+    }
+    resolve_all(image_list, chapter_url)
+}
+
+/// Kagane reader: try the reader container first, then broader selectors,
+/// keeping only absolute image links.
+pub fn kagane_pages(html: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let selectors = vec![
+        Selector::parse("div[data-reader] img").unwrap(),
+        Selector::parse("div.reading-content img").unwrap(),
+        Selector::parse("main img").unwrap(),
+    ];
+    let mut image_list: Vec<String> = Vec::new();
+    for selector in selectors {
+        for element in document.select(&selector) {
+            if let Some(image_url) = element
+                .value()
+                .attr("src")
+                .or_else(|| element.value().attr("data-src"))
+            {
+                if image_url.contains("http")
+                    && (image_url.ends_with(".jpg")
+                        || image_url.ends_with(".jpeg")
+                        || image_url.ends_with(".png"))
+                {
+                    image_list.push(image_url.to_string());
+                }
+            }
+        }
+        if !image_list.is_empty() {
+            break;
+        }
+    }
+    image_list
+}
+
+/// Generic HTML reader: common reader selectors in order, then Next.js
+/// `chapterImages` JSON, then the `"sources":[{"images":[...]}]` reader JSON,
+/// then a regex scan.
+pub fn generic_pages(html: &str, chapter_url: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let selectors = vec![
+        "div.reading-content img",
+        "div.read-content img",
+        "div.page-break img",
+        "div#readerarea img",
+        "div.reader-area img",
+        "div.chapter-content img",
+        "div.entry-content img",
+        "main img[src*='.jpg'], main img[src*='.png'], main img[src*='.webp']",
+        "img[loading='lazy']",
+    ];
+
+    let mut image_list = Vec::new();
+    for selector_str in selectors {
+        if let Ok(selector) = Selector::parse(selector_str) {
+            for element in document.select(&selector) {
+                if let Some(image_url) = element
+                    .value()
+                    .attr("src")
+                    .or_else(|| element.value().attr("data-src"))
+                    .or_else(|| element.value().attr("data-lazy-src"))
+                {
+                    // Filter out obviously non-chapter images
+                    let url_lower = image_url.to_lowercase();
+                    if (url_lower.contains(".jpg")
+                        || url_lower.contains(".jpeg")
+                        || url_lower.contains(".png")
+                        || url_lower.contains(".webp"))
+                        && !url_lower.contains("logo")
+                        && !url_lower.contains("icon")
+                        && !url_lower.contains("avatar")
+                        && !url_lower.contains("banner")
+                    {
+                        image_list.push(image_url.trim().to_string());
+                    }
+                }
+            }
+            if !image_list.is_empty() {
+                break;
+            }
+        }
+    }
+
+    if image_list.is_empty() {
+        image_list = next_data_pages(html);
+    }
+    if image_list.is_empty() {
+        image_list = sources_json_pages(html);
+    }
+    if image_list.is_empty() {
+        image_list = regex_pages(html);
+    }
+    resolve_all(image_list, chapter_url)
+}
+
+/// Next.js pages embed `"chapterImages":[{"url":...}]` in a script tag.
+fn next_data_pages(html: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(json_start) = html.find("\"chapterImages\":[") {
+        if let Some(json_end) = html[json_start..].find(']') {
+            let json_str = &html[json_start..json_start + json_end + 1];
+            if let Some(array_start) = json_str.find('[') {
+                if let Ok(serde_json::Value::Array(images)) =
+                    serde_json::from_str::<serde_json::Value>(&json_str[array_start..])
+                {
+                    for img in images {
+                        if let Some(url) = img.get("url").and_then(|u| u.as_str()) {
+                            out.push(url.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// MangaStream-style readers (RizzComic, Rokari, ...) embed
+/// `"sources":[{"images":[...]}]` in a `ts_reader.run(...)` call.
+fn sources_json_pages(html: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(sources_start) = html.find("\"sources\":[") {
+        let search_range = &html[sources_start..sources_start + (html.len() - sources_start).min(50000)];
+        if let Some(array_end) = search_range.rfind(']') {
+            let json_str = &html[sources_start..sources_start + array_end + 1];
+            if let Some(array_start) = json_str.find('[') {
+                if let Ok(serde_json::Value::Array(sources)) =
+                    serde_json::from_str::<serde_json::Value>(&json_str[array_start..])
+                {
+                    for source in sources {
+                        if let Some(images) = source.get("images").and_then(|i| i.as_array()) {
+                            out.extend(images.iter().filter_map(|u| u.as_str()).map(String::from));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+fn regex_pages(html: &str) -> Vec<String> {
+    let re = Regex::new(r#"https?://[^"'\s>]+\.(?:jpg|jpeg|png)"#).unwrap();
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for cap in re.captures_iter(html) {
+        let url = cap.get(0).unwrap().as_str().to_string();
+        if seen.insert(url.clone()) {
+            out.push(url);
+        }
+    }
+    out
+}
+
+fn resolve_all(image_list: Vec<String>, chapter_url: &str) -> Vec<String> {
+    match Url::parse(chapter_url) {
+        Ok(base) => image_list
+            .into_iter()
+            .map(|src| base.join(&src).map(|u| u.to_string()).unwrap_or(src))
+            .collect(),
+        Err(_) => image_list,
+    }
+}
+
+/// `scheme://host` of a URL, used as the Referer for page requests.
+pub fn origin_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| Some(format!("{}://{}", u.scheme(), u.host_str()?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_content_pages_resolves_relative() {
+        let html = r#"<div class="reading-content"><img src="/img/1.jpg"><img data-src="https://cdn.example.com/2.png"></div>"#;
+        let pages = reader_content_pages(html, "https://example.com/series/a/chapter-1/");
+        assert_eq!(
+            pages,
+            vec![
+                "https://example.com/img/1.jpg".to_string(),
+                "https://cdn.example.com/2.png".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_pages_next_data() {
+        let html =
+            r#"<script>{"chapterImages":[{"url":"https://cdn.example.com/a.webp"}]}</script>"#;
+        let pages = generic_pages(html, "https://example.com/c/1");
+        assert_eq!(pages, vec!["https://cdn.example.com/a.webp".to_string()]);
+    }
+
+    #[test]
+    fn test_generic_pages_sources_json() {
+        let html = r#"<script>ts_reader.run({"sources":[{"source":"Server 1","images":["https://cdn.example.com/1.jpg","https://cdn.example.com/2.jpg"]}]});</script>"#;
+        let pages = generic_pages(html, "https://example.com/c/1");
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1], "https://cdn.example.com/2.jpg");
+    }
+
+    #[test]
+    fn test_page_refs_are_numbered_in_order() {
+        let refs = page_refs(
+            vec!["https://a/1.jpg".to_string(), "https://a/2.jpg".to_string()],
+            "https://a",
+            USER_AGENT,
+        );
+        assert_eq!(refs[0].index, 1);
+        assert_eq!(refs[1].index, 2);
+        assert_eq!(refs[1].referer, "https://a");
+        assert_eq!(refs[0].headers["User-Agent"], USER_AGENT);
+    }
+
+    #[test]
+    fn test_page_cache() {
+        let pages = |n: usize| page_refs(vec!["https://a/1.jpg".to_string(); n], "https://a", USER_AGENT);
+        let cache = PageCache::new(Duration::from_secs(60), 2);
+        assert!(cache.get(1).is_none());
+        cache.insert(1, pages(1));
+        cache.insert(2, pages(2));
+        assert_eq!(cache.get(2).map(|p| p.len()), Some(2));
+        cache.insert(3, pages(3));
+        assert!(cache.get(1).is_none(), "oldest entry is evicted when full");
+        assert!(cache.get(2).is_some() && cache.get(3).is_some());
+
+        let expired = PageCache::new(Duration::ZERO, 2);
+        expired.insert(1, pages(1));
+        assert!(expired.get(1).is_none());
+    }
+}
@@ -0,0 +1,466 @@
+//! Unified source interface and registry.
+//!
+//! Every module under `sources/` is exposed through the [`MangaSource`] trait and
+//! registered here by its numeric source ID. The scheduler, crawler, downloader
+//! and HTTP routes look sources up with [`get`] / [`find`] instead of matching on
+//! IDs themselves.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use rust_manga_scraper::sources::registry;
+//! # async fn run(client: &reqwest::Client) {
+//! if let Some(source) = registry::find("firescans") {
+//!     let results = source.search(client, "solo").await.unwrap_or_default();
+//!     println!("{}: {} results", source.name(), results.len());
+//! }
+//! # }
+//! ```
+
+use super::catalogue::{self, SourceEntry};
+use super::pages;
+use crate::models::{Chapter, Manga, MangaSourceData};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SourceError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<Box<dyn std::error::Error>> for SourceError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        SourceError::Other(e.to_string())
+    }
+}
+
+/// What a source can do. Callers check these instead of hard-coding ID lists.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SourceCapabilities {
+    /// Title search (`search`).
+    pub search: bool,
+    /// Full catalogue listing used by the crawler (`list_all`).
+    pub list_all: bool,
+    /// Chapter lists (`get_chapters`).
+    pub chapters: bool,
+    /// Page image URLs for downloads (`get_pages`).
+    pub pages: bool,
+    /// Chapter pages sit behind Cloudflare and must be fetched with a headless browser.
+    pub requires_browser: bool,
+}
+
+impl SourceCapabilities {
+    /// Derive capabilities from the catalogue flags: metadata-only sources host
+    /// no chapters, and only crawlable sources are walked by the full crawl.
+    pub fn of(entry: &SourceEntry) -> Self {
+        let reader = !entry.flags.metadata_only;
+        Self {
+            search: true,
+            list_all: entry.flags.crawl,
+            chapters: reader,
+            pages: reader,
+            requires_browser: entry.flags.requires_browser,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+pub trait MangaSource: Send + Sync {
+    fn id(&self) -> i32;
+
+    /// Catalogue row for this source; every registered ID must have one.
+    fn entry(&self) -> &'static SourceEntry {
+        catalogue::get(self.id()).expect("registered source missing from catalogue")
+    }
+    fn name(&self) -> &'static str {
+        self.entry().name
+    }
+    fn base_url(&self) -> &'static str {
+        self.entry().base_url
+    }
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::of(self.entry())
+    }
+
+    /// Search by title; an empty title returns the source's default listing.
+    async fn search(
+        &self,
+        client: &Client,
+        title: &str,
+    ) -> Result<Vec<(Manga, String)>, SourceError>;
+
+    /// Everything the source lists, for the full crawl.
+    async fn list_all(&self, client: &Client) -> Result<Vec<(Manga, String)>, SourceError> {
+        self.search(client, "").await
+    }
+
+    /// First page of the catalogue, used by quick imports.
+    async fn first_page(&self, client: &Client) -> Result<Vec<(Manga, String)>, SourceError> {
+        self.search(client, "").await
+    }
+
+    async fn get_chapters(
+        &self,
+        client: &Client,
+        series: &MangaSourceData,
+    ) -> Result<Vec<Chapter>, SourceError>;
+
+    /// Absolute page image URLs for a chapter, in reading order.
+    async fn get_pages(
+        &self,
+        client: &Client,
+        chapter_url: &str,
+    ) -> Result<Vec<String>, SourceError> {
+        let html = pages::fetch_html(client, chapter_url).await?;
+        Ok(pages::generic_pages(&html, chapter_url))
+    }
+
+    /// Referer to send with page image requests.
+    fn page_referer(&self, chapter_url: &str) -> String {
+        pages::origin_of(chapter_url).unwrap_or_else(|| self.base_url().to_string())
+    }
+
+    /// Other providers linked from a series page (aggregators only).
+    async fn provider_links(&self, _client: &Client, _series_url: &str) -> Vec<(i32, String)> {
+        Vec::new()
+    }
+
+    /// Build the `manga_source_data` row for a search result.
+    fn source_data(&self, manga_id: &str, series_url: &str) -> MangaSourceData {
+        MangaSourceData {
+            manga_id: manga_id.to_string(),
+            source_id: self.id(),
+            source_manga_id: series_url.to_string(),
+            source_manga_url: series_url.to_string(),
+        }
+    }
+}
+
+/// How a source lists the first page of its catalogue: the module's own
+/// `search_manga_first_page`, the shared WP-Manga listing, or an empty search.
+macro_rules! first_page {
+    (module, $module:ident, $base:expr, $client:ident) => {
+        super::$module::search_manga_first_page($client).await
+    };
+    (wp, $module:ident, $base:expr, $client:ident) => {
+        super::wp_manga::search_manga_first_page($client, $base).await
+    };
+    (search, $module:ident, $base:expr, $client:ident) => {
+        super::$module::search_manga_with_urls($client, "").await
+    };
+}
+
+/// Adapters for modules exposing `search_manga_with_urls(client, title)` and
+/// `get_chapters(client, series_url)`. Name, base URL and capabilities come from
+/// the [`catalogue`]; each entry names the page extractor from [`pages`] (WP-Manga readers use `reader_content_pages`, everything else the
+/// wider `generic_pages` selector set) and its [`first_page!`] listing.
+macro_rules! scraped_sources {
+    ($($ty:ident => ($id:expr, $module:ident, $pages:ident, $listing:ident),)*) => {
+        $(
+            struct $ty;
+
+            #[async_trait(?Send)]
+            impl MangaSource for $ty {
+                fn id(&self) -> i32 {
+                    $id
+                }
+                async fn search(
+                    &self,
+                    client: &Client,
+                    title: &str,
+                ) -> Result<Vec<(Manga, String)>, SourceError> {
+                    Ok(super::$module::search_manga_with_urls(client, title).await?)
+                }
+                async fn first_page(
+                    &self,
+                    client: &Client,
+                ) -> Result<Vec<(Manga, String)>, SourceError> {
+                    Ok(first_page!($listing, $module, self.base_url(), client)?)
+                }
+                async fn get_chapters(
+                    &self,
+                    client: &Client,
+                    series: &MangaSourceData,
+                ) -> Result<Vec<Chapter>, SourceError> {
+                    Ok(super::$module::get_chapters(client, &series.source_manga_url).await?)
+                }
+                async fn get_pages(
+                    &self,
+                    client: &Client,
+                    chapter_url: &str,
+                ) -> Result<Vec<String>, SourceError> {
+                    let html = pages::fetch_html(client, chapter_url).await?;
+                    Ok(pages::$pages(&html, chapter_url))
+                }
+            }
+        )*
+
+        fn scraped_source_list() -> Vec<Box<dyn MangaSource>> {
+            vec![$(Box::new($ty)),*]
+        }
+    };
+}
+
+scraped_sources! {
+    FireScans => (2, firescans, reader_content_pages, module),
+    RizzComic => (3, rizzcomic, reader_content_pages, module),
+    MyAnimeList => (4, myanimelist, generic_pages, search),
+    AniList => (5, anilist, generic_pages, search),
+    DrakeComic => (6, drakecomic, reader_content_pages, wp),
+    Asmotoon => (8, asmotoon, reader_content_pages, wp),
+    ResetScans => (9, reset_scans, reader_content_pages, wp),
+    AsuraScans => (11, asurascans, generic_pages, wp),
+    BookLive => (12, booklive, generic_pages, search),
+    Comikey => (13, comikey, generic_pages, search),
+    DenpaBooks => (14, denpa_books, generic_pages, search),
+    DarkHorse => (15, dark_horse_comics, generic_pages, search),
+    DayComics => (16, daycomics, generic_pages, search),
+    Fakku => (17, fakku, generic_pages, search),
+    FlameComics => (18, flamecomics, generic_pages, search),
+    GrimScans => (19, grimscans, generic_pages, wp),
+    HiveToons => (20, hivetoons, generic_pages, wp),
+    Inkr => (21, inkr_comics, generic_pages, search),
+    Irodori => (22, irodori_comics, generic_pages, search),
+    JNovelClub => (23, jnovel_club, generic_pages, search),
+    Kana => (24, kana, generic_pages, search),
+    KenScans => (25, kenscans, generic_pages, wp),
+    Kodansha => (26, kodansha_comics, generic_pages, search),
+    KodokuStudio => (27, kodoku_studio, generic_pages, search),
+    Lezhin => (28, lezhin, generic_pages, search),
+    LunaToons => (29, lunatoons, generic_pages, search),
+    MadaraScans => (30, madarascans, generic_pages, wp),
+    Manhuaus => (31, manhuaus, generic_pages, wp),
+    Manta => (32, manta, generic_pages, search),
+    MediBang => (33, medibang, generic_pages, search),
+    NyxScans => (34, nyxscans, generic_pages, wp),
+    OnePeace => (35, one_peace_books, generic_pages, search),
+    Others => (36, others, generic_pages, search),
+    PocketComics => (37, pocket_comics, generic_pages, search),
+    QiScans => (38, qiscans, generic_pages, wp),
+    RizzFables => (39, rizzfables, generic_pages, wp),
+    RokariComics => (40, rokaricomics, generic_pages, wp),
+    SevenSeas => (41, seven_seas, generic_pages, search),
+    Shueisha => (42, shueisha, generic_pages, search),
+    SirenScans => (43, sirenscans, generic_pages, wp),
+    SquareEnix => (44, square_enix_manga, generic_pages, search),
+    StoneScape => (45, stonescape, generic_pages, wp),
+    Tokyopop => (46, tokyopop, generic_pages, search),
+    Tapas => (47, tapas, generic_pages, search),
+    Tappytoon => (48, tappytoon, generic_pages, search),
+    TempleScan => (49, temple_scan, reader_content_pages, wp),
+    ThunderScans => (50, thunderscans, reader_content_pages, wp),
+    Titan => (51, titan_manga, generic_pages, search),
+    Toomics => (52, toomics, generic_pages, search),
+    Udon => (53, udon_entertainment, generic_pages, search),
+    VastVisual => (54, vast_visual, generic_pages, search),
+    Viz => (55, viz_media, generic_pages, search),
+    VortexScans => (56, vortexscans, generic_pages, wp),
+    Webcomics => (57, webcomics, generic_pages, search),
+    Webtoon => (58, webtoon, generic_pages, search),
+    WitchScans => (59, witchscans, generic_pages, wp),
+    YenPress => (60, yen_press, generic_pages, search),
+    MavinTranslations => (61, mavintranslations, generic_pages, search),
+}
+
+/// MangaDex goes through its JSON API: series are keyed by MangaDex UUID and
+/// pages come from the at-home server.
+struct MangaDex;
+
+const MANGADEX_TITLE_URL: &str = "https://mangadex.org/title/";
+
+#[async_trait(?Send)]
+impl MangaSource for MangaDex {
+    fn id(&self) -> i32 {
+        1
+    }
+    async fn search(
+        &self,
+        client: &Client,
+        title: &str,
+    ) -> Result<Vec<(Manga, String)>, SourceError> {
+        let list = super::mangadex::search_manga(client, title, super::mangadex::BASE_URL).await?;
+        Ok(with_title_urls(list))
+    }
+    async fn list_all(&self, client: &Client) -> Result<Vec<(Manga, String)>, SourceError> {
+        let list = super::mangadex::search_all_manga(client, super::mangadex::BASE_URL).await?;
+        Ok(with_title_urls(list))
+    }
+    async fn get_chapters(
+        &self,
+        client: &Client,
+        series: &MangaSourceData,
+    ) -> Result<Vec<Chapter>, SourceError> {
+        Ok(super::mangadex::get_chapters(client, &series.source_manga_id).await?)
+    }
+    async fn get_pages(
+        &self,
+        client: &Client,
+        chapter_url: &str,
+    ) -> Result<Vec<String>, SourceError> {
+        pages::mangadex_pages(client, chapter_url).await
+    }
+    fn page_referer(&self, _chapter_url: &str) -> String {
+        "https://mangadex.org/".to_string()
+    }
+    fn source_data(&self, manga_id: &str, series_url: &str) -> MangaSourceData {
+        let mangadex_id = series_url
+            .strip_prefix(MANGADEX_TITLE_URL)
+            .unwrap_or(series_url);
+        MangaSourceData {
+            manga_id: manga_id.to_string(),
+            source_id: self.id(),
+            source_manga_id: mangadex_id.to_string(),
+            source_manga_url: format!("{}{}", MANGADEX_TITLE_URL, mangadex_id),
+        }
+    }
+}
+
+fn with_title_urls(list: Vec<Manga>) -> Vec<(Manga, String)> {
+    list.into_iter()
+        .map(|m| {
+            let url = format!("{}{}", MANGADEX_TITLE_URL, m.id);
+            (m, url)
+        })
+        .collect()
+}
+
+/// KDT Novels lists series but has no chapter scraping yet.
+struct KdtNovels;
+
+#[async_trait(?Send)]
+impl MangaSource for KdtNovels {
+    fn id(&self) -> i32 {
+        7
+    }
+    async fn search(
+        &self,
+        client: &Client,
+        title: &str,
+    ) -> Result<Vec<(Manga, String)>, SourceError> {
+        Ok(super::kdtnovels::search_manga_with_urls(client, title).await?)
+    }
+    async fn get_chapters(
+        &self,
+        _client: &Client,
+        _series: &MangaSourceData,
+    ) -> Result<Vec<Chapter>, SourceError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Kagane is an aggregator: the full listing is slow, so it is time-boxed, and
+/// series pages link out to other providers.
+struct Kagane;
+
+#[async_trait(?Send)]
+impl MangaSource for Kagane {
+    fn id(&self) -> i32 {
+        10
+    }
+    async fn search(
+        &self,
+        client: &Client,
+        title: &str,
+    ) -> Result<Vec<(Manga, String)>, SourceError> {
+        Ok(super::kagane::search_manga_with_urls(client, title).await?)
+    }
+    async fn list_all(&self, client: &Client) -> Result<Vec<(Manga, String)>, SourceError> {
+        match tokio::time::timeout(
+            Duration::from_secs(15),
+            super::kagane::search_all_series_with_urls(client),
+        )
+        .await
+        {
+            Ok(res) => Ok(res?),
+            Err(_) => Err(SourceError::Other("kagane listing timed out".to_string())),
+        }
+    }
+    async fn first_page(&self, client: &Client) -> Result<Vec<(Manga, String)>, SourceError> {
+        Ok(super::kagane::search_all_series_with_urls(client).await?)
+    }
+    async fn get_chapters(
+        &self,
+        client: &Client,
+        series: &MangaSourceData,
+    ) -> Result<Vec<Chapter>, SourceError> {
+        Ok(super::kagane::get_chapters(client, &series.source_manga_url).await?)
+    }
+    async fn get_pages(
+        &self,
+        client: &Client,
+        chapter_url: &str,
+    ) -> Result<Vec<String>, SourceError> {
+        let html = pages::fetch_html(client, chapter_url).await?;
+        Ok(pages::kagane_pages(&html))
+    }
+    async fn provider_links(&self, client: &Client, series_url: &str) -> Vec<(i32, String)> {
+        super::kagane::extract_provider_links(client, series_url).await
+    }
+}
+
+static REGISTRY: Lazy<HashMap<i32, Box<dyn MangaSource>>> = Lazy::new(|| {
+    let mut sources: Vec<Box<dyn MangaSource>> =
+        vec![Box::new(MangaDex), Box::new(KdtNovels), Box::new(Kagane)];
+    sources.extend(scraped_source_list());
+    sources.into_iter().map(|s| (s.id(), s)).collect()
+});
+
+/// Look up a source by numeric ID.
+pub fn get(id: i32) -> Option<&'static dyn MangaSource> {
+    REGISTRY.get(&id).map(|s| s.as_ref())
+}
+
+/// All registered sources, ordered by ID.
+pub fn all() -> Vec<&'static dyn MangaSource> {
+    let mut v: Vec<&'static dyn MangaSource> = REGISTRY.values().map(|s| s.as_ref()).collect();
+    v.sort_by_key(|s| s.id());
+    v
+}
+
+/// Look up a source by numeric ID, display name or one of the URL-style aliases
+/// accepted by the HTTP API (`firescans`, `qi-scans`, `mal`, ...). See
+/// [`catalogue::find`].
+pub fn find(name: &str) -> Option<&'static dyn MangaSource> {
+    catalogue::find(name).and_then(|e| get(e.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_ids_are_consistent() {
+        for source in all() {
+            assert_eq!(get(source.id()).unwrap().id(), source.id());
+        }
+        assert_eq!(all().len(), catalogue::CATALOGUE.len());
+        for entry in catalogue::CATALOGUE {
+            assert!(get(entry.id).is_some(), "{} is not registered", entry.name);
+        }
+    }
+
+    #[test]
+    fn test_find_by_alias_and_name() {
+        assert_eq!(find("mangadex").unwrap().id(), 1);
+        assert_eq!(find("qi-scans").unwrap().id(), 38);
+        assert_eq!(find("Seven Seas").unwrap().id(), 41);
+        assert_eq!(find("49").unwrap().name(), "TempleScan");
+        assert!(find("nope").is_none());
+    }
+
+    #[test]
+    fn test_mangadex_source_data_uses_uuid() {
+        let msd = get(1)
+            .unwrap()
+            .source_data("m1", "https://mangadex.org/title/abc-123");
+        assert_eq!(msd.source_manga_id, "abc-123");
+        assert_eq!(msd.source_manga_url, "https://mangadex.org/title/abc-123");
+    }
+}
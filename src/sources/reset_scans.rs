@@ -40,6 +40,8 @@ pub async fn search_manga_with_urls(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         series_url,
                     ));
@@ -83,6 +85,8 @@ pub async fn search_manga_with_urls(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         series_url,
                     ));
@@ -144,6 +148,7 @@ pub async fn get_chapters(
                         chapter_number: label,
                         url: href.to_string(),
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -201,6 +206,7 @@ pub async fn get_chapters(
                         chapter_number: label,
                         url: href.to_string(),
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
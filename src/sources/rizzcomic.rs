@@ -43,6 +43,8 @@ pub async fn search_manga(client: &Client, title: &str) -> Result<Vec<Manga>, re
             discover_interval_secs: None,
             last_chapter_check: None,
             last_discover_check: None,
+            status: None,
+            people: Vec::new(),
         };
         manga.alt_titles = Some(format!("::URL::{}", url));
         manga_list.push(manga);
@@ -130,6 +132,8 @@ pub async fn search_manga_with_urls(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             series_url,
                         ));
@@ -214,6 +218,8 @@ pub async fn search_manga_first_page(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         series_url,
                     ));
@@ -277,6 +283,7 @@ pub async fn get_chapters(
                         chapter_number: chapter_title.clone(),
                         url: abs,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -336,6 +343,7 @@ pub async fn get_chapters(
                             chapter_number: t,
                             url: href.to_string(),
                             scraped: false,
+                            ..Default::default()
                         });
                         found_any = true;
                     }
@@ -369,6 +377,7 @@ pub async fn get_chapters(
                                             chapter_number: t,
                                             url: href.to_string(),
                                             scraped: false,
+                                            ..Default::default()
                                         });
                                         got = true;
                                     }
@@ -395,6 +404,7 @@ pub async fn get_chapters(
                                 chapter_number: t,
                                 url: href.to_string(),
                                 scraped: false,
+                                ..Default::default()
                             });
                         }
                     }
@@ -415,7 +425,7 @@ pub async fn get_chapters(
                             for a in doc.select(&sel) {
                                 if let Some(href) = a.value().attr("href") {
                                     let t = a.text().collect::<String>().trim().to_string();
-                                    chapters.push(Chapter { id:0, manga_source_data_id:0, chapter_number: t, url: href.to_string(), scraped:false });
+                                    chapters.push(Chapter { id:0, manga_source_data_id:0, chapter_number: t, url: href.to_string(), scraped:false, ..Default::default() });
                                     any = true;
                                 }
                             }
@@ -85,6 +85,8 @@ pub async fn search_manga_with_urls(
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         url,
                     ));
@@ -141,6 +143,7 @@ pub async fn get_chapters(
                         chapter_number: chapter_num,
                         url,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
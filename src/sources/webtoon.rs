@@ -71,6 +71,8 @@ pub async fn search_manga_with_urls(
                                     discover_interval_secs: None,
                                     last_chapter_check: None,
                                     last_discover_check: None,
+                                    status: None,
+                                    people: Vec::new(),
                                 },
                                 url,
                             ));
@@ -132,6 +134,7 @@ pub async fn get_chapters(
                             chapter_number: title,
                             url,
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -279,6 +279,8 @@ pub async fn search_manga_with_urls_base(
                                     discover_interval_secs: None,
                                     last_chapter_check: None,
                                     last_discover_check: None,
+                                    status: None,
+                                    people: Vec::new(),
                                 },
                                 series_url,
                             ));
@@ -389,6 +391,8 @@ pub async fn search_manga_first_page(
                                         discover_interval_secs: None,
                                         last_chapter_check: None,
                                         last_discover_check: None,
+                                        status: None,
+                                        people: Vec::new(),
                                     },
                                     series_url,
                                 ));
@@ -456,6 +460,8 @@ pub async fn search_manga_first_page(
                                 discover_interval_secs: None,
                                 last_chapter_check: None,
                                 last_discover_check: None,
+                                status: None,
+                                people: Vec::new(),
                             },
                             series_url,
                         ));
@@ -552,6 +558,7 @@ pub async fn get_chapters_base(
                         chapter_number: label,
                         url: abs,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -594,6 +601,7 @@ pub async fn get_chapters_base(
                         chapter_number: format!("Chapter {}", n),
                         url: chapter_url,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -654,6 +662,7 @@ pub async fn get_chapters_base(
                         chapter_number: label,
                         url: abs,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -721,6 +730,7 @@ pub async fn get_chapters_base(
                                 chapter_number: label,
                                 url: abs,
                                 scraped: false,
+                                ..Default::default()
                             });
                         }
                     }
@@ -57,6 +57,7 @@ pub mod asmotoon_browser {
                         chapter_number: chapter_title,
                         url: href.to_string(),
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -96,6 +97,8 @@ pub mod asmotoon_browser {
                     discover_interval_secs: None,
                     last_chapter_check: None,
                     last_discover_check: None,
+                    status: None,
+                    people: Vec::new(),
                 };
 
                 if !series_url.is_empty() {
@@ -196,6 +199,7 @@ pub mod wp_manga_browser {
                             chapter_number: chapter_title,
                             url,
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -264,6 +268,8 @@ pub mod wp_manga_browser {
                                     discover_interval_secs: None,
                                     last_chapter_check: None,
                                     last_discover_check: None,
+                                    status: None,
+                                    people: Vec::new(),
                                 },
                                 series_url,
                             ));
@@ -416,6 +422,7 @@ pub mod drakecomic_browser {
                             chapter_number: chapter_title,
                             url,
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -496,6 +503,7 @@ pub mod madarascans_browser {
                             chapter_number: chapter_title,
                             url,
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -589,6 +597,7 @@ pub mod rizzfables_browser {
                             chapter_number: chapter_title,
                             url,
                             scraped: false,
+                            ..Default::default()
                         });
                     }
                 }
@@ -755,6 +764,8 @@ pub mod kagane_browser {
                             discover_interval_secs: None,
                             last_chapter_check: None,
                             last_discover_check: None,
+                            status: None,
+                            people: Vec::new(),
                         },
                         series_url,
                     ));
@@ -804,6 +815,7 @@ pub mod kagane_browser {
                         },
                         url,
                         scraped: false,
+                        ..Default::default()
                     });
                 }
             }
@@ -0,0 +1,377 @@
+//! Storage backends behind one repository trait.
+//!
+//! Every database operation the server performs is a method of [`Storage`].
+//! [`postgres::PgStorage`] implements it over `pg_db.rs` (tokio-postgres) and
+//! [`sqlite::SqliteStorage`] over `db.rs` (rusqlite), so handlers and
+//! background jobs only ever see `AppState::storage`. The backend is chosen
+//! with `[database] backend` in `config.toml`:
+//!
+//! ```toml
+//! [database]
+//! backend = "sqlite"      # or "postgres" (default)
+//! sqlite_path = "manga.db"
+//! ```
+//!
+//! `tests/storage_tests.rs` runs one suite against both backends.
+
+pub mod postgres;
+pub mod sqlite;
+
+use crate::config::{Backend, DatabaseConfig};
+use crate::auth::Scope;
+use crate::models::{
+    ApiKey, Chapter, ChapterFile, ChapterRead, ChapterWithSource, ContinueReading, Credit,
+    DownloadJob, LibraryEntry, Manga, MangaAuditEntry, MangaSourceData, MergeProposal, Person, Tag,
+    User,
+};
+use crate::reading::LibraryStatus;
+use crate::search::MangaSearch;
+use crate::tags::TagRef;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("postgres: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+    #[error("postgres pool: {0}")]
+    Pool(#[from] deadpool_postgres::PoolError),
+    #[error("sqlite: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("migration: {0}")]
+    Migration(#[from] crate::migrations::MigrationError),
+    #[error("storage task failed: {0}")]
+    Task(String),
+}
+
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+/// Open the configured backend. Nothing is read or written until the first
+/// call, so run [`Storage::migrate`] before anything else.
+pub fn open(config: &DatabaseConfig) -> Result<Arc<dyn Storage>> {
+    Ok(match config.backend {
+        Backend::Postgres => Arc::new(postgres::PgStorage::new(crate::pg_db::create_pool(config))),
+        Backend::Sqlite => Arc::new(sqlite::SqliteStorage::open(&config.sqlite_path)?),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct PerSourceCounts {
+    pub source_id: i32,
+    pub source_name: String,
+    pub manga: i64,
+    pub chapters: i64,
+}
+
+/// Row counts reported by `GET /verify/source/{source}`
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SourceStats {
+    pub manga: i64,
+    pub chapters: i64,
+    /// Manga with at least one metadata provider ID
+    pub metadata_linked: i64,
+}
+
+/// Any one chapter of a source, used to test that downloads still work
+#[derive(Debug, Clone)]
+pub struct SampleChapter {
+    pub manga_id: String,
+    pub chapter_number: String,
+    pub url: String,
+    pub series_url: String,
+}
+
+/// What the series matcher needs to know about one manga
+#[derive(Debug, Clone)]
+pub struct MatchCandidate {
+    pub id: String,
+    pub title: String,
+    pub alt_titles: Option<String>,
+    pub cover_url: Option<String>,
+    pub cover_hash: Option<i64>,
+    /// `provider:id` pairs from `provider_ids`, e.g. `mal:13`
+    pub provider_ids: Vec<String>,
+}
+
+/// Outcome of [`Storage::undo_manga_audit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoOutcome {
+    Undone,
+    NotFound,
+    AlreadyUndone,
+    /// A later merge or split touching the same manga must be undone first
+    Superseded,
+}
+
+/// External metadata providers whose IDs are stored on `manga` and in `provider_ids`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataProvider {
+    MangaBaka,
+    Mal,
+    AniList,
+}
+
+impl MetadataProvider {
+    /// Name used in the `provider_ids` table
+    pub fn name(self) -> &'static str {
+        match self {
+            MetadataProvider::MangaBaka => "mangabaka",
+            MetadataProvider::Mal => "mal",
+            MetadataProvider::AniList => "anilist",
+        }
+    }
+
+    /// `manga` column holding the ID
+    pub fn column(self) -> &'static str {
+        match self {
+            MetadataProvider::MangaBaka => "mangabaka_id",
+            MetadataProvider::Mal => "mal_id",
+            MetadataProvider::AniList => "anilist_id",
+        }
+    }
+
+    /// SQL condition for manga without an ID from this provider
+    pub(crate) fn missing_condition(self) -> &'static str {
+        match self {
+            MetadataProvider::MangaBaka => "(mangabaka_id IS NULL OR mangabaka_id = '')",
+            MetadataProvider::Mal => "(mal_id IS NULL OR mal_id = 0)",
+            MetadataProvider::AniList => "(anilist_id IS NULL OR anilist_id = 0)",
+        }
+    }
+}
+
+/// A manga's titles, for resolving provider IDs
+#[derive(Debug, Clone)]
+pub struct MangaTitles {
+    pub id: String,
+    pub title: String,
+    /// Comma-joined alt titles, empty when there are none
+    pub alt_titles: String,
+}
+
+/// The provider IDs stored on a manga row (empty / 0 when unknown)
+#[derive(Debug, Clone)]
+pub struct ProviderLinks {
+    pub manga_id: String,
+    pub mangabaka_id: String,
+    pub mal_id: i64,
+    pub anilist_id: i64,
+}
+
+/// Every repository operation, implemented by each backend. Semantics are
+/// documented on the PostgreSQL versions in `pg_db.rs`.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Bring the schema up to date; returns the migration versions applied.
+    async fn migrate(&self) -> Result<Vec<i32>>;
+
+    // Sources
+    async fn seed_sources(&self) -> Result<()>;
+    async fn check_sources_table(&self) -> Result<Vec<String>>;
+    async fn get_source_name(&self, source_id: i32) -> Result<String>;
+    async fn get_source_count(&self) -> Result<i64>;
+    async fn get_per_source_counts(&self) -> Result<Vec<PerSourceCounts>>;
+    async fn get_source_stats(&self, source_id: i32) -> Result<SourceStats>;
+    async fn get_sample_chapter(&self, source_id: i32) -> Result<Option<SampleChapter>>;
+
+    // Manga
+    /// One page of `GET /manga` results
+    async fn search_manga(&self, search: &MangaSearch) -> Result<Vec<Manga>>;
+    /// How many manga `search` matches across all pages
+    async fn count_manga_matching(&self, search: &MangaSearch) -> Result<i64>;
+    async fn get_manga_count(&self) -> Result<i64>;
+    async fn get_manga_by_id(&self, manga_id: &str) -> Result<Option<Manga>>;
+    async fn get_manga_by_source(&self, source_id: i32) -> Result<Vec<Manga>>;
+    async fn insert_manga(&self, manga: &Manga) -> Result<()>;
+    async fn set_manga_monitoring(
+        &self,
+        manga_id: &str,
+        monitored: bool,
+        check_interval_secs: Option<i64>,
+        discover_interval_secs: Option<i64>,
+    ) -> Result<()>;
+    async fn get_manga_output_format(&self, manga_id: &str) -> Result<Option<String>>;
+    async fn set_manga_output_format(&self, manga_id: &str, format: Option<&str>) -> Result<u64>;
+    async fn mark_chapter_check(&self, manga_id: &str, ts: i64) -> Result<()>;
+    async fn due_for_chapter_check(&self, now_ts: i64) -> Result<Vec<String>>;
+
+    // Source links
+    async fn insert_manga_source_data(&self, msd: &MangaSourceData) -> Result<i32>;
+    async fn get_manga_source_data_by_manga_id(
+        &self,
+        manga_id: &str,
+    ) -> Result<Vec<MangaSourceData>>;
+    async fn get_manga_source_data_id(&self, manga_id: &str, source_id: i32)
+        -> Result<Option<i32>>;
+    async fn set_source_priority(&self, manga_id: &str, source_ids: &[i32]) -> Result<u64>;
+
+    // Chapters
+    async fn get_chapter_count(&self) -> Result<i64>;
+    async fn get_chapters_by_manga_source_data_id(
+        &self,
+        manga_id: &str,
+        source_id: i32,
+    ) -> Result<Vec<Chapter>>;
+    async fn get_chapter_with_source(&self, chapter_id: i32) -> Result<Option<ChapterWithSource>>;
+    async fn set_chapter_page_count(&self, chapter_id: i32, page_count: i32) -> Result<()>;
+    /// Record a downloaded file and mark the chapter scraped
+    async fn set_chapter_file(&self, chapter_id: i32, file: &ChapterFile, now_ts: i64)
+        -> Result<()>;
+    /// Forget a chapter's file and mark it not scraped
+    async fn clear_chapter_file(&self, chapter_id: i32) -> Result<()>;
+    /// Every chapter with a recorded file, with its manga's title
+    async fn get_downloaded_chapters(&self) -> Result<Vec<(String, ChapterWithSource)>>;
+    async fn insert_chapters(&self, manga_source_data_id: i32, chapters: &[Chapter]) -> Result<()>;
+
+    // Download queue
+    async fn enqueue_download_jobs(
+        &self,
+        chapter_ids: &[i32],
+        format: Option<&str>,
+        max_attempts: i32,
+        now_ts: i64,
+    ) -> Result<u64>;
+    async fn get_unscraped_chapter_ids(&self, limit: i64) -> Result<Vec<i32>>;
+    async fn claim_download_job(&self, now_ts: i64) -> Result<Option<DownloadJob>>;
+    async fn complete_download_job(&self, job_id: i64, file_path: &str, now_ts: i64) -> Result<()>;
+    async fn fail_download_job(
+        &self,
+        job_id: i64,
+        error: &str,
+        retry_at: i64,
+        now_ts: i64,
+    ) -> Result<()>;
+    async fn requeue_running_download_jobs(&self, now_ts: i64) -> Result<u64>;
+    async fn retry_download_job(&self, job_id: i64, now_ts: i64) -> Result<bool>;
+    async fn list_download_jobs(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DownloadJob>>;
+
+    // Series matching, merges and splits
+    async fn get_match_candidates(&self) -> Result<Vec<MatchCandidate>>;
+    async fn set_cover_hash(&self, manga_id: &str, hash: i64) -> Result<()>;
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_merge_proposal(
+        &self,
+        a: (&str, &str),
+        b: (&str, &str),
+        score: f64,
+        reasons: &str,
+        automatic: bool,
+        now_ts: i64,
+    ) -> Result<Option<i32>>;
+    async fn list_merge_proposals(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MergeProposal>>;
+    async fn get_merge_proposal(&self, id: i32) -> Result<Option<MergeProposal>>;
+    async fn set_merge_proposal_state(
+        &self,
+        id: i32,
+        from: &str,
+        to: &str,
+        now_ts: i64,
+    ) -> Result<bool>;
+    async fn merge_manga(&self, target_id: &str, other_id: &str, now_ts: i64) -> Result<i32>;
+    async fn split_manga(
+        &self,
+        manga_id: &str,
+        source_ids: &[i32],
+        title: Option<&str>,
+        now_ts: i64,
+    ) -> Result<Option<(String, i32)>>;
+    async fn list_manga_audit(
+        &self,
+        manga_id: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MangaAuditEntry>>;
+    async fn undo_manga_audit(&self, id: i32, now_ts: i64) -> Result<UndoOutcome>;
+
+    // Metadata providers
+    async fn count_missing_provider(&self, provider: MetadataProvider) -> Result<i64>;
+    async fn get_manga_missing_provider(
+        &self,
+        provider: MetadataProvider,
+        limit: Option<i64>,
+    ) -> Result<Vec<MangaTitles>>;
+    async fn set_provider_id(
+        &self,
+        manga_id: &str,
+        provider: MetadataProvider,
+        provider_id: &str,
+    ) -> Result<()>;
+    async fn get_provider_links(&self) -> Result<Vec<ProviderLinks>>;
+    async fn update_manga_metadata(
+        &self,
+        manga_id: &str,
+        description: Option<&str>,
+        tags: Option<&str>,
+        rating: Option<&str>,
+    ) -> Result<()>;
+
+    // Tags
+    async fn seed_tags(&self) -> Result<()>;
+    async fn set_manga_tags(&self, manga_id: &str, tags: &[TagRef]) -> Result<()>;
+    async fn list_tags(&self, category: Option<&str>) -> Result<Vec<Tag>>;
+    async fn get_manga_without_tag_links(&self) -> Result<Vec<(String, String)>>;
+
+    // People
+    async fn set_manga_people(&self, manga_id: &str, people: &[Credit]) -> Result<()>;
+    async fn get_person(&self, person_id: i32) -> Result<Option<Person>>;
+
+    // Readers
+    /// `None` if the name is taken
+    async fn create_user(&self, name: &str, now_ts: i64) -> Result<Option<User>>;
+    async fn list_users(&self) -> Result<Vec<User>>;
+    async fn get_user_by_name(&self, name: &str) -> Result<Option<User>>;
+    /// False if there is no such manga
+    async fn set_library_entry(
+        &self,
+        user_id: i32,
+        manga_id: &str,
+        status: LibraryStatus,
+        now_ts: i64,
+    ) -> Result<bool>;
+    async fn remove_library_entry(&self, user_id: i32, manga_id: &str) -> Result<bool>;
+    async fn get_library(
+        &self,
+        user_id: i32,
+        status: Option<LibraryStatus>,
+    ) -> Result<Vec<LibraryEntry>>;
+    /// False if there is no such chapter
+    async fn set_chapter_read(
+        &self,
+        user_id: i32,
+        chapter_id: i32,
+        page: i32,
+        completed: bool,
+        now_ts: i64,
+    ) -> Result<bool>;
+    async fn clear_chapter_read(&self, user_id: i32, chapter_id: i32) -> Result<bool>;
+    async fn get_chapter_reads(&self, user_id: i32, manga_id: &str) -> Result<Vec<ChapterRead>>;
+    async fn get_continue_reading(&self, user_id: i32, limit: i64) -> Result<Vec<ContinueReading>>;
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>>;
+
+    // API keys
+    async fn create_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        prefix: &str,
+        scopes: &[Scope],
+        user_id: Option<i32>,
+        now_ts: i64,
+    ) -> Result<ApiKey>;
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>>;
+    /// False if there is no such key or it is already revoked
+    async fn revoke_api_key(&self, id: i32, now_ts: i64) -> Result<bool>;
+    /// The unrevoked key with this hash, marked as used at `now_ts`
+    async fn authenticate_api_key(&self, key_hash: &str, now_ts: i64) -> Result<Option<ApiKey>>;
+}
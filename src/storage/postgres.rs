@@ -0,0 +1,453 @@
+//! [`Storage`] over PostgreSQL: a thin wrapper around the functions in `pg_db.rs`.
+
+use super::{
+    MangaTitles, MatchCandidate, MetadataProvider, PerSourceCounts, ProviderLinks, Result,
+    SampleChapter, SourceStats, Storage, UndoOutcome,
+};
+use crate::auth::Scope;
+use crate::models::{
+    ApiKey, Chapter, ChapterFile, ChapterRead, ChapterWithSource, ContinueReading, Credit,
+    DownloadJob, LibraryEntry, Manga, MangaAuditEntry, MangaSourceData, MergeProposal, Person, Tag,
+    User,
+};
+use crate::reading::LibraryStatus;
+use crate::search::MangaSearch;
+use crate::tags::TagRef;
+use crate::{migrations, pg_db};
+use async_trait::async_trait;
+use deadpool_postgres::Pool;
+
+pub struct PgStorage {
+    pool: Pool,
+}
+
+impl PgStorage {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Storage for PgStorage {
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        Ok(migrations::run(&self.pool).await?)
+    }
+
+    async fn seed_sources(&self) -> Result<()> {
+        Ok(pg_db::seed_sources(&self.pool).await?)
+    }
+
+    async fn check_sources_table(&self) -> Result<Vec<String>> {
+        Ok(pg_db::check_sources_table(&self.pool).await?)
+    }
+
+    async fn get_source_name(&self, source_id: i32) -> Result<String> {
+        Ok(pg_db::get_source_name(&self.pool, source_id).await?)
+    }
+
+    async fn get_source_count(&self) -> Result<i64> {
+        Ok(pg_db::get_source_count(&self.pool).await?)
+    }
+
+    async fn get_per_source_counts(&self) -> Result<Vec<PerSourceCounts>> {
+        Ok(pg_db::get_per_source_counts(&self.pool).await?)
+    }
+
+    async fn get_source_stats(&self, source_id: i32) -> Result<SourceStats> {
+        Ok(pg_db::get_source_stats(&self.pool, source_id).await?)
+    }
+
+    async fn get_sample_chapter(&self, source_id: i32) -> Result<Option<SampleChapter>> {
+        Ok(pg_db::get_sample_chapter(&self.pool, source_id).await?)
+    }
+
+    async fn search_manga(&self, search: &MangaSearch) -> Result<Vec<Manga>> {
+        Ok(pg_db::search_manga(&self.pool, search).await?)
+    }
+
+    async fn count_manga_matching(&self, search: &MangaSearch) -> Result<i64> {
+        Ok(pg_db::count_manga_matching(&self.pool, search).await?)
+    }
+
+    async fn get_manga_count(&self) -> Result<i64> {
+        Ok(pg_db::get_manga_count(&self.pool).await?)
+    }
+
+    async fn get_manga_by_id(&self, manga_id: &str) -> Result<Option<Manga>> {
+        Ok(pg_db::get_manga_by_id(&self.pool, manga_id).await?)
+    }
+
+    async fn get_manga_by_source(&self, source_id: i32) -> Result<Vec<Manga>> {
+        Ok(pg_db::get_manga_by_source(&self.pool, source_id).await?)
+    }
+
+    async fn insert_manga(&self, manga: &Manga) -> Result<()> {
+        Ok(pg_db::insert_manga(&self.pool, manga).await?)
+    }
+
+    async fn set_manga_monitoring(
+        &self,
+        manga_id: &str,
+        monitored: bool,
+        check_interval_secs: Option<i64>,
+        discover_interval_secs: Option<i64>,
+    ) -> Result<()> {
+        Ok(pg_db::set_manga_monitoring(
+            &self.pool,
+            manga_id,
+            monitored,
+            check_interval_secs,
+            discover_interval_secs,
+        )
+        .await?)
+    }
+
+    async fn get_manga_output_format(&self, manga_id: &str) -> Result<Option<String>> {
+        Ok(pg_db::get_manga_output_format(&self.pool, manga_id).await?)
+    }
+
+    async fn set_manga_output_format(&self, manga_id: &str, format: Option<&str>) -> Result<u64> {
+        Ok(pg_db::set_manga_output_format(&self.pool, manga_id, format).await?)
+    }
+
+    async fn mark_chapter_check(&self, manga_id: &str, ts: i64) -> Result<()> {
+        Ok(pg_db::mark_chapter_check(&self.pool, manga_id, ts).await?)
+    }
+
+    async fn due_for_chapter_check(&self, now_ts: i64) -> Result<Vec<String>> {
+        Ok(pg_db::due_for_chapter_check(&self.pool, now_ts).await?)
+    }
+
+    async fn insert_manga_source_data(&self, msd: &MangaSourceData) -> Result<i32> {
+        Ok(pg_db::insert_manga_source_data(&self.pool, msd).await?)
+    }
+
+    async fn get_manga_source_data_by_manga_id(
+        &self,
+        manga_id: &str,
+    ) -> Result<Vec<MangaSourceData>> {
+        Ok(pg_db::get_manga_source_data_by_manga_id(&self.pool, manga_id).await?)
+    }
+
+    async fn get_manga_source_data_id(
+        &self,
+        manga_id: &str,
+        source_id: i32,
+    ) -> Result<Option<i32>> {
+        Ok(pg_db::get_manga_source_data_id(&self.pool, manga_id, source_id).await?)
+    }
+
+    async fn set_source_priority(&self, manga_id: &str, source_ids: &[i32]) -> Result<u64> {
+        Ok(pg_db::set_source_priority(&self.pool, manga_id, source_ids).await?)
+    }
+
+    async fn get_chapter_count(&self) -> Result<i64> {
+        Ok(pg_db::get_chapter_count(&self.pool).await?)
+    }
+
+    async fn get_chapters_by_manga_source_data_id(
+        &self,
+        manga_id: &str,
+        source_id: i32,
+    ) -> Result<Vec<Chapter>> {
+        Ok(pg_db::get_chapters_by_manga_source_data_id(&self.pool, manga_id, source_id).await?)
+    }
+
+    async fn get_chapter_with_source(&self, chapter_id: i32) -> Result<Option<ChapterWithSource>> {
+        Ok(pg_db::get_chapter_with_source(&self.pool, chapter_id).await?)
+    }
+
+    async fn set_chapter_page_count(&self, chapter_id: i32, page_count: i32) -> Result<()> {
+        Ok(pg_db::set_chapter_page_count(&self.pool, chapter_id, page_count).await?)
+    }
+
+    async fn set_chapter_file(
+        &self,
+        chapter_id: i32,
+        file: &ChapterFile,
+        now_ts: i64,
+    ) -> Result<()> {
+        Ok(pg_db::set_chapter_file(&self.pool, chapter_id, file, now_ts).await?)
+    }
+
+    async fn clear_chapter_file(&self, chapter_id: i32) -> Result<()> {
+        Ok(pg_db::clear_chapter_file(&self.pool, chapter_id).await?)
+    }
+
+    async fn get_downloaded_chapters(&self) -> Result<Vec<(String, ChapterWithSource)>> {
+        Ok(pg_db::get_downloaded_chapters(&self.pool).await?)
+    }
+
+    async fn insert_chapters(&self, manga_source_data_id: i32, chapters: &[Chapter]) -> Result<()> {
+        Ok(pg_db::insert_chapters(&self.pool, manga_source_data_id, chapters).await?)
+    }
+
+    async fn enqueue_download_jobs(
+        &self,
+        chapter_ids: &[i32],
+        format: Option<&str>,
+        max_attempts: i32,
+        now_ts: i64,
+    ) -> Result<u64> {
+        Ok(
+            pg_db::enqueue_download_jobs(&self.pool, chapter_ids, format, max_attempts, now_ts)
+                .await?,
+        )
+    }
+
+    async fn get_unscraped_chapter_ids(&self, limit: i64) -> Result<Vec<i32>> {
+        Ok(pg_db::get_unscraped_chapter_ids(&self.pool, limit).await?)
+    }
+
+    async fn claim_download_job(&self, now_ts: i64) -> Result<Option<DownloadJob>> {
+        Ok(pg_db::claim_download_job(&self.pool, now_ts).await?)
+    }
+
+    async fn complete_download_job(&self, job_id: i64, file_path: &str, now_ts: i64) -> Result<()> {
+        Ok(pg_db::complete_download_job(&self.pool, job_id, file_path, now_ts).await?)
+    }
+
+    async fn fail_download_job(
+        &self,
+        job_id: i64,
+        error: &str,
+        retry_at: i64,
+        now_ts: i64,
+    ) -> Result<()> {
+        Ok(pg_db::fail_download_job(&self.pool, job_id, error, retry_at, now_ts).await?)
+    }
+
+    async fn requeue_running_download_jobs(&self, now_ts: i64) -> Result<u64> {
+        Ok(pg_db::requeue_running_download_jobs(&self.pool, now_ts).await?)
+    }
+
+    async fn retry_download_job(&self, job_id: i64, now_ts: i64) -> Result<bool> {
+        Ok(pg_db::retry_download_job(&self.pool, job_id, now_ts).await?)
+    }
+
+    async fn list_download_jobs(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DownloadJob>> {
+        Ok(pg_db::list_download_jobs(&self.pool, state, limit, offset).await?)
+    }
+
+    async fn get_match_candidates(&self) -> Result<Vec<MatchCandidate>> {
+        Ok(pg_db::get_match_candidates(&self.pool).await?)
+    }
+
+    async fn set_cover_hash(&self, manga_id: &str, hash: i64) -> Result<()> {
+        Ok(pg_db::set_cover_hash(&self.pool, manga_id, hash).await?)
+    }
+
+    async fn insert_merge_proposal(
+        &self,
+        a: (&str, &str),
+        b: (&str, &str),
+        score: f64,
+        reasons: &str,
+        automatic: bool,
+        now_ts: i64,
+    ) -> Result<Option<i32>> {
+        Ok(
+            pg_db::insert_merge_proposal(&self.pool, a, b, score, reasons, automatic, now_ts)
+                .await?,
+        )
+    }
+
+    async fn list_merge_proposals(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MergeProposal>> {
+        Ok(pg_db::list_merge_proposals(&self.pool, state, limit, offset).await?)
+    }
+
+    async fn get_merge_proposal(&self, id: i32) -> Result<Option<MergeProposal>> {
+        Ok(pg_db::get_merge_proposal(&self.pool, id).await?)
+    }
+
+    async fn set_merge_proposal_state(
+        &self,
+        id: i32,
+        from: &str,
+        to: &str,
+        now_ts: i64,
+    ) -> Result<bool> {
+        Ok(pg_db::set_merge_proposal_state(&self.pool, id, from, to, now_ts).await?)
+    }
+
+    async fn merge_manga(&self, target_id: &str, other_id: &str, now_ts: i64) -> Result<i32> {
+        Ok(pg_db::merge_manga(&self.pool, target_id, other_id, now_ts).await?)
+    }
+
+    async fn split_manga(
+        &self,
+        manga_id: &str,
+        source_ids: &[i32],
+        title: Option<&str>,
+        now_ts: i64,
+    ) -> Result<Option<(String, i32)>> {
+        Ok(pg_db::split_manga(&self.pool, manga_id, source_ids, title, now_ts).await?)
+    }
+
+    async fn list_manga_audit(
+        &self,
+        manga_id: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MangaAuditEntry>> {
+        Ok(pg_db::list_manga_audit(&self.pool, manga_id, limit, offset).await?)
+    }
+
+    async fn undo_manga_audit(&self, id: i32, now_ts: i64) -> Result<UndoOutcome> {
+        Ok(pg_db::undo_manga_audit(&self.pool, id, now_ts).await?)
+    }
+
+    async fn count_missing_provider(&self, provider: MetadataProvider) -> Result<i64> {
+        Ok(pg_db::count_missing_provider(&self.pool, provider).await?)
+    }
+
+    async fn get_manga_missing_provider(
+        &self,
+        provider: MetadataProvider,
+        limit: Option<i64>,
+    ) -> Result<Vec<MangaTitles>> {
+        Ok(pg_db::get_manga_missing_provider(&self.pool, provider, limit).await?)
+    }
+
+    async fn set_provider_id(
+        &self,
+        manga_id: &str,
+        provider: MetadataProvider,
+        provider_id: &str,
+    ) -> Result<()> {
+        Ok(pg_db::set_provider_id(&self.pool, manga_id, provider, provider_id).await?)
+    }
+
+    async fn get_provider_links(&self) -> Result<Vec<ProviderLinks>> {
+        Ok(pg_db::get_provider_links(&self.pool).await?)
+    }
+
+    async fn update_manga_metadata(
+        &self,
+        manga_id: &str,
+        description: Option<&str>,
+        tags: Option<&str>,
+        rating: Option<&str>,
+    ) -> Result<()> {
+        Ok(pg_db::update_manga_metadata(&self.pool, manga_id, description, tags, rating).await?)
+    }
+
+    async fn seed_tags(&self) -> Result<()> {
+        Ok(pg_db::seed_tags(&self.pool).await?)
+    }
+
+    async fn set_manga_tags(&self, manga_id: &str, tags: &[TagRef]) -> Result<()> {
+        Ok(pg_db::set_manga_tags(&self.pool, manga_id, tags).await?)
+    }
+
+    async fn list_tags(&self, category: Option<&str>) -> Result<Vec<Tag>> {
+        Ok(pg_db::list_tags(&self.pool, category).await?)
+    }
+
+    async fn get_manga_without_tag_links(&self) -> Result<Vec<(String, String)>> {
+        Ok(pg_db::get_manga_without_tag_links(&self.pool).await?)
+    }
+
+    async fn set_manga_people(&self, manga_id: &str, people: &[Credit]) -> Result<()> {
+        Ok(pg_db::set_manga_people(&self.pool, manga_id, people).await?)
+    }
+
+    async fn get_person(&self, person_id: i32) -> Result<Option<Person>> {
+        Ok(pg_db::get_person(&self.pool, person_id).await?)
+    }
+
+    async fn create_user(&self, name: &str, now_ts: i64) -> Result<Option<User>> {
+        Ok(pg_db::create_user(&self.pool, name, now_ts).await?)
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>> {
+        Ok(pg_db::list_users(&self.pool).await?)
+    }
+
+    async fn get_user_by_name(&self, name: &str) -> Result<Option<User>> {
+        Ok(pg_db::get_user_by_name(&self.pool, name).await?)
+    }
+
+    async fn set_library_entry(
+        &self,
+        user_id: i32,
+        manga_id: &str,
+        status: LibraryStatus,
+        now_ts: i64,
+    ) -> Result<bool> {
+        Ok(pg_db::set_library_entry(&self.pool, user_id, manga_id, status, now_ts).await?)
+    }
+
+    async fn remove_library_entry(&self, user_id: i32, manga_id: &str) -> Result<bool> {
+        Ok(pg_db::remove_library_entry(&self.pool, user_id, manga_id).await?)
+    }
+
+    async fn get_library(
+        &self,
+        user_id: i32,
+        status: Option<LibraryStatus>,
+    ) -> Result<Vec<LibraryEntry>> {
+        Ok(pg_db::get_library(&self.pool, user_id, status).await?)
+    }
+
+    async fn set_chapter_read(
+        &self,
+        user_id: i32,
+        chapter_id: i32,
+        page: i32,
+        completed: bool,
+        now_ts: i64,
+    ) -> Result<bool> {
+        Ok(pg_db::set_chapter_read(&self.pool, user_id, chapter_id, page, completed, now_ts).await?)
+    }
+
+    async fn clear_chapter_read(&self, user_id: i32, chapter_id: i32) -> Result<bool> {
+        Ok(pg_db::clear_chapter_read(&self.pool, user_id, chapter_id).await?)
+    }
+
+    async fn get_chapter_reads(&self, user_id: i32, manga_id: &str) -> Result<Vec<ChapterRead>> {
+        Ok(pg_db::get_chapter_reads(&self.pool, user_id, manga_id).await?)
+    }
+
+    async fn get_continue_reading(&self, user_id: i32, limit: i64) -> Result<Vec<ContinueReading>> {
+        Ok(pg_db::get_continue_reading(&self.pool, user_id, limit).await?)
+    }
+
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>> {
+        Ok(pg_db::get_user(&self.pool, user_id).await?)
+    }
+
+    async fn create_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        prefix: &str,
+        scopes: &[Scope],
+        user_id: Option<i32>,
+        now_ts: i64,
+    ) -> Result<ApiKey> {
+        Ok(pg_db::create_api_key(&self.pool, name, key_hash, prefix, scopes, user_id, now_ts).await?)
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        Ok(pg_db::list_api_keys(&self.pool).await?)
+    }
+
+    async fn revoke_api_key(&self, id: i32, now_ts: i64) -> Result<bool> {
+        Ok(pg_db::revoke_api_key(&self.pool, id, now_ts).await?)
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str, now_ts: i64) -> Result<Option<ApiKey>> {
+        Ok(pg_db::authenticate_api_key(&self.pool, key_hash, now_ts).await?)
+    }
+}
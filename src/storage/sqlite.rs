@@ -0,0 +1,604 @@
+//! [`Storage`] over an SQLite file (or `:memory:`), using the functions in
+//! `db.rs`. rusqlite is synchronous, so each call runs on the blocking thread
+//! pool against one shared connection.
+
+use super::{
+    MangaTitles, MatchCandidate, MetadataProvider, PerSourceCounts, ProviderLinks, Result,
+    SampleChapter, SourceStats, Storage, StorageError, UndoOutcome,
+};
+use crate::db;
+use crate::auth::Scope;
+use crate::models::{
+    ApiKey, Chapter, ChapterFile, ChapterRead, ChapterWithSource, ContinueReading, Credit,
+    DownloadJob, LibraryEntry, Manga, MangaAuditEntry, MangaSourceData, MergeProposal, Person, Tag,
+    User,
+};
+use crate::reading::LibraryStatus;
+use crate::search::MangaSearch;
+use crate::tags::TagRef;
+use async_trait::async_trait;
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self> {
+        Ok(Self {
+            conn: Arc::new(Mutex::new(db::open(path)?)),
+        })
+    }
+
+    async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut conn)
+        })
+        .await
+        .map_err(|e| StorageError::Task(e.to_string()))?
+        .map_err(StorageError::from)
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    /// The numbered migrations are PostgreSQL-only; the SQLite schema is
+    /// brought up to date in place, so no versions are reported.
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        self.call(|conn| db::create_tables(conn)).await?;
+        Ok(Vec::new())
+    }
+
+    async fn seed_sources(&self) -> Result<()> {
+        self.call(move |conn| db::seed_sources(conn)).await
+    }
+
+    async fn check_sources_table(&self) -> Result<Vec<String>> {
+        self.call(move |conn| db::check_sources_table(conn)).await
+    }
+
+    async fn get_source_name(&self, source_id: i32) -> Result<String> {
+        self.call(move |conn| db::get_source_name(conn, source_id))
+            .await
+    }
+
+    async fn get_source_count(&self) -> Result<i64> {
+        self.call(move |conn| db::get_source_count(conn)).await
+    }
+
+    async fn get_per_source_counts(&self) -> Result<Vec<PerSourceCounts>> {
+        self.call(move |conn| db::get_per_source_counts(conn)).await
+    }
+
+    async fn get_source_stats(&self, source_id: i32) -> Result<SourceStats> {
+        self.call(move |conn| db::get_source_stats(conn, source_id))
+            .await
+    }
+
+    async fn get_sample_chapter(&self, source_id: i32) -> Result<Option<SampleChapter>> {
+        self.call(move |conn| db::get_sample_chapter(conn, source_id))
+            .await
+    }
+
+    async fn search_manga(&self, search: &MangaSearch) -> Result<Vec<Manga>> {
+        let search = search.clone();
+        self.call(move |conn| db::search_manga(conn, &search)).await
+    }
+
+    async fn count_manga_matching(&self, search: &MangaSearch) -> Result<i64> {
+        let search = search.clone();
+        self.call(move |conn| db::count_manga_matching(conn, &search))
+            .await
+    }
+
+    async fn get_manga_count(&self) -> Result<i64> {
+        self.call(move |conn| db::get_manga_count(conn)).await
+    }
+
+    async fn get_manga_by_id(&self, manga_id: &str) -> Result<Option<Manga>> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::get_manga_by_id(conn, &manga_id))
+            .await
+    }
+
+    async fn get_manga_by_source(&self, source_id: i32) -> Result<Vec<Manga>> {
+        self.call(move |conn| db::get_manga_by_source(conn, source_id))
+            .await
+    }
+
+    async fn insert_manga(&self, manga: &Manga) -> Result<()> {
+        let manga = manga.clone();
+        self.call(move |conn| db::insert_manga(conn, &manga)).await
+    }
+
+    async fn set_manga_monitoring(
+        &self,
+        manga_id: &str,
+        monitored: bool,
+        check_interval_secs: Option<i64>,
+        discover_interval_secs: Option<i64>,
+    ) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| {
+            db::set_manga_monitoring(
+                conn,
+                &manga_id,
+                monitored,
+                check_interval_secs,
+                discover_interval_secs,
+            )
+        })
+        .await
+    }
+
+    async fn get_manga_output_format(&self, manga_id: &str) -> Result<Option<String>> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::get_manga_output_format(conn, &manga_id))
+            .await
+    }
+
+    async fn set_manga_output_format(&self, manga_id: &str, format: Option<&str>) -> Result<u64> {
+        let manga_id = manga_id.to_string();
+        let format = format.map(str::to_string);
+        self.call(move |conn| db::set_manga_output_format(conn, &manga_id, format.as_deref()))
+            .await
+    }
+
+    async fn mark_chapter_check(&self, manga_id: &str, ts: i64) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::mark_chapter_check(conn, &manga_id, ts))
+            .await
+    }
+
+    async fn due_for_chapter_check(&self, now_ts: i64) -> Result<Vec<String>> {
+        self.call(move |conn| db::due_for_chapter_check(conn, now_ts))
+            .await
+    }
+
+    async fn insert_manga_source_data(&self, msd: &MangaSourceData) -> Result<i32> {
+        let msd = msd.clone();
+        self.call(move |conn| db::insert_manga_source_data(conn, &msd))
+            .await
+    }
+
+    async fn get_manga_source_data_by_manga_id(
+        &self,
+        manga_id: &str,
+    ) -> Result<Vec<MangaSourceData>> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::get_manga_source_data_by_manga_id(conn, &manga_id))
+            .await
+    }
+
+    async fn get_manga_source_data_id(
+        &self,
+        manga_id: &str,
+        source_id: i32,
+    ) -> Result<Option<i32>> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::get_manga_source_data_id(conn, &manga_id, source_id))
+            .await
+    }
+
+    async fn set_source_priority(&self, manga_id: &str, source_ids: &[i32]) -> Result<u64> {
+        let manga_id = manga_id.to_string();
+        let source_ids = source_ids.to_vec();
+        self.call(move |conn| db::set_source_priority(conn, &manga_id, &source_ids))
+            .await
+    }
+
+    async fn get_chapter_count(&self) -> Result<i64> {
+        self.call(move |conn| db::get_chapter_count(conn)).await
+    }
+
+    async fn get_chapters_by_manga_source_data_id(
+        &self,
+        manga_id: &str,
+        source_id: i32,
+    ) -> Result<Vec<Chapter>> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::get_chapters_by_manga_source_data_id(conn, &manga_id, source_id))
+            .await
+    }
+
+    async fn get_chapter_with_source(&self, chapter_id: i32) -> Result<Option<ChapterWithSource>> {
+        self.call(move |conn| db::get_chapter_with_source(conn, chapter_id))
+            .await
+    }
+
+    async fn set_chapter_page_count(&self, chapter_id: i32, page_count: i32) -> Result<()> {
+        self.call(move |conn| db::set_chapter_page_count(conn, chapter_id, page_count))
+            .await
+    }
+
+    async fn set_chapter_file(
+        &self,
+        chapter_id: i32,
+        file: &ChapterFile,
+        now_ts: i64,
+    ) -> Result<()> {
+        let file = file.clone();
+        self.call(move |conn| db::set_chapter_file(conn, chapter_id, &file, now_ts))
+            .await
+    }
+
+    async fn clear_chapter_file(&self, chapter_id: i32) -> Result<()> {
+        self.call(move |conn| db::clear_chapter_file(conn, chapter_id)).await
+    }
+
+    async fn get_downloaded_chapters(&self) -> Result<Vec<(String, ChapterWithSource)>> {
+        self.call(|conn| db::get_downloaded_chapters(conn)).await
+    }
+
+    async fn insert_chapters(&self, manga_source_data_id: i32, chapters: &[Chapter]) -> Result<()> {
+        let chapters = chapters.to_vec();
+        self.call(move |conn| db::insert_chapters(conn, manga_source_data_id, &chapters))
+            .await
+    }
+
+    async fn enqueue_download_jobs(
+        &self,
+        chapter_ids: &[i32],
+        format: Option<&str>,
+        max_attempts: i32,
+        now_ts: i64,
+    ) -> Result<u64> {
+        let chapter_ids = chapter_ids.to_vec();
+        let format = format.map(str::to_string);
+        self.call(move |conn| {
+            db::enqueue_download_jobs(conn, &chapter_ids, format.as_deref(), max_attempts, now_ts)
+        })
+        .await
+    }
+
+    async fn get_unscraped_chapter_ids(&self, limit: i64) -> Result<Vec<i32>> {
+        self.call(move |conn| db::get_unscraped_chapter_ids(conn, limit))
+            .await
+    }
+
+    async fn claim_download_job(&self, now_ts: i64) -> Result<Option<DownloadJob>> {
+        self.call(move |conn| db::claim_download_job(conn, now_ts))
+            .await
+    }
+
+    async fn complete_download_job(&self, job_id: i64, file_path: &str, now_ts: i64) -> Result<()> {
+        let file_path = file_path.to_string();
+        self.call(move |conn| db::complete_download_job(conn, job_id, &file_path, now_ts))
+            .await
+    }
+
+    async fn fail_download_job(
+        &self,
+        job_id: i64,
+        error: &str,
+        retry_at: i64,
+        now_ts: i64,
+    ) -> Result<()> {
+        let error = error.to_string();
+        self.call(move |conn| db::fail_download_job(conn, job_id, &error, retry_at, now_ts))
+            .await
+    }
+
+    async fn requeue_running_download_jobs(&self, now_ts: i64) -> Result<u64> {
+        self.call(move |conn| db::requeue_running_download_jobs(conn, now_ts))
+            .await
+    }
+
+    async fn retry_download_job(&self, job_id: i64, now_ts: i64) -> Result<bool> {
+        self.call(move |conn| db::retry_download_job(conn, job_id, now_ts))
+            .await
+    }
+
+    async fn list_download_jobs(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DownloadJob>> {
+        let state = state.map(str::to_string);
+        self.call(move |conn| db::list_download_jobs(conn, state.as_deref(), limit, offset))
+            .await
+    }
+
+    async fn get_match_candidates(&self) -> Result<Vec<MatchCandidate>> {
+        self.call(move |conn| db::get_match_candidates(conn)).await
+    }
+
+    async fn set_cover_hash(&self, manga_id: &str, hash: i64) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::set_cover_hash(conn, &manga_id, hash))
+            .await
+    }
+
+    async fn insert_merge_proposal(
+        &self,
+        a: (&str, &str),
+        b: (&str, &str),
+        score: f64,
+        reasons: &str,
+        automatic: bool,
+        now_ts: i64,
+    ) -> Result<Option<i32>> {
+        let a = (a.0.to_string(), a.1.to_string());
+        let b = (b.0.to_string(), b.1.to_string());
+        let reasons = reasons.to_string();
+        self.call(move |conn| {
+            db::insert_merge_proposal(
+                conn,
+                (&a.0, &a.1),
+                (&b.0, &b.1),
+                score,
+                &reasons,
+                automatic,
+                now_ts,
+            )
+        })
+        .await
+    }
+
+    async fn list_merge_proposals(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MergeProposal>> {
+        let state = state.map(str::to_string);
+        self.call(move |conn| db::list_merge_proposals(conn, state.as_deref(), limit, offset))
+            .await
+    }
+
+    async fn get_merge_proposal(&self, id: i32) -> Result<Option<MergeProposal>> {
+        self.call(move |conn| db::get_merge_proposal(conn, id))
+            .await
+    }
+
+    async fn set_merge_proposal_state(
+        &self,
+        id: i32,
+        from: &str,
+        to: &str,
+        now_ts: i64,
+    ) -> Result<bool> {
+        let from = from.to_string();
+        let to = to.to_string();
+        self.call(move |conn| db::set_merge_proposal_state(conn, id, &from, &to, now_ts))
+            .await
+    }
+
+    async fn merge_manga(&self, target_id: &str, other_id: &str, now_ts: i64) -> Result<i32> {
+        let target_id = target_id.to_string();
+        let other_id = other_id.to_string();
+        self.call(move |conn| db::merge_manga(conn, &target_id, &other_id, now_ts))
+            .await
+    }
+
+    async fn split_manga(
+        &self,
+        manga_id: &str,
+        source_ids: &[i32],
+        title: Option<&str>,
+        now_ts: i64,
+    ) -> Result<Option<(String, i32)>> {
+        let manga_id = manga_id.to_string();
+        let source_ids = source_ids.to_vec();
+        let title = title.map(str::to_string);
+        self.call(move |conn| {
+            db::split_manga(conn, &manga_id, &source_ids, title.as_deref(), now_ts)
+        })
+        .await
+    }
+
+    async fn list_manga_audit(
+        &self,
+        manga_id: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MangaAuditEntry>> {
+        let manga_id = manga_id.map(str::to_string);
+        self.call(move |conn| db::list_manga_audit(conn, manga_id.as_deref(), limit, offset))
+            .await
+    }
+
+    async fn undo_manga_audit(&self, id: i32, now_ts: i64) -> Result<UndoOutcome> {
+        self.call(move |conn| db::undo_manga_audit(conn, id, now_ts))
+            .await
+    }
+
+    async fn count_missing_provider(&self, provider: MetadataProvider) -> Result<i64> {
+        self.call(move |conn| db::count_missing_provider(conn, provider))
+            .await
+    }
+
+    async fn get_manga_missing_provider(
+        &self,
+        provider: MetadataProvider,
+        limit: Option<i64>,
+    ) -> Result<Vec<MangaTitles>> {
+        self.call(move |conn| db::get_manga_missing_provider(conn, provider, limit))
+            .await
+    }
+
+    async fn set_provider_id(
+        &self,
+        manga_id: &str,
+        provider: MetadataProvider,
+        provider_id: &str,
+    ) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        let provider_id = provider_id.to_string();
+        self.call(move |conn| db::set_provider_id(conn, &manga_id, provider, &provider_id))
+            .await
+    }
+
+    async fn get_provider_links(&self) -> Result<Vec<ProviderLinks>> {
+        self.call(move |conn| db::get_provider_links(conn)).await
+    }
+
+    async fn update_manga_metadata(
+        &self,
+        manga_id: &str,
+        description: Option<&str>,
+        tags: Option<&str>,
+        rating: Option<&str>,
+    ) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        let description = description.map(str::to_string);
+        let tags = tags.map(str::to_string);
+        let rating = rating.map(str::to_string);
+        self.call(move |conn| {
+            db::update_manga_metadata(
+                conn,
+                &manga_id,
+                description.as_deref(),
+                tags.as_deref(),
+                rating.as_deref(),
+            )
+        })
+        .await
+    }
+
+    async fn seed_tags(&self) -> Result<()> {
+        self.call(move |conn| db::seed_tags(conn)).await
+    }
+
+    async fn set_manga_tags(&self, manga_id: &str, tags: &[TagRef]) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        let tags = tags.to_vec();
+        self.call(move |conn| db::set_manga_tags(conn, &manga_id, &tags))
+            .await
+    }
+
+    async fn list_tags(&self, category: Option<&str>) -> Result<Vec<Tag>> {
+        let category = category.map(str::to_string);
+        self.call(move |conn| db::list_tags(conn, category.as_deref()))
+            .await
+    }
+
+    async fn get_manga_without_tag_links(&self) -> Result<Vec<(String, String)>> {
+        self.call(move |conn| db::get_manga_without_tag_links(conn))
+            .await
+    }
+
+    async fn set_manga_people(&self, manga_id: &str, people: &[Credit]) -> Result<()> {
+        let manga_id = manga_id.to_string();
+        let people = people.to_vec();
+        self.call(move |conn| db::set_manga_people(conn, &manga_id, &people))
+            .await
+    }
+
+    async fn get_person(&self, person_id: i32) -> Result<Option<Person>> {
+        self.call(move |conn| db::get_person(conn, person_id)).await
+    }
+
+    async fn create_user(&self, name: &str, now_ts: i64) -> Result<Option<User>> {
+        let name = name.to_string();
+        self.call(move |conn| db::create_user(conn, &name, now_ts)).await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>> {
+        self.call(move |conn| db::list_users(conn)).await
+    }
+
+    async fn get_user_by_name(&self, name: &str) -> Result<Option<User>> {
+        let name = name.to_string();
+        self.call(move |conn| db::get_user_by_name(conn, &name)).await
+    }
+
+    async fn set_library_entry(
+        &self,
+        user_id: i32,
+        manga_id: &str,
+        status: LibraryStatus,
+        now_ts: i64,
+    ) -> Result<bool> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::set_library_entry(conn, user_id, &manga_id, status, now_ts))
+            .await
+    }
+
+    async fn remove_library_entry(&self, user_id: i32, manga_id: &str) -> Result<bool> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::remove_library_entry(conn, user_id, &manga_id))
+            .await
+    }
+
+    async fn get_library(
+        &self,
+        user_id: i32,
+        status: Option<LibraryStatus>,
+    ) -> Result<Vec<LibraryEntry>> {
+        self.call(move |conn| db::get_library(conn, user_id, status)).await
+    }
+
+    async fn set_chapter_read(
+        &self,
+        user_id: i32,
+        chapter_id: i32,
+        page: i32,
+        completed: bool,
+        now_ts: i64,
+    ) -> Result<bool> {
+        self.call(move |conn| {
+            db::set_chapter_read(conn, user_id, chapter_id, page, completed, now_ts)
+        })
+        .await
+    }
+
+    async fn clear_chapter_read(&self, user_id: i32, chapter_id: i32) -> Result<bool> {
+        self.call(move |conn| db::clear_chapter_read(conn, user_id, chapter_id))
+            .await
+    }
+
+    async fn get_chapter_reads(&self, user_id: i32, manga_id: &str) -> Result<Vec<ChapterRead>> {
+        let manga_id = manga_id.to_string();
+        self.call(move |conn| db::get_chapter_reads(conn, user_id, &manga_id))
+            .await
+    }
+
+    async fn get_continue_reading(&self, user_id: i32, limit: i64) -> Result<Vec<ContinueReading>> {
+        self.call(move |conn| db::get_continue_reading(conn, user_id, limit))
+            .await
+    }
+
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>> {
+        self.call(move |conn| db::get_user(conn, user_id)).await
+    }
+
+    async fn create_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        prefix: &str,
+        scopes: &[Scope],
+        user_id: Option<i32>,
+        now_ts: i64,
+    ) -> Result<ApiKey> {
+        let (name, key_hash, prefix) = (name.to_string(), key_hash.to_string(), prefix.to_string());
+        let scopes = scopes.to_vec();
+        self.call(move |conn| {
+            db::create_api_key(conn, &name, &key_hash, &prefix, &scopes, user_id, now_ts)
+        })
+        .await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        self.call(move |conn| db::list_api_keys(conn)).await
+    }
+
+    async fn revoke_api_key(&self, id: i32, now_ts: i64) -> Result<bool> {
+        self.call(move |conn| db::revoke_api_key(conn, id, now_ts)).await
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str, now_ts: i64) -> Result<Option<ApiKey>> {
+        let key_hash = key_hash.to_string();
+        self.call(move |conn| db::authenticate_api_key(conn, &key_hash, now_ts))
+            .await
+    }
+}
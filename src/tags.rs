@@ -0,0 +1,359 @@
+//! Canonical tag dictionary.
+//!
+//! Sources and metadata providers name the same tag differently: MAL says
+//! "Sci-Fi", others "Sci Fi" or "Science Fiction", MAL's "Gourmet" is
+//! everyone else's "Cooking". Every label is mapped to one [`DICTIONARY`]
+//! entry, first through the entry's synonyms for the label's provider, then by
+//! comparing letters and digits only with the entry's name and generic
+//! synonyms. Labels the dictionary does not know are kept under their own name
+//! with no category.
+//!
+//! The `tags` table is seeded from the dictionary at startup and `manga_tags`
+//! links manga to it; `manga.tags` keeps the canonical names joined with ", "
+//! for ComicInfo and full-text search.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_manga_scraper::tags::{self, TagCategory};
+//!
+//! let tag = tags::canonicalize(Some("mal"), "Gourmet").unwrap();
+//! assert_eq!((tag.slug.as_str(), tag.category), ("cooking", Some(TagCategory::Genre)));
+//! assert_eq!(tags::canonicalize(None, "Sci Fi").unwrap().name, "Sci-Fi");
+//! ```
+
+use crate::storage::Storage;
+use log::{error, info};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagCategory {
+    Genre,
+    Theme,
+    /// How the work is published or laid out (long strip, oneshot, ...)
+    Format,
+    ContentWarning,
+}
+
+impl TagCategory {
+    pub const ALL: [TagCategory; 4] = [
+        TagCategory::Genre,
+        TagCategory::Theme,
+        TagCategory::Format,
+        TagCategory::ContentWarning,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TagCategory::Genre => "genre",
+            TagCategory::Theme => "theme",
+            TagCategory::Format => "format",
+            TagCategory::ContentWarning => "content_warning",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+}
+
+pub struct TagEntry {
+    pub slug: &'static str,
+    pub name: &'static str,
+    pub category: TagCategory,
+    /// Other names for the tag, from any provider
+    pub synonyms: &'static [&'static str],
+    /// `(provider, label)` pairs that only mean this tag coming from that
+    /// provider (`mal`, `anilist`, `mangabaka` or a source slug)
+    pub provider_synonyms: &'static [(&'static str, &'static str)],
+}
+
+const fn tag(slug: &'static str, name: &'static str, category: TagCategory) -> TagEntry {
+    TagEntry {
+        slug,
+        name,
+        category,
+        synonyms: &[],
+        provider_synonyms: &[],
+    }
+}
+
+use TagCategory::{ContentWarning, Format, Genre, Theme};
+
+#[rustfmt::skip]
+pub const DICTIONARY: &[TagEntry] = &[
+    tag("action", "Action", Genre),
+    tag("adventure", "Adventure", Genre),
+    TagEntry { synonyms: &["Yaoi", "Shounen Ai", "BL"], ..tag("boys-love", "Boys' Love", Genre) },
+    tag("comedy", "Comedy", Genre),
+    TagEntry { provider_synonyms: &[("mal", "Gourmet")], ..tag("cooking", "Cooking", Genre) },
+    tag("crime", "Crime", Genre),
+    tag("drama", "Drama", Genre),
+    tag("ecchi", "Ecchi", Genre),
+    tag("fantasy", "Fantasy", Genre),
+    TagEntry { synonyms: &["Yuri", "Shoujo Ai", "GL"], ..tag("girls-love", "Girls' Love", Genre) },
+    tag("historical", "Historical", Genre),
+    tag("horror", "Horror", Genre),
+    tag("isekai", "Isekai", Genre),
+    TagEntry { synonyms: &["Mahou Shoujo"], ..tag("magical-girls", "Magical Girls", Genre) },
+    tag("mecha", "Mecha", Genre),
+    tag("medical", "Medical", Genre),
+    tag("mystery", "Mystery", Genre),
+    tag("philosophical", "Philosophical", Genre),
+    TagEntry { synonyms: &["Psychological Thriller"], ..tag("psychological", "Psychological", Genre) },
+    tag("romance", "Romance", Genre),
+    TagEntry { synonyms: &["Science Fiction", "SF"], ..tag("sci-fi", "Sci-Fi", Genre) },
+    tag("slice-of-life", "Slice of Life", Genre),
+    tag("sports", "Sports", Genre),
+    tag("superhero", "Superhero", Genre),
+    TagEntry { synonyms: &["Suspense"], ..tag("thriller", "Thriller", Genre) },
+    tag("tragedy", "Tragedy", Genre),
+    tag("wuxia", "Wuxia", Genre),
+    tag("aliens", "Aliens", Theme),
+    tag("animals", "Animals", Theme),
+    TagEntry { synonyms: &["Demon"], ..tag("demons", "Demons", Theme) },
+    tag("game", "Game", Theme),
+    tag("gyaru", "Gyaru", Theme),
+    TagEntry { synonyms: &["Reverse Harem"], ..tag("harem", "Harem", Theme) },
+    tag("magic", "Magic", Theme),
+    tag("martial-arts", "Martial Arts", Theme),
+    tag("military", "Military", Theme),
+    tag("monsters", "Monsters", Theme),
+    tag("music", "Music", Theme),
+    tag("ninja", "Ninja", Theme),
+    tag("office-workers", "Office Workers", Theme),
+    tag("police", "Police", Theme),
+    TagEntry { synonyms: &["Post-Apocalypse", "Apocalypse"], ..tag("post-apocalyptic", "Post-Apocalyptic", Theme) },
+    tag("reincarnation", "Reincarnation", Theme),
+    tag("samurai", "Samurai", Theme),
+    TagEntry { synonyms: &["School"], ..tag("school-life", "School Life", Theme) },
+    tag("supernatural", "Supernatural", Theme),
+    tag("survival", "Survival", Theme),
+    TagEntry { synonyms: &["Time Manipulation"], ..tag("time-travel", "Time Travel", Theme) },
+    TagEntry { synonyms: &["Vampire"], ..tag("vampires", "Vampires", Theme) },
+    tag("video-games", "Video Games", Theme),
+    tag("villainess", "Villainess", Theme),
+    tag("virtual-reality", "Virtual Reality", Theme),
+    TagEntry { synonyms: &["Zombie"], ..tag("zombies", "Zombies", Theme) },
+    TagEntry { synonyms: &["Yonkoma"], ..tag("4-koma", "4-Koma", Format) },
+    tag("adaptation", "Adaptation", Format),
+    tag("anthology", "Anthology", Format),
+    tag("award-winning", "Award Winning", Format),
+    tag("doujinshi", "Doujinshi", Format),
+    TagEntry { synonyms: &["Full Colour", "Colored", "Official Colored"], ..tag("full-color", "Full Color", Format) },
+    TagEntry { synonyms: &["Webtoon", "Vertical Scroll"], ..tag("long-strip", "Long Strip", Format) },
+    tag("oneshot", "Oneshot", Format),
+    tag("user-created", "User Created", Format),
+    tag("web-comic", "Web Comic", Format),
+    tag("gore", "Gore", ContentWarning),
+    tag("self-harm", "Self-Harm", ContentWarning),
+    tag("sexual-violence", "Sexual Violence", ContentWarning),
+    tag("suicide", "Suicide", ContentWarning),
+];
+
+/// A tag as stored: a dictionary entry, or a label the dictionary does not know
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct TagRef {
+    pub slug: String,
+    pub name: String,
+    pub category: Option<TagCategory>,
+}
+
+impl From<&TagEntry> for TagRef {
+    fn from(entry: &TagEntry) -> Self {
+        TagRef {
+            slug: entry.slug.to_string(),
+            name: entry.name.to_string(),
+            category: Some(entry.category),
+        }
+    }
+}
+
+/// Letters and digits of a label, lowercased: "Sci-Fi" and "sci fi" agree.
+fn key(label: &str) -> String {
+    label
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// URL-safe identifier for a label outside the dictionary ("Time Loop" gives `time-loop`).
+pub fn slugify(label: &str) -> String {
+    label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Map one label from `provider` (or from no particular provider) to its
+/// canonical tag. Returns `None` for labels with no letters or digits.
+pub fn canonicalize(provider: Option<&str>, label: &str) -> Option<TagRef> {
+    let k = key(label);
+    if k.is_empty() {
+        return None;
+    }
+    if let Some(p) = provider {
+        let by_provider = DICTIONARY.iter().find(|e| {
+            e.provider_synonyms
+                .iter()
+                .any(|(sp, s)| *sp == p && key(s) == k)
+        });
+        if let Some(entry) = by_provider {
+            return Some(entry.into());
+        }
+    }
+    let entry = DICTIONARY
+        .iter()
+        .find(|e| key(e.name) == k || key(e.slug) == k)
+        .or_else(|| {
+            DICTIONARY
+                .iter()
+                .find(|e| e.synonyms.iter().any(|s| key(s) == k))
+        });
+    Some(match entry {
+        Some(entry) => entry.into(),
+        None => TagRef {
+            slug: slugify(label),
+            name: label.trim().to_string(),
+            category: None,
+        },
+    })
+}
+
+/// Canonical tags for a list of labels, without duplicates, in first-seen order.
+pub fn canonicalize_all<S: AsRef<str>>(
+    provider: Option<&str>,
+    labels: impl IntoIterator<Item = S>,
+) -> Vec<TagRef> {
+    let mut out: Vec<TagRef> = Vec::new();
+    for label in labels {
+        if let Some(tag) = canonicalize(provider, label.as_ref()) {
+            if !out.iter().any(|t| t.slug == tag.slug) {
+                out.push(tag);
+            }
+        }
+    }
+    out
+}
+
+/// Canonical tags for a comma-separated `manga.tags` value.
+pub fn parse_list(provider: Option<&str>, tags: &str) -> Vec<TagRef> {
+    canonicalize_all(provider, tags.split(','))
+}
+
+/// `manga.tags` value for a set of tags: names sorted and joined with ", ".
+pub fn display_list(tags: &[TagRef]) -> Option<String> {
+    if tags.is_empty() {
+        return None;
+    }
+    let mut names: Vec<&str> = tags.iter().map(|t| t.name.as_str()).collect();
+    names.sort_unstable_by_key(|n| n.to_lowercase());
+    Some(names.join(", "))
+}
+
+/// Seed statement for the `tags` table. Dictionary rows are updated in place so
+/// renamed or recategorised entries keep their IDs.
+pub fn seed_sql() -> String {
+    let rows: Vec<String> = DICTIONARY
+        .iter()
+        .map(|e| {
+            format!(
+                "('{}', '{}', '{}')",
+                e.slug,
+                e.name.replace('\'', "''"),
+                e.category.as_str()
+            )
+        })
+        .collect();
+    format!(
+        "INSERT INTO tags (slug, name, category) VALUES\n{}\nON CONFLICT (slug) DO UPDATE SET name = excluded.name, category = excluded.category;",
+        rows.join(",\n")
+    )
+}
+
+/// Link manga whose `manga.tags` predates the `manga_tags` table (or was
+/// written by hand) to their canonical tags. Run at startup.
+pub async fn link_untagged(storage: &dyn Storage) {
+    let pending = match storage.get_manga_without_tag_links().await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to list manga without tag links: {}", e);
+            return;
+        }
+    };
+    if pending.is_empty() {
+        return;
+    }
+    let mut linked = 0;
+    for (manga_id, tags) in &pending {
+        match storage
+            .set_manga_tags(manga_id, &parse_list(None, tags))
+            .await
+        {
+            Ok(()) => linked += 1,
+            Err(e) => error!("Failed to link tags of manga {}: {}", manga_id, e),
+        }
+    }
+    info!("Linked canonical tags for {} manga", linked);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_dictionary_is_consistent() {
+        let mut slugs = HashSet::new();
+        let mut keys = HashSet::new();
+        for entry in DICTIONARY {
+            assert!(slugs.insert(entry.slug), "duplicate slug {}", entry.slug);
+            assert_eq!(slugify(entry.slug), entry.slug);
+            for name in std::iter::once(&entry.name).chain(entry.synonyms) {
+                let k = key(name);
+                assert!(keys.insert(k), "{} is claimed twice", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let slug = |p: Option<&str>, l: &str| canonicalize(p, l).map(|t| t.slug);
+        assert_eq!(slug(Some("mal"), "Sci-Fi").as_deref(), Some("sci-fi"));
+        assert_eq!(slug(Some("anilist"), "Sci Fi").as_deref(), Some("sci-fi"));
+        assert_eq!(slug(None, "science fiction").as_deref(), Some("sci-fi"));
+        assert_eq!(
+            slug(Some("mal"), "Shounen Ai").as_deref(),
+            Some("boys-love")
+        );
+        assert_eq!(slug(Some("mal"), "Gourmet").as_deref(), Some("cooking"));
+        assert_eq!(slug(Some("anilist"), "Gourmet").as_deref(), Some("gourmet"));
+        assert_eq!(slug(None, " - "), None);
+
+        let unknown = canonicalize(None, " Time Loop ").unwrap();
+        assert_eq!(
+            (
+                unknown.slug.as_str(),
+                unknown.name.as_str(),
+                unknown.category
+            ),
+            ("time-loop", "Time Loop", None)
+        );
+    }
+
+    #[test]
+    fn test_parse_and_display_list() {
+        let tags = parse_list(Some("mal"), "Sci-Fi, Action,sci fi, , Webtoon");
+        let slugs: Vec<&str> = tags.iter().map(|t| t.slug.as_str()).collect();
+        assert_eq!(slugs, vec!["sci-fi", "action", "long-strip"]);
+        assert_eq!(
+            display_list(&tags).as_deref(),
+            Some("Action, Long Strip, Sci-Fi")
+        );
+        assert_eq!(display_list(&[]), None);
+    }
+}
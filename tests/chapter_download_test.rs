@@ -3,7 +3,10 @@
 /// This validates that the actual scraping and downloading works end-to-end
 use reqwest::Client;
 use rust_manga_scraper::models::{Chapter, Manga, Source};
-use rust_manga_scraper::scraper::download_chapter;
+use rust_manga_scraper::config::Config;
+use rust_manga_scraper::naming::{ChapterName, Template};
+use rust_manga_scraper::output::OutputFormat;
+use rust_manga_scraper::scraper::{download_chapter, ChapterDownload, PageFetcher};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::time::{Duration, Instant};
@@ -88,14 +91,27 @@ macro_rules! test_download {
                                     let test_dir = "test_downloads";
                                     std::fs::create_dir_all(test_dir).ok();
 
-                                    match download_chapter(
-                                        &client,
-                                        $source_id,
-                                        chapter_url,
+                                    let name = ChapterName::new(
                                         manga_title,
+                                        $source_id,
                                         chapter_number,
+                                        chapter_url,
+                                    );
+                                    let file_path =
+                                        Template::default().render(&name, OutputFormat::Cbz);
+                                    let fetcher = PageFetcher::from_config(&Config::default());
+
+                                    match download_chapter(
+                                        &client,
+                                        &fetcher,
                                         test_dir,
-                                        None,
+                                        ChapterDownload {
+                                            source_id: $source_id,
+                                            chapter_url,
+                                            file_path: &file_path,
+                                            comicinfo: None,
+                                            format: OutputFormat::Cbz,
+                                        },
                                     )
                                     .await
                                     {
@@ -306,14 +322,26 @@ async fn test_download_mangadex() -> DownloadTestResult {
                             let test_dir = "test_downloads";
                             std::fs::create_dir_all(test_dir).ok();
 
-                            match download_chapter(
-                                &client,
-                                Source::MangaDex as i32,
-                                chapter_url,
+                            let name = ChapterName::new(
                                 manga_title,
+                                Source::MangaDex as i32,
                                 chapter_number,
+                                chapter_url,
+                            );
+                            let file_path = Template::default().render(&name, OutputFormat::Cbz);
+                            let fetcher = PageFetcher::from_config(&Config::default());
+
+                            match download_chapter(
+                                &client,
+                                &fetcher,
                                 test_dir,
-                                None,
+                                ChapterDownload {
+                                    source_id: Source::MangaDex as i32,
+                                    chapter_url,
+                                    file_path: &file_path,
+                                    comicinfo: None,
+                                    format: OutputFormat::Cbz,
+                                },
                             )
                             .await
                             {